@@ -4,8 +4,10 @@ use atlas::{
     iso::{self, Iso2DConfig, Program},
     vm,
 };
-use compiler::jit2::{self, F64X2};
+use compiler::jit::Program as JitProgram;
+use compiler::jit2;
 use criterion::{BenchmarkId, Criterion, Throughput, black_box, criterion_group, criterion_main};
+use glam::DVec2;
 use utils::Intrvl;
 
 fn jit_native_vs_interpreter(c: &mut Criterion) {
@@ -13,22 +15,21 @@ fn jit_native_vs_interpreter(c: &mut Criterion) {
 
     let mut vm = vm::VM::with_instr_table(vm::IntrvlInstrTable);
 
-    let f1_intrvl = jit.compile_2intrvl_intrvl("f_intrvl", &Program::Dense2.bytecode());
+    let f1_intrvl =
+        jit.compile::<jit2::Intrvl>("f_intrvl", &JitProgram::from(Program::Dense2.bytecode()), 2);
 
     let op_code = Program::Dense2.opcode();
 
-    let intrvl_x = F64X2(-0.1, 0.1);
-    let intrvl_y = F64X2(-0.1, 0.1);
-
-    let mut out = F64X2::ZERO;
+    let intrvl_x = jit2::Intrvl::new(-0.1, 0.1);
+    let intrvl_y = jit2::Intrvl::new(-0.1, 0.1);
 
     c.bench_function("intrvl_native", |b| {
-        b.iter(|| f1_intrvl(intrvl_x, intrvl_y, &mut out))
+        b.iter(|| f1_intrvl.eval(&[intrvl_x, intrvl_y]))
     });
     c.bench_function("intrvl_interpreter", |b| {
         b.iter(|| {
-            vm.reg[1] = Intrvl::new(intrvl_x.0, intrvl_x.1);
-            vm.reg[2] = Intrvl::new(intrvl_y.0, intrvl_y.1);
+            vm.reg[1] = Intrvl::new(intrvl_x.lo, intrvl_x.hi);
+            vm.reg[2] = Intrvl::new(intrvl_y.lo, intrvl_y.hi);
 
             vm.eval(black_box(&op_code))
         })
@@ -37,7 +38,55 @@ fn jit_native_vs_interpreter(c: &mut Criterion) {
     // c.benchmark_group(BenchmarkId::new("native_intrvl"), &(xs, ys), |b, &
 }
 
-criterion_group!(benches, jit_native_vs_interpreter,);
+/// the grid before the tape specialization, every cell is evaluated with the interval jit
+fn uniform_grid(f: &jit2::Func<'_, jit2::Intrvl>, config: &Iso2DConfig) -> usize {
+    let res = 1u32 << config.intrvl_depth;
+    let size = (config.max - config.min) / res as f64;
+    let mut kept = 0;
+    for j in 0..res {
+        for i in 0..res {
+            let min = config.min + DVec2::new(i as f64, j as f64) * size;
+            let max = min + size;
+            let r = f.eval(&[
+                jit2::Intrvl::new(min.x, max.x),
+                jit2::Intrvl::new(min.y, max.y),
+            ]);
+            kept += !(r.lo > 0.0 || r.hi < 0.0) as usize;
+        }
+    }
+    kept
+}
+
+fn subdivision(c: &mut Criterion) {
+    let mut group = c.benchmark_group("subdivision");
+    for program in [Program::Union, Program::Dense1, Program::Dense3] {
+        let config = Iso2DConfig {
+            min: DVec2::new(-2.1, -1.9),
+            max: DVec2::new(2.9, 2.1),
+            intrvl_depth: 8,
+            program,
+            ..Default::default()
+        };
+        let name = format!("{program:?}");
+
+        let jit = jit2::JIT::init();
+        let intrvl =
+            jit.compile::<jit2::Intrvl>("intrvl", &JitProgram::from(program.bytecode()), 2);
+        group.bench_with_input(BenchmarkId::new("uniform", &name), &config, |b, config| {
+            b.iter(|| uniform_grid(&intrvl, black_box(config)))
+        });
+
+        let f = iso::JitFunction::new(program);
+        group.bench_with_input(
+            BenchmarkId::new("specialized", &name),
+            &config,
+            |b, config| b.iter(|| iso::build_grid(black_box(config), &f, &[0.0])),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, jit_native_vs_interpreter, subdivision);
 criterion_main!(benches);
 
 // criterion_group!(
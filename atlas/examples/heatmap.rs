@@ -0,0 +1,52 @@
+//! renders the heatmap of a program to a png without opening a window
+//!
+//! `cargo run -p atlas --example heatmap -- <program.asm> <out.png> [resolution]`, the program is
+//! written in the assembly format of [`atlas::vm::asm`] and sampled over [-2, 2] x [-2, 2]
+
+use atlas::heatmap::{HeatmapConfig, export_png};
+use atlas::iso::{Iso2DConfig, JitFunction};
+use glam::DVec2;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [src, out, rest @ ..] = args.as_slice() else {
+        eprintln!("usage: heatmap <program.asm> <out.png> [resolution]");
+        std::process::exit(2);
+    };
+    let resolution = match rest.first().map(|r| r.parse()) {
+        None => HeatmapConfig::default().resolution,
+        Some(Ok(r)) => r,
+        Some(Err(e)) => {
+            eprintln!("invalid resolution: {e}");
+            std::process::exit(2);
+        }
+    };
+
+    let src = std::fs::read_to_string(src).unwrap_or_else(|e| {
+        eprintln!("failed to read {src}: {e}");
+        std::process::exit(1);
+    });
+    let code = atlas::vm::asm::from_asm(&src).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    });
+
+    let program = Iso2DConfig::default().program;
+    let config = Iso2DConfig {
+        min: DVec2::splat(-2.0),
+        max: DVec2::splat(2.0),
+        program,
+        code: Some((program, code)),
+        ..Default::default()
+    };
+    let heatmap = HeatmapConfig {
+        resolution,
+        ..Default::default()
+    };
+
+    let f = JitFunction::from_config(&config);
+    if let Err(e) = export_png(out, &config, &f, &heatmap) {
+        eprintln!("failed to write {out}: {e}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,17 @@
+# sin(sin(1/x) + cos(1/y)) - cos(sin(1/x * 1/y) + cos(1/x)), iso::Program::Dense2
+reg x = 1
+reg y = 2
+
+DIV[1_f, x] -> x
+DIV[1_f, y] -> y
+SIN[x] -> 3
+COS[y] -> 4
+ADD[3, 4] -> 3
+SIN[3] -> 3
+MUL[x, y] -> 5
+SIN[5] -> 5
+COS[x] -> 6
+ADD[5, 6] -> 5
+COS[5] -> 5
+SUB[3, 5] -> x
+EXT
@@ -139,6 +139,70 @@ impl CameraController {
         (min, max)
     }
 
+    /// the pan state as a [`Pan2D`], both directions of the screen mapping go through it
+    fn pan_2d(&self) -> (Pan2D, CameraConfig) {
+        let pan = Pan2D {
+            pos: self.center.truncate(),
+            zoom: self.zoom,
+            d_pos: DVec2::ZERO,
+            d_zoom: 0.0,
+        };
+        let config = CameraConfig {
+            aspect: self.aspect,
+            vp_height: self.vp_height,
+            vp_width: self.vp_width,
+            ..Default::default()
+        };
+        (pan, config)
+    }
+
+    /// screen is relative to the viewport origin (top left)
+    pub fn pan_screen_to_world(&self, screen: Vec2) -> DVec2 {
+        let (pan, config) = self.pan_2d();
+        // Pan2D measures screen from the bottom left
+        pan.screen_to_world(Vec2::new(screen.x, self.vp_height - screen.y), &config)
+    }
+
+    /// inverse of [`CameraController::pan_screen_to_world`]
+    pub fn pan_world_to_screen(&self, world: DVec2) -> Vec2 {
+        let (pan, config) = self.pan_2d();
+        let screen = pan.world_to_screen(world, &config);
+        Vec2::new(screen.x, self.vp_height - screen.y)
+    }
+
+    fn orbit_view_proj(&self, zoomed: bool) -> Mat4 {
+        let view = if zoomed {
+            self.view_mat_zoomed_kind(CameraKind::Orbit)
+        } else {
+            self.view_mat_kind(CameraKind::Orbit)
+        };
+        self.proj_mat_kind(CameraKind::Orbit) * view
+    }
+
+    /// returns origin and direction of the camera ray through screen
+    pub fn orbit_screen_ray(&self, screen: Vec2, zoomed: bool) -> (Vec3, Vec3) {
+        let ndc = Vec2::new(
+            screen.x / self.vp_width * 2.0 - 1.0,
+            1.0 - screen.y / self.vp_height * 2.0,
+        );
+        let inv = self.orbit_view_proj(zoomed).inverse();
+        let near = inv.project_point3(ndc.extend(0.0));
+        let far = inv.project_point3(ndc.extend(0.5));
+        (near, (far - near).normalize())
+    }
+
+    pub fn orbit_world_to_screen(&self, world: Vec3, zoomed: bool) -> Option<Vec2> {
+        let clip = self.orbit_view_proj(zoomed) * world.extend(1.0);
+        if clip.w <= 0.0 {
+            return None;
+        }
+        let ndc = clip.truncate() / clip.w;
+        Some(Vec2::new(
+            (ndc.x + 1.0) * 0.5 * self.vp_width,
+            (1.0 - ndc.y) * 0.5 * self.vp_height,
+        ))
+    }
+
     pub fn proj_mat_kind(&self, kind: CameraKind) -> Mat4 {
         match kind {
             CameraKind::Orbit => {
@@ -329,6 +393,17 @@ impl Pan2D {
         DVec2::new(min.x + u * (max.x - min.x), min.y + v * (max.y - min.y))
     }
 
+    /// inverse of [`Pan2D::screen_to_world`]
+    pub fn world_to_screen(&self, world: DVec2, config: &CameraConfig) -> Vec2 {
+        let (min, max) = self.get_bounds(config);
+        let vp_width = config.vp_height * config.aspect;
+        let uv = (world - min) / (max - min);
+        Vec2::new(
+            (uv.x * vp_width as f64) as f32,
+            (uv.y * config.vp_height as f64) as f32,
+        )
+    }
+
     pub fn proj_mat(&self, config: &CameraConfig) -> Mat4 {
         Mat4::orthographic_lh(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0)
     }
@@ -760,3 +835,32 @@ impl Camera {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pan_screen_round_trip() {
+        let mut camera = CameraController::orbit(Vec3::Z, Vec3::ZERO, 1.0);
+        camera.kind = CameraKind::Pan;
+        camera.center = DVec3::new(3.0, -2.0, 0.0);
+        camera.zoom = 5.0;
+        camera.set_aspect(1280, 720);
+
+        for screen in [
+            Vec2::ZERO,
+            Vec2::new(1280.0, 720.0),
+            Vec2::new(640.0, 360.0),
+            Vec2::new(17.5, 603.25),
+        ] {
+            let world = camera.pan_screen_to_world(screen);
+            let back = camera.pan_world_to_screen(world);
+            assert!((back - screen).abs().max_element() < 1e-3, "{screen} -> {back}");
+        }
+
+        let (min, max) = camera.pan_get_bounds();
+        assert!((camera.pan_world_to_screen(min) - Vec2::new(0.0, 720.0)).length() < 1e-3);
+        assert!((camera.pan_world_to_screen(max) - Vec2::new(1280.0, 0.0)).length() < 1e-3);
+    }
+}
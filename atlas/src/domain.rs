@@ -0,0 +1,142 @@
+use egui_probe::EguiProbe;
+use glam::{DVec2, Vec3};
+use utils::Complex;
+
+use crate::heatmap::NAN_COLORS;
+use crate::iso::{Iso2DConfig, JitFunction};
+
+/// colors f(z) with z = x + iy, hue from arg(f(z)) and brightness from |f(z)|
+#[derive(Debug, Clone, PartialEq, EguiProbe)]
+pub struct DomainConfig {
+    #[egui_probe(toggle_switch)]
+    pub enabled: bool,
+    /// width of the sampled field in pixels
+    pub resolution: u32,
+    /// shade the bands between powers of two of |f(z)|
+    pub modulus_bands: bool,
+}
+
+impl Default for DomainConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            resolution: 256,
+            modulus_bands: true,
+        }
+    }
+}
+
+/// fully saturated color of hue h in [0, 1)
+pub fn hue_color(h: f32) -> Vec3 {
+    let h = h.rem_euclid(1.0) * 6.0;
+    let x = 1.0 - (h % 2.0 - 1.0).abs();
+    match h as u32 {
+        0 => Vec3::new(1.0, x, 0.0),
+        1 => Vec3::new(x, 1.0, 0.0),
+        2 => Vec3::new(0.0, 1.0, x),
+        3 => Vec3::new(0.0, x, 1.0),
+        4 => Vec3::new(x, 0.0, 1.0),
+        _ => Vec3::new(1.0, 0.0, x),
+    }
+}
+
+/// zeros are black, poles white and |w| = 1 is the pure hue
+pub fn domain_color(w: Complex, modulus_bands: bool) -> Vec3 {
+    use std::f64::consts::{FRAC_2_PI, TAU};
+
+    let r = w.abs();
+    let hue = (w.arg() / TAU) as f32;
+    let mut l = (FRAC_2_PI * r.atan()) as f32;
+
+    if modulus_bands && r.is_normal() {
+        l *= 0.85 + 0.15 * r.log2().rem_euclid(1.0) as f32;
+    }
+
+    // hsl with full saturation
+    let c = 1.0 - (2.0 * l - 1.0).abs();
+    Vec3::splat(l - c * 0.5) + hue_color(hue) * c
+}
+
+/// values of f(x + iy) sampled on a regular grid, rows from top to bottom
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComplexField {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<Complex>,
+}
+
+impl ComplexField {
+    pub fn sample(f: &JitFunction, min: DVec2, max: DVec2, width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            data: f.sample_grid_complex(min, max, width, height),
+        }
+    }
+
+    /// samples f over the config bounds with the aspect ratio of the bounds
+    pub fn from_config(config: &Iso2DConfig, f: &JitFunction, width: u32) -> Self {
+        let size = config.max - config.min;
+        let height = ((width as f64 * size.y / size.x).round() as u32).max(1);
+        Self::sample(f, config.min, config.max, width.max(1), height)
+    }
+
+    /// maps the field to rgba8, non finite values are shaded with stripes
+    pub fn to_rgba(&self, config: &DomainConfig) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity(self.data.len() * 4);
+        for (k, &w) in self.data.iter().enumerate() {
+            if w.is_finite() {
+                let c = domain_color(w, config.modulus_bands) * 255.0;
+                rgba.extend([c.x as u8, c.y as u8, c.z as u8, 255]);
+            } else {
+                let (i, j) = (k as u32 % self.width, k as u32 / self.width);
+                rgba.extend(NAN_COLORS[((i + j) / 4 % 2) as usize]);
+            }
+        }
+        rgba
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn colors() {
+        assert_eq!(domain_color(Complex::ONE, false), Vec3::X);
+        assert_eq!(domain_color(Complex::ZERO, true), Vec3::ZERO);
+        assert_eq!(domain_color(Complex::real(f64::MAX), false), Vec3::ONE);
+
+        let c = domain_color(Complex::real(-1.0), false);
+        assert!(
+            (c - Vec3::new(0.0, 1.0, 1.0)).abs().max_element() < 1e-6,
+            "{c}"
+        );
+        let c = domain_color(Complex::I, false);
+        assert!(
+            (c - Vec3::new(0.5, 1.0, 0.0)).abs().max_element() < 1e-6,
+            "{c}"
+        );
+    }
+
+    #[test]
+    fn sample_z2() {
+        // x^2 + x - y with y = 0 is z^2 + z
+        let config = Iso2DConfig {
+            min: DVec2::splat(-1.0),
+            max: DVec2::splat(1.0),
+            program: crate::iso::Program::X2X,
+            ..Default::default()
+        };
+        let f = JitFunction::new(config.program);
+        let field = ComplexField::from_config(&config, &f, 4);
+        assert_eq!((field.width, field.height), (4, 4));
+
+        // top left pixel center
+        let z = Complex::new(-0.75, 0.75);
+        assert_eq!(field.data[0], z.mul(z).add(z));
+
+        let rgba = field.to_rgba(&Default::default());
+        assert_eq!(rgba.len(), 4 * 4 * 4);
+    }
+}
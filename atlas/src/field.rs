@@ -0,0 +1,368 @@
+use egui_probe::EguiProbe;
+use glam::{DVec2, Vec3};
+
+use crate::heatmap::Colormap;
+use crate::iso::Program;
+use crate::{LineSegmentInst, vm};
+
+/// 2d systems (u(x, y), v(x, y)), the program returns u and v
+#[derive(Debug, Default, Clone, Copy, PartialEq, EguiProbe)]
+pub enum VectorProgram {
+    #[egui_probe(name = "(-y, x)")]
+    Rotation,
+    #[egui_probe(name = "(x, -y)")]
+    Saddle,
+    #[default]
+    #[egui_probe(name = "(y, -sin(x))")]
+    Pendulum,
+    #[egui_probe(name = "(y, (1-x^2)*y-x)")]
+    VanDerPol,
+    #[egui_probe(name = "(x*(1-y), y*(x-1))")]
+    LotkaVolterra,
+}
+
+impl VectorProgram {
+    pub fn opcode(&self) -> Vec<vm::Opcode> {
+        use vm::op;
+        match self {
+            VectorProgram::Rotation => [
+                op::SUB_IMM_REG(0.0, 2, 3),
+                op::MOV(1, 2),
+                op::MOV(3, 1),
+                op::RET(1),
+                op::RET(2),
+                op::EXT(0),
+            ]
+            .to_vec(),
+            VectorProgram::Saddle => [
+                op::SUB_IMM_REG(0.0, 2, 2),
+                op::RET(1),
+                op::RET(2),
+                op::EXT(0),
+            ]
+            .to_vec(),
+            VectorProgram::Pendulum => [
+                op::SIN(1, 3),
+                op::SUB_IMM_REG(0.0, 3, 3),
+                op::MOV(2, 1),
+                op::MOV(3, 2),
+                op::RET(1),
+                op::RET(2),
+                op::EXT(0),
+            ]
+            .to_vec(),
+            VectorProgram::VanDerPol => [
+                op::MUL_REG_REG(1, 1, 3),
+                op::SUB_IMM_REG(1.0, 3, 3),
+                op::MUL_REG_REG(3, 2, 3),
+                op::SUB_REG_REG(3, 1, 3),
+                op::MOV(2, 1),
+                op::MOV(3, 2),
+                op::RET(1),
+                op::RET(2),
+                op::EXT(0),
+            ]
+            .to_vec(),
+            VectorProgram::LotkaVolterra => [
+                op::SUB_IMM_REG(1.0, 2, 3),
+                op::MUL_REG_REG(1, 3, 3),
+                op::SUB_REG_IMM(1, 1.0, 4),
+                op::MUL_REG_REG(2, 4, 2),
+                op::MOV(3, 1),
+                op::RET(1),
+                op::RET(2),
+                op::EXT(0),
+            ]
+            .to_vec(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, EguiProbe)]
+pub enum FieldKind {
+    #[egui_probe(name = "vector field")]
+    Vector(VectorProgram),
+    /// dy/dx = f(x, y) with f one of the 2d programs
+    #[egui_probe(name = "slope field")]
+    Slope(Program),
+}
+
+#[derive(Debug, Clone, PartialEq, EguiProbe)]
+pub struct FieldConfig {
+    #[egui_probe(toggle_switch)]
+    pub enabled: bool,
+    pub kind: FieldKind,
+    /// number of arrows along the x axis
+    pub density: u32,
+    /// local error tolerance of the rk45 integrator
+    pub tolerance: f64,
+    pub max_steps: u32,
+
+    #[egui_probe(skip)]
+    pub seeds: Vec<DVec2>,
+}
+
+impl Default for FieldConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            kind: FieldKind::Vector(Default::default()),
+            density: 24,
+            tolerance: 1e-6,
+            max_steps: 2000,
+            seeds: vec![],
+        }
+    }
+}
+
+/// evaluates a vector or slope field with the interpreter
+pub struct Field {
+    kind: FieldKind,
+    op_codes: Vec<vm::Opcode>,
+    vm: vm::VM<f64>,
+}
+
+impl Field {
+    pub fn new(kind: FieldKind) -> Self {
+        let op_codes = match kind {
+            FieldKind::Vector(p) => p.opcode(),
+            FieldKind::Slope(p) => p.opcode(),
+        };
+
+        Self {
+            kind,
+            op_codes,
+            vm: vm::VM::with_instr_table(vm::F64InstrTable),
+        }
+    }
+
+    /// direction of the field at p, slope fields are parametrized by x
+    pub fn eval(&mut self, p: DVec2) -> DVec2 {
+        let out = self.vm.call([p.x, p.y, 0.0], &self.op_codes);
+        match self.kind {
+            FieldKind::Vector(_) => DVec2::new(out[0], out[1]),
+            FieldKind::Slope(_) => DVec2::new(1.0, out[0]),
+        }
+    }
+}
+
+/// one dormand-prince step, returns the 5th order solution and the error estimate
+pub fn rk45_step(f: &mut impl FnMut(DVec2) -> DVec2, p: DVec2, h: f64) -> (DVec2, f64) {
+    let k1 = f(p);
+    let k2 = f(p + h * (k1 / 5.0));
+    let k3 = f(p + h * (k1 * (3.0 / 40.0) + k2 * (9.0 / 40.0)));
+    let k4 = f(p + h * (k1 * (44.0 / 45.0) - k2 * (56.0 / 15.0) + k3 * (32.0 / 9.0)));
+    let k5 = f(p + h
+        * (k1 * (19372.0 / 6561.0) - k2 * (25360.0 / 2187.0) + k3 * (64448.0 / 6561.0)
+            - k4 * (212.0 / 729.0)));
+    let k6 = f(p + h
+        * (k1 * (9017.0 / 3168.0) - k2 * (355.0 / 33.0)
+            + k3 * (46732.0 / 5247.0)
+            + k4 * (49.0 / 176.0)
+            - k5 * (5103.0 / 18656.0)));
+
+    let next = p + h
+        * (k1 * (35.0 / 384.0) + k3 * (500.0 / 1113.0) + k4 * (125.0 / 192.0)
+            - k5 * (2187.0 / 6784.0)
+            + k6 * (11.0 / 84.0));
+    let k7 = f(next);
+
+    // difference between the 5th and the embedded 4th order solution
+    let err = h
+        * (k1 * (71.0 / 57600.0) - k3 * (71.0 / 16695.0) + k4 * (71.0 / 1920.0)
+            - k5 * (17253.0 / 339200.0)
+            + k6 * (22.0 / 525.0)
+            - k7 * (1.0 / 40.0));
+
+    (next, err.length())
+}
+
+/// integrates p' = f(p) from p0 with adaptive step size until the path leaves [min, max]
+///
+/// dir is 1 or -1, a single step is never longer than max_len
+pub fn trajectory(
+    mut f: impl FnMut(DVec2) -> DVec2,
+    p0: DVec2,
+    dir: f64,
+    (min, max): (DVec2, DVec2),
+    max_len: f64,
+    tol: f64,
+    max_steps: u32,
+) -> Vec<DVec2> {
+    let mut path = vec![p0];
+    let mut p = p0;
+    let mut h = dir * max_len;
+
+    for _ in 0..max_steps {
+        let speed = f(p).length();
+        if !speed.is_finite() || speed <= f64::EPSILON {
+            break;
+        }
+        let h_max = max_len / speed;
+        h = h.signum() * h.abs().min(h_max);
+
+        let (next, err) = rk45_step(&mut f, p, h);
+        if !err.is_finite() {
+            break;
+        }
+
+        let accept = err <= tol || h.abs() <= h_max * 1e-6;
+        if accept {
+            p = next;
+            path.push(p);
+            if p.cmplt(min).any() || p.cmpgt(max).any() {
+                break;
+            }
+        }
+
+        let scale = 0.9 * (tol / err).powf(0.2);
+        h *= if scale.is_finite() {
+            scale.clamp(0.2, 5.0)
+        } else {
+            5.0
+        };
+    }
+
+    path
+}
+
+/// arrows / slope marks on a grid and trajectories through the seeds
+///
+/// segments are normalized to [-0.5, 0.5] like the 2d isolines
+pub fn build_segments(config: &FieldConfig, min: DVec2, max: DVec2) -> Vec<LineSegmentInst> {
+    let size = max - min;
+    let to_local = |p: DVec2| ((p - min) / size - 0.5).as_vec2().extend(0.0);
+    let mut segments = vec![];
+    let mut line = |a: DVec2, b: DVec2, col: Vec3| {
+        segments.push(LineSegmentInst {
+            a: to_local(a),
+            b: to_local(b),
+            col,
+        })
+    };
+
+    let mut field = Field::new(config.kind);
+    let nx = config.density.max(1);
+    let spacing = size.x / nx as f64;
+    let ny = ((size.y / spacing).round() as u32).max(1);
+
+    let mut samples = Vec::with_capacity((nx * ny) as usize);
+    for j in 0..ny {
+        for i in 0..nx {
+            let p = min + DVec2::new(i as f64 + 0.5, j as f64 + 0.5) * spacing;
+            samples.push((p, field.eval(p)));
+        }
+    }
+
+    let max_len = samples
+        .iter()
+        .map(|(_, v)| v.length())
+        .filter(|l| l.is_finite())
+        .fold(0.0, f64::max);
+
+    for &(p, v) in &samples {
+        let len = v.length();
+        if !len.is_finite() || len <= f64::EPSILON {
+            continue;
+        }
+        let d = v / len;
+
+        match config.kind {
+            FieldKind::Vector(_) => {
+                let t = len / max_len;
+                let tip = d * spacing * 0.8 * t.max(0.2);
+                let col = Colormap::Viridis.eval(t as f32);
+                let (a, b) = (p - tip * 0.5, p + tip * 0.5);
+                line(a, b, col);
+                // arrow head
+                for side in [-1.0, 1.0] {
+                    let back = DVec2::from_angle(side * 2.6).rotate(tip * 0.3);
+                    line(b, b + back, col);
+                }
+            }
+            FieldKind::Slope(_) => {
+                let half = d * spacing * 0.35;
+                line(p - half, p + half, Vec3::splat(0.7));
+            }
+        }
+    }
+
+    let bounds = (min - size, max + size);
+    let col = Vec3::new(0.98, 0.7, 0.53);
+    for &seed in &config.seeds {
+        for dir in [-1.0, 1.0] {
+            let path = trajectory(
+                |p| field.eval(p),
+                seed,
+                dir,
+                bounds,
+                spacing * 0.25,
+                config.tolerance,
+                config.max_steps,
+            );
+            for w in path.windows(2) {
+                line(w[0], w[1], col);
+            }
+        }
+    }
+
+    segments
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rotation_orbit() {
+        let mut field = Field::new(FieldKind::Vector(VectorProgram::Rotation));
+        assert_eq!(field.eval(DVec2::new(1.0, 2.0)), DVec2::new(-2.0, 1.0));
+
+        let bounds = (DVec2::splat(-2.0), DVec2::splat(2.0));
+        let path = trajectory(|p| field.eval(p), DVec2::X, 1.0, bounds, 0.05, 1e-9, 500);
+        assert!(path.len() > 100);
+        for p in path {
+            assert!((p.length() - 1.0).abs() < 1e-6, "{p}");
+        }
+    }
+
+    #[test]
+    fn slope_solution() {
+        // dy/dx = x - y through (0, 0) is y = x - 1 + e^-x
+        let mut field = Field::new(FieldKind::Slope(Program::X));
+        let bounds = (DVec2::new(-1.0, -10.0), DVec2::new(3.0, 10.0));
+        let path = trajectory(
+            |p| field.eval(p),
+            DVec2::ZERO,
+            1.0,
+            bounds,
+            0.1,
+            1e-10,
+            1000,
+        );
+
+        let end = *path.last().unwrap();
+        assert!(end.x > 3.0);
+        for p in path {
+            let y = p.x - 1.0 + (-p.x).exp();
+            assert!((p.y - y).abs() < 1e-6, "{p} {y}");
+        }
+    }
+
+    #[test]
+    fn arrows() {
+        let config = FieldConfig {
+            density: 4,
+            kind: FieldKind::Vector(VectorProgram::Saddle),
+            seeds: vec![DVec2::new(0.5, 0.5)],
+            ..Default::default()
+        };
+
+        let segments = build_segments(&config, DVec2::splat(-1.0), DVec2::splat(1.0));
+        // 16 arrows with heads and the trajectory
+        assert!(segments.len() > 16 * 3);
+        for s in &segments[..16 * 3] {
+            assert!(s.a.abs().max_element() <= 0.5 && s.b.abs().max_element() <= 0.5);
+        }
+    }
+}
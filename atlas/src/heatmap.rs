@@ -0,0 +1,434 @@
+use egui_probe::EguiProbe;
+use glam::{DVec2, Vec3};
+
+use crate::iso::{Iso2DConfig, JitFunction};
+
+#[derive(Debug, Clone, Copy, PartialEq, EguiProbe)]
+pub enum Colormap {
+    Viridis,
+    #[egui_probe(name = "diverging")]
+    Diverging,
+}
+
+#[derive(Debug, Clone, PartialEq, EguiProbe)]
+pub struct HeatmapConfig {
+    #[egui_probe(toggle_switch)]
+    pub enabled: bool,
+    /// width of the sampled field in pixels
+    pub resolution: u32,
+    pub colormap: Colormap,
+    /// scale values by sign(v) * ln(1 + |v|)
+    pub log_scale: bool,
+}
+
+impl Default for HeatmapConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            resolution: 256,
+            colormap: Colormap::Viridis,
+            log_scale: false,
+        }
+    }
+}
+
+// viridis sampled at 9 evenly spaced stops
+const VIRIDIS: [Vec3; 9] = [
+    Vec3::new(0.267, 0.005, 0.329),
+    Vec3::new(0.278, 0.175, 0.483),
+    Vec3::new(0.231, 0.322, 0.545),
+    Vec3::new(0.173, 0.449, 0.558),
+    Vec3::new(0.128, 0.567, 0.551),
+    Vec3::new(0.153, 0.683, 0.502),
+    Vec3::new(0.361, 0.784, 0.388),
+    Vec3::new(0.668, 0.862, 0.196),
+    Vec3::new(0.993, 0.906, 0.144),
+];
+
+const DIVERGING: [Vec3; 3] = [
+    Vec3::new(0.230, 0.299, 0.754),
+    Vec3::new(0.865, 0.865, 0.865),
+    Vec3::new(0.706, 0.016, 0.150),
+];
+
+pub(crate) const NAN_COLORS: [[u8; 4]; 2] = [[40, 40, 40, 255], [64, 64, 64, 255]];
+
+fn lerp_stops(stops: &[Vec3], t: f32) -> Vec3 {
+    let t = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+    let i = (t as usize).min(stops.len() - 2);
+    stops[i].lerp(stops[i + 1], t - i as f32)
+}
+
+impl Colormap {
+    /// t in [0, 1], for diverging 0.5 is zero
+    pub fn eval(&self, t: f32) -> Vec3 {
+        match self {
+            Colormap::Viridis => lerp_stops(&VIRIDIS, t),
+            Colormap::Diverging => lerp_stops(&DIVERGING, t),
+        }
+    }
+}
+
+/// values of f sampled on a regular grid, rows from top to bottom
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScalarField {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<f64>,
+}
+
+impl ScalarField {
+    pub fn sample(f: &JitFunction, min: DVec2, max: DVec2, width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            data: f.sample_grid(min, max, width, height),
+        }
+    }
+
+    /// samples f over the config bounds with the aspect ratio of the bounds
+    pub fn from_config(config: &Iso2DConfig, f: &JitFunction, width: u32) -> Self {
+        let size = config.max - config.min;
+        let height = ((width as f64 * size.y / size.x).round() as u32).max(1);
+        Self::sample(f, config.min, config.max, width.max(1), height)
+    }
+
+    /// maps the field to rgba8, non finite values are shaded with stripes
+    pub fn to_rgba(&self, config: &HeatmapConfig) -> Vec<u8> {
+        let scale = |v: f64| {
+            if config.log_scale {
+                v.signum() * v.abs().ln_1p()
+            } else {
+                v
+            }
+        };
+
+        let (mut lo, mut hi) = (f64::INFINITY, f64::NEG_INFINITY);
+        for &v in self.data.iter().filter(|v| v.is_finite()) {
+            let v = scale(v);
+            lo = lo.min(v);
+            hi = hi.max(v);
+        }
+
+        let norm = |v: f64| -> f32 {
+            let t = match config.colormap {
+                Colormap::Viridis => (v - lo) / (hi - lo),
+                Colormap::Diverging => 0.5 + 0.5 * v / lo.abs().max(hi.abs()),
+            };
+            if t.is_finite() { t as f32 } else { 0.5 }
+        };
+
+        let mut rgba = Vec::with_capacity(self.data.len() * 4);
+        for (k, &v) in self.data.iter().enumerate() {
+            if v.is_finite() {
+                let c = config.colormap.eval(norm(scale(v))) * 255.0;
+                rgba.extend([c.x as u8, c.y as u8, c.z as u8, 255]);
+            } else {
+                let (i, j) = (k as u32 % self.width, k as u32 / self.width);
+                rgba.extend(NAN_COLORS[((i + j) / 4 % 2) as usize]);
+            }
+        }
+        rgba
+    }
+}
+
+pub fn write_png(
+    path: impl AsRef<std::path::Path>,
+    width: u32,
+    height: u32,
+    rgba: &[u8],
+) -> std::io::Result<()> {
+    let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(rgba)?;
+    Ok(())
+}
+
+/// renders the heatmap of f over the bounds of config to a png without a window, see the
+/// `heatmap` example
+pub fn export_png(
+    path: impl AsRef<std::path::Path>,
+    config: &Iso2DConfig,
+    f: &JitFunction,
+    heatmap: &HeatmapConfig,
+) -> std::io::Result<()> {
+    let field = ScalarField::from_config(config, f, heatmap.resolution);
+    write_png(path, field.width, field.height, &field.to_rgba(heatmap))
+}
+
+const SRC: &str = r#"
+struct WorldUniform {
+    light_pos: vec3<f32>,
+    _pad0: f32,
+    camera_pos: vec3<f32>,
+    _pad1: f32,
+
+    line_thickness_and_pad: vec4<f32>,
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+
+}
+
+@group(0) @binding(0)
+var<uniform> world: WorldUniform;
+
+@group(1) @binding(0)
+var field: texture_2d<f32>;
+@group(1) @binding(1)
+var field_sampler: sampler;
+
+struct FsIn {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) indx: u32) -> FsIn {
+    let uv = vec2(f32(indx & 1u), f32(indx >> 1u));
+
+    var out: FsIn;
+    out.pos = world.proj * world.view * vec4(uv.x * 2.0 - 1.0, uv.y * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+@fragment
+fn fs_main(in: FsIn) -> @location(0) vec4<f32> {
+    return textureSample(field, field_sampler, in.uv);
+}
+"#;
+
+pub struct Pipeline {
+    pipeline: wgpu::RenderPipeline,
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    texture: Option<(wgpu::Texture, wgpu::BindGroup)>,
+    pub visible: bool,
+}
+
+impl Pipeline {
+    pub fn init(wgpu: &crate::WGPU) -> Self {
+        let layout = wgpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("heatmap_bind_group_layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+
+        let sampler = wgpu.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("heatmap_sampler"),
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            pipeline: load_pipeline(wgpu, &layout),
+            layout,
+            sampler,
+            texture: None,
+            visible: false,
+        }
+    }
+
+    pub fn upload(&mut self, wgpu: &crate::WGPU, width: u32, height: u32, rgba: &[u8]) {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+
+        let reuse = matches!(&self.texture, Some((t, _)) if t.size() == size);
+        if !reuse {
+            let texture = wgpu.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("heatmap_texture"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&Default::default());
+            let bind_group = wgpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("heatmap_bind_group"),
+                layout: &self.layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            });
+            self.texture = Some((texture, bind_group));
+        }
+
+        let (texture, _) = self.texture.as_ref().unwrap();
+        wgpu.queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            rgba,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+    }
+
+    /// draws the field behind everything else in the pass
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass, world_uniform: &wgpu::BindGroup) {
+        let Some((_, bind_group)) = &self.texture else {
+            return;
+        };
+        if !self.visible {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, world_uniform, &[]);
+        render_pass.set_bind_group(1, bind_group, &[]);
+        render_pass.draw(0..4, 0..1);
+    }
+}
+
+pub fn load_pipeline(wgpu: &crate::WGPU, layout: &wgpu::BindGroupLayout) -> wgpu::RenderPipeline {
+    let world_bind_group_layout = crate::WorldUniform::layout(wgpu);
+
+    let shader_module = wgpu
+        .device
+        .create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("heatmap"),
+            source: wgpu::ShaderSource::Wgsl(SRC.into()),
+        });
+
+    let pipeline_layout = wgpu
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("heatmap_pipeline_layout"),
+            bind_group_layouts: &[&world_bind_group_layout, layout],
+            push_constant_ranges: &[],
+        });
+
+    wgpu.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("heatmap_pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu.surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: None,
+                unclipped_depth: false,
+                front_face: wgpu::FrontFace::Ccw,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                strip_index_format: None,
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                conservative: false,
+            },
+            // never occludes the curves drawn on top
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: crate::AtlasRenderer::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: crate::multisample_state(),
+            multiview: None,
+            cache: None,
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn colormap_and_nan() {
+        let field = ScalarField {
+            width: 3,
+            height: 1,
+            data: vec![-2.0, f64::NAN, 2.0],
+        };
+
+        let mut config = HeatmapConfig::default();
+        let rgba = field.to_rgba(&config);
+        assert_eq!(rgba.len(), 12);
+        assert_eq!(rgba[0..3], [68, 1, 83]);
+        assert_eq!(rgba[4..8], NAN_COLORS[0]);
+        assert_eq!(rgba[8..11], [253, 231, 36]);
+
+        config.colormap = Colormap::Diverging;
+        config.log_scale = true;
+        let rgba = field.to_rgba(&config);
+        let (lo, hi) = (DIVERGING[0] * 255.0, DIVERGING[2] * 255.0);
+        assert_eq!(rgba[0..3], [lo.x as u8, lo.y as u8, lo.z as u8]);
+        assert_eq!(rgba[8..11], [hi.x as u8, hi.y as u8, hi.z as u8]);
+    }
+
+    #[test]
+    fn headless_png() {
+        let config = Iso2DConfig {
+            min: DVec2::splat(-2.0),
+            max: DVec2::new(2.0, 0.0),
+            program: crate::iso::Program::XY,
+            ..Default::default()
+        };
+        let heatmap = HeatmapConfig {
+            resolution: 32,
+            ..Default::default()
+        };
+
+        let path = std::env::temp_dir().join(format!("atlas_heatmap_{}.png", std::process::id()));
+        let f = JitFunction::from_config(&config);
+        export_png(&path, &config, &f, &heatmap).unwrap();
+
+        let decoder = png::Decoder::new(std::fs::File::open(&path).unwrap());
+        let reader = decoder.read_info().unwrap();
+        assert_eq!((reader.info().width, reader.info().height), (32, 16));
+        std::fs::remove_file(path).unwrap();
+    }
+}
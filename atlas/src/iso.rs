@@ -1,23 +1,25 @@
 use std::{
     cell::OnceCell,
     fmt,
+    rc::Rc,
     time::{Duration, Instant},
 };
 
-use compiler::jit2::{self, F64X2};
+use compiler::jit2::{self, F64X2, RowParams};
 #[cfg(feature = "native-codegen")]
 use compiler::{bytecode, jit};
 
 use egui_probe::EguiProbe;
 use glam::{DVec2, DVec3, I64Vec2, Vec3};
 use rayon::prelude::*;
+use utils::Complex;
 
 type BitGrid = bitgrid::BitGrid2D<u16>;
 
 // use utils::BitGrid;
 // type BitGrid = utils::BitGrid;
 
-use crate::{LineSegmentInst, Vertex, vm};
+use crate::{LineSegmentInst, Vertex, tape, vm};
 
 #[derive(Debug, Clone, PartialEq, EguiProbe)]
 pub struct Iso2DConfig {
@@ -34,10 +36,17 @@ pub struct Iso2DConfig {
     pub line_thickness: f32,
 
     pub program: Program,
+    pub complex: ComplexMode,
+    pub contour: ContourConfig,
     pub debug: bool,
 
     #[cfg_attr(target_arch = "wasm32", egui_probe(skip))]
     pub simd: bool,
+
+    /// bytecode restored from a scene, plotted instead of `program` while that is still the
+    /// program the scene was saved with, see [`Iso2DConfig::opcode`]
+    #[egui_probe(skip)]
+    pub code: Option<(Program, Vec<vm::Opcode>)>,
 }
 
 impl Default for Iso2DConfig {
@@ -49,13 +58,137 @@ impl Default for Iso2DConfig {
             subdiv_depth: 0,
             line_thickness: 1.,
             program: Program::Dense3,
+            complex: ComplexMode::Real,
+            contour: Default::default(),
             simd: false,
             debug: false,
+            code: None,
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, EguiProbe)]
+impl Iso2DConfig {
+    /// the saved bytecode if it is plotted, see [`Iso2DConfig::code`]
+    pub fn saved_code(&self) -> Option<&[vm::Opcode]> {
+        self.code
+            .as_ref()
+            .filter(|(p, _)| *p == self.program)
+            .map(|(_, code)| code.as_slice())
+    }
+
+    /// the plotted vm program
+    pub fn opcode(&self) -> Vec<vm::Opcode> {
+        self.saved_code()
+            .map_or_else(|| self.program.opcode(), <[_]>::to_vec)
+    }
+
+    /// the plotted program for the jit, saved bytecode without a jit version plots `program`
+    #[cfg(feature = "native-codegen")]
+    pub fn bytecode(&self) -> Vec<jit::Instr> {
+        self.saved_code()
+            .and_then(jit_bytecode)
+            .unwrap_or_else(|| self.program.bytecode())
+    }
+}
+
+/// the jit bytecode of a vm program returning in register 0, None if the jit can't run it
+#[cfg(feature = "native-codegen")]
+pub fn jit_bytecode(ops: &[vm::Opcode]) -> Option<Vec<jit::Instr>> {
+    vm::opt::to_bytecode(ops)
+        .filter(|(program, _)| program.outputs == [0])
+        .map(|(program, _)| program.bytecode)
+}
+
+/// plots the real or imaginary part of f(z) with z = x + iy, the program input y is set to zero
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EguiProbe)]
+pub enum ComplexMode {
+    #[egui_probe(name = "f(x, y)")]
+    Real,
+    #[egui_probe(name = "Re(f(x+iy))")]
+    Re,
+    #[egui_probe(name = "Im(f(x+iy))")]
+    Im,
+}
+
+#[derive(Debug, Clone, PartialEq, EguiProbe)]
+pub enum Levels {
+    #[egui_probe(name = "evenly spaced")]
+    Range { from: f64, to: f64, count: u32 },
+    #[egui_probe(name = "list")]
+    List(Vec<f64>),
+}
+
+/// plots f(x, y) = c for every level c instead of only f(x, y) = 0
+#[derive(Debug, Clone, PartialEq, EguiProbe)]
+pub struct ContourConfig {
+    #[egui_probe(toggle_switch)]
+    pub enabled: bool,
+    pub levels: Levels,
+}
+
+impl Default for ContourConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            levels: Levels::Range {
+                from: -1.0,
+                to: 1.0,
+                count: 5,
+            },
+        }
+    }
+}
+
+impl ContourConfig {
+    /// sorted list of finite levels, only the zero level if disabled
+    pub fn levels(&self) -> Vec<f64> {
+        if !self.enabled {
+            return vec![0.0];
+        }
+
+        let mut levels = match &self.levels {
+            Levels::Range { from, to, count } => match *count {
+                0 => vec![],
+                1 => vec![*from],
+                n => (0..n)
+                    .map(|i| from + (to - from) * i as f64 / (n - 1) as f64)
+                    .collect(),
+            },
+            Levels::List(l) => l.clone(),
+        };
+
+        levels.retain(|l| l.is_finite());
+        levels.sort_by(f64::total_cmp);
+        levels.dedup();
+        levels
+    }
+}
+
+/// line color of the i-th of n levels
+pub fn level_color(i: usize, n: usize) -> Vec3 {
+    if n <= 1 {
+        return Vec3::ONE;
+    }
+
+    // hue ramp from blue to red
+    let h = (1.0 - i as f32 / (n - 1) as f32) * 240.0 / 60.0;
+    let x = 1.0 - (h % 2.0 - 1.0).abs();
+    match h as u32 {
+        0 => Vec3::new(1.0, x, 0.0),
+        1 => Vec3::new(x, 1.0, 0.0),
+        2 => Vec3::new(0.0, 1.0, x),
+        _ => Vec3::new(0.0, x, 1.0),
+    }
+}
+
+/// true if any of the sorted levels lies in [l, u]
+#[inline]
+fn contains_level(r: vm::Range, levels: &[f64]) -> bool {
+    let i = levels.partition_point(|&c| c < r.l);
+    i < levels.len() && levels[i] <= r.u
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, EguiProbe)]
 pub enum Program {
     #[egui_probe(name = "x-y=0")]
     X,
@@ -63,6 +196,8 @@ pub enum Program {
     XY,
     #[egui_probe(name = "x^2-x=0")]
     X2X,
+    #[egui_probe(name = "min(x^2+y^2-1, (x-1.5)^2+y^2-0.5)=0")]
+    Union,
     #[egui_probe(name = "sin(x)-y=0")]
     Sin,
     #[egui_probe(name = "cos(x)-y=0")]
@@ -79,6 +214,13 @@ pub enum Program {
     Dense1,
     #[egui_probe(name = "sin(sin(1/x)+cos(1/y))-cos(sin(1/(x*y))+cos(1/x))=0")]
     Dense2,
+    #[egui_probe(name = "y-(x<0 ? x+1 : sin(x))=0")]
+    Piecewise,
+    #[egui_probe(name = "y-floor(x)=0")]
+    Floor,
+    #[egui_probe(name = "y-gamma(x)=0")]
+    Gamma,
+    #[default]
     #[egui_probe(name = "sin(sin(1/x)+sin(1/y))-sin(sin(1/(x*y))+sin(1/x))=0")]
     Dense3,
 }
@@ -191,6 +333,37 @@ impl Program {
                 op::EXT(0),
             ]
             .into(),
+            Program::Piecewise => [
+                // x + 1 -> 3
+                op::ADD_REG_IMM(1, 1.0, 3),
+                // sin(x) -> 4
+                op::SIN(1, 4),
+                // x < 0 -> 5
+                op::LT_REG_IMM(1, 0.0, 5),
+                // x < 0 ? x + 1 : sin(x) -> 5
+                op::SEL_REG_REG(3, 4, 5),
+                op::SUB_REG_REG(2, 5, 1),
+                op::EXT(0),
+            ]
+            .into(),
+            Program::Floor => [op::FLOOR(1, 3), op::SUB_REG_REG(2, 3, 1), op::EXT(0)].into(),
+            Program::Gamma => [op::GAMMA(1, 3), op::SUB_REG_REG(2, 3, 1), op::EXT(0)].into(),
+            Program::Union => [
+                // y^2 -> 2
+                op::MUL_REG_REG(2, 2, 2),
+                // x^2 + y^2 - 1 -> 3
+                op::MUL_REG_REG(1, 1, 3),
+                op::ADD_REG_REG(3, 2, 3),
+                op::SUB_REG_IMM(3, 1.0, 3),
+                // (x-1.5)^2 + y^2 - 0.5 -> 1
+                op::SUB_REG_IMM(1, 1.5, 1),
+                op::MUL_REG_REG(1, 1, 1),
+                op::ADD_REG_REG(1, 2, 1),
+                op::SUB_REG_IMM(1, 0.5, 1),
+                op::MIN_REG_REG(3, 1, 1),
+                op::EXT(0),
+            ]
+            .into(),
         }
     }
 
@@ -284,166 +457,271 @@ impl Program {
                 SUB[0, 1] -> 0,
             ]
             .to_vec(),
+            Program::Union => compiler::bytecode! [
+                MUL[1, 1] -> 1,
+                MUL[0, 0] -> 2,
+                ADD[2, 1] -> 2,
+                SUB[2, imm(1.0)] -> 2,
+                SUB[0, imm(1.5)] -> 0,
+                MUL[0, 0] -> 0,
+                ADD[0, 1] -> 0,
+                SUB[0, imm(0.5)] -> 0,
+                MIN[2, 0] -> 0,
+            ]
+            .to_vec(),
+            // the select is only lowered once, in the vm program
+            Program::Piecewise => jit_bytecode(&self.opcode()).unwrap(),
+            Program::Floor => compiler::bytecode! [
+                FLOOR[0] -> 2,
+                SUB[1, 2] -> 0,
+            ]
+            .to_vec(),
+            Program::Gamma => compiler::bytecode! [
+                GAMMA[0] -> 2,
+                SUB[1, 2] -> 0,
+            ]
+            .to_vec(),
         }
     }
 }
 
-pub struct JitFunction<'a> {
+pub struct JitFunction {
+    /// shared with other functions compiled into the same module, see [`FunctionCache`]
     #[cfg(feature = "native-codegen")]
-    native_f64_to_f64: extern "C" fn(f64, f64) -> f64,
+    jit: Rc<jit2::JIT>,
     #[cfg(feature = "native-codegen")]
-    native_f64x2_to_f64x2: extern "C" fn(&mut [f64; 2], [f64; 2], [f64; 2]),
-
+    f64_fn: jit2::Entry<f64>,
+    /// row sampling kernel with the widest lanes of the host, see [`jit2::simd_lanes`]
     #[cfg(feature = "native-codegen")]
-    native_intrvl_to_intrvl: extern "C" fn(*mut [f64; 2], [f64; 2], [f64; 2]),
-
+    row_fn: jit2::RowEntry,
+    /// compiled on first use, see [`JitFunction::complex_fn`]
     #[cfg(feature = "native-codegen")]
-    f64x2_fn: extern "C" fn(F64X2, F64X2, *mut F64X2),
+    complex_fn: OnceCell<jit2::Entry<Complex>>,
+    /// the vm program compiled for intervals on first use, see [`JitFunction::intrvl_2d`]
     #[cfg(feature = "native-codegen")]
-    f64_fn: extern "C" fn(f64, f64) -> f64,
+    intrvl_fn: OnceCell<Option<jit2::Entry<jit2::Intrvl>>>,
     #[cfg(feature = "native-codegen")]
-    intrvl_fn: extern "C" fn(F64X2, F64X2, *mut F64X2),
+    program: jit::Program,
 
-    jit2: jit2::JIT<'a>,
-
-    // #[cfg(feature = "native-codegen")]
-    // native_f64x8_to_f64x8: extern "C" fn(*const [f64; 8], *const [f64; 8], *mut [f64; 8]),
-    // #[cfg(feature = "native-codegen")]
-    // native_f64x8_to_f64x8: extern "C" fn(*const [f64; 8], *const [f64; 8], *mut [f64; 8]),
     op_codes: Vec<vm::Opcode>,
+    complex: ComplexMode,
 }
 
-impl JitFunction<'_> {
-    pub fn new_3d(program: crate::iso_3d::Program3D) -> Self {
-        #[cfg(feature = "native-codegen")]
-        let jit_config = jit::CompConfig::default();
-        #[cfg(feature = "native-codegen")]
-        let mut jit = jit::JITCompiler::init();
-
+impl JitFunction {
+    fn compile(
+        #[cfg(feature = "native-codegen")] jit: Rc<jit2::JIT>,
+        bytecode: Vec<jit::Instr>,
+        op_codes: Vec<vm::Opcode>,
+    ) -> Self {
+        debug_assert_eq!(vm::verify(&op_codes), Ok(()), "invalid bytecode");
         #[cfg(feature = "native-codegen")]
-        let jit2 = jit2::JIT::init();
+        let program = jit::Program::from(bytecode);
 
-        let res = Self {
+        Self {
             #[cfg(feature = "native-codegen")]
-            native_f64_to_f64: jit
-                .compile_for_f64("jit_fn", &program.bytecode(), &jit_config)
-                .fn_ptr,
+            f64_fn: jit.compile("f64", &program, 2).entry(),
             #[cfg(feature = "native-codegen")]
-            native_f64x2_to_f64x2: jit
-                .compile_for_f64x2("jit_fn2", &program.bytecode(), &jit_config)
-                .fn_ptr,
-
-            #[cfg(feature = "native-codegen")]
-            native_intrvl_to_intrvl: jit
-                .compile_for_intrvl("intrvl_jit", &program.bytecode(), &jit_config)
-                .fn_ptr,
-
+            row_fn: match jit2::simd_lanes() {
+                8 => jit.compile_row::<jit2::F64X8>("row", &program),
+                4 => jit.compile_row::<jit2::F64X4>("row", &program),
+                2 => jit.compile_row::<F64X2>("row", &program),
+                _ => jit.compile_row::<f64>("row", &program),
+            }
+            .entry(),
             #[cfg(feature = "native-codegen")]
-            f64x2_fn: unsafe { jit2.compile_static_2f64x2_f64("f64x2", &program.bytecode()) },
+            complex_fn: OnceCell::new(),
             #[cfg(feature = "native-codegen")]
-            intrvl_fn: unsafe { jit2.compile_static_2intrvl_intrvl("intrvl", &program.bytecode()) },
-
+            intrvl_fn: OnceCell::new(),
             #[cfg(feature = "native-codegen")]
-            f64_fn: unsafe { jit2.compile_static_2f64_f64("f64", &program.bytecode()) },
+            jit,
             #[cfg(feature = "native-codegen")]
-            jit2,
-            // #[cfg(feature = "native-codegen")]
-            // native_f64x8_to_f64x8: jit.compile_for_f64x2x4("jit_fn4", &[], &jit_config).fn_ptr,
-            op_codes: program.opcode(),
-        };
-
-        // let mut out = [0.0; 2];
-        // (res.native_intrvl_to_intrvl)(&mut out, [1., 2.], [3., 4.]);
-
-        res
+            program,
+            op_codes,
+            complex: ComplexMode::Real,
+        }
     }
-    pub fn new(program: Program) -> Self {
-        #[cfg(feature = "native-codegen")]
-        let jit_config = jit::CompConfig::default();
-        #[cfg(feature = "native-codegen")]
-        let mut jit = jit::JITCompiler::init();
-
-        #[cfg(feature = "native-codegen")]
-        let jit2 = jit2::JIT::init();
 
-        let res = Self {
-            #[cfg(feature = "native-codegen")]
-            native_f64_to_f64: jit
-                .compile_for_f64("jit_fn", &program.bytecode(), &jit_config)
-                .fn_ptr,
+    pub fn new_3d(program: crate::iso_3d::Program3D) -> Self {
+        Self::compile(
             #[cfg(feature = "native-codegen")]
-            native_f64x2_to_f64x2: jit
-                .compile_for_f64x2("jit_fn2", &program.bytecode(), &jit_config)
-                .fn_ptr,
+            Rc::new(jit2::JIT::init()),
+            program.bytecode(),
+            program.opcode().to_vec(),
+        )
+    }
 
-            #[cfg(feature = "native-codegen")]
-            native_intrvl_to_intrvl: jit
-                .compile_for_intrvl("intrvl_jit", &program.bytecode(), &jit_config)
-                .fn_ptr,
+    pub fn new(program: Program) -> Self {
+        Self::new_complex(program, ComplexMode::Real)
+    }
 
-            #[cfg(feature = "native-codegen")]
-            f64x2_fn: unsafe { jit2.compile_static_2f64x2_f64("f64x2", &program.bytecode()) },
-            #[cfg(feature = "native-codegen")]
-            intrvl_fn: unsafe { jit2.compile_static_2intrvl_intrvl("intrvl", &program.bytecode()) },
+    /// f evaluates Re(f(x + iy)) or Im(f(x + iy)) instead, see [`ComplexMode`]
+    pub fn new_complex(program: Program, complex: ComplexMode) -> Self {
+        Self::from_config(&Iso2DConfig {
+            program,
+            complex,
+            ..Default::default()
+        })
+    }
 
+    /// the plotted program of config, see [`Iso2DConfig::opcode`]
+    pub fn from_config(config: &Iso2DConfig) -> Self {
+        Self::new_in(
             #[cfg(feature = "native-codegen")]
-            f64_fn: unsafe { jit2.compile_static_2f64_f64("f64", &program.bytecode()) },
+            &Rc::new(jit2::JIT::init()),
+            config,
+        )
+    }
+
+    /// compiles into an existing module, functions the module compiled before are reused
+    pub fn new_in(
+        #[cfg(feature = "native-codegen")] jit: &Rc<jit2::JIT>,
+        config: &Iso2DConfig,
+    ) -> Self {
+        // point evaluation is optimized, intervals keep the original as x * x is wider than x^2
+        let mut f = Self::compile(
             #[cfg(feature = "native-codegen")]
-            jit2,
-            // #[cfg(feature = "native-codegen")]
-            // native_f64x8_to_f64x8: jit.compile_for_f64x2x4("jit_fn4", &[], &jit_config).fn_ptr,
-            op_codes: program.opcode(),
-        };
+            jit.clone(),
+            compiler::opt::optimize(&config.bytecode().into()).bytecode,
+            config.opcode(),
+        );
+        f.complex = config.complex;
+        f
+    }
 
-        // let mut out = [0.0; 2];
-        // (res.native_intrvl_to_intrvl)(&mut out, [1., 2.], [3., 4.]);
+    /// f(z) with complex words, real plots never need it so it is compiled on first use
+    #[cfg(feature = "native-codegen")]
+    fn complex_fn(&self) -> jit2::Func<'_, Complex> {
+        let entry = self
+            .complex_fn
+            .get_or_init(|| self.jit.compile("complex", &self.program, 2).entry());
+        self.jit.get(*entry)
+    }
 
-        res
+    /// f(x, y) depending on the [`ComplexMode`], cheap to copy into worker threads
+    #[cfg(feature = "native-codegen")]
+    fn real_fn(&self) -> impl Fn(f64, f64) -> f64 + Copy + Send + Sync + '_ {
+        let f64_fn = self.jit.get(self.f64_fn);
+        let complex_fn = (self.complex != ComplexMode::Real).then(|| self.complex_fn());
+        let complex = self.complex;
+        move |x, y| match (complex, complex_fn) {
+            (ComplexMode::Re, Some(f)) => f.eval(&[Complex::new(x, y), Complex::ZERO]).re,
+            (ComplexMode::Im, Some(f)) => f.eval(&[Complex::new(x, y), Complex::ZERO]).im,
+            _ => f64_fn.eval(&[x, y]),
+        }
     }
 
     fn f64_to_f64(&self, a: f64, b: f64) -> f64 {
-        let mut out = 0.0;
+        #[cfg(feature = "native-codegen")]
+        {
+            self.real_fn()(a, b)
+        }
+        #[cfg(not(feature = "native-codegen"))]
+        {
+            match self.complex {
+                ComplexMode::Real => {
+                    let mut vm = vm::VM::with_instr_table(vm::F64InstrTable);
+                    vm.call([a, b, 0.0], &self.op_codes)[0]
+                }
+                ComplexMode::Re => self.complex_to_complex(Complex::new(a, b)).re,
+                ComplexMode::Im => self.complex_to_complex(Complex::new(a, b)).im,
+            }
+        }
+    }
 
+    pub fn complex_to_complex(&self, z: Complex) -> Complex {
         #[cfg(feature = "native-codegen")]
         {
-            out = (self.native_f64_to_f64)(a, b)
+            self.complex_fn().eval(&[z, Complex::ZERO])
         }
         #[cfg(not(feature = "native-codegen"))]
         {
-            let mut vm = vm::VM::with_instr_table(vm::F64InstrTable);
-            out = vm.call([a, b, 0.0], &self.op_codes);
+            let mut vm = vm::VM::with_instr_table(vm::ComplexInstrTable);
+            vm.call([z, Complex::ZERO, Complex::ZERO], &self.op_codes)[0]
         }
+    }
 
-        out
+    /// samples f along a row with the jit row kernel, point by point in complex mode
+    #[cfg(feature = "native-codegen")]
+    fn row_fn(&self) -> impl Fn(&RowParams, &mut [f64]) + Copy + Send + Sync + '_ {
+        let real_fn = self.real_fn();
+        let row_fn = (self.complex == ComplexMode::Real).then(|| self.jit.get_row(self.row_fn));
+
+        move |params, out| match row_fn {
+            Some(row_fn) => row_fn.eval(params, out),
+            None => {
+                for (k, out) in out.iter_mut().enumerate() {
+                    let x = params.origin[0] + k as f64 * params.step[0];
+                    let y = params.origin[1] + k as f64 * params.step[1];
+                    *out = real_fn(x, y);
+                }
+            }
+        }
     }
 
-    fn f64x2_to_f64x2(&self, a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
-        let mut out = [0.0; 2];
+    /// samples f at the pixel centers of a w x h grid over [min, max], top row first
+    pub fn sample_grid(&self, min: DVec2, max: DVec2, w: u32, h: u32) -> Vec<f64> {
+        let size = max - min;
+        let mut out = vec![0.0; (w * h) as usize];
+
         #[cfg(feature = "native-codegen")]
         {
-            (self.native_f64x2_to_f64x2)(&mut out, a, b);
+            let f64_fn = self.real_fn();
+            out.par_chunks_mut(w as usize)
+                .enumerate()
+                .for_each(|(j, row)| {
+                    let y = max.y - (j as f64 + 0.5) / h as f64 * size.y;
+                    for (i, v) in row.iter_mut().enumerate() {
+                        let x = min.x + (i as f64 + 0.5) / w as f64 * size.x;
+                        *v = f64_fn(x, y);
+                    }
+                });
         }
         #[cfg(not(feature = "native-codegen"))]
         {
             let mut vm = vm::VM::with_instr_table(vm::F64InstrTable);
-            out[0] = vm.call([a[0], b[0], 0.0], &self.op_codes);
-            out[1] = vm.call([a[1], b[1], 0.0], &self.op_codes);
+            for (j, row) in out.chunks_mut(w as usize).enumerate() {
+                let y = max.y - (j as f64 + 0.5) / h as f64 * size.y;
+                for (i, v) in row.iter_mut().enumerate() {
+                    let x = min.x + (i as f64 + 0.5) / w as f64 * size.x;
+                    *v = vm.call([x, y, 0.0], &self.op_codes)[0];
+                }
+            }
         }
+
         out
     }
-    fn f64x8_to_f64x8(&self, a: [f64; 8], b: [f64; 8]) -> [f64; 8] {
-        let mut out = [0.0; 8];
-
-        for i in 0..4 {
-            // let a_2 = a[i*2..i*2+1];
-            let a_2 = [a[i * 2], a[i * 2 + 1]];
-            let b_2 = [b[i * 2], b[i * 2 + 1]];
-            let mut out_2 = [0.0; 2];
-            (self.native_f64x2_to_f64x2)(&mut out_2, a_2, b_2);
-
-            out[i * 2..i * 2 + 2].copy_from_slice(&out_2);
-            // (self.native_f64x8_to_f64x8)(&a, &b, &mut out);
+
+    /// samples f(x + iy) at the pixel centers of a w x h grid over [min, max], top row first
+    pub fn sample_grid_complex(&self, min: DVec2, max: DVec2, w: u32, h: u32) -> Vec<Complex> {
+        let size = max - min;
+        let mut out = vec![Complex::ZERO; (w * h) as usize];
+
+        #[cfg(feature = "native-codegen")]
+        {
+            let complex_fn = self.complex_fn();
+            out.par_chunks_mut(w as usize)
+                .enumerate()
+                .for_each(|(j, row)| {
+                    let y = max.y - (j as f64 + 0.5) / h as f64 * size.y;
+                    for (i, v) in row.iter_mut().enumerate() {
+                        let x = min.x + (i as f64 + 0.5) / w as f64 * size.x;
+                        *v = complex_fn.eval(&[Complex::new(x, y), Complex::ZERO]);
+                    }
+                });
+        }
+        #[cfg(not(feature = "native-codegen"))]
+        {
+            let mut vm = vm::VM::with_instr_table(vm::ComplexInstrTable);
+            for (j, row) in out.chunks_mut(w as usize).enumerate() {
+                let y = max.y - (j as f64 + 0.5) / h as f64 * size.y;
+                for (i, v) in row.iter_mut().enumerate() {
+                    let x = min.x + (i as f64 + 0.5) / w as f64 * size.x;
+                    let z = Complex::new(x, y);
+                    *v = vm.call([z, Complex::ZERO, Complex::ZERO], &self.op_codes)[0];
+                }
+            }
         }
+
         out
     }
 
@@ -514,6 +792,34 @@ impl JitFunction<'_> {
         (range.l, range.u)
     }
 
+    /// the vm program the interval evaluation runs on
+    pub fn opcode(&self) -> &[vm::Opcode] {
+        &self.op_codes
+    }
+
+    /// f over the box [min, max] with the jit, compiled from [`JitFunction::opcode`] so it
+    /// matches the range vm. None if the program has no bytecode, like programs using the stack
+    pub fn intrvl_2d(&self, min: DVec2, max: DVec2) -> Option<vm::Range> {
+        #[cfg(feature = "native-codegen")]
+        {
+            let entry = self.intrvl_fn.get_or_init(|| {
+                let (program, _) = vm::opt::to_bytecode(&self.op_codes)?;
+                Some(self.jit.compile("intrvl", &program, 2).entry())
+            });
+            let f = self.jit.get((*entry)?);
+            let out = f.eval(&[
+                jit2::Intrvl::new(min.x, max.x),
+                jit2::Intrvl::new(min.y, max.y),
+            ]);
+            Some(vm::Range::new(out.lo, out.hi))
+        }
+        #[cfg(not(feature = "native-codegen"))]
+        {
+            let _ = (min, max);
+            None
+        }
+    }
+
     fn intrvl(&self, min: DVec2, max: DVec2) -> vm::Range {
         // let mut out = [0.0; 2];
         // let x = [min.x, max.x];
@@ -534,6 +840,63 @@ impl JitFunction<'_> {
     }
 }
 
+/// the function of the last rebuild, recompiled when the program or the complex mode change
+///
+/// all functions are compiled into one module, so switching back to an earlier program
+/// reuses its code
+pub struct FunctionCache {
+    #[cfg(feature = "native-codegen")]
+    jit: Rc<jit2::JIT>,
+    /// the code of the module is released before compiling once it holds this many functions
+    pub max_functions: usize,
+    cached: Option<(Iso2DConfig, JitFunction)>,
+}
+
+impl Default for FunctionCache {
+    fn default() -> Self {
+        Self {
+            #[cfg(feature = "native-codegen")]
+            jit: Rc::new(jit2::JIT::init()),
+            max_functions: 256,
+            cached: None,
+        }
+    }
+}
+
+impl FunctionCache {
+    pub fn get(&mut self, config: &Iso2DConfig) -> &JitFunction {
+        let changed = |c: &Iso2DConfig| {
+            c.program != config.program
+                || c.complex != config.complex
+                || c.saved_code() != config.saved_code()
+        };
+        if self.cached.as_ref().is_none_or(|(c, _)| changed(c)) {
+            // the old function shares the module, drop it before the code is released
+            self.cached = None;
+            #[cfg(feature = "native-codegen")]
+            if self.jit.n_functions() >= self.max_functions
+                && let Some(jit) = Rc::get_mut(&mut self.jit)
+            {
+                jit.free_memory();
+            }
+
+            let f = JitFunction::new_in(
+                #[cfg(feature = "native-codegen")]
+                &self.jit,
+                config,
+            );
+            self.cached = Some((config.clone(), f));
+        }
+        &self.cached.as_ref().unwrap().1
+    }
+
+    /// the module the functions are compiled into
+    #[cfg(feature = "native-codegen")]
+    pub fn jit(&self) -> &jit2::JIT {
+        &self.jit
+    }
+}
+
 // type JITParam = [f64; 2];
 // type Impl2DFuncf64x2 = extern "C" fn(*mut [f64; 2], JITParam, JITParam);
 type Impl2DFunc = (
@@ -542,7 +905,16 @@ type Impl2DFunc = (
     extern "C" fn(*const [f64; 8], *const [f64; 8], *mut [f64; 8]),
 );
 
-fn build_grid(config: &Iso2DConfig, f: &JitFunction) -> (Vec<Vertex>, BitGrid) {
+/// the leaf cells to sample and the cells where an undecided select or a step function can make
+/// f jump
+///
+/// boxes on the original tape are culled with the interval jit, the range vm only runs to
+/// record the choices of the boxes that are kept and on specialized tapes
+pub fn build_grid(
+    config: &Iso2DConfig,
+    f: &JitFunction,
+    levels: &[f64],
+) -> (Vec<Vertex>, BitGrid, BitGrid) {
     let mut verts = vec![];
     let res = 2u32.pow(config.intrvl_depth);
 
@@ -551,46 +923,119 @@ fn build_grid(config: &Iso2DConfig, f: &JitFunction) -> (Vec<Vertex>, BitGrid) {
     let size = max - min;
 
     let mut grid = BitGrid::new(res as u32, res as u32);
+    let mut switches = BitGrid::new(res, res);
+
+    // the zero level only depends on the sign of f
+    let sign_only = levels == [0.0];
+    let cell_pos = |i: u32, j: u32| DVec2::new(i as f64, j as f64) / res as f64 * size + min;
+
+    // quadtree nodes (i, j, depth, tape) covering 2^(intrvl_depth - depth) cells
+    let root = Rc::<[vm::Opcode]>::from(f.opcode());
+    let mut stack = vec![(0, 0, 0, root.clone())];
+    let mut eval = tape::Evaluator::default();
+    let may_switch = tape::may_switch(&root);
+
+    while let Some((i, j, depth, mut tape)) = stack.pop() {
+        let n = res >> depth;
+        let q_min = cell_pos(i, j);
+        let q_max = cell_pos(i + n, j + n);
+        let mut switch = false;
+
+        // there is no complex interval evaluation, keep every cell
+        if config.complex == ComplexMode::Real {
+            let culled = |intrvl: vm::Range| !contains_level(intrvl, levels) && intrvl.is_valid();
+            let jit = Rc::ptr_eq(&tape, &root)
+                .then(|| f.intrvl_2d(q_min, q_max))
+                .flatten();
+            if jit.is_some_and(culled) {
+                continue;
+            }
 
-    for j in 0..res {
-        let y0 = (j as f64 / res as f64) * size.y + min.y;
-        let y1 = ((j + 1) as f64 / res as f64) * size.y + min.y;
-        for i in 0..res {
-            let x0 = (i as f64 / res as f64) * size.x + min.x;
-            let x1 = ((i + 1) as f64 / res as f64) * size.x + min.x;
-
-            let q_min = DVec2::new(x0, y0);
-            let q_max = DVec2::new(x1, y1);
-
-            let a = q_min.as_vec2().extend(0.0).extend(1.0);
-            let c = q_max.as_vec2().extend(0.0).extend(1.0);
-            let b = a.with_x(c.x);
-            let d = a.with_y(c.y);
-            let col = glam::Vec4::new(0., 0., 0., 0.);
-
-            let intrvl = {
-                let mut out = F64X2(0.0, 0.0);
-                let f1 = F64X2(q_min.x, q_max.x);
-                let f2 = F64X2(q_min.y, q_max.y);
-                (f.intrvl_fn)(f1, f2, &mut out);
-                vm::Range::new(out.0, out.1)
-            };
+            // leaves are not specialized, so the vm is only needed if f can jump
+            let leaf = depth == config.intrvl_depth;
+            if !(leaf && jit.is_some() && !may_switch) {
+                let x = vm::Range::new(q_min.x, q_max.x);
+                let y = vm::Range::new(q_min.y, q_max.y);
+                let region = eval.eval(&tape, &[x, y]);
+                if culled(region.value) {
+                    continue;
+                }
+                switch = region.switches;
 
-            if intrvl.contains_zero() || !intrvl.is_valid() {
-                grid.set(i as u32, j as u32);
-                verts.extend([
-                    Vertex { pos: a, col },
-                    Vertex { pos: b, col },
-                    Vertex { pos: c, col },
-                    Vertex { pos: a, col },
-                    Vertex { pos: c, col },
-                    Vertex { pos: d, col },
-                ]);
+                if !leaf
+                    && region.n_decided() != 0
+                    && let Some(specialized) = tape::specialize(&tape, &region.choices, sign_only)
+                {
+                    tape = specialized.into();
+                }
             }
         }
+
+        if depth < config.intrvl_depth {
+            let h = n / 2;
+            for (di, dj) in [(0, 0), (h, 0), (0, h), (h, h)] {
+                stack.push((i + di, j + dj, depth + 1, tape.clone()));
+            }
+            continue;
+        }
+
+        let a = q_min.as_vec2().extend(0.0).extend(1.0);
+        let c = q_max.as_vec2().extend(0.0).extend(1.0);
+        let b = a.with_x(c.x);
+        let d = a.with_y(c.y);
+        let col = glam::Vec4::new(0., 0., 0., 0.);
+
+        grid.set(i, j);
+        if switch {
+            switches.set(i, j);
+        }
+        verts.extend([
+            Vertex { pos: a, col },
+            Vertex { pos: b, col },
+            Vertex { pos: c, col },
+            Vertex { pos: a, col },
+            Vertex { pos: c, col },
+            Vertex { pos: d, col },
+        ]);
     }
 
-    (verts, grid)
+    (verts, grid, switches)
+}
+
+/// bisects the sign change of f between a and b, it is a jump if a select switches or
+/// a step function jumps in the final bracket, the same interval test as the cells use, see
+/// [`tape::Region::switches`]
+///
+/// the samples and a, b are rounded differently, the bracket is widened by `tol`
+fn is_jump(
+    f: impl Fn(f64, f64) -> f64,
+    eval: &mut tape::Evaluator,
+    ops: &[vm::Opcode],
+    a: DVec2,
+    b: DVec2,
+    va: f64,
+    tol: f64,
+) -> bool {
+    const STEPS: i32 = 32;
+    let (mut a, mut b, mut fa) = (a, b, va);
+
+    for _ in 0..STEPS {
+        let m = (a + b) * 0.5;
+        let fm = f(m.x, m.y);
+        if !fm.is_finite() {
+            return true;
+        }
+        if (fm > 0.0) == (fa > 0.0) {
+            (a, fa) = (m, fm);
+        } else {
+            b = m;
+        }
+    }
+
+    let (min, max) = (a.min(b) - tol, a.max(b) + tol);
+    let x = vm::Range::new(min.x, max.x);
+    let y = vm::Range::new(min.y, max.y);
+    eval.eval(ops, &[x, y]).switches
 }
 
 // atan(0.5)
@@ -839,9 +1284,11 @@ fn subdiv_sample_grid_rot_dbg(
 
 fn subdiv_sample_grid_rot_par(
     grid: &BitGrid,
+    switches: &BitGrid,
     config: &Iso2DConfig,
     f: &JitFunction,
-) -> (Vec<Vertex>, Vec<(DVec2, DVec2)>) {
+    levels: &[f64],
+) -> (Vec<Vertex>, Vec<(DVec2, DVec2, usize)>) {
     let mut verts = Vec::new();
     // let mut segments = Vec::new();
 
@@ -860,8 +1307,21 @@ fn subdiv_sample_grid_rot_par(
     const MAX_SUB_DEPTH: usize = 7;
     assert!(MAX_SUB_DEPTH >= sub_depth as usize);
 
-    let f64_fn = f.f64_fn;
-    let f64x2_fn = f.f64x2_fn;
+    let row_fn = f.row_fn();
+    let real_fn = f.real_fn();
+    let ops = f.opcode();
+    // rounding error of the sample positions
+    let tol = 4.0 * f64::EPSILON * (config.min.abs() + config.max.abs()).max_element();
+
+    // the sample transform is linear, every row is origin + k * step
+    let row_step = sample_transpose(DVec2::new(full_res_inv, 0.0)) * size;
+    let row_params = |i: i64, j: i64| {
+        let origin = sample_transpose(I64Vec2::new(i, j).as_dvec2() * full_res_inv) * size;
+        RowParams {
+            origin: (origin + config.min).to_array(),
+            step: row_step.to_array(),
+        }
+    };
 
     let segments: Vec<_> = grid
         .iter()
@@ -870,6 +1330,11 @@ fn subdiv_sample_grid_rot_par(
             let mut segments = Vec::new();
             let cell_bound_min = DVec2::new(cx as f64, cy as f64) * cell_res_inv - 0.5;
             let cell_bound_max = cell_bound_min + cell_res_inv;
+            // the rotated samples reach into the neighbouring cells
+            let switch = (cx.saturating_sub(1)..(cx + 2).min(grid.width())).any(|i| {
+                (cy.saturating_sub(1)..(cy + 2).min(grid.height())).any(|j| switches.get(i, j))
+            });
+            let mut eval: Option<tape::Evaluator> = None;
 
             let mut prev_row = [0.0f64; { 1 << MAX_SUB_DEPTH + 1 }];
             let mut curr_row = [0.0f64; { 1 << MAX_SUB_DEPTH + 1 }];
@@ -899,14 +1364,8 @@ fn subdiv_sample_grid_rot_par(
             };
 
             // sample first row
-            for i in min_indx.x..=max_indx.x {
-                let f_idx = I64Vec2::new(i, min_indx.y).as_dvec2();
-                let s_sub_min = f_idx * full_res_inv;
-                // let s_sub_max = s_sub_min + full_res_inv;
-
-                let sample_pt = sample_transpose(f_idx * full_res_inv) * size + config.min;
-                curr_row[(i - min_indx.x) as usize] = f64_fn(sample_pt.x, sample_pt.y);
-            }
+            let n = (max_indx.x - min_indx.x + 1) as usize;
+            row_fn(&row_params(min_indx.x, min_indx.y), &mut curr_row[..n]);
 
             // skip first row
             for j in min_indx.y + 1..=max_indx.y {
@@ -914,75 +1373,70 @@ fn subdiv_sample_grid_rot_par(
 
                 // sample current row
 
-                for i in (min_indx.x..=max_indx.x).step_by(2) {
-                    let l = (i - min_indx.x) as usize;
-
-                    let x0 = i as f64 * full_res_inv;
-                    let x1 = x0 + full_res_inv;
-                    let y0 = j as f64 * full_res_inv;
-
-                    let r0 = sample_transpose((x0, y0).into()) * size + config.min;
-                    let r1 = sample_transpose((x1, y0).into()) * size + config.min;
-
-                    let mut out = F64X2(0., 0.);
-
-                    // f64x2_fn(F64X2(r0.x, r1.x), F64X2(r0.y, r1.y), &mut out);
-                    out.0 = f64_fn(r0.x, r0.y);
-                    out.1 = f64_fn(r1.x, r1.y);
-                    // if config.debug {
-                    // } else {
-                    // }
-
-                    curr_row[l] = out.0;
-                    curr_row[l + 1] = out.1;
-
-                    // curr_row[l+1] = f.1(sample_pt.x, sample_pt.y);
-                }
+                row_fn(&row_params(min_indx.x, j), &mut curr_row[..n]);
                 for i in min_indx.x + 1..=max_indx.x {
                     let l = (i - min_indx.x) as usize;
                     let p_max = DVec2::new(i as f64, j as f64) * full_res_inv;
                     let p_min = p_max - full_res_inv;
 
-                    let screen_pts = [p_min, p_min.with_x(p_max.x), p_max, p_min.with_y(p_max.y)]
-                        .map(|p| sample_transpose(p) - 0.5);
+                    let corners = [p_min, p_min.with_x(p_max.x), p_max, p_min.with_y(p_max.y)];
+                    let screen_pts = corners.map(|p| sample_transpose(p) - 0.5);
 
-                    let values = [prev_row[l - 1], prev_row[l], curr_row[l], curr_row[l - 1]]
+                    let samples = [prev_row[l - 1], prev_row[l], curr_row[l], curr_row[l - 1]]
                         .map(|v| if v.is_nan() { f64::MIN } else { v });
 
-                    let mut ms_code = 0;
-                    for (k, &v) in values.iter().enumerate() {
-                        if v > 0.0 {
-                            ms_code |= 1 << k;
+                    for (level, c) in levels.iter().enumerate() {
+                        let values = samples.map(|v| v - c);
+
+                        let mut ms_code = 0;
+                        for (k, &v) in values.iter().enumerate() {
+                            if v > 0.0 {
+                                ms_code |= 1 << k;
+                            }
                         }
-                    }
 
-                    if ms_code == 5 || ms_code == 10 {
-                        let avg = values.into_iter().sum::<f64>() * 0.25;
-                        if avg > 0.0 {
-                            ms_code = 15 - ms_code;
+                        if ms_code == 0 || ms_code == 15 {
+                            continue;
                         }
-                    }
 
-                    let mut edge_duals = [DVec2::ZERO; 4];
-                    for edge in 0..4 {
-                        let i0 = edge;
-                        let i1 = (edge + 1) & 3;
-                        let v0 = values[i0];
-                        let v1 = values[i1];
+                        if ms_code == 5 || ms_code == 10 {
+                            let avg = values.into_iter().sum::<f64>() * 0.25;
+                            if avg > 0.0 {
+                                ms_code = 15 - ms_code;
+                            }
+                        }
 
-                        // if (v0 <= 0.0 && v1 > 0.0) || (v0 > 0.0 && v1 <= 0.0)
-                        if v0.is_finite() && v1.is_finite() && v0 * v1 < 0.0 {
-                            let t = v0 / (v0 - v1);
-                            edge_duals[edge] = screen_pts[i0].lerp(screen_pts[i1], t);
+                        let mut edge_duals = [DVec2::ZERO; 4];
+                        let mut jumps = [false; 4];
+                        for edge in 0..4 {
+                            let i0 = edge;
+                            let i1 = (edge + 1) & 3;
+                            let v0 = values[i0];
+                            let v1 = values[i1];
+
+                            // if (v0 <= 0.0 && v1 > 0.0) || (v0 > 0.0 && v1 <= 0.0)
+                            if v0.is_finite() && v1.is_finite() && v0 * v1 < 0.0 {
+                                let t = v0 / (v0 - v1);
+                                edge_duals[edge] = screen_pts[i0].lerp(screen_pts[i1], t);
+
+                                // the sign change of a branch switch is not a crossing
+                                if switch {
+                                    let [a, b] = [i0, i1]
+                                        .map(|k| sample_transpose(corners[k]) * size + config.min);
+                                    let eval = eval.get_or_insert_with(Default::default);
+                                    let f = |x, y| real_fn(x, y) - *c;
+                                    jumps[edge] = is_jump(f, eval, ops, a, b, v0, tol);
+                                }
+                            }
                         }
-                    }
 
-                    for (e1, e2) in EDGE_LOOKUP[ms_code] {
-                        if e1 == e2 {
-                            continue;
-                        };
-                        let (p1, p2) = (edge_duals[e1], edge_duals[e2]);
-                        segments.push((p1, p2));
+                        for (e1, e2) in EDGE_LOOKUP[ms_code] {
+                            if e1 == e2 || jumps[e1] || jumps[e2] {
+                                continue;
+                            };
+                            let (p1, p2) = (edge_duals[e1], edge_duals[e2]);
+                            segments.push((p1, p2, level));
+                        }
                     }
                 }
             }
@@ -1286,15 +1740,19 @@ fn subdiv_sample_grid_rot_par(
 //    (verts, segments)
 //}
 
-pub(crate) fn build_2d(config: &Iso2DConfig) -> (Vec<Vertex>, Vec<LineSegmentInst>) {
+/// f is the function of config.program, see [`FunctionCache`]
+pub(crate) fn build_2d(
+    config: &Iso2DConfig,
+    f: &JitFunction,
+) -> (Vec<Vertex>, Vec<LineSegmentInst>) {
     if config.max.is_nan() || config.max.is_nan() {
         return (vec![], vec![]);
     }
 
-    let f = JitFunction::new(config.program);
+    let levels = config.contour.levels();
 
     let start_build_grid = Instant::now();
-    let (verts, grid) = build_grid(&config, &f);
+    let (verts, grid, switches) = build_grid(config, f, &levels);
 
     log::info!("build_grid: {}", start_build_grid.elapsed().as_micros());
     // let (verts, segments) = if config.simd {
@@ -1302,13 +1760,14 @@ pub(crate) fn build_2d(config: &Iso2DConfig) -> (Vec<Vertex>, Vec<LineSegmentIns
     // } else {
     // };
     // let (verts, segments) = subdiv_sample_grid_rot_par(&grid, config, &f);
-    let (_, segments) = subdiv_sample_grid_rot_par(&grid, config, &f);
+    let (_, segments) = subdiv_sample_grid_rot_par(&grid, &switches, config, f, &levels);
 
     let segments = segments
         .into_iter()
-        .map(|(a, b)| LineSegmentInst {
+        .map(|(a, b, level)| LineSegmentInst {
             a: a.as_vec2().extend(0.0),
             b: b.as_vec2().extend(0.0),
+            col: level_color(level, levels.len()),
         })
         .collect();
 
@@ -1317,10 +1776,214 @@ pub(crate) fn build_2d(config: &Iso2DConfig) -> (Vec<Vertex>, Vec<LineSegmentIns
 
 #[cfg(test)]
 mod test {
-    use jit::{CompConfig, JITCompiler};
-
     use super::*;
 
+    #[test]
+    fn contour_levels() {
+        let mut contour = ContourConfig::default();
+        assert_eq!(contour.levels(), [0.0]);
+
+        contour.enabled = true;
+        assert_eq!(contour.levels(), [-1.0, -0.5, 0.0, 0.5, 1.0]);
+
+        contour.levels = Levels::List(vec![2.0, f64::NAN, -3.0, 2.0]);
+        let levels = contour.levels();
+        assert_eq!(levels, [-3.0, 2.0]);
+
+        assert!(contains_level(vm::Range::new(-4.0, -2.0), &levels));
+        assert!(contains_level(vm::Range::new(2.0, 2.0), &levels));
+        assert!(!contains_level(vm::Range::new(-2.0, 1.0), &levels));
+        assert!(!contains_level(vm::Range::new(3.0, 4.0), &levels));
+    }
+
+    #[test]
+    fn row_fn() {
+        let params = RowParams {
+            origin: [-2.0, 1.5],
+            step: [0.13, -0.07],
+        };
+        for complex in [ComplexMode::Real, ComplexMode::Re] {
+            let f = JitFunction::new_complex(Program::Dense3, complex);
+            let mut out = vec![0.0; 37];
+            f.row_fn()(&params, &mut out);
+
+            for (k, &v) in out.iter().enumerate() {
+                let x = params.origin[0] + k as f64 * params.step[0];
+                let y = params.origin[1] + k as f64 * params.step[1];
+                let expected = f.f64_to_f64(x, y);
+                assert!(
+                    v == expected || v.is_nan() && expected.is_nan(),
+                    "{v} != {expected}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn specialized_grid() {
+        for program in [Program::Union, Program::XY, Program::X2X, Program::Dense1] {
+            let config = Iso2DConfig {
+                min: DVec2::new(-2.1, -1.9),
+                max: DVec2::new(2.9, 2.1),
+                intrvl_depth: 5,
+                program,
+                ..Default::default()
+            };
+            let f = JitFunction::new(program);
+            let (verts, grid, _) = build_grid(&config, &f, &[0.0]);
+
+            // same cells as evaluating every cell with the full tape on the jit and the vm
+            let mut eval = tape::Evaluator::default();
+            let res = 32;
+            let mut n_cells = 0;
+            for j in 0..res {
+                for i in 0..res {
+                    let p = |i: u32, j: u32| {
+                        DVec2::new(i as f64, j as f64) / res as f64 * (config.max - config.min)
+                            + config.min
+                    };
+                    let (q_min, q_max) = (p(i, j), p(i + 1, j + 1));
+                    let x = vm::Range::new(q_min.x, q_max.x);
+                    let y = vm::Range::new(q_min.y, q_max.y);
+                    let keeps = |r: vm::Range| contains_level(r, &[0.0]) || !r.is_valid();
+                    let r = eval.eval(f.opcode(), &[x, y]).value;
+                    let keep = keeps(r) && f.intrvl_2d(q_min, q_max).is_none_or(keeps);
+                    assert_eq!(grid.get(i, j), keep, "{program:?} {i} {j}");
+                    n_cells += keep as usize;
+                }
+            }
+            assert_eq!(verts.len(), 6 * n_cells);
+        }
+    }
+
+    #[test]
+    fn piecewise_jump() {
+        let config = Iso2DConfig {
+            min: DVec2::new(-2.0, -2.0),
+            max: DVec2::new(2.0, 2.0),
+            intrvl_depth: 4,
+            subdiv_depth: 3,
+            program: Program::Piecewise,
+            ..Default::default()
+        };
+
+        // segments are in [-0.5, 0.5]
+        let to_world = |p: Vec3| (p.truncate().as_dvec2() + 0.5) * 4.0 + config.min;
+        let (_, segments) = build_2d(
+            &config,
+            &JitFunction::new_complex(config.program, config.complex),
+        );
+        assert!(!segments.is_empty());
+
+        // the branch switch at x = 0 jumps from y = 1 to y = 0, it is not part of the curve
+        for s in &segments {
+            let (a, b) = (to_world(s.a), to_world(s.b));
+            for p in [a, b] {
+                let branch = if p.x < 0.0 { p.x + 1.0 } else { p.x.sin() };
+                assert!((p.y - branch).abs() < 0.1, "{a} {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn floor_steps() {
+        // the cell borders do not fall onto the integers
+        let config = Iso2DConfig {
+            min: DVec2::new(-2.1, -2.05),
+            max: DVec2::new(1.9, 1.95),
+            intrvl_depth: 4,
+            subdiv_depth: 3,
+            program: Program::Floor,
+            ..Default::default()
+        };
+
+        let to_world = |p: Vec3| (p.truncate().as_dvec2() + 0.5) * 4.0 + config.min;
+        let (_, segments) = build_2d(
+            &config,
+            &JitFunction::new_complex(config.program, config.complex),
+        );
+        assert!(!segments.is_empty());
+
+        // only the steps y = floor(x), no vertical connectors at the jumps
+        for s in &segments {
+            let (a, b) = (to_world(s.a), to_world(s.b));
+            assert!((a.y - b.y).abs() < 1e-3, "{a} {b}");
+            assert!((a.y - a.y.round()).abs() < 1e-3, "{a} {b}");
+        }
+    }
+
+    #[test]
+    fn complex_parts() {
+        // f(z) = z^2 + z, Im(f) = y (2x + 1) vanishes on y = 0 and x = -1/2
+        // the bounds keep the zeros off the sample points
+        let mut config = Iso2DConfig {
+            min: DVec2::new(-2.03, -2.31),
+            max: DVec2::new(1.97, 1.69),
+            intrvl_depth: 3,
+            subdiv_depth: 3,
+            program: Program::X2X,
+            complex: ComplexMode::Im,
+            ..Default::default()
+        };
+
+        // segments are in [-0.5, 0.5]
+        let to_world = |p: Vec3| (p.truncate().as_dvec2() + 0.5) * 4.0 + config.min;
+        let (_, segments) = build_2d(
+            &config,
+            &JitFunction::new_complex(config.program, config.complex),
+        );
+        assert!(!segments.is_empty());
+        for s in &segments {
+            let (a, b) = (to_world(s.a), to_world(s.b));
+            // marching squares cuts the corner of the cell where the lines cross
+            let on_line = |p: DVec2| {
+                p.y.abs() < 1e-2
+                    || (p.x + 0.5).abs() < 1e-2
+                    || p.distance(DVec2::new(-0.5, 0.0)) < 0.1
+            };
+            assert!(on_line(a) && on_line(b), "{a} {b}");
+        }
+
+        // Re(f) = x^2 - y^2 + x
+        config.complex = ComplexMode::Re;
+        let (_, segments) = build_2d(
+            &config,
+            &JitFunction::new_complex(config.program, config.complex),
+        );
+        assert!(!segments.is_empty());
+        for s in &segments {
+            let p = to_world(s.a);
+            assert!((p.x * p.x - p.y * p.y + p.x).abs() < 0.1, "{p}");
+        }
+    }
+
+    #[cfg(feature = "native-codegen")]
+    #[test]
+    fn function_cache() {
+        let mut cache = FunctionCache::default();
+        let mut config = Iso2DConfig {
+            program: Program::X,
+            ..Default::default()
+        };
+
+        assert_eq!(cache.get(&config).f64_to_f64(3.0, 1.0), 2.0);
+        let n = cache.jit().n_functions();
+        config.program = Program::XY;
+        assert_eq!(cache.get(&config).f64_to_f64(3.0, 2.0), 6.0);
+        let m = cache.jit().n_functions();
+        assert!(m > n);
+
+        // switching back finds the code of the first program
+        config.program = Program::X;
+        assert_eq!(cache.get(&config).f64_to_f64(3.0, 1.0), 2.0);
+        assert_eq!(cache.jit().n_functions(), m);
+
+        cache.max_functions = m;
+        config.program = Program::XY;
+        assert_eq!(cache.get(&config).f64_to_f64(3.0, 2.0), 6.0);
+        assert!(cache.jit().n_functions() < m);
+    }
+
     #[test]
     fn eval_f64x4x2() {
         for prog in [
@@ -1329,29 +1992,27 @@ mod test {
             Program::OneDivX,
             Program::Dense1,
         ] {
-            let program = prog.bytecode();
-            let config = CompConfig::default();
+            let program = jit::Program::from(prog.bytecode());
 
-            let mut jit = JITCompiler::init();
-            let f_f64 = jit.compile_for_f64("f_f64", &program, &config).fn_ptr;
-            let f_f64x2 = jit.compile_for_f64x2("f_f64x2", &program, &config).fn_ptr;
+            let jit = jit2::JIT::init();
+            let f_f64 = jit.compile::<f64>("f_f64", &program, 2);
+            let f_f64x2 = jit.compile::<F64X2>("f_f64x2", &program, 2);
 
             let a = [0.0; 1028].map(|_| rand::random());
             let b = [0.0; 1028].map(|_| rand::random());
             let mut out = [0.0; 1028];
 
             for i in (0..a.len()).step_by(2) {
-                let x = [a[i], a[i + 1]];
-                let y = [b[i], b[i + 1]];
-                let mut o = [0.0; 2];
-                f_f64x2(&mut o, x, y);
-                out[i] = o[0];
-                out[i + 1] = o[1];
+                let x = F64X2(a[i], a[i + 1]);
+                let y = F64X2(b[i], b[i + 1]);
+                let o = f_f64x2.eval(&[x, y]);
+                out[i] = o.0;
+                out[i + 1] = o.1;
             }
 
             for ((x, y), o) in a.into_iter().zip(b).zip(out) {
                 let o1 = o;
-                let o2 = f_f64(x, y);
+                let o2 = f_f64.eval(&[x, y]);
                 assert!(
                     (o1 - o2).abs() < f32::EPSILON as f64,
                     "{o1} vs {o2}, in {:?}",
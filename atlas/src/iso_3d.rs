@@ -1,10 +1,12 @@
+use std::rc::Rc;
+
 use egui_probe::EguiProbe;
 use glam::{DMat3, DVec2, DVec3, Vec3};
 
 use crate::{
     graph_3d_shader::Vertex,
     iso::{self, JitFunction},
-    vm,
+    tape, vm,
 };
 
 fn implicit_fn(x: f64, y: f64) -> f64 {
@@ -19,10 +21,14 @@ pub struct Bounds {
 pub fn subdivide_octree(f: &JitFunction, cfg: &Iso3DConfig) -> Vec<Bounds> {
     let mut stack = Vec::new();
     let mut leaves = Vec::new();
-    stack.push((cfg.min, cfg.max, 0));
-
-    while let Some((bmin, bmax, depth)) = stack.pop() {
-        let (fmin, fmax) = f.intrvl_3d(bmin, bmax);
+    // every child continues on the tape specialized to its parent
+    stack.push((cfg.min, cfg.max, 0, Rc::<[vm::Opcode]>::from(f.opcode())));
+    let mut eval = tape::Evaluator::default();
+
+    while let Some((bmin, bmax, depth, mut tape)) = stack.pop() {
+        let inputs: [vm::Range; 3] = std::array::from_fn(|i| (bmin[i], bmax[i]).into());
+        let region = eval.eval(&tape, &inputs);
+        let (fmin, fmax) = (region.value.l, region.value.u);
         if fmin > 0.0 || fmax < 0.0 {
             continue;
         }
@@ -35,6 +41,12 @@ pub fn subdivide_octree(f: &JitFunction, cfg: &Iso3DConfig) -> Vec<Bounds> {
                 max: bmax,
             });
         } else {
+            if region.n_decided() != 0
+                && let Some(specialized) = tape::specialize(&tape, &region.choices, true)
+            {
+                tape = specialized.into();
+            }
+
             let mid = (bmin + bmax) * 0.5;
             for &ix in &[bmin.x, mid.x] {
                 for &iy in &[bmin.y, mid.y] {
@@ -45,7 +57,7 @@ pub fn subdivide_octree(f: &JitFunction, cfg: &Iso3DConfig) -> Vec<Bounds> {
                             if iy == bmin.y { mid.y } else { bmax.y },
                             if iz == bmin.z { mid.z } else { bmax.z },
                         );
-                        stack.push((child_min, child_max, depth + 1));
+                        stack.push((child_min, child_max, depth + 1, tape.clone()));
                     }
                 }
             }
@@ -63,33 +75,37 @@ pub enum Program3D {
 }
 
 impl Program3D {
-    pub fn opcode(&self) -> Vec<vm::Opcode> {
+    /// constant, so tracing can evaluate it every frame without allocating
+    pub fn opcode(&self) -> &'static [vm::Opcode] {
         use vm::op;
         match self {
-            Self::Sphere => [
-                op::MUL_REG_REG(1, 1, 1),
-                op::MUL_REG_REG(2, 2, 2),
-                op::MUL_REG_REG(3, 3, 3),
-                op::ADD_REG_REG(1, 2, 1),
-                op::ADD_REG_REG(1, 3, 1),
-                op::SUB_REG_IMM(1, 1.0, 1),
-                op::EXT(0),
-            ]
-            .to_vec(),
-            Program3D::Plane => [
-                op::SUB_REG_REG(1, 2, 1),
-                op::SUB_REG_REG(1, 3, 1),
-                op::EXT(0),
-            ]
-            .to_vec(),
-            Program3D::Waves => [
-                op::SIN(1, 1),
-                op::SIN(2, 2),
-                op::MUL_REG_REG(1, 2, 1),
-                op::SUB_REG_REG(1, 3, 1),
-                op::EXT(0),
-            ]
-            .to_vec(),
+            Self::Sphere => &const {
+                [
+                    op::MUL_REG_REG(1, 1, 1),
+                    op::MUL_REG_REG(2, 2, 2),
+                    op::MUL_REG_REG(3, 3, 3),
+                    op::ADD_REG_REG(1, 2, 1),
+                    op::ADD_REG_REG(1, 3, 1),
+                    op::SUB_REG_IMM(1, 1.0, 1),
+                    op::EXT(0),
+                ]
+            },
+            Program3D::Plane => &const {
+                [
+                    op::SUB_REG_REG(1, 2, 1),
+                    op::SUB_REG_REG(1, 3, 1),
+                    op::EXT(0),
+                ]
+            },
+            Program3D::Waves => &const {
+                [
+                    op::SIN(1, 1),
+                    op::SIN(2, 2),
+                    op::MUL_REG_REG(1, 2, 1),
+                    op::SUB_REG_REG(1, 3, 1),
+                    op::EXT(0),
+                ]
+            },
         }
     }
 
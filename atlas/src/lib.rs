@@ -1,12 +1,20 @@
 mod camera;
+pub mod domain;
+pub mod field;
 pub mod graph_3d_shader;
+pub mod heatmap;
 pub mod iso;
 pub mod iso_3d;
+mod scene;
 // pub mod pdb;
+pub mod trace;
 mod ui;
 
+pub mod tape;
 pub mod vm;
 pub mod vm2;
+pub mod vm_trace;
+pub mod zero_set;
 
 pub extern crate self as atlas;
 
@@ -63,6 +71,7 @@ pub struct Vertex {
 pub struct LineSegmentInst {
     pub a: Vec3,
     pub b: Vec3,
+    pub col: Vec3,
 }
 
 impl Vertex {
@@ -204,6 +213,14 @@ struct AtlasSettings {
     iso_2d_config: iso::Iso2DConfig,
     iso_3d_config: iso_3d::Iso3DConfig,
     // iso_3d_config: iso::Iso3DConfig,
+    heatmap: heatmap::HeatmapConfig,
+    domain: domain::DomainConfig,
+    field: field::FieldConfig,
+    trace: trace::TraceConfig,
+    vm_trace: vm_trace::VmTraceConfig,
+    /// draw the zero set with the generated wgsl shader instead of the mesh
+    #[egui_probe(toggle_switch)]
+    gpu_zero_set: bool,
     #[egui_probe(skip)]
     show_tree: bool,
     #[egui_probe(skip)]
@@ -218,6 +235,9 @@ struct AtlasSettings {
     mesh_gen: MeshGenerator,
     #[egui_probe(skip)]
     render_config: RenderConfig,
+    #[cfg(not(target_arch = "wasm32"))]
+    #[egui_probe(skip)]
+    paths: ui::FilePaths,
 }
 
 impl Default for AtlasSettings {
@@ -232,6 +252,12 @@ impl Default for AtlasSettings {
                 ..Default::default()
             },
             iso_3d_config: Default::default(),
+            heatmap: Default::default(),
+            domain: Default::default(),
+            field: Default::default(),
+            trace: Default::default(),
+            vm_trace: Default::default(),
+            gpu_zero_set: false,
             camera_mode: camera::CameraKind::Orbit,
             lock_zoom: true,
 
@@ -244,6 +270,8 @@ impl Default for AtlasSettings {
                 fov: 90.0,
                 depthbuffer: false,
             },
+            #[cfg(not(target_arch = "wasm32"))]
+            paths: Default::default(),
         }
     }
 }
@@ -498,6 +526,8 @@ impl AtlasApp {
         };
 
         let pipeline_3d = graph_3d_shader::Pipeline::init(&wgpu);
+        let heatmap = heatmap::Pipeline::init(wgpu);
+        let zero_set = zero_set::Pipeline::init(wgpu);
 
         let data = WindowData {
             mouse_pixel_pos: Vec2::ZERO,
@@ -547,7 +577,10 @@ impl AtlasApp {
             settings: AtlasSettings::default(),
             egui_state: ui_state,
             mesh_2d,
+            function: Default::default(),
             pipeline_3d,
+            heatmap,
+            zero_set,
             last_size: UVec2::ZERO,
             last_render_time: None,
         }
@@ -670,6 +703,9 @@ impl AtlasApp {
                 if let Ok(Some(renderer)) = receiver.try_recv() {
                     *self = Self::Init(Self::init_app(window.as_ref().unwrap().clone(), renderer));
                     if let Self::Init(app) = self {
+                        if let Some(scene) = scene::from_url() {
+                            scene.apply(&mut app.settings, &mut app.camera_controll);
+                        }
                         return Some(app);
                     }
                 }
@@ -737,8 +773,12 @@ struct AppData {
     settings: AtlasSettings,
 
     mesh_2d: ModelInstance,
+    /// the function of the 2d program, shared by the mesh, heatmap and domain coloring
+    function: iso::FunctionCache,
 
     pipeline_3d: graph_3d_shader::Pipeline,
+    heatmap: heatmap::Pipeline,
+    zero_set: zero_set::Pipeline,
 
     last_size: UVec2,
     last_render_time: Option<Instant>,
@@ -899,7 +939,7 @@ fn load_line_shader(wgpu: &WGPU) -> wgpu::RenderPipeline {
                     wgpu::VertexBufferLayout {
                         array_stride: std::mem::size_of::<LineSegmentInst>() as u64,
                         step_mode: wgpu::VertexStepMode::Instance,
-                        attributes: &wgpu::vertex_attr_array![2 => Float32x3, 3 => Float32x3],
+                        attributes: &wgpu::vertex_attr_array![2 => Float32x3, 3 => Float32x3, 4 => Float32x3],
                     },
                 ],
             },
@@ -939,7 +979,8 @@ fn load_line_shader(wgpu: &WGPU) -> wgpu::RenderPipeline {
 
 impl AppData {
     fn rebuild_mesh_2d(&mut self) -> (Vec<Vertex>, Vec<LineSegmentInst>) {
-        let (verts, mut lines) = build_mesh_2d(&self.settings);
+        let f = self.function.get(&self.settings.iso_2d_config);
+        let (verts, mut lines) = build_mesh_2d(&self.settings, f);
         for l in &mut lines {
             l.a = l.a * 2.0;
             l.b = l.b * 2.0;
@@ -998,7 +1039,9 @@ impl AppData {
             vp_texture: self.renderer.fb_egui_id,
             // camera: &self.camera,
             window_info: &mut self.data,
+            camera: &mut self.camera_controll,
             settings: &mut self.settings,
+            function: &mut self.function,
         };
 
         self.ui_state.ui(&self.egui_state.egui_ctx(), access);
@@ -1028,6 +1071,36 @@ impl AppData {
                 bytemuck::cast_slice(&lines),
                 lines.len() as u64,
             );
+
+            self.heatmap.visible = self.settings.heatmap.enabled || self.settings.domain.enabled;
+            // the function compiled for the mesh
+            let f = self.function.get(&self.settings.iso_2d_config);
+            // domain coloring takes precedence, both share the same texture
+            if self.settings.domain.enabled {
+                let field = domain::ComplexField::from_config(
+                    &self.settings.iso_2d_config,
+                    f,
+                    self.settings.domain.resolution,
+                );
+                self.heatmap.upload(
+                    &self.renderer.wgpu,
+                    field.width,
+                    field.height,
+                    &field.to_rgba(&self.settings.domain),
+                );
+            } else if self.settings.heatmap.enabled {
+                let field = heatmap::ScalarField::from_config(
+                    &self.settings.iso_2d_config,
+                    f,
+                    self.settings.heatmap.resolution,
+                );
+                self.heatmap.upload(
+                    &self.renderer.wgpu,
+                    field.width,
+                    field.height,
+                    &field.to_rgba(&self.settings.heatmap),
+                );
+            }
         }
 
         let vp_size = self.data.viewport_dim();
@@ -1051,7 +1124,15 @@ impl AppData {
         self.renderer.world_uniform.light_pos = Vec3::new(1000., 1000., 0.).normalize();
         self.renderer.update_world_uniform();
 
-        self.renderer.render_model_inst(&self.mesh_2d);
+        self.zero_set.visible = self.settings.gpu_zero_set;
+        self.zero_set.update(
+            &self.renderer.wgpu,
+            &self.settings.iso_2d_config,
+            Vec4::ONE,
+        );
+
+        self.renderer
+            .render_model_inst(&self.mesh_2d, &self.heatmap, &self.zero_set);
 
         self.pipeline_3d
             .update(&self.renderer.wgpu, &self.settings.iso_3d_config);
@@ -1489,10 +1570,22 @@ struct AtlasRenderer {
     // drop last
 }
 
-fn build_mesh_2d(settings: &AtlasSettings) -> (Vec<Vertex>, Vec<LineSegmentInst>) {
+fn build_mesh_2d(
+    settings: &AtlasSettings,
+    f: &iso::JitFunction,
+) -> (Vec<Vertex>, Vec<LineSegmentInst>) {
     let start = Instant::now();
 
-    let (vertices, segments) = iso::build_2d(&settings.iso_2d_config);
+    let (vertices, mut segments) = iso::build_2d(&settings.iso_2d_config, f);
+
+    if settings.field.enabled {
+        let config = &settings.iso_2d_config;
+        segments.extend(field::build_segments(
+            &settings.field,
+            config.min,
+            config.max,
+        ));
+    }
 
     log::info!(
         "extracted isosurface in: {} s / {} ms",
@@ -1684,7 +1777,12 @@ impl AtlasRenderer {
         );
     }
 
-    fn render_model_inst(&self, model: &ModelInstance) {
+    fn render_model_inst(
+        &self,
+        model: &ModelInstance,
+        heatmap: &heatmap::Pipeline,
+        zero_set: &zero_set::Pipeline,
+    ) {
         if self.wgpu.surface_config.width == 1 || self.wgpu.surface_config.height == 1 {
             return;
         }
@@ -1726,7 +1824,10 @@ impl AtlasRenderer {
                 occlusion_query_set: None,
             });
 
-            if model.n_instances != 0 {
+            heatmap.draw(&mut render_pass, &self.world_uniform_binding.bind_group);
+            zero_set.draw(&mut render_pass);
+
+            if model.n_instances != 0 && !zero_set.visible {
                 render_pass.set_vertex_buffer(
                     0,
                     model
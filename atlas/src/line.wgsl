@@ -14,6 +14,7 @@ struct VertexInput {
 struct Instance {
   @location(2) a: vec3<f32>,
   @location(3) b: vec3<f32>,
+  @location(4) col: vec3<f32>,
 };
 
 struct WorldUniform {
@@ -83,7 +84,7 @@ fn vs_main(v: VertexInput, inst: Instance, @builtin(vertex_index) indx: u32) ->
 
   var out: FsIn;
   out.pos = world.proj * world.view * vec4(pos, 1.0);
-  out.col = v.col;
+  out.col = v.col * vec4(inst.col, 1.0);
   return out;
 }
 
@@ -0,0 +1,930 @@
+//! versioned binary format of plot scenes, see [`Scene`]
+//!
+//! a scene starts with [`MAGIC`] and the format version as little endian u16, followed by
+//! sections of `tag: u8, len: u32, payload`. unknown sections are skipped and missing ones
+//! keep their defaults. when a payload changes [`VERSION`] is bumped and a migration from the
+//! previous version is appended to [`MIGRATIONS`]
+//!
+//! on wasm a scene is shared as the url fragment `#scene=<base64url>`
+
+use std::fmt;
+
+use glam::{DVec2, DVec3, Vec3};
+use utils::asm::Labels;
+
+use crate::{
+    AtlasSettings, MeshGenerator, PolygonMode, RenderConfig,
+    camera::{CameraController, CameraKind},
+    domain::DomainConfig,
+    heatmap::{Colormap, HeatmapConfig},
+    iso::{ComplexMode, ContourConfig, Iso2DConfig, Levels, Program},
+    iso_3d::{Iso3DConfig, Program3D},
+    vm,
+};
+
+pub const MAGIC: [u8; 4] = *b"ATLS";
+pub const VERSION: u16 = 1;
+
+/// rewrites the sections of version `i + 1` into version `i + 2`
+pub type Migration = fn(&mut Vec<Section>) -> Result<(), SceneError>;
+pub const MIGRATIONS: [Migration; VERSION as usize - 1] = [];
+
+/// the largest grid depth a scene may ask for, the grids of deeper plots don't fit in memory
+pub const MAX_DEPTH: u32 = 12;
+/// the largest width of a sampled field in pixels
+pub const MAX_RESOLUTION: u32 = 4096;
+
+const SETTINGS: u8 = 1;
+const CAMERA: u8 = 2;
+const PROGRAM: u8 = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SceneError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    UnexpectedEof,
+    InvalidUtf8,
+    UnknownVariant {
+        ty: &'static str,
+        name: String,
+    },
+    InvalidProgram(String),
+    OutOfRange {
+        field: &'static str,
+        value: u32,
+    },
+    #[cfg(any(target_arch = "wasm32", test))]
+    InvalidFragment,
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadMagic => write!(f, "not a scene file"),
+            Self::UnsupportedVersion(v) => {
+                write!(
+                    f,
+                    "unsupported scene version {v}, expected at most {VERSION}"
+                )
+            }
+            Self::UnexpectedEof => write!(f, "unexpected end of scene"),
+            Self::InvalidUtf8 => write!(f, "invalid utf-8 string"),
+            Self::UnknownVariant { ty, name } => write!(f, "unknown {ty} `{name}`"),
+            Self::InvalidProgram(e) => write!(f, "invalid program: {e}"),
+            Self::OutOfRange { field, value } => write!(f, "{field} {value} is out of range"),
+            #[cfg(any(target_arch = "wasm32", test))]
+            Self::InvalidFragment => write!(f, "invalid url fragment"),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+pub trait Encode {
+    fn encode(&self, w: &mut Vec<u8>);
+}
+
+pub trait Decode: Sized {
+    fn decode(r: &mut Reader<'_>) -> Result<Self, SceneError>;
+}
+
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn take(&mut self, n: usize) -> Result<&'a [u8], SceneError> {
+        if self.bytes.len() < n {
+            return Err(SceneError::UnexpectedEof);
+        }
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    pub fn read<T: Decode>(&mut self) -> Result<T, SceneError> {
+        T::decode(self)
+    }
+}
+
+macro_rules! le_bytes {
+    ($($ty:ty),*) => {
+        $(
+            impl Encode for $ty {
+                fn encode(&self, w: &mut Vec<u8>) {
+                    w.extend_from_slice(&self.to_le_bytes());
+                }
+            }
+
+            impl Decode for $ty {
+                fn decode(r: &mut Reader<'_>) -> Result<Self, SceneError> {
+                    let bytes = r.take(size_of::<$ty>())?;
+                    Ok(<$ty>::from_le_bytes(bytes.try_into().unwrap()))
+                }
+            }
+        )*
+    };
+}
+
+le_bytes!(u8, u16, u32, u64, f32, f64);
+
+impl Encode for bool {
+    fn encode(&self, w: &mut Vec<u8>) {
+        (*self as u8).encode(w)
+    }
+}
+
+impl Decode for bool {
+    fn decode(r: &mut Reader<'_>) -> Result<Self, SceneError> {
+        Ok(r.read::<u8>()? != 0)
+    }
+}
+
+impl Encode for str {
+    fn encode(&self, w: &mut Vec<u8>) {
+        (self.len() as u32).encode(w);
+        w.extend_from_slice(self.as_bytes());
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, w: &mut Vec<u8>) {
+        self.as_str().encode(w)
+    }
+}
+
+impl Decode for String {
+    fn decode(r: &mut Reader<'_>) -> Result<Self, SceneError> {
+        let len = r.read::<u32>()? as usize;
+        let bytes = r.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| SceneError::InvalidUtf8)
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, w: &mut Vec<u8>) {
+        (self.len() as u32).encode(w);
+        for item in self {
+            item.encode(w);
+        }
+    }
+}
+
+/// the length isn't trusted for preallocation, a corrupt length fails with UnexpectedEof
+impl<T: Decode> Decode for Vec<T> {
+    fn decode(r: &mut Reader<'_>) -> Result<Self, SceneError> {
+        let len = r.read::<u32>()?;
+        (0..len).map(|_| r.read()).collect()
+    }
+}
+
+/// encodes structs field by field, fields not listed are set by `..rest`. decoded values are
+/// rejected if the optional check fails
+macro_rules! fields {
+    ($ty:ty { $($field:ident),* $(,)? } $(.. $rest:expr)? $(, $check:path)?) => {
+        impl Encode for $ty {
+            fn encode(&self, w: &mut Vec<u8>) {
+                $(self.$field.encode(w);)*
+            }
+        }
+
+        impl Decode for $ty {
+            fn decode(r: &mut Reader<'_>) -> Result<Self, SceneError> {
+                let val = Self {
+                    $($field: r.read()?,)*
+                    $(.. $rest)?
+                };
+                $($check(&val)?;)?
+                Ok(val)
+            }
+        }
+    };
+}
+
+/// encodes fieldless enums by variant name, so variants can be reordered
+macro_rules! variant_names {
+    ($ty:ident { $($variant:ident),* $(,)? }) => {
+        impl Encode for $ty {
+            fn encode(&self, w: &mut Vec<u8>) {
+                let name = match self {
+                    $($ty::$variant => stringify!($variant),)*
+                };
+                name.encode(w)
+            }
+        }
+
+        impl Decode for $ty {
+            fn decode(r: &mut Reader<'_>) -> Result<Self, SceneError> {
+                let name: String = r.read()?;
+                match name.as_str() {
+                    $(stringify!($variant) => Ok($ty::$variant),)*
+                    _ => Err(SceneError::UnknownVariant {
+                        ty: stringify!($ty),
+                        name,
+                    }),
+                }
+            }
+        }
+    };
+}
+
+fn check_range(
+    field: &'static str,
+    value: u32,
+    range: std::ops::RangeInclusive<u32>,
+) -> Result<(), SceneError> {
+    match range.contains(&value) {
+        true => Ok(()),
+        false => Err(SceneError::OutOfRange { field, value }),
+    }
+}
+
+/// scenes are shared as links, so the sizes of the grids are bounded before anything is built
+fn check_iso_2d(c: &Iso2DConfig) -> Result<(), SceneError> {
+    check_range("intrvl_depth", c.intrvl_depth, 0..=MAX_DEPTH)?;
+    check_range("subdiv_depth", c.subdiv_depth, 0..=MAX_DEPTH)
+}
+
+fn check_iso_3d(c: &Iso3DConfig) -> Result<(), SceneError> {
+    check_range("max_depth", c.max_depth, 0..=MAX_DEPTH)
+}
+
+fn check_heatmap(c: &HeatmapConfig) -> Result<(), SceneError> {
+    check_range("resolution", c.resolution, 1..=MAX_RESOLUTION)
+}
+
+fn check_domain(c: &DomainConfig) -> Result<(), SceneError> {
+    check_range("resolution", c.resolution, 1..=MAX_RESOLUTION)
+}
+
+fields!(DVec2 { x, y });
+fields!(DVec3 { x, y, z });
+fields!(Vec3 { x, y, z });
+
+variant_names!(Program {
+    X,
+    XY,
+    X2X,
+    Union,
+    Sin,
+    Cos,
+    Tan,
+    OneDivX,
+    Sin1DivX,
+    Cos1DivX,
+    Dense1,
+    Dense2,
+    Piecewise,
+    Floor,
+    Gamma,
+    Dense3,
+});
+variant_names!(Program3D {
+    Sphere,
+    Plane,
+    Waves
+});
+variant_names!(ComplexMode { Real, Re, Im });
+variant_names!(Colormap { Viridis, Diverging });
+variant_names!(CameraKind { Orbit, Pan });
+variant_names!(MeshGenerator { Iso2D });
+
+/// line mode doesn't exist on wasm, scenes saved with it are filled there
+impl Encode for PolygonMode {
+    fn encode(&self, w: &mut Vec<u8>) {
+        self.to_string().encode(w)
+    }
+}
+
+impl Decode for PolygonMode {
+    fn decode(r: &mut Reader<'_>) -> Result<Self, SceneError> {
+        let name: String = r.read()?;
+        match name.as_str() {
+            "Fill" => Ok(PolygonMode::Fill),
+            #[cfg(not(target_arch = "wasm32"))]
+            "Line" => Ok(PolygonMode::Line),
+            #[cfg(target_arch = "wasm32")]
+            "Line" => Ok(PolygonMode::Fill),
+            _ => Err(SceneError::UnknownVariant {
+                ty: "PolygonMode",
+                name,
+            }),
+        }
+    }
+}
+
+impl Encode for Levels {
+    fn encode(&self, w: &mut Vec<u8>) {
+        match self {
+            Levels::Range { from, to, count } => {
+                0u8.encode(w);
+                from.encode(w);
+                to.encode(w);
+                count.encode(w);
+            }
+            Levels::List(levels) => {
+                1u8.encode(w);
+                levels.encode(w);
+            }
+        }
+    }
+}
+
+impl Decode for Levels {
+    fn decode(r: &mut Reader<'_>) -> Result<Self, SceneError> {
+        match r.read::<u8>()? {
+            0 => Ok(Levels::Range {
+                from: r.read()?,
+                to: r.read()?,
+                count: r.read()?,
+            }),
+            1 => Ok(Levels::List(r.read()?)),
+            tag => Err(SceneError::UnknownVariant {
+                ty: "Levels",
+                name: tag.to_string(),
+            }),
+        }
+    }
+}
+
+fields!(ContourConfig { enabled, levels });
+// the saved bytecode is the program section, see [`Scene::apply`]
+fields!(
+    Iso2DConfig {
+        min,
+        max,
+        intrvl_depth,
+        subdiv_depth,
+        line_thickness,
+        program,
+        complex,
+        contour,
+        debug,
+        simd,
+    }..Default::default(),
+    check_iso_2d
+);
+fields!(
+    Iso3DConfig {
+        min,
+        max,
+        max_depth,
+        min_size,
+        flat_tol,
+        program,
+        rotate_every,
+    },
+    check_iso_3d
+);
+fields!(
+    HeatmapConfig {
+        enabled,
+        resolution,
+        colormap,
+        log_scale,
+    },
+    check_heatmap
+);
+fields!(
+    DomainConfig {
+        enabled,
+        resolution,
+        modulus_bands,
+    },
+    check_domain
+);
+fields!(RenderConfig {
+    polygon_mode,
+    fov,
+    depthbuffer,
+});
+// traces, vm traces, field seeds and file paths belong to the session and aren't saved
+fields!(
+    AtlasSettings {
+        iso_2d_config,
+        iso_3d_config,
+        heatmap,
+        domain,
+        camera_mode,
+        lock_zoom,
+        render_config,
+        mesh_gen,
+    }..Default::default()
+);
+
+/// position of the [`CameraController`], its kind is [`AtlasSettings::camera_mode`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraView {
+    pub center: DVec3,
+    pub zoom: f64,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+fields!(CameraView {
+    center,
+    zoom,
+    yaw,
+    pitch
+});
+
+impl CameraView {
+    pub fn of(camera: &CameraController) -> Self {
+        Self {
+            center: camera.center,
+            zoom: camera.zoom,
+            yaw: camera.yaw,
+            pitch: camera.pitch,
+        }
+    }
+
+    pub fn apply(&self, camera: &mut CameraController) {
+        camera.center = self.center;
+        camera.zoom = self.zoom;
+        camera.yaw = self.yaw;
+        camera.pitch = self.pitch;
+    }
+}
+
+/// the plotted vm program as text assembly and bytecode. the bytecode is kept so a scene
+/// still shows what was plotted after the built in program changes
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgramCode {
+    pub source: String,
+    pub bytecode: Vec<vm::Opcode>,
+}
+
+impl ProgramCode {
+    pub fn new(bytecode: Vec<vm::Opcode>) -> Self {
+        let labels = Labels::new([("x", 1), ("y", 2)]);
+        Self {
+            source: vm::asm::to_asm(&bytecode, &labels),
+            bytecode,
+        }
+    }
+}
+
+impl Encode for ProgramCode {
+    fn encode(&self, w: &mut Vec<u8>) {
+        self.source.encode(w);
+        self.bytecode.encode(w);
+    }
+}
+
+/// the source has to assemble to the bytecode and the bytecode has to pass [`vm::verify`]
+impl Decode for ProgramCode {
+    fn decode(r: &mut Reader<'_>) -> Result<Self, SceneError> {
+        let source: String = r.read()?;
+        let bytecode: Vec<vm::Opcode> = r.read()?;
+
+        let assembled =
+            vm::asm::from_asm(&source).map_err(|e| SceneError::InvalidProgram(e.to_string()))?;
+        if assembled != bytecode {
+            return Err(SceneError::InvalidProgram(
+                "source and bytecode differ".into(),
+            ));
+        }
+        vm::verify(&bytecode).map_err(|e| SceneError::InvalidProgram(e.to_string()))?;
+
+        Ok(Self { source, bytecode })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub tag: u8,
+    pub data: Vec<u8>,
+}
+
+impl Section {
+    fn new(tag: u8, val: &impl Encode) -> Self {
+        let mut data = vec![];
+        val.encode(&mut data);
+        Self { tag, data }
+    }
+}
+
+fn migrate(
+    version: u16,
+    sections: &mut Vec<Section>,
+    migrations: &[Migration],
+) -> Result<(), SceneError> {
+    for m in &migrations[version as usize - 1..] {
+        m(sections)?;
+    }
+    Ok(())
+}
+
+/// everything needed to reopen a plot
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scene {
+    pub settings: AtlasSettings,
+    pub camera: CameraView,
+    pub program: ProgramCode,
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        let settings = AtlasSettings::default();
+        Self {
+            program: ProgramCode::new(settings.iso_2d_config.program.opcode()),
+            camera: CameraView {
+                center: DVec3::ZERO,
+                zoom: 1.0,
+                yaw: 0.0,
+                pitch: 0.0,
+            },
+            settings,
+        }
+    }
+}
+
+impl Scene {
+    pub fn capture(settings: &AtlasSettings, camera: &CameraController) -> Self {
+        Self {
+            settings: settings.clone(),
+            camera: CameraView::of(camera),
+            program: ProgramCode::new(settings.iso_2d_config.opcode()),
+        }
+    }
+
+    /// replaces the saved settings, session state like traces is kept
+    ///
+    /// the saved bytecode is plotted if the built in program changed since the scene was saved
+    pub fn apply(self, settings: &mut AtlasSettings, camera: &mut CameraController) {
+        let s = self.settings;
+        let mut iso = s.iso_2d_config;
+        iso.code = None;
+        if self.program.bytecode != iso.program.opcode() {
+            #[cfg(feature = "native-codegen")]
+            let plottable = crate::iso::jit_bytecode(&self.program.bytecode).is_some();
+            #[cfg(not(feature = "native-codegen"))]
+            let plottable = true;
+
+            if plottable {
+                log::info!("{:?} changed since the scene was saved", iso.program);
+                iso.code = Some((iso.program, self.program.bytecode));
+            } else {
+                log::warn!(
+                    "the saved program can't be plotted, using {:?}",
+                    iso.program
+                );
+            }
+        }
+        settings.iso_2d_config = iso;
+        settings.iso_3d_config = s.iso_3d_config;
+        settings.heatmap = s.heatmap;
+        settings.domain = s.domain;
+        settings.camera_mode = s.camera_mode;
+        settings.lock_zoom = s.lock_zoom;
+        settings.render_config = s.render_config;
+        settings.mesh_gen = s.mesh_gen;
+        settings.rebuild_mesh = true;
+
+        self.camera.apply(camera);
+    }
+
+    fn sections(&self) -> Vec<Section> {
+        vec![
+            Section::new(SETTINGS, &self.settings),
+            Section::new(CAMERA, &self.camera),
+            Section::new(PROGRAM, &self.program),
+        ]
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        VERSION.encode(&mut bytes);
+        for s in self.sections() {
+            s.tag.encode(&mut bytes);
+            (s.data.len() as u32).encode(&mut bytes);
+            bytes.extend_from_slice(&s.data);
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SceneError> {
+        let mut r = Reader::new(bytes);
+        if r.take(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+            return Err(SceneError::BadMagic);
+        }
+        let version: u16 = r.read()?;
+        if version == 0 || version > VERSION {
+            return Err(SceneError::UnsupportedVersion(version));
+        }
+
+        let mut sections = vec![];
+        while !r.is_empty() {
+            let tag = r.read()?;
+            let len = r.read::<u32>()? as usize;
+            let data = r.take(len)?.to_vec();
+            sections.push(Section { tag, data });
+        }
+        migrate(version, &mut sections, &MIGRATIONS)?;
+
+        let mut scene = Self::default();
+        let mut program = None;
+        for s in sections {
+            let mut r = Reader::new(&s.data);
+            match s.tag {
+                SETTINGS => scene.settings = r.read()?,
+                CAMERA => scene.camera = r.read()?,
+                PROGRAM => program = Some(r.read()?),
+                tag => log::warn!("skipping unknown scene section {tag}"),
+            }
+        }
+        // without a program section the built in program of the settings is plotted
+        scene.program = program
+            .unwrap_or_else(|| ProgramCode::new(scene.settings.iso_2d_config.program.opcode()));
+        Ok(scene)
+    }
+
+    /// `scene=<base64url>`, without the leading '#'
+    #[cfg(any(target_arch = "wasm32", test))]
+    pub fn to_fragment(&self) -> String {
+        format!("scene={}", base64_encode(&self.to_bytes()))
+    }
+
+    /// the leading '#' is optional
+    #[cfg(any(target_arch = "wasm32", test))]
+    pub fn from_fragment(fragment: &str) -> Result<Self, SceneError> {
+        let fragment = fragment.strip_prefix('#').unwrap_or(fragment);
+        let data = fragment
+            .strip_prefix("scene=")
+            .ok_or(SceneError::InvalidFragment)?;
+        Self::from_bytes(&base64_decode(data).ok_or(SceneError::InvalidFragment)?)
+    }
+}
+
+/// the scene shared in the url of the page, if any
+#[cfg(target_arch = "wasm32")]
+pub fn from_url() -> Option<Scene> {
+    let hash = web_sys::window()?.location().hash().ok()?;
+    if hash.is_empty() {
+        return None;
+    }
+    Scene::from_fragment(&hash)
+        .inspect_err(|e| log::error!("failed to load the scene of the url: {e}"))
+        .ok()
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn set_url(scene: &Scene) {
+    let Some(window) = web_sys::window() else {
+        return;
+    };
+    if window.location().set_hash(&scene.to_fragment()).is_err() {
+        log::error!("failed to set the url fragment");
+    }
+}
+
+#[cfg(any(target_arch = "wasm32", test))]
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// url safe base64 without padding
+#[cfg(any(target_arch = "wasm32", test))]
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            out.push(BASE64[(n >> (18 - 6 * i)) as usize & 63] as char);
+        }
+    }
+    out
+}
+
+#[cfg(any(target_arch = "wasm32", test))]
+fn base64_decode(s: &str) -> Option<Vec<u8>> {
+    let vals = s
+        .bytes()
+        .map(|c| BASE64.iter().position(|&b| b == c).map(|v| v as u32))
+        .collect::<Option<Vec<_>>>()?;
+
+    let mut out = Vec::with_capacity(vals.len() * 3 / 4);
+    for chunk in vals.chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &v)| n | v << (18 - 6 * i));
+        for i in 0..chunk.len() - 1 {
+            out.push((n >> (16 - 8 * i)) as u8);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn scene() -> Scene {
+        let mut settings = AtlasSettings::default();
+        settings.iso_2d_config.program = Program::Dense2;
+        settings.iso_2d_config.complex = ComplexMode::Im;
+        settings.iso_2d_config.contour = ContourConfig {
+            enabled: true,
+            levels: Levels::List(vec![-0.5, 0.25, 1e-9]),
+        };
+        settings.iso_3d_config.program = Program3D::Waves;
+        settings.heatmap.colormap = Colormap::Diverging;
+        settings.camera_mode = CameraKind::Pan;
+
+        let camera = CameraController::orbit(Vec3::new(1.0, 2.0, 3.0), Vec3::ZERO, 1.2);
+        Scene::capture(&settings, &camera)
+    }
+
+    #[test]
+    fn round_trip() {
+        let scene = scene();
+        let bytes = scene.to_bytes();
+        assert_eq!(bytes[..4], MAGIC);
+        assert_eq!(Scene::from_bytes(&bytes).unwrap(), scene);
+
+        let fragment = scene.to_fragment();
+        assert!(
+            fragment
+                .bytes()
+                .all(|c| c.is_ascii_alphanumeric() || b"=-_".contains(&c))
+        );
+        assert_eq!(
+            Scene::from_fragment(&format!("#{fragment}")).unwrap(),
+            scene
+        );
+
+        for len in 0..8 {
+            let bytes: Vec<u8> = (0..len).map(|i| (i * 97 + 13) as u8).collect();
+            assert_eq!(base64_decode(&base64_encode(&bytes)).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn invalid_scenes() {
+        let bytes = scene().to_bytes();
+
+        assert_eq!(Scene::from_bytes(b"ATL"), Err(SceneError::BadMagic));
+        let mut newer = bytes.clone();
+        newer[4..6].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert_eq!(
+            Scene::from_bytes(&newer),
+            Err(SceneError::UnsupportedVersion(VERSION + 1))
+        );
+        assert_eq!(
+            Scene::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(SceneError::UnexpectedEof)
+        );
+        assert_eq!(
+            Scene::from_fragment("#scene=a!"),
+            Err(SceneError::InvalidFragment)
+        );
+
+        let mut program = ProgramCode::new(Program::XY.opcode());
+        program.source = program.source.replace("MUL", "ADD");
+        let mut bytes = MAGIC.to_vec();
+        VERSION.encode(&mut bytes);
+        let s = Section::new(PROGRAM, &program);
+        s.tag.encode(&mut bytes);
+        (s.data.len() as u32).encode(&mut bytes);
+        bytes.extend(s.data);
+        assert_eq!(
+            Scene::from_bytes(&bytes),
+            Err(SceneError::InvalidProgram(
+                "source and bytecode differ".into()
+            ))
+        );
+
+        // a shared link must not be able to ask for grids that overflow or exhaust memory
+        let settings_scene = |settings: &AtlasSettings| {
+            let mut bytes = MAGIC.to_vec();
+            VERSION.encode(&mut bytes);
+            let s = Section::new(SETTINGS, settings);
+            s.tag.encode(&mut bytes);
+            (s.data.len() as u32).encode(&mut bytes);
+            bytes.extend(s.data);
+            Scene::from_bytes(&bytes)
+        };
+        let mut settings = scene().settings;
+        settings.iso_2d_config.intrvl_depth = 32;
+        assert_eq!(
+            settings_scene(&settings),
+            Err(SceneError::OutOfRange {
+                field: "intrvl_depth",
+                value: 32
+            })
+        );
+        settings.iso_2d_config.intrvl_depth = MAX_DEPTH;
+        settings.iso_2d_config.subdiv_depth = u32::MAX;
+        assert_eq!(
+            settings_scene(&settings),
+            Err(SceneError::OutOfRange {
+                field: "subdiv_depth",
+                value: u32::MAX
+            })
+        );
+        settings.iso_2d_config.subdiv_depth = 0;
+        settings.heatmap.resolution = MAX_RESOLUTION + 1;
+        assert_eq!(
+            settings_scene(&settings),
+            Err(SceneError::OutOfRange {
+                field: "resolution",
+                value: MAX_RESOLUTION + 1
+            })
+        );
+        settings.heatmap.resolution = 0;
+        assert!(settings_scene(&settings).is_err());
+        settings.heatmap.resolution = MAX_RESOLUTION;
+        assert!(settings_scene(&settings).is_ok());
+    }
+
+    #[test]
+    fn sections() {
+        let scene = scene();
+
+        // unknown sections are skipped, missing ones are default
+        let mut bytes = MAGIC.to_vec();
+        VERSION.encode(&mut bytes);
+        for s in [
+            Section::new(42, &String::from("future")),
+            Section::new(CAMERA, &scene.camera),
+        ] {
+            s.tag.encode(&mut bytes);
+            (s.data.len() as u32).encode(&mut bytes);
+            bytes.extend(s.data);
+        }
+        let loaded = Scene::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.camera, scene.camera);
+        assert_eq!(loaded.settings, AtlasSettings::default());
+
+        // migrations from older versions run in order
+        let migrations: [Migration; 2] = [
+            |s| {
+                s.push(Section::new(7, &1u8));
+                Ok(())
+            },
+            |s| {
+                s.iter_mut().for_each(|s| s.tag += 1);
+                Ok(())
+            },
+        ];
+        let mut sections = scene.sections();
+        migrate(1, &mut sections, &migrations).unwrap();
+        assert_eq!(
+            sections.iter().map(|s| s.tag).collect::<Vec<_>>(),
+            [2, 3, 4, 8]
+        );
+        let mut sections = scene.sections();
+        migrate(3, &mut sections, &migrations).unwrap();
+        assert_eq!(sections, scene.sections());
+    }
+
+    #[test]
+    fn apply_program() {
+        let mut camera = CameraController::orbit(Vec3::ONE, Vec3::ZERO, 1.0);
+
+        // a scene saved before the built in XY changed to x - y
+        let mut saved = scene();
+        saved.settings.iso_2d_config.program = Program::XY;
+        saved.settings.iso_2d_config.complex = ComplexMode::Real;
+        saved.program = ProgramCode::new(Program::X.opcode());
+        let saved = Scene::from_bytes(&saved.to_bytes()).unwrap();
+
+        let mut settings = AtlasSettings::default();
+        saved.apply(&mut settings, &mut camera);
+        let iso = &settings.iso_2d_config;
+        assert_eq!(iso.program, Program::XY);
+        assert_eq!(iso.opcode(), Program::X.opcode());
+        #[cfg(feature = "native-codegen")]
+        {
+            let p = DVec2::new(3.0, 1.0);
+            let f = crate::iso::JitFunction::from_config(iso);
+            assert_eq!(f.sample_grid(p, p, 1, 1), [2.0]);
+        }
+        assert_eq!(
+            Scene::capture(&settings, &camera).program.bytecode,
+            Program::X.opcode()
+        );
+
+        // picking another program plots the built in one
+        settings.iso_2d_config.program = Program::Sin;
+        assert_eq!(settings.iso_2d_config.opcode(), Program::Sin.opcode());
+
+        // an unchanged program isn't overridden
+        scene().apply(&mut settings, &mut camera);
+        assert_eq!(settings.iso_2d_config.program, Program::Dense2);
+        assert_eq!(settings.iso_2d_config.code, None);
+    }
+}
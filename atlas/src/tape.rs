@@ -0,0 +1,470 @@
+//! per-region tape specialization
+//!
+//! while subdividing, the interval evaluation of a box often decides branches for the whole box:
+//! a min / max or select always picks the same side, or a factor of the result has a constant
+//! sign. undecided selects and the jumps of floor, ceil, round, fract and mod are flagged, so
+//! their discontinuities are not mistaken for zero crossings.
+//! [`eval`] records these choices and [`specialize`] emits a shorter tape that is valid for every
+//! sub-box, so deeper levels of the subdivision evaluate less instructions.
+
+use crate::vm::{self, Opcode, Range, VM, VmWord, op};
+
+/// which side of an instruction is needed inside a region
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Choice {
+    Both,
+    /// the result equals the lhs, for mul / div only its sign
+    Lhs,
+    Rhs,
+    /// the result has the opposite sign of the lhs
+    NegLhs,
+    NegRhs,
+}
+
+/// interval evaluation of a tape over a box
+#[derive(Debug, Clone)]
+pub struct Region {
+    pub value: Range,
+    /// one choice per instruction
+    pub choices: Vec<Choice>,
+    /// a select is undecided or a step function jumps, so the value can jump inside the box
+    pub switches: bool,
+}
+
+impl Region {
+    pub fn n_decided(&self) -> usize {
+        self.choices.iter().filter(|&&c| c != Choice::Both).count()
+    }
+}
+
+/// strictly positive and finite, so multiplying by it never changes the sign
+fn is_pos(r: Range) -> bool {
+    r.l > 0.0 && r.u.is_finite()
+}
+
+fn is_neg(r: Range) -> bool {
+    r.u < 0.0 && r.l.is_finite()
+}
+
+fn choose(code: u8, a: Range, b: Range) -> Choice {
+    if a.is_empty() || b.is_empty() {
+        return Choice::Both;
+    }
+
+    match code {
+        op::OP_MIN if a.u <= b.l => Choice::Lhs,
+        op::OP_MIN if b.u <= a.l => Choice::Rhs,
+        op::OP_MAX if a.l >= b.u => Choice::Lhs,
+        op::OP_MAX if b.l >= a.u => Choice::Rhs,
+        op::OP_MUL | op::OP_DIV if is_pos(b) => Choice::Lhs,
+        op::OP_MUL | op::OP_DIV if is_neg(b) => Choice::NegLhs,
+        // a / b with constant a can still change sign where b is undefined
+        op::OP_MUL if is_pos(a) => Choice::Rhs,
+        op::OP_MUL if is_neg(a) => Choice::NegRhs,
+        _ => Choice::Both,
+    }
+}
+
+/// the branch a select takes for every condition in `cond`
+fn choose_branch(cond: Range) -> Choice {
+    if cond.is_undecided() {
+        Choice::Both
+    } else if cond.contains_zero() {
+        Choice::Rhs
+    } else {
+        Choice::Lhs
+    }
+}
+
+/// records the choices of tapes on the range vm, keeps its buffers between evaluations
+pub struct Evaluator {
+    vm: VM<Range>,
+    region: Region,
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Self {
+            vm: VM::with_instr_table(vm::RangeInstrTable),
+            region: Region {
+                value: Range::UNDEF,
+                choices: vec![],
+                switches: false,
+            },
+        }
+    }
+}
+
+impl Evaluator {
+    /// evaluates the tape over the box given by the input ranges and records the choices
+    ///
+    /// the vm runs one instruction at a time, see [`VM::step`], the choices only look at the
+    /// operands before the instruction
+    pub fn eval(&mut self, ops: &[Opcode], inputs: &[Range]) -> &Region {
+        let Self { vm, region } = self;
+        vm.reg.fill(Range::UNDEF);
+        vm.reg[1..=inputs.len()].copy_from_slice(inputs);
+        vm.sp = 0;
+        vm.out.clear();
+        region.choices.clear();
+        region.choices.resize(ops.len(), Choice::Both);
+        region.switches = false;
+
+        let mut pc = Some(0);
+        while let Some(i) = pc.filter(|&i| i < ops.len()) {
+            let (code, l, r, out, imm) = op::decode(ops[i]);
+            let arg = |a: usize| {
+                if a == 0 {
+                    Range::from_imm(imm)
+                } else {
+                    vm.reg[a]
+                }
+            };
+            let (a, b) = (arg(l), arg(r));
+
+            match code {
+                // the condition is the out register
+                op::OP_SEL => {
+                    region.choices[i] = choose_branch(vm.reg[out]);
+                    region.switches |= region.choices[i] == Choice::Both;
+                }
+                op::OP_FLOOR | op::OP_CEIL | op::OP_ROUND | op::OP_FRACT | op::OP_MOD => {
+                    region.switches |= Range::has_step(code, a, b);
+                }
+                _ if op::is_binary(code) => region.choices[i] = choose(code, a, b),
+                _ => (),
+            }
+            pc = vm.step(ops, i);
+        }
+
+        region.value = vm.out.first().copied().unwrap_or(vm.reg[1]);
+        &self.region
+    }
+}
+
+/// the tape has an instruction that can set [`Region::switches`]
+pub fn may_switch(ops: &[Opcode]) -> bool {
+    body(ops).iter().any(|&o| {
+        matches!(
+            op::get_op(o),
+            op::OP_SEL | op::OP_FLOOR | op::OP_CEIL | op::OP_ROUND | op::OP_FRACT | op::OP_MOD
+        )
+    })
+}
+
+/// registers read by the instruction, reg 0 is the immediate
+fn reads(opcode: Opcode) -> impl Iterator<Item = usize> {
+    let (code, l, r, out, _) = op::decode(opcode);
+    let r = if op::is_binary(code) { r } else { 0 };
+    let l = match code {
+        op::OP_NOP | op::OP_POP | op::OP_EXT => 0,
+        _ => l,
+    };
+    // the condition of a select
+    let out = if code == op::OP_SEL { out } else { 0 };
+    [l, r, out].into_iter().filter(|&a| a != 0)
+}
+
+fn writes(opcode: Opcode) -> Option<usize> {
+    match op::get_op(opcode) {
+        op::OP_OUT | op::OP_PSH | op::OP_RET | op::OP_NOP | op::OP_EXT => None,
+        _ => Some(op::get_out(opcode)),
+    }
+}
+
+/// the tape up to and excluding EXT
+fn body(ops: &[Opcode]) -> &[Opcode] {
+    let end = ops
+        .iter()
+        .position(|&o| op::get_op(o) == op::OP_EXT)
+        .unwrap_or(ops.len());
+    &ops[..end]
+}
+
+/// move of the chosen operand, negated if only the opposite sign is known
+fn select(opcode: Opcode, choice: Choice) -> Opcode {
+    let (_, l, r, out, imm) = op::decode(opcode);
+    let imm = op::float_from_imm(imm);
+    let (src, neg) = match choice {
+        Choice::Both => return opcode,
+        Choice::Lhs => (l, false),
+        Choice::Rhs => (r, false),
+        Choice::NegLhs => (l, true),
+        Choice::NegRhs => (r, true),
+    };
+
+    match (src, neg) {
+        (0, false) => op::MOV_IMM(imm, out as u8),
+        (0, true) => op::MOV_IMM(-imm, out as u8),
+        (src, false) => op::MOV(src as u8, out as u8),
+        (src, true) => op::SUB_IMM_REG(0.0, src as u8, out as u8),
+    }
+}
+
+/// the value written at `def` is only read by instruction `at` and does not reach the output
+fn only_used_at(ops: &[Opcode], def: usize, at: usize) -> bool {
+    let Some(r) = writes(ops[def]) else {
+        return false;
+    };
+    for (i, &o) in ops.iter().enumerate().skip(def + 1) {
+        if i != at && reads(o).any(|a| a == r) {
+            return false;
+        }
+        if writes(o) == Some(r) {
+            return true;
+        }
+    }
+    r != 1
+}
+
+/// the value of reg `r` before instruction `at` only matters by its sign,
+/// follows the chain of moves, negations and decided factors
+fn specialize_sign(ops: &mut [Opcode], choices: &[Choice], mut r: usize, mut at: usize) {
+    while r != 0 {
+        let Some(def) = (0..at).rev().find(|&i| writes(ops[i]) == Some(r)) else {
+            return;
+        };
+        if at < ops.len() && !only_used_at(ops, def, at) {
+            return;
+        }
+
+        let (code, l, _, _, imm) = op::decode(ops[def]);
+        let is_neg = code == op::OP_SUB && l == 0 && op::float_from_imm(imm) == 0.0;
+        if matches!(code, op::OP_MUL | op::OP_DIV) && choices[def] != Choice::Both {
+            ops[def] = select(ops[def], choices[def]);
+        } else if code != op::OP_MOV && !is_neg {
+            return;
+        }
+
+        r = reads(ops[def]).last().unwrap_or(0);
+        at = def;
+    }
+}
+
+/// replaces reads of moved registers with their source
+fn propagate_copies(ops: &mut [Opcode]) {
+    let mut alias: [usize; vm::REGISTER_COUNT] = std::array::from_fn(|i| i);
+
+    for o in ops.iter_mut() {
+        let (code, l, r, out, imm) = op::decode(*o);
+        let (l, r) = (alias[l], if op::is_binary(code) { alias[r] } else { r });
+        // the raw immediate, CALL stores the id of the native in it
+        *o = op::build_opcode(code, l as u8, r as u8, out as u8, imm);
+
+        if let Some(w) = writes(*o) {
+            for (i, a) in alias.iter_mut().enumerate() {
+                if *a == w || i == w {
+                    *a = i;
+                }
+            }
+            if code == op::OP_MOV && l != 0 {
+                alias[w] = l;
+            }
+        }
+    }
+}
+
+fn eliminate_dead_code(ops: &[Opcode]) -> Vec<Opcode> {
+    let rets = ops.iter().any(|&o| op::get_op(o) == op::OP_RET);
+    let mut live = [false; vm::REGISTER_COUNT];
+    live[1] = !rets;
+
+    let mut keep = vec![false; ops.len()];
+    for (i, &o) in ops.iter().enumerate().rev() {
+        let code = op::get_op(o);
+        let needed = match writes(o) {
+            _ if matches!(code, op::OP_OUT | op::OP_RET) => true,
+            Some(w) if code == op::OP_MOV && op::get_lhs(o) == w => false,
+            Some(w) => live[w],
+            None => false,
+        };
+        if !needed {
+            continue;
+        }
+        keep[i] = true;
+        if let Some(w) = writes(o) {
+            live[w] = false;
+        }
+        for a in reads(o) {
+            live[a] = true;
+        }
+    }
+
+    ops.iter()
+        .zip(keep)
+        .filter_map(|(&o, k)| k.then_some(o))
+        .collect()
+}
+
+/// emits a tape that is valid for every sub-box of the region the choices were made in
+///
+/// with `sign_only` factors of the result with a constant sign are dropped,
+/// so the result keeps its sign but not its value.
+/// returns None if the tape can not be shortened
+pub fn specialize(ops: &[Opcode], choices: &[Choice], sign_only: bool) -> Option<Vec<Opcode>> {
+    let body = body(ops);
+    if body
+        .iter()
+        .any(|&o| matches!(op::get_op(o), op::OP_PSH | op::OP_POP))
+    {
+        return None;
+    }
+
+    let mut tape: Vec<_> = body
+        .iter()
+        .zip(choices)
+        .map(|(&o, &c)| match op::get_op(o) {
+            op::OP_MIN | op::OP_MAX | op::OP_SEL => select(o, c),
+            _ => o,
+        })
+        .collect();
+
+    let rets = tape.iter().any(|&o| op::get_op(o) == op::OP_RET);
+    if sign_only && !rets {
+        let at = tape.len();
+        specialize_sign(&mut tape, choices, 1, at);
+    }
+
+    propagate_copies(&mut tape);
+    let mut tape = eliminate_dead_code(&tape);
+
+    if tape.len() >= body.len() {
+        return None;
+    }
+    // EXT and the constant pool after it
+    match &ops[body.len()..] {
+        [] => tape.push(op::EXT(0)),
+        rest => tape.extend_from_slice(rest),
+    }
+    Some(tape)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::iso::Program;
+
+    fn eval(ops: &[Opcode], inputs: &[Range]) -> Region {
+        Evaluator::default().eval(ops, inputs).clone()
+    }
+
+    fn eval_vm(ops: &[Opcode], x: f64, y: f64) -> f64 {
+        let mut vm = vm::VM::with_instr_table(vm::F64InstrTable);
+        vm.call([x, y], ops)[0]
+    }
+
+    fn boxes() -> impl Iterator<Item = [Range; 2]> {
+        (0..64).map(|i| {
+            let x = (i % 8) as f64 * 0.5 - 2.0;
+            let y = (i / 8) as f64 * 0.5 - 2.0;
+            [Range::new(x, x + 0.5), Range::new(y, y + 0.5)]
+        })
+    }
+
+    #[test]
+    fn union() {
+        let ops = Program::Union.opcode();
+        let mut shortened = 0;
+
+        for [x, y] in boxes() {
+            let region = eval(&ops, &[x, y]);
+            let Some(tape) = specialize(&ops, &region.choices, false) else {
+                continue;
+            };
+            shortened += 1;
+            assert!(tape.len() < ops.len());
+
+            // the specialized tape computes the same values inside the box
+            for (s, t) in [(0.0, 0.0), (0.3, 0.7), (1.0, 1.0), (0.5, 0.1)] {
+                let (px, py) = (x.l + s * 0.5, y.l + t * 0.5);
+                assert_eq!(eval_vm(&ops, px, py), eval_vm(&tape, px, py));
+            }
+
+            let sub = [Range::new(x.l, x.l + 0.25), Range::new(y.l, y.l + 0.25)];
+            assert_eq!(eval(&ops, &sub).value, eval(&tape, &sub).value);
+        }
+
+        assert!(shortened > 0);
+    }
+
+    #[test]
+    fn sign_only() {
+        let ops = [
+            op::SIN(1, 3),
+            op::ADD_REG_IMM(2, 3.0, 4),
+            op::MUL_REG_REG(3, 4, 1),
+            op::EXT(0),
+        ];
+
+        let region = eval(&ops, &[Range::new(0.0, 1.0), Range::new(-1.0, 1.0)]);
+        assert_eq!(region.choices[2], Choice::Lhs);
+        assert_eq!(specialize(&ops, &region.choices, false), None);
+
+        let tape = specialize(&ops, &region.choices, true).unwrap();
+        assert_eq!(tape, [op::SIN(1, 3), op::MOV(3, 1), op::EXT(0)]);
+
+        // y in [-1, 1] is not constant signed
+        let region = eval(&ops, &[Range::new(0.0, 1.0), Range::new(-4.0, 1.0)]);
+        assert_eq!(specialize(&ops, &region.choices, true), None);
+    }
+
+    #[test]
+    fn select() {
+        let ops = Program::Piecewise.opcode();
+        let y = Range::new(-1.0, 1.0);
+
+        for (x, choice) in [
+            (Range::new(-2.0, -1.0), Choice::Lhs),
+            (Range::new(1.0, 2.0), Choice::Rhs),
+            (Range::new(-0.5, 0.5), Choice::Both),
+        ] {
+            let region = eval(&ops, &[x, y]);
+            assert_eq!(region.choices[3], choice);
+            assert_eq!(region.switches, choice == Choice::Both);
+            if choice == Choice::Both {
+                continue;
+            }
+
+            let tape = specialize(&ops, &region.choices, false).unwrap();
+            assert!(!tape.iter().any(|&o| op::decode(o).0 == op::OP_SEL));
+            for s in [0.0, 0.3, 1.0] {
+                let px = x.l + s * (x.u - x.l);
+                assert_eq!(eval_vm(&ops, px, 0.5), eval_vm(&tape, px, 0.5));
+            }
+        }
+
+        // an undecided condition gives the hull of both branches
+        let region = eval(&ops, &[Range::new(-0.5, 0.5), Range::new(0.0, 0.0)]);
+        assert!(region.value.l <= -1.5 && region.value.u >= -0.5f64.sin());
+    }
+
+    #[test]
+    fn steps() {
+        let ops = Program::Floor.opcode();
+        let y = Range::new(-1.0, 1.0);
+
+        let region = eval(&ops, &[Range::new(0.25, 0.75), y]);
+        assert!(!region.switches);
+        assert_eq!(region.value, Range::new(-1.0, 1.0));
+
+        // floor jumps at x = 1
+        let region = eval(&ops, &[Range::new(0.75, 1.25), y]);
+        assert!(region.switches);
+        assert_eq!(region.value, Range::new(-2.0, 1.0));
+    }
+
+    #[test]
+    fn stack_is_kept() {
+        let ops = [
+            op::PSH(1),
+            op::MIN_REG_IMM(2, 0.0, 1),
+            op::POP(2),
+            op::ADD_REG_REG(1, 2, 1),
+            op::EXT(0),
+        ];
+        let region = eval(&ops, &[Range::new(1.0, 2.0), Range::new(1.0, 2.0)]);
+        assert_eq!(region.value, Range::new(1.0, 2.0));
+        assert_eq!(region.n_decided(), 1);
+        assert_eq!(specialize(&ops, &region.choices, false), None);
+    }
+}
@@ -0,0 +1,225 @@
+use egui_probe::EguiProbe;
+use glam::{DVec2, DVec3};
+
+use crate::vm;
+
+/// a point on the implicit curve / surface f = 0
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TracePoint {
+    pub pos: DVec3,
+    pub val: f64,
+    pub grad: DVec3,
+    pub is_3d: bool,
+}
+
+impl TracePoint {
+    /// dy/dx of the level curve through pos
+    pub fn slope(&self) -> f64 {
+        -self.grad.x / self.grad.y
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, EguiProbe)]
+pub struct TraceConfig {
+    #[egui_probe(toggle_switch)]
+    pub enabled: bool,
+    pub newton_iters: u32,
+    /// max. distance between cursor and curve in points
+    pub snap_radius: f32,
+    pub march_depth: u32,
+
+    #[egui_probe(skip)]
+    pub pinned: Vec<TracePoint>,
+}
+
+impl Default for TraceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            newton_iters: 16,
+            snap_radius: 24.0,
+            march_depth: 24,
+            pinned: vec![],
+        }
+    }
+}
+
+/// evaluates f and its gradient at p, dims is the number of input registers
+pub fn eval_grad(ops: &[vm::Opcode], p: DVec3, dims: usize) -> (f64, DVec3) {
+    let mut vm = vm::VM::with_instr_table(vm::F64DerivInstrTable);
+    let mut val = f64::NAN;
+    let mut grad = DVec3::ZERO;
+
+    for d in 0..dims {
+        for i in 0..3 {
+            vm.reg[i + 1] = if i == d {
+                vm::F64Deriv::var(p[i])
+            } else {
+                vm::F64Deriv::cnst(p[i])
+            };
+        }
+        vm.eval(ops);
+        val = vm.reg[1].val;
+        grad[d] = vm.reg[1].grad;
+    }
+
+    (val, grad)
+}
+
+/// projects p onto f(x, y) = 0 using newton steps along the gradient
+///
+/// returns None if the iteration does not end up within tol of the curve
+pub fn project_2d(ops: &[vm::Opcode], p: DVec2, iters: u32, tol: f64) -> Option<TracePoint> {
+    let mut p = p.extend(0.0);
+
+    for _ in 0..iters {
+        let (val, grad) = eval_grad(ops, p, 2);
+        let len2 = grad.length_squared();
+        if !val.is_finite() || !len2.is_normal() {
+            return None;
+        }
+
+        let step = grad * (val / len2);
+        p -= step;
+
+        if step.length() <= tol * 1e-6 {
+            break;
+        }
+    }
+
+    let (val, grad) = eval_grad(ops, p, 2);
+    let dist = val.abs() / grad.length();
+
+    (dist <= tol).then_some(TracePoint {
+        pos: p,
+        val,
+        grad,
+        is_3d: false,
+    })
+}
+
+/// entry and exit parameter of the ray o + t * d through the box [min, max]
+pub fn ray_box(o: DVec3, d: DVec3, min: DVec3, max: DVec3) -> Option<(f64, f64)> {
+    let inv = d.recip();
+    let t0 = (min - o) * inv;
+    let t1 = (max - o) * inv;
+
+    let t_enter = t0.min(t1).max_element().max(0.0);
+    let t_exit = t0.max(t1).min_element();
+
+    (t_enter <= t_exit).then_some((t_enter, t_exit))
+}
+
+/// first intersection of the ray o + t * d with f(x, y, z) = 0 for t in [t_min, t_max]
+///
+/// the ray is split in halves, segments whose bounding box can not contain a root are skipped
+pub fn ray_march(
+    ops: &[vm::Opcode],
+    o: DVec3,
+    d: DVec3,
+    t_min: f64,
+    t_max: f64,
+    depth: u32,
+) -> Option<f64> {
+    let mut vm = vm::VM::with_instr_table(vm::RangeInstrTable);
+    let mut stack = vec![(t_min, t_max, 0)];
+
+    while let Some((t0, t1, lvl)) = stack.pop() {
+        let a = o + d * t0;
+        let b = o + d * t1;
+        let (min, max) = (a.min(b), a.max(b));
+
+        for i in 0..3 {
+            vm.reg[i + 1] = (min[i], max[i]).into();
+        }
+        vm.eval(ops);
+        let range = vm.reg[1];
+
+        if !range.contains_zero() && range.is_valid() {
+            continue;
+        }
+
+        let mid = (t0 + t1) * 0.5;
+        if lvl >= depth {
+            if range.contains_zero() {
+                return Some(mid);
+            }
+            continue;
+        }
+
+        // near half is popped first
+        stack.push((mid, t1, lvl + 1));
+        stack.push((t0, mid, lvl + 1));
+    }
+
+    None
+}
+
+/// intersects the ray with the surface inside the box [min, max]
+pub fn trace_3d(
+    ops: &[vm::Opcode],
+    o: DVec3,
+    d: DVec3,
+    min: DVec3,
+    max: DVec3,
+    depth: u32,
+) -> Option<TracePoint> {
+    let (t0, t1) = ray_box(o, d, min, max)?;
+    let t = ray_march(ops, o, d, t0, t1, depth)?;
+    let pos = o + d * t;
+    let (val, grad) = eval_grad(ops, pos, 3);
+
+    Some(TracePoint {
+        pos,
+        val,
+        grad,
+        is_3d: true,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use vm::op;
+
+    #[test]
+    fn project_circle() {
+        let circle = [
+            op::MUL_REG_REG(1, 1, 1),
+            op::MUL_REG_REG(2, 2, 2),
+            op::ADD_REG_REG(1, 2, 1),
+            op::SUB_REG_IMM(1, 1.0, 1),
+            op::EXT(0),
+        ];
+
+        let p = project_2d(&circle, DVec2::new(2.0, 1.0), 16, 1e-9).unwrap();
+        assert!((p.pos.length() - 1.0).abs() < 1e-9);
+        // newton steps move along the gradient, which is radial for a circle
+        assert!((p.pos.y / p.pos.x - 0.5).abs() < 1e-9);
+        assert!((p.slope() + p.pos.x / p.pos.y).abs() < 1e-9);
+
+        assert!(project_2d(&circle, DVec2::ZERO, 16, 1e-9).is_none());
+    }
+
+    #[test]
+    fn march_sphere() {
+        let sphere = crate::iso_3d::Program3D::Sphere.opcode();
+        let o = DVec3::new(-3.0, 0.25, 0.0);
+        let d = DVec3::X;
+
+        let p = trace_3d(&sphere, o, d, DVec3::splat(-2.0), DVec3::splat(2.0), 40).unwrap();
+        let expected = -(1.0f64 - 0.25 * 0.25).sqrt();
+        assert!((p.pos.x - expected).abs() < 1e-6, "{:?}", p.pos);
+        assert!(p.grad.x < 0.0);
+
+        let miss = trace_3d(
+            &sphere,
+            o.with_y(1.5),
+            d,
+            DVec3::splat(-2.0),
+            DVec3::splat(2.0),
+            40,
+        );
+        assert!(miss.is_none());
+    }
+}
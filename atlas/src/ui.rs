@@ -1,6 +1,8 @@
 use std::{fmt, str::FromStr};
 
-use crate::camera::Camera;
+use crate::camera::{CameraController, CameraKind};
+use crate::scene::Scene;
+use crate::trace::TracePoint;
 use crate::{AtlasSettings, WindowData};
 
 use egui::Rect;
@@ -187,9 +189,12 @@ pub struct UiAccess<'a> {
     pub vp_texture: egui::TextureId,
     // pub camera: &'a Camera,
     pub window_info: &'a mut WindowData,
+    pub camera: &'a mut CameraController,
     //vp_dragged: &'a mut bool,
     //vp_rect: &'a mut egui::Rect,
     pub settings: &'a mut AtlasSettings,
+    /// the compiled function of the 2d program
+    pub function: &'a mut crate::iso::FunctionCache,
 }
 
 //type UiDemo = egui_demo_lib::WidgetGallery;
@@ -240,6 +245,14 @@ fn edit_field<T: fmt::Display + FromStr + Default + PartialEq>(
     res
 }
 
+fn trace_coords(p: &TracePoint) -> String {
+    if p.is_3d {
+        format!("({:.4}, {:.4}, {:.4})", p.pos.x, p.pos.y, p.pos.z)
+    } else {
+        format!("({:.4}, {:.4})", p.pos.x, p.pos.y)
+    }
+}
+
 impl UiAccess<'_> {
     fn viewport(&mut self, ui: &mut egui::Ui, tile_id: tiles::TileId) -> tiles::UiResponse {
         let min = ui.cursor().min;
@@ -249,11 +262,27 @@ impl UiAccess<'_> {
             .image(self.vp_texture, ui.max_rect(), uv, egui::Color32::WHITE);
 
         //ui.allocate_space(ui.available_size());
-        let resp = ui.allocate_rect(ui.max_rect(), egui::Sense::drag());
+        let resp = ui.allocate_rect(ui.max_rect(), egui::Sense::click_and_drag());
 
         self.window_info.viewport_rect = resp.rect;
         self.window_info.viewport_dragged = resp.dragged();
 
+        if self.settings.trace.enabled {
+            self.trace(ui, &resp);
+        }
+
+        if self.settings.iso_2d_config.contour.enabled {
+            self.contour_legend(ui, resp.rect);
+        }
+
+        if self.settings.field.enabled && self.camera.kind == CameraKind::Pan {
+            self.field_seeds(ui, &resp);
+        }
+
+        if self.settings.vm_trace.enabled && self.camera.kind == CameraKind::Pan {
+            self.vm_trace_cell(ui, &resp);
+        }
+
         // let gizmo = &mut self.gizmo;
 
         // let mut config = gizmo.config().clone();
@@ -308,6 +337,192 @@ impl UiAccess<'_> {
         tiles::UiResponse::None
     }
 
+    fn contour_legend(&self, ui: &mut egui::Ui, rect: Rect) {
+        let levels = self.settings.iso_2d_config.contour.levels();
+        let painter = ui.painter_at(rect);
+        let font = egui::FontId::monospace(11.0);
+        let row_h = 16.0;
+
+        let mut pos = rect.right_top() + egui::vec2(-96.0, 8.0);
+        let bg = Rect::from_min_size(pos, egui::vec2(88.0, row_h * levels.len() as f32 + 8.0));
+        painter.rect_filled(bg, 4.0, egui::Color32::from_black_alpha(160));
+
+        pos += egui::vec2(6.0, 4.0);
+        for (i, c) in levels.iter().enumerate() {
+            let col = crate::iso::level_color(i, levels.len()) * 255.0;
+            let col = egui::Color32::from_rgb(col.x as u8, col.y as u8, col.z as u8);
+
+            let swatch = Rect::from_min_size(pos + egui::vec2(0.0, 3.0), egui::vec2(16.0, 10.0));
+            painter.rect_filled(swatch, 2.0, col);
+            painter.text(
+                pos + egui::vec2(22.0, 0.0),
+                egui::Align2::LEFT_TOP,
+                format!("{c:.3}"),
+                font.clone(),
+                egui::Color32::WHITE,
+            );
+            pos.y += row_h;
+        }
+    }
+
+    /// the 2d program is traced with the bytecode of the plotted function
+    fn trace_at(&mut self, screen: glam::Vec2) -> Option<TracePoint> {
+        let cfg = &self.settings.trace;
+        match self.camera.kind {
+            CameraKind::Pan => {
+                let iso = &self.settings.iso_2d_config;
+                let p = self.camera.pan_screen_to_world(screen);
+                let tol = (iso.max.y - iso.min.y) / self.camera.vp_height as f64;
+                let ops = self.function.get(iso).opcode();
+                crate::trace::project_2d(ops, p, cfg.newton_iters, tol)
+            }
+            CameraKind::Orbit => {
+                // the 3d mesh is normalized to [-1, 1]^3
+                let iso = &self.settings.iso_3d_config;
+                let (center, half) = ((iso.max + iso.min) * 0.5, (iso.max - iso.min) * 0.5);
+                let (o, d) = self
+                    .camera
+                    .orbit_screen_ray(screen, self.settings.lock_zoom);
+                let o = center + o.as_dvec3() * half;
+                let d = d.as_dvec3() * half;
+                crate::trace::trace_3d(
+                    iso.program.opcode(),
+                    o,
+                    d,
+                    iso.min,
+                    iso.max,
+                    cfg.march_depth,
+                )
+            }
+        }
+    }
+
+    fn trace_to_screen(&self, p: &TracePoint) -> Option<glam::Vec2> {
+        if p.is_3d {
+            let iso = &self.settings.iso_3d_config;
+            let (center, half) = ((iso.max + iso.min) * 0.5, (iso.max - iso.min) * 0.5);
+            let r = (p.pos - center) / half;
+            self.camera
+                .orbit_world_to_screen(r.as_vec3(), self.settings.lock_zoom)
+        } else {
+            Some(self.camera.pan_world_to_screen(p.pos.truncate()))
+        }
+    }
+
+    fn trace(&mut self, ui: &mut egui::Ui, resp: &egui::Response) {
+        let rect = resp.rect;
+        let is_3d = self.camera.kind == CameraKind::Orbit;
+        let to_pos = |p: glam::Vec2| rect.min + egui::vec2(p.x, p.y);
+
+        let painter = ui.painter_at(rect);
+        let pin_col = egui::Color32::from_rgb(250, 179, 135);
+        let hover_col = egui::Color32::from_rgb(137, 180, 250);
+
+        for p in self
+            .settings
+            .trace
+            .pinned
+            .iter()
+            .filter(|p| p.is_3d == is_3d)
+        {
+            if let Some(s) = self.trace_to_screen(p) {
+                let pos = to_pos(s);
+                painter.circle_filled(pos, 4.0, pin_col);
+                painter.text(
+                    pos + egui::vec2(6.0, -6.0),
+                    egui::Align2::LEFT_BOTTOM,
+                    trace_coords(p),
+                    egui::FontId::monospace(11.0),
+                    pin_col,
+                );
+            }
+        }
+
+        let Some(hover) = resp.hover_pos() else {
+            return;
+        };
+        let screen = glam::Vec2::new(hover.x - rect.min.x, hover.y - rect.min.y);
+
+        let snapped = self.trace_at(screen).and_then(|p| {
+            let s = self.trace_to_screen(&p)?;
+            (s.distance(screen) <= self.settings.trace.snap_radius).then_some((p, s))
+        });
+
+        let Some((p, s)) = snapped else {
+            return;
+        };
+
+        painter.circle_stroke(to_pos(s), 5.0, egui::Stroke::new(2.0, hover_col));
+        resp.clone().on_hover_ui_at_pointer(|ui| {
+            ui.label(trace_coords(&p));
+            if p.is_3d {
+                ui.label(format!(
+                    "∇f = ({:.4}, {:.4}, {:.4})",
+                    p.grad.x, p.grad.y, p.grad.z
+                ));
+            } else {
+                ui.label(format!("dy/dx = {:.4}", p.slope()));
+            }
+            ui.weak("click to pin");
+        });
+
+        if resp.clicked() {
+            self.settings.trace.pinned.push(p);
+        }
+    }
+
+    /// draws the trajectory seeds, right click adds a new one
+    fn field_seeds(&mut self, ui: &mut egui::Ui, resp: &egui::Response) {
+        let rect = resp.rect;
+        let painter = ui.painter_at(rect);
+        let seed_col = egui::Color32::from_rgb(250, 179, 135);
+
+        for &seed in &self.settings.field.seeds {
+            let s = self.camera.pan_world_to_screen(seed);
+            painter.circle_filled(rect.min + egui::vec2(s.x, s.y), 3.0, seed_col);
+        }
+
+        if resp.secondary_clicked()
+            && let Some(pos) = resp.interact_pointer_pos()
+        {
+            let screen = glam::Vec2::new(pos.x - rect.min.x, pos.y - rect.min.y);
+            let seed = self.camera.pan_screen_to_world(screen);
+            self.settings.field.seeds.push(seed);
+        }
+    }
+
+    /// outlines the traced cell, middle click traces the cell under the cursor
+    fn vm_trace_cell(&mut self, ui: &mut egui::Ui, resp: &egui::Response) {
+        let rect = resp.rect;
+        let iso = &self.settings.iso_2d_config;
+
+        if resp.middle_clicked()
+            && let Some(pos) = resp.interact_pointer_pos()
+        {
+            let screen = glam::Vec2::new(pos.x - rect.min.x, pos.y - rect.min.y);
+            let p = self.camera.pan_screen_to_world(screen);
+            self.settings
+                .vm_trace
+                .select(&iso.opcode(), iso.min, iso.max, iso.intrvl_depth, p);
+        }
+
+        if let Some((min, max)) = self.settings.vm_trace.cell {
+            let a = self.camera.pan_world_to_screen(min);
+            let b = self.camera.pan_world_to_screen(max);
+            let cell = Rect::from_two_pos(
+                rect.min + egui::vec2(a.x, a.y),
+                rect.min + egui::vec2(b.x, b.y),
+            );
+            let col = egui::Color32::from_rgb(243, 139, 168);
+            ui.painter_at(rect).rect_stroke(
+                cell,
+                0.0,
+                egui::Stroke::new(1.5, col),
+                egui::StrokeKind::Middle,
+            );
+        }
+    }
+
     fn placeholder(&mut self, ui: &mut egui::Ui, tile_id: tiles::TileId) -> tiles::UiResponse {
         let color = egui::epaint::Rgba::from_rgb(0.2, 0.0, 0.2);
         ui.painter().rect_filled(ui.max_rect(), 0.0, color);
@@ -332,6 +547,99 @@ impl UiAccess<'_> {
 
         Probe::new(settings).show(ui);
 
+        #[cfg(not(target_arch = "wasm32"))]
+        if settings.heatmap.enabled {
+            ui.horizontal(|ui| {
+                let path = &mut settings.paths.heatmap;
+                if ui.button("export heatmap").clicked() {
+                    let config = &settings.iso_2d_config;
+                    let res = crate::heatmap::export_png(
+                        &*path,
+                        config,
+                        self.function.get(config),
+                        &settings.heatmap,
+                    );
+                    match res {
+                        Ok(()) => log::info!("exported heatmap to {path}"),
+                        Err(e) => log::error!("failed to export heatmap: {e}"),
+                    }
+                }
+                ui.text_edit_singleline(path);
+            });
+        }
+
+        ui.horizontal(|ui| {
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                let path = settings.paths.scene.clone();
+                if ui.button("save scene").clicked() {
+                    let scene = Scene::capture(settings, self.camera);
+                    match std::fs::write(&path, scene.to_bytes()) {
+                        Ok(()) => log::info!("saved scene to {path}"),
+                        Err(e) => log::error!("failed to save scene: {e}"),
+                    }
+                }
+                if ui.button("open scene").clicked() {
+                    let scene = std::fs::read(&path)
+                        .map_err(|e| e.to_string())
+                        .and_then(|b| Scene::from_bytes(&b).map_err(|e| e.to_string()));
+                    match scene {
+                        Ok(scene) => scene.apply(settings, self.camera),
+                        Err(e) => log::error!("failed to open scene: {e}"),
+                    }
+                }
+                ui.text_edit_singleline(&mut settings.paths.scene);
+            }
+            #[cfg(target_arch = "wasm32")]
+            if ui.button("share link").clicked() {
+                crate::scene::set_url(&Scene::capture(settings, self.camera));
+            }
+        });
+
+        if !settings.trace.pinned.is_empty() {
+            ui.collapsing("pinned points", |ui| {
+                let mut remove = None;
+                for (i, p) in settings.trace.pinned.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui.small_button("x").clicked() {
+                            remove = Some(i);
+                        }
+                        ui.monospace(trace_coords(p));
+                    });
+                }
+                if let Some(i) = remove {
+                    settings.trace.pinned.remove(i);
+                }
+                if ui.button("clear").clicked() {
+                    settings.trace.pinned.clear();
+                }
+            });
+        }
+
+        if settings.vm_trace.enabled {
+            ui.collapsing("vm trace", |ui| {
+                settings.vm_trace.ui(ui);
+
+                #[cfg(not(target_arch = "wasm32"))]
+                if settings.vm_trace.cell.is_some() {
+                    ui.horizontal(|ui| {
+                        let path = &mut settings.paths.vm_trace;
+                        if ui.button("export trace").clicked() {
+                            match settings.vm_trace.export(&*path) {
+                                Ok(()) => log::info!("exported trace to {path}"),
+                                Err(e) => log::error!("failed to export trace: {e}"),
+                            }
+                        }
+                        ui.text_edit_singleline(path);
+                    });
+                }
+            });
+        }
+
+        if !settings.field.seeds.is_empty() && ui.button("clear trajectories").clicked() {
+            settings.field.seeds.clear();
+        }
+
         ui.add_space(12.0);
 
         let ctx = ui.ctx().clone();
@@ -369,6 +677,29 @@ impl tiles::Behavior<UiTab> for UiAccess<'_> {
     }
 }
 
+/// files written and read by the settings panel, relative to the working directory
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilePaths {
+    /// png the heatmap is exported to
+    pub heatmap: String,
+    /// the vm trace is exported as csv if the path ends in `.csv`, as json otherwise
+    pub vm_trace: String,
+    /// scenes are saved to and opened from here, see [`Scene`]
+    pub scene: String,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for FilePaths {
+    fn default() -> Self {
+        Self {
+            heatmap: "heatmap.png".into(),
+            vm_trace: "trace.json".into(),
+            scene: "scene.atlas".into(),
+        }
+    }
+}
+
 pub struct UiState {
     pub tile_state: tiles::Tree<UiTab>,
 }
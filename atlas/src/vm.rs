@@ -6,7 +6,7 @@ use std::{
 
 use paste::paste;
 
-use utils::{ExplicitCopy, Intrvl};
+use utils::{Complex, ExplicitCopy, Intrvl, native::NativeId, special::Special};
 
 pub type Opcode = u64;
 pub type Address = usize;
@@ -87,6 +87,8 @@ pub mod op {
         OP_MUL,
         OP_DIV,
         OP_POW,
+        OP_MIN,
+        OP_MAX,
         OP_SIN,
         OP_COS,
         OP_TAN,
@@ -99,12 +101,41 @@ pub mod op {
         OP_POP,
         // push lhs reg value to the stack
         OP_PSH,
+        // append lhs reg value to the outputs
+        OP_RET,
 
         OP_EXT,
+
+        // appended so the numbers of stored programs stay valid
+        // 1 if lhs < rhs else 0
+        OP_LT,
+        // 1 if lhs <= rhs else 0
+        OP_LE,
+        // out = lhs if out != 0, else rhs. the condition is the previous value of out, like
+        // `compiler::jit::Instr::Select` with cond = out
+        OP_SEL,
+        OP_FLOOR,
+        OP_CEIL,
+        // rounds half to even
+        OP_ROUND,
+        // lhs - floor(lhs)
+        OP_FRACT,
+        // lhs - rhs * floor(lhs / rhs)
+        OP_MOD,
+        // special functions of lhs, see op::special
+        OP_GAMMA,
+        OP_ERF,
+        OP_J0,
+        OP_J1,
+        OP_LAMBERTW,
+        // the native with the id imm of the lhs register, see utils::native
+        OP_CALL,
+        // move the f64 constant imm of the pool after EXT to out, see op::constant
+        OP_LIT,
     }
 
     #[inline(always)]
-    const fn build_opcode(op: u8, lhs: u8, rhs: u8, out: u8, imm: u32) -> Opcode {
+    pub(crate) const fn build_opcode(op: u8, lhs: u8, rhs: u8, out: u8, imm: u32) -> Opcode {
         let opcode =
             ((out as u64) << 24) | ((rhs as u64) << 16) | ((lhs as u64) << 8) | (op as u64);
 
@@ -112,7 +143,13 @@ pub mod op {
     }
 
     #[inline(always)]
-    const fn build_opcode_float(op: u8, lhs: u8, rhs: u8, out: u8, imm: float) -> Opcode {
+    pub(crate) const fn build_opcode_float(
+        op: u8,
+        lhs: u8,
+        rhs: u8,
+        out: u8,
+        imm: float,
+    ) -> Opcode {
         build_opcode(op, lhs, rhs, out, float_to_imm(imm))
     }
 
@@ -179,6 +216,16 @@ pub mod op {
         build_opcode_float(OP_POP, 0, 0, out, 0.0)
     }
 
+    #[allow(non_snake_case)]
+    pub const fn RET(lhs: u8) -> Opcode {
+        build_opcode_float(OP_RET, lhs, 0, 0, 0.0)
+    }
+
+    #[allow(non_snake_case)]
+    pub const fn RET_IMM(imm: float) -> Opcode {
+        build_opcode_float(OP_RET, 0, 0, 0, imm)
+    }
+
     #[allow(non_snake_case)]
     pub const fn EXT(exit_code: u32) -> Opcode {
         build_opcode(OP_EXT, 0, 0, 0, exit_code)
@@ -189,17 +236,73 @@ pub mod op {
     binop_opcode!(MUL);
     binop_opcode!(DIV);
     binop_opcode!(POW);
+    binop_opcode!(MIN);
+    binop_opcode!(MAX);
+    binop_opcode!(LT);
+    binop_opcode!(LE);
+    binop_opcode!(SEL);
+    binop_opcode!(MOD);
 
     unary_opcode!(SIN);
     unary_opcode!(COS);
     unary_opcode!(TAN);
     unary_opcode!(MOV);
+    unary_opcode!(FLOOR);
+    unary_opcode!(CEIL);
+    unary_opcode!(ROUND);
+    unary_opcode!(FRACT);
+    unary_opcode!(GAMMA);
+    unary_opcode!(ERF);
+    unary_opcode!(J0);
+    unary_opcode!(J1);
+    unary_opcode!(LAMBERTW);
+
+    /// natives have no immediate operand, the immediate holds the id
+    #[allow(non_snake_case)]
+    pub const fn CALL(f: NativeId, lhs: u8, out: u8) -> Opcode {
+        build_opcode(OP_CALL, lhs, 0, out, f.to_raw())
+    }
+
+    /// immediates are f32, wider constants are loaded from the pool, see [`constant`]
+    #[allow(non_snake_case)]
+    pub const fn LIT(index: u32, out: u8) -> Opcode {
+        build_opcode(OP_LIT, 0, 0, out, index)
+    }
+
+    /// the constant `index` of the pool, which is stored backwards at the end of the
+    /// program after EXT. removing instructions before EXT keeps the indices valid
+    #[inline(always)]
+    pub fn constant(bin: &[Opcode], index: u32) -> Option<float> {
+        let i = bin.len().checked_sub(index as usize + 1)?;
+        Some(float::from_bits(bin[i]))
+    }
+
+    /// true if `v` survives the round trip through an f32 immediate
+    pub fn fits_imm(v: float) -> bool {
+        v.is_nan() || float_from_imm(float_to_imm(v)).to_bits() == v.to_bits()
+    }
 
     #[allow(non_snake_case)]
     pub const fn EXP(lhs: u8, out: u8) -> Opcode {
         POW_REG_IMM(lhs, E, out)
     }
 
+    /// 1 if lhs >= 0, else 0, so heaviside(0) = 1 and nan is 0
+    #[allow(non_snake_case)]
+    pub const fn HEAVISIDE(lhs: u8, out: u8) -> Opcode {
+        LE_IMM_REG(0.0, lhs, out)
+    }
+
+    /// -1, 0 or 1 as (0 < lhs) - (lhs < 0), so nan is 0. tmp is overwritten and can't be lhs
+    #[allow(non_snake_case)]
+    pub const fn SIGN(lhs: u8, tmp: u8, out: u8) -> [Opcode; 3] {
+        [
+            LT_IMM_REG(0.0, lhs, tmp),
+            LT_REG_IMM(lhs, 0.0, out),
+            SUB_REG_REG(tmp, out, out),
+        ]
+    }
+
     //#[allow(non_snake_case)]
     //#[inline(always)]
     //pub const fn LIT_F32(v: float) -> Opcode {
@@ -291,6 +394,8 @@ pub mod op {
             OP_MUL => "MUL",
             OP_DIV => "DIV",
             OP_POW => "POW",
+            OP_MIN => "MIN",
+            OP_MAX => "MAX",
             OP_SIN => "SIN",
             OP_COS => "COS",
             OP_TAN => "TAN",
@@ -299,24 +404,95 @@ pub mod op {
             OP_EXT => "EXT",
             OP_POP => "POP",
             OP_PSH => "PSH",
+            OP_RET => "RET",
+            OP_LT => "LT",
+            OP_LE => "LE",
+            OP_SEL => "SEL",
+            OP_FLOOR => "FLOOR",
+            OP_CEIL => "CEIL",
+            OP_ROUND => "ROUND",
+            OP_FRACT => "FRACT",
+            OP_MOD => "MOD",
+            OP_GAMMA => "GAMMA",
+            OP_ERF => "ERF",
+            OP_J0 => "J0",
+            OP_J1 => "J1",
+            OP_LAMBERTW => "LAMBERTW",
+            OP_CALL => "CALL",
+            OP_LIT => "LIT",
             _ => "UNKNOWN",
         }
     }
 
     pub const fn is_binary(op: u8) -> bool {
         match op {
-            OP_ADD | OP_SUB | OP_MUL | OP_DIV | OP_POW => true,
+            OP_ADD | OP_SUB | OP_MUL | OP_DIV | OP_POW | OP_MIN | OP_MAX | OP_LT | OP_LE
+            | OP_SEL | OP_MOD => true,
             _ => false,
         }
     }
+
+    /// reads lhs and writes out
+    pub const fn is_unary(op: u8) -> bool {
+        matches!(
+            op,
+            OP_SIN | OP_COS | OP_TAN | OP_MOV | OP_FLOOR | OP_CEIL | OP_ROUND | OP_FRACT
+        ) || special(op).is_some()
+    }
+
+    /// the field of an instruction naming a register
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Field {
+        Lhs,
+        Rhs,
+        Out,
+    }
+
+    /// the fields `op` reads, in the order the vm reads them, and whether it writes out.
+    /// lhs and rhs of register 0 read the immediate, LIT reads the constant pool and no
+    /// register. None for unknown opcodes
+    pub const fn operands(op: u8) -> Option<(&'static [Field], bool)> {
+        use Field::*;
+        Some(match op {
+            // the condition is the previous value of out
+            OP_SEL => (&[Lhs, Rhs, Out], true),
+            _ if is_binary(op) => (&[Lhs, Rhs], true),
+            _ if is_unary(op) => (&[Lhs], true),
+            OP_CALL => (&[Lhs], true),
+            OP_LIT | OP_POP => (&[], true),
+            OP_OUT | OP_PSH | OP_RET => (&[Lhs], false),
+            OP_NOP | OP_EXT => (&[], false),
+            _ => return None,
+        })
+    }
+
+    /// the special function computed by `op`
+    pub const fn special(op: u8) -> Option<Special> {
+        match op {
+            OP_GAMMA => Some(Special::Gamma),
+            OP_ERF => Some(Special::Erf),
+            OP_J0 => Some(Special::J0),
+            OP_J1 => Some(Special::J1),
+            OP_LAMBERTW => Some(Special::LambertW),
+            _ => None,
+        }
+    }
 }
 
 pub fn instr_to_str(instr: u64) -> String {
-    let (op, lhs, rhs, out, imm) = op::decode(instr);
+    let (op, lhs, rhs, out, raw) = op::decode(instr);
 
-    let imm = op::float_from_imm(imm);
+    let imm = op::float_from_imm(raw);
     let op_str = op::op_to_str(op);
 
+    if op == op::OP_CALL {
+        let name = NativeId::from_raw(raw).map_or("?", NativeId::name);
+        return format!("{op_str}_{name}({lhs}r) -> {out}r");
+    }
+    if op == op::OP_LIT {
+        return format!("{op_str}(#{raw}) -> {out}r");
+    }
+
     let lhs_str = if lhs == 0 {
         format!("{imm}f")
     } else {
@@ -380,7 +556,7 @@ impl InstrTape<'_> {
 
 const STACK_SIZE: usize = 256;
 
-const REGISTER_COUNT: usize = 16;
+pub(crate) const REGISTER_COUNT: usize = 16;
 
 pub trait InstrTable<VM> {
     fn nop(vm: &mut VM, t: &InstrTape) {}
@@ -389,9 +565,25 @@ pub trait InstrTable<VM> {
     fn mul(vm: &mut VM, t: &InstrTape);
     fn div(vm: &mut VM, t: &InstrTape);
     fn pow(vm: &mut VM, t: &InstrTape);
+    fn min(vm: &mut VM, t: &InstrTape);
+    fn max(vm: &mut VM, t: &InstrTape);
+    fn lt(vm: &mut VM, t: &InstrTape);
+    fn le(vm: &mut VM, t: &InstrTape);
+    /// the condition is the previous value of the out register
+    fn sel(vm: &mut VM, t: &InstrTape);
     fn sin(vm: &mut VM, t: &InstrTape);
     fn cos(vm: &mut VM, t: &InstrTape);
     fn tan(vm: &mut VM, t: &InstrTape);
+    fn floor(vm: &mut VM, t: &InstrTape);
+    fn ceil(vm: &mut VM, t: &InstrTape);
+    fn round(vm: &mut VM, t: &InstrTape);
+    fn fract(vm: &mut VM, t: &InstrTape);
+    /// lhs - rhs * floor(lhs / rhs)
+    fn modulo(vm: &mut VM, t: &InstrTape);
+    /// the special function [`op::special`] of the opcode `OP`
+    fn special<const OP: u8>(vm: &mut VM, t: &InstrTape);
+    /// the native of the immediate, see [`op::CALL`]
+    fn call(vm: &mut VM, t: &InstrTape);
     fn out(vm: &mut VM, t: &InstrTape);
     fn mov(vm: &mut VM, t: &InstrTape);
     fn psh(vm: &mut VM, t: &InstrTape);
@@ -409,9 +601,25 @@ pub trait InstrTable<VM> {
         table[op::OP_MUL as usize] = Self::mul;
         table[op::OP_DIV as usize] = Self::div;
         table[op::OP_POW as usize] = Self::pow;
+        table[op::OP_MIN as usize] = Self::min;
+        table[op::OP_MAX as usize] = Self::max;
+        table[op::OP_LT as usize] = Self::lt;
+        table[op::OP_LE as usize] = Self::le;
+        table[op::OP_SEL as usize] = Self::sel;
         table[op::OP_SIN as usize] = Self::sin;
         table[op::OP_COS as usize] = Self::cos;
         table[op::OP_TAN as usize] = Self::tan;
+        table[op::OP_FLOOR as usize] = Self::floor;
+        table[op::OP_CEIL as usize] = Self::ceil;
+        table[op::OP_ROUND as usize] = Self::round;
+        table[op::OP_FRACT as usize] = Self::fract;
+        table[op::OP_MOD as usize] = Self::modulo;
+        table[op::OP_GAMMA as usize] = Self::special::<{ op::OP_GAMMA }>;
+        table[op::OP_ERF as usize] = Self::special::<{ op::OP_ERF }>;
+        table[op::OP_J0 as usize] = Self::special::<{ op::OP_J0 }>;
+        table[op::OP_J1 as usize] = Self::special::<{ op::OP_J1 }>;
+        table[op::OP_LAMBERTW as usize] = Self::special::<{ op::OP_LAMBERTW }>;
+        table[op::OP_CALL as usize] = Self::call;
         table[op::OP_OUT as usize] = Self::out;
         table[op::OP_NOP as usize] = Self::nop;
         table[op::OP_MOV as usize] = Self::mov;
@@ -648,8 +856,12 @@ impl InstrTable for F32EvalInstrTable {
 
 pub trait VmWord: Clone + fmt::Debug + PartialEq {
     type Data: Default;
-    fn from_imm(imm: u32) -> Self;
+    fn from_float(v: float) -> Self;
     fn uninit() -> Self;
+
+    fn from_imm(imm: u32) -> Self {
+        Self::from_float(op::float_from_imm(imm))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -660,6 +872,8 @@ pub struct VM<WORD: VmWord> {
     pub stack: [WORD; STACK_SIZE],
     pub sp: Address,
     pub pc: usize,
+    /// values returned with RET during the last evaluation
+    pub out: Vec<WORD>,
     pub data: WORD::Data,
 }
 
@@ -686,12 +900,14 @@ impl<WORD: VmWord> VM<WORD> {
             stack: vec![WORD::uninit(); STACK_SIZE].try_into().unwrap(),
             sp: 0,
             pc: 0,
+            out: vec![],
             data: Default::default(),
         }
     }
 
+    /// returns the values passed to RET, or reg[1] if the program has no RET
     #[inline]
-    pub fn call<I: IntoIterator<Item = WORD>>(&mut self, args: I, bin: &[Opcode]) -> WORD {
+    pub fn call<I: IntoIterator<Item = WORD>>(&mut self, args: I, bin: &[Opcode]) -> &[WORD] {
         for (i, arg) in args.into_iter().enumerate() {
             self.reg[i + 1] = arg;
         }
@@ -699,19 +915,80 @@ impl<WORD: VmWord> VM<WORD> {
         // self.reg[2] = y;
         // self.reg[3] = z;
         self.eval(bin);
-        self.reg[1].clone()
+        if self.out.is_empty() {
+            std::slice::from_ref(&self.reg[1])
+        } else {
+            &self.out
+        }
+    }
+
+    /// [`VM::call`] on verified bytecode, see [`verify`]
+    pub fn try_call<I: IntoIterator<Item = WORD>>(
+        &mut self,
+        args: I,
+        bin: &[Opcode],
+    ) -> Result<&[WORD], VerifyError> {
+        verify(bin)?;
+        Ok(self.call(args, bin))
+    }
+
+    /// [`VM::eval`] on verified bytecode, see [`verify`]
+    pub fn try_eval(&mut self, bin: &[Opcode]) -> Result<(), VerifyError> {
+        verify(bin)?;
+        self.eval(bin);
+        Ok(())
     }
 
     pub fn eval(&mut self, bin: &[Opcode]) {
         self.pc = 0;
         self.sp = 0;
+        self.out.clear();
         let t = InstrTape { bin };
         let instr = op::get_op(t.fetch(self.pc));
         (self.instr_table[instr as usize])(self, &t)
     }
 
+    /// runs the instruction at `pc` on a tape of its own and returns the pc of the next one,
+    /// None after EXT or an abort. unlike [`VM::eval`] nothing is reset, so callers can look
+    /// at the registers between instructions
+    pub fn step(&mut self, bin: &[Opcode], pc: usize) -> Option<usize> {
+        let opcode = bin[pc];
+        let (op, _, _, out, imm) = op::decode(opcode);
+        // LIT reads its constant from the pool of the tape
+        let one = match op {
+            op::OP_LIT => [
+                op::LIT(0, out as u8),
+                op::EXT(0),
+                op::constant(bin, imm)?.to_bits(),
+            ],
+            _ => [opcode, op::EXT(0), 0],
+        };
+
+        self.pc = 0;
+        (self.instr_table[op as usize])(self, &InstrTape { bin: &one });
+        (op != op::OP_EXT && self.pc == 1).then_some(pc + 1)
+    }
+
     pub fn set_instr_table<T: InstrTable<Self>>(&mut self, _instr_table: T) {
         self.instr_table = T::build_table();
+        self.instr_table[op::OP_RET as usize] = Self::ret;
+        self.instr_table[op::OP_LIT as usize] = Self::lit;
+    }
+
+    // same for every word type, so not part of the InstrTable
+    fn ret(&mut self, t: &InstrTape) {
+        let (val, _) = self.unary_arg(t);
+        self.out.push(val);
+        log::trace!("   {:?} -> out[{}]", self.out.last(), self.out.len() - 1);
+        self.next(t);
+    }
+
+    fn lit(&mut self, t: &InstrTape) {
+        let (_, _, _, out, index) = op::decode(t.fetch(self.pc));
+        let v = op::constant(t.bin, index).expect("constant out of bounds, see verify");
+        *self.reg_mut(out) = WORD::from_float(v);
+        log::trace!("    {:?} -> reg[{out}]", self.reg[out]);
+        self.next(t);
     }
 
     fn skip_to_end(&mut self) {
@@ -767,6 +1044,14 @@ impl<WORD: VmWord> VM<WORD> {
         (lhs, rhs, out)
     }
 
+    /// return (native, lhs value, out register)
+    #[inline(always)]
+    fn call_arg(&mut self, t: &InstrTape) -> (NativeId, WORD, usize) {
+        let (_, l, _, out, imm) = op::decode(t.fetch(self.pc));
+        let f = NativeId::from_raw(imm).expect("unregistered native, see verify");
+        (f, self.reg(l).clone(), out)
+    }
+
     pub fn stack_push(&mut self, f: WORD) {
         self.sp += 1;
         if self.sp < self.stack.len() {
@@ -804,11 +1089,130 @@ impl<WORD: VmWord> VM<WORD> {
     }
 }
 
+/// reason bytecode was rejected by [`verify`], `pc` is the index of the offending instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyError {
+    UnknownOpcode {
+        pc: usize,
+        op: u8,
+    },
+    RegisterOutOfBounds {
+        pc: usize,
+        reg: usize,
+    },
+    StackUnderflow {
+        pc: usize,
+    },
+    StackOverflow {
+        pc: usize,
+    },
+    /// pushes without a matching pop when the program ends at the EXT at `pc`
+    UnbalancedStack {
+        pc: usize,
+        depth: usize,
+    },
+    /// the program ends without EXT
+    MissingExt,
+    UnknownNative {
+        pc: usize,
+        id: u32,
+    },
+    /// natives only take registers
+    CallImmediate {
+        pc: usize,
+    },
+    /// LIT of a constant that is not in the pool after EXT
+    UnknownConstant {
+        pc: usize,
+        index: u32,
+    },
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownOpcode { pc, op } => write!(f, "unknown opcode {op} at {pc}"),
+            Self::RegisterOutOfBounds { pc, reg } => write!(
+                f,
+                "register {reg} at {pc}, expected less than {REGISTER_COUNT}"
+            ),
+            Self::StackUnderflow { pc } => write!(f, "pop from an empty stack at {pc}"),
+            Self::StackOverflow { pc } => write!(f, "more than {} pushes at {pc}", STACK_SIZE - 1),
+            Self::UnbalancedStack { pc, depth } => {
+                write!(f, "{depth} values left on the stack at EXT at {pc}")
+            }
+            Self::MissingExt => write!(f, "program does not end with EXT"),
+            Self::UnknownNative { pc, id } => write!(f, "call of unregistered native {id} at {pc}"),
+            Self::CallImmediate { pc } => write!(f, "call with an immediate at {pc}"),
+            Self::UnknownConstant { pc, index } => {
+                write!(f, "constant {index} at {pc} is not in the pool")
+            }
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// checks that `bin` runs without panicking on any [`VM`]: known opcodes, registers within
+/// [`REGISTER_COUNT`], no stack under- or overflow, an empty stack at the EXT ending the program
+/// and constants within the pool. instructions after the first EXT are never executed and not checked
+pub fn verify(bin: &[Opcode]) -> Result<(), VerifyError> {
+    let mut depth = 0;
+    // (pc, index) of the LIT with the largest index
+    let mut lit: Option<(usize, u32)> = None;
+
+    for (pc, &opcode) in bin.iter().enumerate() {
+        let (op, lhs, rhs, out, imm) = op::decode(opcode);
+
+        let Some((reads, writes)) = op::operands(op) else {
+            return Err(VerifyError::UnknownOpcode { pc, op });
+        };
+        match op {
+            op::OP_CALL if NativeId::from_raw(imm).is_none() => {
+                return Err(VerifyError::UnknownNative { pc, id: imm });
+            }
+            op::OP_CALL if lhs == 0 => return Err(VerifyError::CallImmediate { pc }),
+            op::OP_LIT if lit.is_none_or(|(_, index)| imm > index) => lit = Some((pc, imm)),
+            _ => (),
+        }
+        let reg = |field| match field {
+            op::Field::Lhs => lhs,
+            op::Field::Rhs => rhs,
+            op::Field::Out => out,
+        };
+        let mut regs = reads.iter().map(|&f| reg(f)).chain(writes.then_some(out));
+        if let Some(reg) = regs.find(|&r| r >= REGISTER_COUNT) {
+            return Err(VerifyError::RegisterOutOfBounds { pc, reg });
+        }
+
+        match op {
+            op::OP_PSH if depth + 1 >= STACK_SIZE => return Err(VerifyError::StackOverflow { pc }),
+            op::OP_PSH => depth += 1,
+            op::OP_POP if depth == 0 => return Err(VerifyError::StackUnderflow { pc }),
+            op::OP_POP => depth -= 1,
+            op::OP_EXT if depth != 0 => return Err(VerifyError::UnbalancedStack { pc, depth }),
+            op::OP_EXT => {
+                // the pool is everything after EXT
+                let pool = bin.len() - pc - 1;
+                return match lit {
+                    Some((pc, index)) if index as usize >= pool => {
+                        Err(VerifyError::UnknownConstant { pc, index })
+                    }
+                    _ => Ok(()),
+                };
+            }
+            _ => (),
+        }
+    }
+
+    Err(VerifyError::MissingExt)
+}
+
 impl VmWord for f64 {
     type Data = ();
 
-    fn from_imm(imm: u32) -> Self {
-        op::float_from_imm(imm)
+    fn from_float(v: float) -> Self {
+        v
     }
 
     fn uninit() -> Self {
@@ -854,6 +1258,91 @@ impl InstrTable<VM<f64>> for F64InstrTable {
         vm.next(t);
     }
 
+    fn min(vm: &mut VM<f64>, t: &InstrTape) {
+        let (lhs, rhs, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = lhs.min(rhs);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn max(vm: &mut VM<f64>, t: &InstrTape) {
+        let (lhs, rhs, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = lhs.max(rhs);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn lt(vm: &mut VM<f64>, t: &InstrTape) {
+        let (lhs, rhs, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = (lhs < rhs) as u8 as f64;
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn le(vm: &mut VM<f64>, t: &InstrTape) {
+        let (lhs, rhs, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = (lhs <= rhs) as u8 as f64;
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn sel(vm: &mut VM<f64>, t: &InstrTape) {
+        let (lhs, rhs, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = if vm.reg[out] != 0.0 { lhs } else { rhs };
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn floor(vm: &mut VM<f64>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = a.floor();
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn ceil(vm: &mut VM<f64>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = a.ceil();
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn round(vm: &mut VM<f64>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = a.round_ties_even();
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn fract(vm: &mut VM<f64>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = a - a.floor();
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn modulo(vm: &mut VM<f64>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = a - b * (a / b).floor();
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn special<const OP: u8>(vm: &mut VM<f64>, t: &InstrTape) {
+        let f = op::special(OP).unwrap();
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = f.eval(a);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn call(vm: &mut VM<f64>, t: &InstrTape) {
+        let (f, a, out) = vm.call_arg(t);
+        *vm.reg_mut(out) = f.eval(a);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
     fn sin(vm: &mut VM<f64>, t: &InstrTape) {
         let (val, out) = vm.unary_arg(t);
         *vm.reg_mut(out) = val.sin();
@@ -926,8 +1415,8 @@ impl F64Deriv {
 impl VmWord for F64Deriv {
     type Data = ();
 
-    fn from_imm(imm: u32) -> Self {
-        Self::cnst(op::float_from_imm(imm))
+    fn from_float(v: float) -> Self {
+        Self::cnst(v)
     }
 
     fn uninit() -> Self {
@@ -995,6 +1484,98 @@ impl InstrTable<VM<F64Deriv>> for F64DerivInstrTable {
         vm.next(t);
     }
 
+    fn min(vm: &mut VM<F64Deriv>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = if a.val <= b.val { a } else { b };
+        vm.next(t);
+    }
+
+    fn max(vm: &mut VM<F64Deriv>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = if a.val >= b.val { a } else { b };
+        vm.next(t);
+    }
+
+    // steps are flat everywhere but at the jump
+    fn lt(vm: &mut VM<F64Deriv>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = F64Deriv::cnst((a.val < b.val) as u8 as f64);
+        vm.next(t);
+    }
+
+    fn le(vm: &mut VM<F64Deriv>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = F64Deriv::cnst((a.val <= b.val) as u8 as f64);
+        vm.next(t);
+    }
+
+    fn sel(vm: &mut VM<F64Deriv>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = if vm.reg[out].val != 0.0 { a } else { b };
+        vm.next(t);
+    }
+
+    // steps are flat everywhere but at the jumps
+    fn floor(vm: &mut VM<F64Deriv>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = F64Deriv::cnst(a.val.floor());
+        vm.next(t);
+    }
+
+    fn ceil(vm: &mut VM<F64Deriv>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = F64Deriv::cnst(a.val.ceil());
+        vm.next(t);
+    }
+
+    fn round(vm: &mut VM<F64Deriv>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = F64Deriv::cnst(a.val.round_ties_even());
+        vm.next(t);
+    }
+
+    fn fract(vm: &mut VM<F64Deriv>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        let c = F64Deriv {
+            val: a.val - a.val.floor(),
+            grad: a.grad,
+        };
+        *vm.reg_mut(out) = c;
+        vm.next(t);
+    }
+
+    fn modulo(vm: &mut VM<F64Deriv>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        let k = (a.val / b.val).floor();
+        let c = F64Deriv {
+            val: a.val - b.val * k,
+            grad: a.grad - b.grad * k,
+        };
+        *vm.reg_mut(out) = c;
+        vm.next(t);
+    }
+
+    fn special<const OP: u8>(vm: &mut VM<F64Deriv>, t: &InstrTape) {
+        let f = op::special(OP).unwrap();
+        let (a, out) = vm.unary_arg(t);
+        let c = F64Deriv {
+            val: f.eval(a.val),
+            grad: f.deriv(a.val) * a.grad,
+        };
+        *vm.reg_mut(out) = c;
+        vm.next(t);
+    }
+
+    fn call(vm: &mut VM<F64Deriv>, t: &InstrTape) {
+        let (f, a, out) = vm.call_arg(t);
+        let c = F64Deriv {
+            val: f.eval(a.val),
+            grad: f.deriv(a.val) * a.grad,
+        };
+        *vm.reg_mut(out) = c;
+        vm.next(t);
+    }
+
     fn sin(vm: &mut VM<F64Deriv>, t: &InstrTape) {
         let (a, out) = vm.unary_arg(t);
         let c = F64Deriv {
@@ -1053,9 +1634,8 @@ impl InstrTable<VM<F64Deriv>> for F64DerivInstrTable {
 impl VmWord for Range {
     type Data = ();
 
-    fn from_imm(imm: u32) -> Self {
-        let imm = op::float_from_imm(imm);
-        Range::new(imm, imm)
+    fn from_float(v: float) -> Self {
+        Range::new(v, v)
     }
 
     fn uninit() -> Self {
@@ -1066,9 +1646,8 @@ impl VmWord for Range {
 impl VmWord for Intrvl {
     type Data = ();
 
-    fn from_imm(imm: u32) -> Self {
-        let imm = op::float_from_imm(imm);
-        Intrvl::scalar(imm)
+    fn from_float(v: float) -> Self {
+        Intrvl::scalar(v)
     }
 
     fn uninit() -> Self {
@@ -1125,70 +1704,165 @@ impl InstrTable<VM<Range>> for RangeInstrTable {
         vm.next(t)
     }
 
-    fn sin(vm: &mut VM<Range>, t: &InstrTape) {
-        let (a, out) = vm.unary_arg(t);
-        let b = Range::of_sin(a);
-        *vm.reg_mut(out) = b;
+    fn min(vm: &mut VM<Range>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        let c = Range::of_min(a, b);
+        log::debug!("min({a}, {b}) = {c}");
+        *vm.reg_mut(out) = c;
         log::trace!("    {} -> reg[{out}]", vm.reg[out]);
         vm.next(t)
     }
 
-    fn cos(vm: &mut VM<Range>, t: &InstrTape) {
-        let (a, out) = vm.unary_arg(t);
-        let b = Range::of_cos(a);
-        *vm.reg_mut(out) = b;
+    fn max(vm: &mut VM<Range>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        let c = Range::of_max(a, b);
+        log::debug!("max({a}, {b}) = {c}");
+        *vm.reg_mut(out) = c;
         log::trace!("    {} -> reg[{out}]", vm.reg[out]);
         vm.next(t)
     }
 
-    fn tan(vm: &mut VM<Range>, t: &InstrTape) {
-        let (a, out) = vm.unary_arg(t);
-        let b = Range::of_tan(a);
-        *vm.reg_mut(out) = b;
+    fn lt(vm: &mut VM<Range>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        let c = Range::of_lt(a, b);
+        log::debug!("lt({a}, {b}) = {c}");
+        *vm.reg_mut(out) = c;
         log::trace!("    {} -> reg[{out}]", vm.reg[out]);
         vm.next(t)
     }
 
-    fn out(vm: &mut VM<Range>, t: &InstrTape) {
-        let (val, _) = vm.unary_arg(t);
-        println!("{val}");
+    fn le(vm: &mut VM<Range>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        let c = Range::of_le(a, b);
+        log::debug!("le({a}, {b}) = {c}");
+        *vm.reg_mut(out) = c;
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
         vm.next(t)
     }
 
-    fn mov(vm: &mut VM<Range>, t: &InstrTape) {
-        let (a, out) = vm.unary_arg(t);
-        log::trace!("   {a} -> {out}");
-        *vm.reg_mut(out) = a;
+    fn sel(vm: &mut VM<Range>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        let c = vm.reg[out];
+        let r = Range::of_select(c, a, b);
+        log::debug!("sel({c}, {a}, {b}) = {r}");
+        *vm.reg_mut(out) = r;
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
         vm.next(t)
     }
 
-    fn psh(vm: &mut VM<Range>, t: &InstrTape) {
-        let (val, _) = vm.unary_arg(t);
-        vm.stack_push(val);
-        log::trace!("   {} -> stack[{}]", vm.stack[vm.sp], vm.sp);
+    fn floor(vm: &mut VM<Range>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = Range::of_floor(a);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
         vm.next(t)
     }
 
-    fn pop(vm: &mut VM<Range>, t: &InstrTape) {
-        let (_, out) = vm.unary_arg(t);
-        *vm.reg_mut(out) = vm.stack_pop();
+    fn ceil(vm: &mut VM<Range>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = Range::of_ceil(a);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
         vm.next(t)
     }
-}
-
-pub struct IntrvlInstrTable;
 
-impl InstrTable<VM<Intrvl>> for IntrvlInstrTable {
-    fn add(vm: &mut VM<Intrvl>, t: &InstrTape) {
-        let (a, b, out) = vm.binop_arg(t);
-        let c = Intrvl::add(a, b);
-        *vm.reg_mut(out) = c;
-        log::debug!("add({a}, {b}) = {c}");
+    fn round(vm: &mut VM<Range>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = Range::of_round(a);
         log::trace!("    {} -> reg[{out}]", vm.reg[out]);
         vm.next(t)
     }
 
-    fn sub(vm: &mut VM<Intrvl>, t: &InstrTape) {
+    fn fract(vm: &mut VM<Range>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = Range::of_fract(a);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn modulo(vm: &mut VM<Range>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = Range::of_mod(a, b);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn special<const OP: u8>(vm: &mut VM<Range>, t: &InstrTape) {
+        let f = op::special(OP).unwrap();
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = Range::of_special(f, a);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn call(vm: &mut VM<Range>, t: &InstrTape) {
+        let (f, a, out) = vm.call_arg(t);
+        *vm.reg_mut(out) = Range::of_native(f, a);
+        vm.next(t);
+    }
+
+    fn sin(vm: &mut VM<Range>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        let b = Range::of_sin(a);
+        *vm.reg_mut(out) = b;
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn cos(vm: &mut VM<Range>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        let b = Range::of_cos(a);
+        *vm.reg_mut(out) = b;
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn tan(vm: &mut VM<Range>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        let b = Range::of_tan(a);
+        *vm.reg_mut(out) = b;
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn out(vm: &mut VM<Range>, t: &InstrTape) {
+        let (val, _) = vm.unary_arg(t);
+        println!("{val}");
+        vm.next(t)
+    }
+
+    fn mov(vm: &mut VM<Range>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        log::trace!("   {a} -> {out}");
+        *vm.reg_mut(out) = a;
+        vm.next(t)
+    }
+
+    fn psh(vm: &mut VM<Range>, t: &InstrTape) {
+        let (val, _) = vm.unary_arg(t);
+        vm.stack_push(val);
+        log::trace!("   {} -> stack[{}]", vm.stack[vm.sp], vm.sp);
+        vm.next(t)
+    }
+
+    fn pop(vm: &mut VM<Range>, t: &InstrTape) {
+        let (_, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = vm.stack_pop();
+        vm.next(t)
+    }
+}
+
+pub struct IntrvlInstrTable;
+
+impl InstrTable<VM<Intrvl>> for IntrvlInstrTable {
+    fn add(vm: &mut VM<Intrvl>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        let c = Intrvl::add(a, b);
+        *vm.reg_mut(out) = c;
+        log::debug!("add({a}, {b}) = {c}");
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn sub(vm: &mut VM<Intrvl>, t: &InstrTape) {
         let (a, b, out) = vm.binop_arg(t);
         let c = Intrvl::sub(a, b);
         *vm.reg_mut(out) = c;
@@ -1225,6 +1899,101 @@ impl InstrTable<VM<Intrvl>> for IntrvlInstrTable {
         vm.next(t)
     }
 
+    fn min(vm: &mut VM<Intrvl>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        let c = Intrvl::min(a, b);
+        log::debug!("min({a}, {b}) = {c}");
+        *vm.reg_mut(out) = c;
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn max(vm: &mut VM<Intrvl>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        let c = Intrvl::max(a, b);
+        log::debug!("max({a}, {b}) = {c}");
+        *vm.reg_mut(out) = c;
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn lt(vm: &mut VM<Intrvl>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        let c = Intrvl::lt(a, b);
+        log::debug!("lt({a}, {b}) = {c}");
+        *vm.reg_mut(out) = c;
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn le(vm: &mut VM<Intrvl>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        let c = Intrvl::le(a, b);
+        log::debug!("le({a}, {b}) = {c}");
+        *vm.reg_mut(out) = c;
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn sel(vm: &mut VM<Intrvl>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        let c = vm.reg[out];
+        let r = Intrvl::select(c, a, b);
+        log::debug!("sel({c}, {a}, {b}) = {r}");
+        *vm.reg_mut(out) = r;
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn floor(vm: &mut VM<Intrvl>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = Intrvl::floor(a);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn ceil(vm: &mut VM<Intrvl>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = Intrvl::ceil(a);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn round(vm: &mut VM<Intrvl>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = Intrvl::round(a);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn fract(vm: &mut VM<Intrvl>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = Intrvl::fract(a);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn modulo(vm: &mut VM<Intrvl>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = Intrvl::modulo(a, b);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn special<const OP: u8>(vm: &mut VM<Intrvl>, t: &InstrTape) {
+        let f = op::special(OP).unwrap();
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = Intrvl::special(a, f);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn call(vm: &mut VM<Intrvl>, t: &InstrTape) {
+        let (f, a, out) = vm.call_arg(t);
+        *vm.reg_mut(out) = Intrvl::native(a, f);
+        vm.next(t);
+    }
+
     fn sin(vm: &mut VM<Intrvl>, t: &InstrTape) {
         let (a, out) = vm.unary_arg(t);
         let b = Intrvl::sin(a);
@@ -1276,6 +2045,207 @@ impl InstrTable<VM<Intrvl>> for IntrvlInstrTable {
     }
 }
 
+impl VmWord for Complex {
+    type Data = ();
+
+    fn from_float(v: float) -> Self {
+        Complex::real(v)
+    }
+
+    fn uninit() -> Self {
+        Complex::UNDEF
+    }
+}
+
+/// evaluates f(z) over the complex numbers, immediates are real
+pub struct ComplexInstrTable;
+
+impl InstrTable<VM<Complex>> for ComplexInstrTable {
+    fn add(vm: &mut VM<Complex>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        let c = Complex::add(a, b);
+        *vm.reg_mut(out) = c;
+        log::debug!("add({a}, {b}) = {c}");
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn sub(vm: &mut VM<Complex>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        let c = Complex::sub(a, b);
+        *vm.reg_mut(out) = c;
+        log::debug!("sub({a}, {b}) = {c}");
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn mul(vm: &mut VM<Complex>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        let c = Complex::mul(a, b);
+        *vm.reg_mut(out) = c;
+        log::debug!("mul({a}, {b}) = {c}");
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn div(vm: &mut VM<Complex>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        let c = Complex::div(a, b);
+        *vm.reg_mut(out) = c;
+        log::debug!("div({a}, {b}) = {c}");
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn pow(vm: &mut VM<Complex>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        let c = Complex::pow(a, b);
+        *vm.reg_mut(out) = c;
+        log::debug!("pow({a}, {b}) = {c}");
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    // ordered by the real part
+    fn min(vm: &mut VM<Complex>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        let c = if a.re <= b.re { a } else { b };
+        *vm.reg_mut(out) = c;
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn max(vm: &mut VM<Complex>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        let c = if a.re >= b.re { a } else { b };
+        *vm.reg_mut(out) = c;
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn lt(vm: &mut VM<Complex>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = Complex::real((a.re < b.re) as u8 as f64);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn le(vm: &mut VM<Complex>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = Complex::real((a.re <= b.re) as u8 as f64);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    // the condition is its real part
+    fn sel(vm: &mut VM<Complex>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = if vm.reg[out].re != 0.0 { a } else { b };
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    // component wise
+    fn floor(vm: &mut VM<Complex>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = Complex::floor(a);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn ceil(vm: &mut VM<Complex>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = Complex::ceil(a);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn round(vm: &mut VM<Complex>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = Complex::round(a);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn fract(vm: &mut VM<Complex>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = Complex::fract(a);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn modulo(vm: &mut VM<Complex>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = Complex::modulo(a, b);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn special<const OP: u8>(vm: &mut VM<Complex>, t: &InstrTape) {
+        let f = op::special(OP).unwrap();
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = Complex::special(a, f);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn call(vm: &mut VM<Complex>, t: &InstrTape) {
+        let (f, a, out) = vm.call_arg(t);
+        *vm.reg_mut(out) = Complex::native(a, f);
+        vm.next(t);
+    }
+
+    fn sin(vm: &mut VM<Complex>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        let b = Complex::sin(a);
+        *vm.reg_mut(out) = b;
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn cos(vm: &mut VM<Complex>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        let b = Complex::cos(a);
+        *vm.reg_mut(out) = b;
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn tan(vm: &mut VM<Complex>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        let b = Complex::tan(a);
+        *vm.reg_mut(out) = b;
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t)
+    }
+
+    fn out(vm: &mut VM<Complex>, t: &InstrTape) {
+        let (val, _) = vm.unary_arg(t);
+        println!("{val}");
+        vm.next(t)
+    }
+
+    fn mov(vm: &mut VM<Complex>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        log::trace!("   {a} -> {out}");
+        *vm.reg_mut(out) = a;
+        vm.next(t)
+    }
+
+    fn psh(vm: &mut VM<Complex>, t: &InstrTape) {
+        let (val, _) = vm.unary_arg(t);
+        vm.stack_push(val);
+        log::trace!("   {} -> stack[{}]", vm.stack[vm.sp], vm.sp);
+        vm.next(t)
+    }
+
+    fn pop(vm: &mut VM<Complex>, t: &InstrTape) {
+        let (_, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = vm.stack_pop();
+        vm.next(t)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub struct RangeDeriv {
     pub val: Range,
@@ -1344,6 +2314,43 @@ impl RangeDeriv {
         }
     }
 
+    /// if the ranges overlap either side can be the min, so the gradients are joined
+    pub fn min_deriv(self, other: Self) -> Self {
+        let (a, b) = (self, other);
+        if a.val.u <= b.val.l {
+            a
+        } else if b.val.u <= a.val.l {
+            b
+        } else {
+            Self {
+                val: a.val.min(b.val),
+                grad: Range::of_hull(a.grad, b.grad),
+            }
+        }
+    }
+
+    pub fn max_deriv(self, other: Self) -> Self {
+        let (a, b) = (self, other);
+        if a.val.l >= b.val.u {
+            a
+        } else if b.val.l >= a.val.u {
+            b
+        } else {
+            Self {
+                val: a.val.max(b.val),
+                grad: Range::of_hull(a.grad, b.grad),
+            }
+        }
+    }
+
+    /// the derivative of the taken branch, both are joined if the branch is undecided
+    pub fn select_deriv(self, a: Self, b: Self) -> Self {
+        Self {
+            val: Range::of_select(self.val, a.val, b.val),
+            grad: Range::of_select(self.val, a.grad, b.grad),
+        }
+    }
+
     pub fn sin_deriv(self) -> Self {
         Self {
             val: self.val.sin(),
@@ -1366,13 +2373,59 @@ impl RangeDeriv {
                 .mul(self.grad),
         }
     }
+
+    /// flat everywhere but at the jumps, see [`Range::has_step`]
+    pub fn floor_deriv(self) -> Self {
+        Self::cnst(self.val.floor())
+    }
+
+    pub fn ceil_deriv(self) -> Self {
+        Self::cnst(self.val.ceil())
+    }
+
+    pub fn round_deriv(self) -> Self {
+        Self::cnst(self.val.round())
+    }
+
+    pub fn fract_deriv(self) -> Self {
+        Self {
+            val: self.val.fract(),
+            grad: self.grad,
+        }
+    }
+
+    /// f(a)' = f'(a) a', the enclosure of f' is evaluated over the whole value range
+    pub fn special_deriv(self, f: Special) -> Self {
+        Self {
+            val: Range::of_special(f, self.val),
+            grad: Range::from(f.deriv_intrvl(self.val.l, self.val.u)).mul(self.grad),
+        }
+    }
+
+    /// like [`Self::special_deriv`], natives without a derivative enclosure have unbounded
+    /// gradients
+    pub fn native_deriv(self, f: NativeId) -> Self {
+        Self {
+            val: Range::of_native(f, self.val),
+            grad: Range::from(f.deriv_intrvl(self.val.l, self.val.u)).mul(self.grad),
+        }
+    }
+
+    pub fn rem_deriv(self, other: Self) -> Self {
+        let (a, b) = (self, other);
+        let k = a.val.div(b.val).floor();
+        Self {
+            val: a.val.modulo(b.val),
+            grad: a.grad.sub(b.grad.mul(k)),
+        }
+    }
 }
 
 impl VmWord for RangeDeriv {
     type Data = ();
 
-    fn from_imm(imm: u32) -> Self {
-        Self::cnst(Range::new_const(op::float_from_imm(imm)))
+    fn from_float(v: float) -> Self {
+        Self::cnst(Range::new_const(v))
     }
 
     fn uninit() -> Self {
@@ -1421,6 +2474,84 @@ impl InstrTable<VM<RangeDeriv>> for RangeDerivInstrTable {
         vm.next(t);
     }
 
+    fn min(vm: &mut VM<RangeDeriv>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        let c = a.min_deriv(b);
+        *vm.reg_mut(out) = c;
+        vm.next(t);
+    }
+
+    fn max(vm: &mut VM<RangeDeriv>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        let c = a.max_deriv(b);
+        *vm.reg_mut(out) = c;
+        vm.next(t);
+    }
+
+    fn lt(vm: &mut VM<RangeDeriv>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        let c = RangeDeriv::cnst(Range::of_lt(a.val, b.val));
+        *vm.reg_mut(out) = c;
+        vm.next(t);
+    }
+
+    fn le(vm: &mut VM<RangeDeriv>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        let c = RangeDeriv::cnst(Range::of_le(a.val, b.val));
+        *vm.reg_mut(out) = c;
+        vm.next(t);
+    }
+
+    fn sel(vm: &mut VM<RangeDeriv>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        let c = vm.reg[out].select_deriv(a, b);
+        *vm.reg_mut(out) = c;
+        vm.next(t);
+    }
+
+    fn floor(vm: &mut VM<RangeDeriv>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = a.floor_deriv();
+        vm.next(t);
+    }
+
+    fn ceil(vm: &mut VM<RangeDeriv>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = a.ceil_deriv();
+        vm.next(t);
+    }
+
+    fn round(vm: &mut VM<RangeDeriv>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = a.round_deriv();
+        vm.next(t);
+    }
+
+    fn fract(vm: &mut VM<RangeDeriv>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = a.fract_deriv();
+        vm.next(t);
+    }
+
+    fn modulo(vm: &mut VM<RangeDeriv>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = a.rem_deriv(b);
+        vm.next(t);
+    }
+
+    fn special<const OP: u8>(vm: &mut VM<RangeDeriv>, t: &InstrTape) {
+        let f = op::special(OP).unwrap();
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = a.special_deriv(f);
+        vm.next(t);
+    }
+
+    fn call(vm: &mut VM<RangeDeriv>, t: &InstrTape) {
+        let (f, a, out) = vm.call_arg(t);
+        *vm.reg_mut(out) = a.native_deriv(f);
+        vm.next(t);
+    }
+
     fn sin(vm: &mut VM<RangeDeriv>, t: &InstrTape) {
         let (a, out) = vm.unary_arg(t);
         let c = a.sin_deriv();
@@ -1506,44 +2637,128 @@ impl VM<F64Vec> {
             F64Vec::Imm(imm) => vec![imm; self.data],
         }
     }
-}
+}
+
+impl InstrTable<VM<F64Vec>> for F64VecInstrTable {
+    fn add(vm: &mut VM<F64Vec>, t: &InstrTape) {
+        let (lhs, rhs, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = lhs.add(&rhs);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn sub(vm: &mut VM<F64Vec>, t: &InstrTape) {
+        let (lhs, rhs, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = lhs.sub(&rhs);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn mul(vm: &mut VM<F64Vec>, t: &InstrTape) {
+        let (lhs, rhs, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = lhs.mul(&rhs);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn div(vm: &mut VM<F64Vec>, t: &InstrTape) {
+        let (lhs, rhs, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = lhs.div(&rhs);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
 
-impl InstrTable<VM<F64Vec>> for F64VecInstrTable {
-    fn add(vm: &mut VM<F64Vec>, t: &InstrTape) {
+    fn pow(vm: &mut VM<F64Vec>, t: &InstrTape) {
         let (lhs, rhs, out) = vm.binop_arg(t);
-        *vm.reg_mut(out) = lhs.add(&rhs);
+        *vm.reg_mut(out) = lhs.pow(&rhs);
         log::trace!("    {} -> reg[{out}]", vm.reg[out]);
         vm.next(t);
     }
 
-    fn sub(vm: &mut VM<F64Vec>, t: &InstrTape) {
+    fn min(vm: &mut VM<F64Vec>, t: &InstrTape) {
         let (lhs, rhs, out) = vm.binop_arg(t);
-        *vm.reg_mut(out) = lhs.sub(&rhs);
+        *vm.reg_mut(out) = lhs.min(&rhs);
         log::trace!("    {} -> reg[{out}]", vm.reg[out]);
         vm.next(t);
     }
 
-    fn mul(vm: &mut VM<F64Vec>, t: &InstrTape) {
+    fn max(vm: &mut VM<F64Vec>, t: &InstrTape) {
         let (lhs, rhs, out) = vm.binop_arg(t);
-        *vm.reg_mut(out) = lhs.mul(&rhs);
+        *vm.reg_mut(out) = lhs.max(&rhs);
         log::trace!("    {} -> reg[{out}]", vm.reg[out]);
         vm.next(t);
     }
 
-    fn div(vm: &mut VM<F64Vec>, t: &InstrTape) {
+    fn lt(vm: &mut VM<F64Vec>, t: &InstrTape) {
         let (lhs, rhs, out) = vm.binop_arg(t);
-        *vm.reg_mut(out) = lhs.div(&rhs);
+        *vm.reg_mut(out) = lhs.lt(&rhs);
         log::trace!("    {} -> reg[{out}]", vm.reg[out]);
         vm.next(t);
     }
 
-    fn pow(vm: &mut VM<F64Vec>, t: &InstrTape) {
+    fn le(vm: &mut VM<F64Vec>, t: &InstrTape) {
         let (lhs, rhs, out) = vm.binop_arg(t);
-        *vm.reg_mut(out) = lhs.pow(&rhs);
+        *vm.reg_mut(out) = lhs.le(&rhs);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn sel(vm: &mut VM<F64Vec>, t: &InstrTape) {
+        let (lhs, rhs, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = vm.reg[out].select(&lhs, &rhs);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn floor(vm: &mut VM<F64Vec>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = a.floor();
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn ceil(vm: &mut VM<F64Vec>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = a.ceil();
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn round(vm: &mut VM<F64Vec>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = a.round();
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn fract(vm: &mut VM<F64Vec>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = a.fract();
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn modulo(vm: &mut VM<F64Vec>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = a.modulo(&b);
         log::trace!("    {} -> reg[{out}]", vm.reg[out]);
         vm.next(t);
     }
 
+    fn special<const OP: u8>(vm: &mut VM<F64Vec>, t: &InstrTape) {
+        let f = op::special(OP).unwrap();
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = a.special(f);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn call(vm: &mut VM<F64Vec>, t: &InstrTape) {
+        let (f, a, out) = vm.call_arg(t);
+        *vm.reg_mut(out) = a.native(f);
+        vm.next(t);
+    }
+
     fn sin(vm: &mut VM<F64Vec>, t: &InstrTape) {
         let (lhs, out) = vm.unary_arg(t);
         *vm.reg_mut(out) = lhs.sin();
@@ -1669,6 +2884,90 @@ impl InstrTable<VM<RangeVec>> for RangeVecInstrTable {
         vm.next(t);
     }
 
+    fn min(vm: &mut VM<RangeVec>, t: &InstrTape) {
+        let (lhs, rhs, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = lhs.min(&rhs);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn max(vm: &mut VM<RangeVec>, t: &InstrTape) {
+        let (lhs, rhs, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = lhs.max(&rhs);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn lt(vm: &mut VM<RangeVec>, t: &InstrTape) {
+        let (lhs, rhs, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = lhs.lt(&rhs);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn le(vm: &mut VM<RangeVec>, t: &InstrTape) {
+        let (lhs, rhs, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = lhs.le(&rhs);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn sel(vm: &mut VM<RangeVec>, t: &InstrTape) {
+        let (lhs, rhs, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = vm.reg[out].select(&lhs, &rhs);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn floor(vm: &mut VM<RangeVec>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = a.floor();
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn ceil(vm: &mut VM<RangeVec>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = a.ceil();
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn round(vm: &mut VM<RangeVec>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = a.round();
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn fract(vm: &mut VM<RangeVec>, t: &InstrTape) {
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = a.fract();
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn modulo(vm: &mut VM<RangeVec>, t: &InstrTape) {
+        let (a, b, out) = vm.binop_arg(t);
+        *vm.reg_mut(out) = a.modulo(&b);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn special<const OP: u8>(vm: &mut VM<RangeVec>, t: &InstrTape) {
+        let f = op::special(OP).unwrap();
+        let (a, out) = vm.unary_arg(t);
+        *vm.reg_mut(out) = a.special(f);
+        log::trace!("    {} -> reg[{out}]", vm.reg[out]);
+        vm.next(t);
+    }
+
+    fn call(vm: &mut VM<RangeVec>, t: &InstrTape) {
+        let (f, a, out) = vm.call_arg(t);
+        *vm.reg_mut(out) = a.native(f);
+        vm.next(t);
+    }
+
     fn sin(vm: &mut VM<RangeVec>, t: &InstrTape) {
         let (lhs, out) = vm.unary_arg(t);
         *vm.reg_mut(out) = lhs.sin();
@@ -1726,8 +3025,7 @@ pub enum F64Vec {
 impl VmWord for F64Vec {
     type Data = usize;
 
-    fn from_imm(imm: u32) -> Self {
-        let v = op::float_from_imm(imm);
+    fn from_float(v: float) -> Self {
         F64Vec::Imm(v)
     }
 
@@ -1878,6 +3176,41 @@ macro_rules! op_vec_vec {
 }
 
 macro_rules! impl_vec_op {
+    ($typ:ident, $c:ident: $fvec1:expr, $lhs:ident: $fvec2:expr, $rhs:ident: $fvec3:expr => $body:block) => {{
+        match ($fvec1, $fvec2, $fvec3) {
+            ($typ::Imm(i1), $typ::Imm(i2), $typ::Imm(i3)) => {
+                let $c = i1.copy();
+                let $lhs = i2.copy();
+                let $rhs = i3.copy();
+                $typ::Imm($body)
+            }
+            (v1, v2, v3) => {
+                let at = |v: &$typ, i: usize| match v {
+                    $typ::Vec(vec) => vec[i].copy(),
+                    $typ::Imm(imm) => imm.copy(),
+                };
+                let len = [v1, v2, v3]
+                    .iter()
+                    .find_map(|v| match v {
+                        $typ::Vec(vec) => Some(vec.len()),
+                        $typ::Imm(_) => None,
+                    })
+                    .unwrap();
+
+                let res: Vec<_> = (0..len)
+                    .map(|i| {
+                        let $c = at(v1, i);
+                        let $lhs = at(v2, i);
+                        let $rhs = at(v3, i);
+                        $body
+                    })
+                    .collect();
+
+                $typ::Vec(res.into())
+            }
+        }
+    }};
+
     ($typ:ident, $lhs:ident: $fvec1:expr, $rhs:ident: $fvec2:expr => $body:block) => {{
         match ($fvec1, $fvec2) {
             ($typ::Vec(v1), $typ::Vec(v2)) => op_vec_vec!($typ, $lhs: v1,$rhs: v2 => $body),
@@ -1938,6 +3271,26 @@ impl F64Vec {
         impl_vec_op!(F64Vec, lhs: self, rhs: other => { lhs.powf(rhs) })
     }
     #[inline(always)]
+    pub fn min(&self, other: &Self) -> Self {
+        impl_vec_op!(F64Vec, lhs: self, rhs: other => { lhs.min(rhs) })
+    }
+    #[inline(always)]
+    pub fn max(&self, other: &Self) -> Self {
+        impl_vec_op!(F64Vec, lhs: self, rhs: other => { lhs.max(rhs) })
+    }
+    #[inline(always)]
+    pub fn lt(&self, other: &Self) -> Self {
+        impl_vec_op!(F64Vec, lhs: self, rhs: other => { (lhs < rhs) as u8 as f64 })
+    }
+    #[inline(always)]
+    pub fn le(&self, other: &Self) -> Self {
+        impl_vec_op!(F64Vec, lhs: self, rhs: other => { (lhs <= rhs) as u8 as f64 })
+    }
+    #[inline(always)]
+    pub fn select(&self, a: &Self, b: &Self) -> Self {
+        impl_vec_op!(F64Vec, c: self, lhs: a, rhs: b => { if c != 0.0 { lhs } else { rhs } })
+    }
+    #[inline(always)]
     pub fn sin(&self) -> Self {
         impl_vec_op!(F64Vec, v: self => { v.sin() })
     }
@@ -1949,6 +3302,36 @@ impl F64Vec {
     pub fn tan(&self) -> Self {
         impl_vec_op!(F64Vec, v: self => { v.tan() })
     }
+    #[inline(always)]
+    pub fn floor(&self) -> Self {
+        impl_vec_op!(F64Vec, v: self => { v.floor() })
+    }
+    #[inline(always)]
+    pub fn ceil(&self) -> Self {
+        impl_vec_op!(F64Vec, v: self => { v.ceil() })
+    }
+    #[inline(always)]
+    pub fn round(&self) -> Self {
+        impl_vec_op!(F64Vec, v: self => { v.round_ties_even() })
+    }
+    #[inline(always)]
+    pub fn fract(&self) -> Self {
+        impl_vec_op!(F64Vec, v: self => { v - v.floor() })
+    }
+    #[inline(always)]
+    pub fn modulo(&self, other: &Self) -> Self {
+        impl_vec_op!(F64Vec, lhs: self, rhs: other => { lhs - rhs * (lhs / rhs).floor() })
+    }
+    #[inline(always)]
+    pub fn special(&self, f: Special) -> Self {
+        impl_vec_op!(F64Vec, v: self => { f.eval(v) })
+    }
+    /// the native is looked up once for the whole vector
+    #[inline(always)]
+    pub fn native(&self, f: NativeId) -> Self {
+        let f = f.get().f64;
+        impl_vec_op!(F64Vec, v: self => { f(v) })
+    }
 }
 
 #[inline(always)]
@@ -2053,6 +3436,14 @@ impl Range {
         Self::of_pow(self, other)
     }
     #[inline(always)]
+    pub fn min(self, other: Self) -> Self {
+        Self::of_min(self, other)
+    }
+    #[inline(always)]
+    pub fn max(self, other: Self) -> Self {
+        Self::of_max(self, other)
+    }
+    #[inline(always)]
     pub fn sin(self) -> Self {
         Self::of_sin(self)
     }
@@ -2068,6 +3459,26 @@ impl Range {
     pub fn ln(self) -> Self {
         Self::of_ln(self)
     }
+    #[inline(always)]
+    pub fn floor(self) -> Self {
+        Self::of_floor(self)
+    }
+    #[inline(always)]
+    pub fn ceil(self) -> Self {
+        Self::of_ceil(self)
+    }
+    #[inline(always)]
+    pub fn round(self) -> Self {
+        Self::of_round(self)
+    }
+    #[inline(always)]
+    pub fn fract(self) -> Self {
+        Self::of_fract(self)
+    }
+    #[inline(always)]
+    pub fn modulo(self, other: Self) -> Self {
+        Self::of_mod(self, other)
+    }
 
     // pub fn of_sin(a: Range) -> Self {
     //     if a.is_undef() {
@@ -2375,6 +3786,75 @@ impl Range {
         }
     }
 
+    #[inline(always)]
+    pub fn of_min(a: Range, b: Range) -> Self {
+        if a.is_empty() || b.is_empty() {
+            return Self::UNDEF;
+        }
+        Range::new(a.l.min(b.l), a.u.min(b.u))
+    }
+
+    #[inline(always)]
+    pub fn of_max(a: Range, b: Range) -> Self {
+        if a.is_empty() || b.is_empty() {
+            return Self::UNDEF;
+        }
+        Range::new(a.l.max(b.l), a.u.max(b.u))
+    }
+
+    /// smallest range containing both a and b
+    #[inline(always)]
+    pub fn of_hull(a: Range, b: Range) -> Self {
+        Range::new(a.l.min(b.l), a.u.max(b.u))
+    }
+
+    /// a < b is 1 if it holds for every pair, 0 if for none and (0, 1) otherwise
+    #[inline(always)]
+    pub fn of_lt(a: Range, b: Range) -> Self {
+        if a.is_empty() || b.is_empty() {
+            Self::UNDEF
+        } else if a.u < b.l {
+            Self::ONE
+        } else if a.l >= b.u {
+            Self::new(0.0, 0.0)
+        } else {
+            Self::new(0.0, 1.0)
+        }
+    }
+
+    #[inline(always)]
+    pub fn of_le(a: Range, b: Range) -> Self {
+        if a.is_empty() || b.is_empty() {
+            Self::UNDEF
+        } else if a.u <= b.l {
+            Self::ONE
+        } else if a.l > b.u {
+            Self::new(0.0, 0.0)
+        } else {
+            Self::new(0.0, 1.0)
+        }
+    }
+
+    /// a if c is not zero, b if it is. the hull of both if c is undecided
+    #[inline(always)]
+    pub fn of_select(c: Range, a: Range, b: Range) -> Self {
+        if c.is_empty() {
+            Self::UNDEF
+        } else if !c.contains_zero() {
+            a
+        } else if c.l == 0.0 && c.u == 0.0 {
+            b
+        } else {
+            Self::of_hull(a, b)
+        }
+    }
+
+    /// the condition of a select doesn't decide the branch
+    #[inline(always)]
+    pub fn is_undecided(&self) -> bool {
+        self.is_empty() || (self.contains_zero() && (self.l != 0.0 || self.u != 0.0))
+    }
+
     #[inline(always)]
     pub fn of_ln(a: Range) -> Self {
         if a.is_empty() || a.l <= 0.0 {
@@ -2384,6 +3864,86 @@ impl Range {
         (a.l.ln(), a.u.ln()).into()
     }
 
+    /// monotonic, [`Range::has_step`] tells if a jump lies inside
+    #[inline(always)]
+    pub fn of_floor(a: Range) -> Self {
+        (a.l.floor(), a.u.floor()).into()
+    }
+
+    #[inline(always)]
+    pub fn of_ceil(a: Range) -> Self {
+        (a.l.ceil(), a.u.ceil()).into()
+    }
+
+    /// rounds half to even
+    #[inline(always)]
+    pub fn of_round(a: Range) -> Self {
+        (a.l.round_ties_even(), a.u.round_ties_even()).into()
+    }
+
+    /// x - floor(x), [0, 1] if an integer lies inside
+    #[inline(always)]
+    pub fn of_fract(a: Range) -> Self {
+        if a.is_empty() {
+            return a;
+        }
+
+        let k = a.l.floor();
+        if k == a.u.floor() {
+            (a.l - k, a.u - k).into()
+        } else {
+            (0.0, 1.0).into()
+        }
+    }
+
+    /// a - b floor(a / b), between 0 and b if a / b crosses an integer
+    #[inline(always)]
+    pub fn of_mod(a: Range, b: Range) -> Self {
+        if a.is_empty() || b.is_empty() || b.contains_zero() {
+            return Self::UNDEF;
+        }
+
+        if b.l == b.u {
+            let k = (a.l / b.l).floor();
+            if k == (a.u / b.l).floor() {
+                return (a.l - b.l * k, a.u - b.l * k).into();
+            }
+        }
+        (b.l.min(0.0), b.u.max(0.0)).into()
+    }
+
+    /// see [`utils::special`] for the enclosures
+    #[inline(always)]
+    pub fn of_special(f: Special, a: Range) -> Self {
+        f.intrvl(a.l, a.u).into()
+    }
+
+    /// the enclosure registered with the native
+    #[inline(always)]
+    pub fn of_native(f: NativeId, a: Range) -> Self {
+        f.intrvl(a.l, a.u).into()
+    }
+
+    /// the step function `code` (FLOOR, CEIL, ROUND, FRACT or MOD) jumps somewhere in a, b is
+    /// the divisor of MOD
+    pub fn has_step(code: u8, a: Range, b: Range) -> bool {
+        if a.is_empty() {
+            return false;
+        }
+        match code {
+            op::OP_FLOOR | op::OP_FRACT => a.l.floor() != a.u.floor(),
+            op::OP_CEIL => a.l.ceil() != a.u.ceil(),
+            op::OP_ROUND => a.l.round_ties_even() != a.u.round_ties_even(),
+            op::OP_MOD => {
+                b.is_empty()
+                    || b.l != b.u
+                    || b.l == 0.0
+                    || (a.l / b.l).floor() != (a.u / b.l).floor()
+            }
+            _ => false,
+        }
+    }
+
     //     #[inline(always)]
     //     pub const fn in_range(&self, r: Range) -> bool {
     //         self.l > r.l && self.u < r.u
@@ -2494,8 +4054,7 @@ pub enum RangeVec {
 impl VmWord for RangeVec {
     type Data = usize;
 
-    fn from_imm(imm: u32) -> Self {
-        let v = op::float_from_imm(imm);
+    fn from_float(v: float) -> Self {
         RangeVec::Imm(Range::imm(v))
     }
 
@@ -2547,16 +4106,36 @@ impl RangeVec {
         impl_vec_op!(RangeVec, lhs: self, rhs: other => { lhs - rhs })
     }
     #[inline(always)]
-    pub fn mul(&self, other: &Self) -> Self {
-        impl_vec_op!(RangeVec, lhs: self, rhs: other => { lhs * rhs })
+    pub fn mul(&self, other: &Self) -> Self {
+        impl_vec_op!(RangeVec, lhs: self, rhs: other => { lhs * rhs })
+    }
+    #[inline(always)]
+    pub fn div(&self, other: &Self) -> Self {
+        impl_vec_op!(RangeVec, lhs: self, rhs: other => { lhs / rhs })
+    }
+    #[inline(always)]
+    pub fn pow(&self, other: &Self) -> Self {
+        impl_vec_op!(RangeVec, lhs: self, rhs: other => { lhs.pow(rhs) })
+    }
+    #[inline(always)]
+    pub fn min(&self, other: &Self) -> Self {
+        impl_vec_op!(RangeVec, lhs: self, rhs: other => { lhs.min(rhs) })
+    }
+    #[inline(always)]
+    pub fn max(&self, other: &Self) -> Self {
+        impl_vec_op!(RangeVec, lhs: self, rhs: other => { lhs.max(rhs) })
+    }
+    #[inline(always)]
+    pub fn lt(&self, other: &Self) -> Self {
+        impl_vec_op!(RangeVec, lhs: self, rhs: other => { Range::of_lt(lhs, rhs) })
     }
     #[inline(always)]
-    pub fn div(&self, other: &Self) -> Self {
-        impl_vec_op!(RangeVec, lhs: self, rhs: other => { lhs / rhs })
+    pub fn le(&self, other: &Self) -> Self {
+        impl_vec_op!(RangeVec, lhs: self, rhs: other => { Range::of_le(lhs, rhs) })
     }
     #[inline(always)]
-    pub fn pow(&self, other: &Self) -> Self {
-        impl_vec_op!(RangeVec, lhs: self, rhs: other => { lhs.pow(rhs) })
+    pub fn select(&self, a: &Self, b: &Self) -> Self {
+        impl_vec_op!(RangeVec, c: self, lhs: a, rhs: b => { Range::of_select(c, lhs, rhs) })
     }
     #[inline(always)]
     pub fn sin(&self) -> Self {
@@ -2570,11 +4149,40 @@ impl RangeVec {
     pub fn tan(&self) -> Self {
         impl_vec_op!(RangeVec, v: self => { v.tan() })
     }
+    #[inline(always)]
+    pub fn floor(&self) -> Self {
+        impl_vec_op!(RangeVec, v: self => { v.floor() })
+    }
+    #[inline(always)]
+    pub fn ceil(&self) -> Self {
+        impl_vec_op!(RangeVec, v: self => { v.ceil() })
+    }
+    #[inline(always)]
+    pub fn round(&self) -> Self {
+        impl_vec_op!(RangeVec, v: self => { v.round() })
+    }
+    #[inline(always)]
+    pub fn fract(&self) -> Self {
+        impl_vec_op!(RangeVec, v: self => { v.fract() })
+    }
+    #[inline(always)]
+    pub fn modulo(&self, other: &Self) -> Self {
+        impl_vec_op!(RangeVec, lhs: self, rhs: other => { lhs.modulo(rhs) })
+    }
+    #[inline(always)]
+    pub fn special(&self, f: Special) -> Self {
+        impl_vec_op!(RangeVec, v: self => { Range::of_special(f, v) })
+    }
+    #[inline(always)]
+    pub fn native(&self, f: NativeId) -> Self {
+        let f = f.get().intrvl;
+        impl_vec_op!(RangeVec, v: self => { Range::from(f(v.l, v.u)) })
+    }
 }
 
 pub mod simd {
     use super::*;
-    use wide::f64x4;
+    use wide::{CmpLe, CmpLt, CmpNe, f64x4};
 
     const SIMD_WIDTH: usize = 4;
 
@@ -2587,8 +4195,7 @@ pub mod simd {
     impl VmWord for F64x4Vec {
         type Data = usize;
 
-        fn from_imm(imm: u32) -> Self {
-            let v = op::float_from_imm(imm);
+        fn from_float(v: float) -> Self {
             Self::Imm(f64x4::splat(v))
         }
 
@@ -2656,6 +4263,29 @@ pub mod simd {
             impl_vec_op!(F64x4Vec, lhs: self, rhs: other => { lhs.pow_f64x4(rhs) })
         }
         #[inline]
+        pub fn min(&self, other: &Self) -> Self {
+            impl_vec_op!(F64x4Vec, lhs: self, rhs: other => { lhs.min(rhs) })
+        }
+        #[inline]
+        pub fn max(&self, other: &Self) -> Self {
+            impl_vec_op!(F64x4Vec, lhs: self, rhs: other => { lhs.max(rhs) })
+        }
+        #[inline]
+        pub fn lt(&self, other: &Self) -> Self {
+            impl_vec_op!(F64x4Vec, lhs: self, rhs: other => { lhs.cmp_lt(rhs) & f64x4::ONE })
+        }
+        #[inline]
+        pub fn le(&self, other: &Self) -> Self {
+            impl_vec_op!(F64x4Vec, lhs: self, rhs: other => { lhs.cmp_le(rhs) & f64x4::ONE })
+        }
+        /// branchless, the condition mask blends both sides
+        #[inline]
+        pub fn select(&self, a: &Self, b: &Self) -> Self {
+            impl_vec_op!(F64x4Vec, c: self, lhs: a, rhs: b => {
+                c.cmp_ne(f64x4::ZERO).blend(lhs, rhs)
+            })
+        }
+        #[inline]
         pub fn sin(&self) -> Self {
             impl_vec_op!(F64x4Vec, v: self => { v.sin() })
         }
@@ -2667,6 +4297,37 @@ pub mod simd {
         pub fn tan(&self) -> Self {
             impl_vec_op!(F64x4Vec, v: self => { v.tan() })
         }
+        #[inline]
+        pub fn floor(&self) -> Self {
+            impl_vec_op!(F64x4Vec, v: self => { v.floor() })
+        }
+        #[inline]
+        pub fn ceil(&self) -> Self {
+            impl_vec_op!(F64x4Vec, v: self => { v.ceil() })
+        }
+        #[inline]
+        pub fn round(&self) -> Self {
+            impl_vec_op!(F64x4Vec, v: self => { v.round() })
+        }
+        #[inline]
+        pub fn fract(&self) -> Self {
+            impl_vec_op!(F64x4Vec, v: self => { v - v.floor() })
+        }
+        #[inline]
+        pub fn modulo(&self, other: &Self) -> Self {
+            impl_vec_op!(F64x4Vec, lhs: self, rhs: other => { lhs - rhs * (lhs / rhs).floor() })
+        }
+        /// evaluated lane wise
+        #[inline]
+        pub fn special(&self, f: Special) -> Self {
+            impl_vec_op!(F64x4Vec, v: self => { f64x4::new(v.to_array().map(|x| f.eval(x))) })
+        }
+        /// evaluated lane wise
+        #[inline]
+        pub fn native(&self, f: NativeId) -> Self {
+            let f = f.get().f64;
+            impl_vec_op!(F64x4Vec, v: self => { f64x4::new(v.to_array().map(f)) })
+        }
 
         pub fn len(&self) -> usize {
             match self {
@@ -2774,6 +4435,79 @@ pub mod simd {
             vm.next(t);
         }
 
+        fn min(vm: &mut VM<F64x4Vec>, t: &InstrTape) {
+            let (lhs, rhs, out) = vm.binop_arg(t);
+            *vm.reg_mut(out) = lhs.min(&rhs);
+            vm.next(t);
+        }
+
+        fn max(vm: &mut VM<F64x4Vec>, t: &InstrTape) {
+            let (lhs, rhs, out) = vm.binop_arg(t);
+            *vm.reg_mut(out) = lhs.max(&rhs);
+            vm.next(t);
+        }
+
+        fn lt(vm: &mut VM<F64x4Vec>, t: &InstrTape) {
+            let (lhs, rhs, out) = vm.binop_arg(t);
+            *vm.reg_mut(out) = lhs.lt(&rhs);
+            vm.next(t);
+        }
+
+        fn le(vm: &mut VM<F64x4Vec>, t: &InstrTape) {
+            let (lhs, rhs, out) = vm.binop_arg(t);
+            *vm.reg_mut(out) = lhs.le(&rhs);
+            vm.next(t);
+        }
+
+        fn sel(vm: &mut VM<F64x4Vec>, t: &InstrTape) {
+            let (lhs, rhs, out) = vm.binop_arg(t);
+            *vm.reg_mut(out) = vm.reg[out].select(&lhs, &rhs);
+            vm.next(t);
+        }
+
+        fn floor(vm: &mut VM<F64x4Vec>, t: &InstrTape) {
+            let (a, out) = vm.unary_arg(t);
+            *vm.reg_mut(out) = a.floor();
+            vm.next(t);
+        }
+
+        fn ceil(vm: &mut VM<F64x4Vec>, t: &InstrTape) {
+            let (a, out) = vm.unary_arg(t);
+            *vm.reg_mut(out) = a.ceil();
+            vm.next(t);
+        }
+
+        fn round(vm: &mut VM<F64x4Vec>, t: &InstrTape) {
+            let (a, out) = vm.unary_arg(t);
+            *vm.reg_mut(out) = a.round();
+            vm.next(t);
+        }
+
+        fn fract(vm: &mut VM<F64x4Vec>, t: &InstrTape) {
+            let (a, out) = vm.unary_arg(t);
+            *vm.reg_mut(out) = a.fract();
+            vm.next(t);
+        }
+
+        fn modulo(vm: &mut VM<F64x4Vec>, t: &InstrTape) {
+            let (a, b, out) = vm.binop_arg(t);
+            *vm.reg_mut(out) = a.modulo(&b);
+            vm.next(t);
+        }
+
+        fn special<const OP: u8>(vm: &mut VM<F64x4Vec>, t: &InstrTape) {
+            let f = op::special(OP).unwrap();
+            let (a, out) = vm.unary_arg(t);
+            *vm.reg_mut(out) = a.special(f);
+            vm.next(t);
+        }
+
+        fn call(vm: &mut VM<F64x4Vec>, t: &InstrTape) {
+            let (f, a, out) = vm.call_arg(t);
+            *vm.reg_mut(out) = a.native(f);
+            vm.next(t);
+        }
+
         fn sin(vm: &mut VM<F64x4Vec>, t: &InstrTape) {
             let (lhs, out) = vm.unary_arg(t);
             *vm.reg_mut(out) = lhs.sin();
@@ -2839,6 +4573,512 @@ pub mod simd {
 //    vm.eval_range(&code);
 //}
 
+/// runs the optimizer of the jit bytecode on vm programs
+///
+/// vm register r is jit register r - 1, the inputs are reg[1..=3] and the outputs the RET
+/// values or reg[1]
+#[cfg(feature = "native-codegen")]
+pub mod opt {
+    use super::*;
+    use compiler::jit::{BinOp, Instr, Oprnd, Program, UnOp};
+
+    /// the program and whether it returns with RET, None for stack and output ops
+    pub fn to_bytecode(ops: &[Opcode]) -> Option<(Program, bool)> {
+        let mut bytecode = vec![];
+        let mut outputs = vec![];
+
+        for &code in ops {
+            let (op, l, r, out, raw) = op::decode(code);
+            let imm = op::float_from_imm(raw);
+            let oprnd = |reg: usize| match reg {
+                0 => Oprnd::Imm(imm),
+                reg => Oprnd::Reg(reg as u8 - 1),
+            };
+            let dst = (out as u8).wrapping_sub(1);
+
+            let instr = |op| Instr::UnOp {
+                op,
+                val: oprnd(l),
+                dst,
+            };
+            let binop = |op| Instr::BinOp {
+                op,
+                lhs: oprnd(l),
+                rhs: oprnd(r),
+                dst,
+            };
+
+            bytecode.push(match op {
+                op::OP_NOP => continue,
+                op::OP_EXT => break,
+                op::OP_RET if l != 0 => {
+                    outputs.push(l as u8 - 1);
+                    continue;
+                }
+                _ if out == 0 => return None,
+                op::OP_ADD => binop(BinOp::ADD),
+                op::OP_SUB => binop(BinOp::SUB),
+                op::OP_MUL => binop(BinOp::MUL),
+                op::OP_DIV => binop(BinOp::DIV),
+                op::OP_POW => binop(BinOp::POW),
+                op::OP_MIN => binop(BinOp::MIN),
+                op::OP_MAX => binop(BinOp::MAX),
+                op::OP_LT => binop(BinOp::LT),
+                op::OP_LE => binop(BinOp::LE),
+                // the condition of SEL is the out register
+                op::OP_SEL => Instr::Select {
+                    cond: Oprnd::Reg(dst),
+                    lhs: oprnd(l),
+                    rhs: oprnd(r),
+                    dst,
+                },
+                op::OP_SIN => instr(UnOp::SIN),
+                op::OP_COS => instr(UnOp::COS),
+                op::OP_TAN => instr(UnOp::TAN),
+                op::OP_MOV => instr(UnOp::MOV),
+                op::OP_FLOOR => instr(UnOp::FLOOR),
+                op::OP_CEIL => instr(UnOp::CEIL),
+                op::OP_ROUND => instr(UnOp::ROUND),
+                op::OP_FRACT => instr(UnOp::FRACT),
+                op::OP_MOD => binop(BinOp::MOD),
+                op::OP_GAMMA => instr(UnOp::GAMMA),
+                op::OP_ERF => instr(UnOp::ERF),
+                op::OP_J0 => instr(UnOp::J0),
+                op::OP_J1 => instr(UnOp::J1),
+                op::OP_LAMBERTW => instr(UnOp::LAMBERTW),
+                op::OP_CALL if l != 0 => instr(UnOp::CALL(NativeId::from_raw(raw)?)),
+                op::OP_LIT => Instr::UnOp {
+                    op: UnOp::MOV,
+                    val: Oprnd::Imm(op::constant(ops, raw)?),
+                    dst,
+                },
+                _ => return None,
+            });
+        }
+
+        let ret = !outputs.is_empty();
+        if !ret {
+            outputs.push(0);
+        }
+        Some((Program::with_outputs(bytecode, outputs), ret))
+    }
+
+    /// loads constants that are not exact as f32 immediates from the pool after EXT
+    #[derive(Default)]
+    struct Pool {
+        constants: Vec<float>,
+    }
+
+    impl Pool {
+        fn mov_imm(&mut self, v: float, out: u8) -> Opcode {
+            if op::fits_imm(v) {
+                return op::MOV_IMM(v, out);
+            }
+            let index = match self
+                .constants
+                .iter()
+                .position(|c| c.to_bits() == v.to_bits())
+            {
+                Some(i) => i,
+                None => {
+                    self.constants.push(v);
+                    self.constants.len() - 1
+                }
+            };
+            op::LIT(index as u32, out)
+        }
+
+        /// EXT followed by the pool, the first constant is the last word
+        fn finish(self, ops: &mut Vec<Opcode>) {
+            ops.push(op::EXT(0));
+            ops.extend(self.constants.iter().rev().map(|c| c.to_bits()));
+        }
+    }
+
+    /// None if a register does not fit into the vm
+    pub fn from_bytecode(program: &Program, ret: bool) -> Option<Vec<Opcode>> {
+        let reg = |r: u8| (r as usize + 1 < REGISTER_COUNT).then_some(r + 1);
+        let mut ops = vec![];
+        let mut pool = Pool::default();
+
+        // holds wide constants of instructions that also read a register
+        let scratch = || {
+            let regs = program.bytecode.iter().flat_map(|&i| {
+                let (a, b, c, dst) = match i {
+                    Instr::UnOp { val, dst, .. } => (val, val, val, dst),
+                    Instr::BinOp { lhs, rhs, dst, .. } => (lhs, rhs, rhs, dst),
+                    Instr::Select {
+                        cond,
+                        lhs,
+                        rhs,
+                        dst,
+                    } => (cond, lhs, rhs, dst),
+                };
+                let r = |o| match o {
+                    Oprnd::Reg(r) => r,
+                    Oprnd::Imm(_) => 0,
+                };
+                [r(a), r(b), r(c), dst]
+            });
+            let max = regs.chain(program.outputs.iter().copied()).max()?;
+            reg(max.checked_add(1)?)
+        };
+
+        for &instr in &program.bytecode {
+            let (code, lhs, rhs, dst) = match instr {
+                Instr::UnOp {
+                    op: UnOp::CALL(f),
+                    val,
+                    dst,
+                } => {
+                    // the immediate holds the id, so constants are moved into out first
+                    let out = reg(dst)?;
+                    let lhs = match val {
+                        Oprnd::Reg(r) => reg(r)?,
+                        Oprnd::Imm(v) => {
+                            ops.push(pool.mov_imm(v, out));
+                            out
+                        }
+                    };
+                    ops.push(op::CALL(f, lhs, out));
+                    continue;
+                }
+                Instr::UnOp { op, val, dst } => {
+                    let code = match op {
+                        UnOp::MOV => op::OP_MOV,
+                        UnOp::SIN => op::OP_SIN,
+                        UnOp::COS => op::OP_COS,
+                        UnOp::TAN => op::OP_TAN,
+                        UnOp::FLOOR => op::OP_FLOOR,
+                        UnOp::CEIL => op::OP_CEIL,
+                        UnOp::ROUND => op::OP_ROUND,
+                        UnOp::FRACT => op::OP_FRACT,
+                        UnOp::GAMMA => op::OP_GAMMA,
+                        UnOp::ERF => op::OP_ERF,
+                        UnOp::J0 => op::OP_J0,
+                        UnOp::J1 => op::OP_J1,
+                        UnOp::LAMBERTW => op::OP_LAMBERTW,
+                        UnOp::CALL(_) => unreachable!("calls are handled above"),
+                    };
+                    (code, val, None, dst)
+                }
+                Instr::BinOp { op, lhs, rhs, dst } => {
+                    let code = match op {
+                        BinOp::ADD => op::OP_ADD,
+                        BinOp::SUB => op::OP_SUB,
+                        BinOp::MUL => op::OP_MUL,
+                        BinOp::DIV => op::OP_DIV,
+                        BinOp::POW => op::OP_POW,
+                        BinOp::MIN => op::OP_MIN,
+                        BinOp::MAX => op::OP_MAX,
+                        BinOp::LT => op::OP_LT,
+                        BinOp::LE => op::OP_LE,
+                        BinOp::MOD => op::OP_MOD,
+                    };
+                    (code, lhs, Some(rhs), dst)
+                }
+                Instr::Select {
+                    cond,
+                    lhs,
+                    rhs,
+                    dst,
+                } => {
+                    // SEL reads the condition from its out register, which is the scratch
+                    // register if an operand still needs dst
+                    let dst = reg(dst)?;
+                    let reads_dst = [lhs, rhs].contains(&Oprnd::Reg(dst - 1));
+                    let out = match cond {
+                        Oprnd::Reg(c) if c == dst - 1 => dst,
+                        _ if reads_dst => scratch()?,
+                        _ => dst,
+                    };
+                    match cond {
+                        Oprnd::Reg(c) if reg(c)? == out => (),
+                        Oprnd::Reg(c) => ops.push(op::MOV(reg(c)?, out)),
+                        Oprnd::Imm(v) => ops.push(pool.mov_imm(v, out)),
+                    }
+
+                    // one f32 immediate, another constant is loaded into the scratch register
+                    let mut imm = None;
+                    let mut loaded = false;
+                    let mut args = [0; 2];
+                    for (a, o) in args.iter_mut().zip([lhs, rhs]) {
+                        *a = match o {
+                            Oprnd::Reg(r) => reg(r)?,
+                            Oprnd::Imm(v)
+                                if op::fits_imm(v)
+                                    && imm.is_none_or(|i: float| i.to_bits() == v.to_bits()) =>
+                            {
+                                imm = Some(v);
+                                0
+                            }
+                            Oprnd::Imm(v) => {
+                                let s = scratch().filter(|&s| s != out && !loaded)?;
+                                ops.push(pool.mov_imm(v, s));
+                                loaded = true;
+                                s
+                            }
+                        };
+                    }
+                    let imm = imm.unwrap_or(0.0);
+                    ops.push(op::build_opcode_float(
+                        op::OP_SEL,
+                        args[0],
+                        args[1],
+                        out,
+                        imm,
+                    ));
+                    if out != dst {
+                        ops.push(op::MOV(out, dst));
+                    }
+                    continue;
+                }
+            };
+            let out = reg(dst)?;
+
+            // an instruction only has one immediate
+            let (lhs, rhs) = match (lhs, rhs) {
+                (Oprnd::Imm(l), Some(Oprnd::Imm(r))) if l.to_bits() != r.to_bits() => {
+                    ops.push(pool.mov_imm(l, out));
+                    (Oprnd::Reg(dst), Some(Oprnd::Imm(r)))
+                }
+                oprnds => oprnds,
+            };
+
+            // wide constants are loaded into a register
+            let wide = [Some(lhs), rhs].into_iter().find_map(|o| match o {
+                Some(Oprnd::Imm(v)) if !op::fits_imm(v) => Some(v),
+                _ => None,
+            });
+            let (lhs, rhs) = match wide {
+                Some(v) if code == op::OP_MOV => {
+                    ops.push(pool.mov_imm(v, out));
+                    continue;
+                }
+                Some(v) => {
+                    let s = scratch()?;
+                    ops.push(pool.mov_imm(v, s));
+                    let to_reg = |o: Oprnd| match o {
+                        Oprnd::Imm(_) => Oprnd::Reg(s - 1),
+                        o => o,
+                    };
+                    (to_reg(lhs), rhs.map(to_reg))
+                }
+                None => (lhs, rhs),
+            };
+
+            let mut imm = 0.0;
+            let mut arg = |o: Option<Oprnd>| match o {
+                Some(Oprnd::Reg(r)) => reg(r),
+                Some(Oprnd::Imm(v)) => {
+                    imm = v;
+                    Some(0)
+                }
+                None => Some(0),
+            };
+            let (l, r) = (arg(Some(lhs))?, arg(rhs)?);
+            ops.push(op::build_opcode_float(code, l, r, out, imm));
+        }
+
+        if ret {
+            for &r in &program.outputs {
+                ops.push(op::RET(reg(r)?));
+            }
+        }
+        pool.finish(&mut ops);
+        Some(ops)
+    }
+
+    /// returns the program unchanged if it can not be represented as bytecode
+    ///
+    /// folded constants that are not exact as f32 immediates are loaded from the pool
+    pub fn optimize(ops: &[Opcode]) -> Vec<Opcode> {
+        to_bytecode(ops)
+            .and_then(|(program, ret)| from_bytecode(&compiler::opt::optimize(&program), ret))
+            .unwrap_or_else(|| ops.to_vec())
+    }
+}
+
+/// text format of vm programs, see [`utils::asm`]
+///
+/// immediates are written as operands, `DIV[1_f, 2] -> 2` is `DIV_IMM_REG(1.0, 2, 2)`.
+/// OUT, PSH and RET have no destination, POP no operands and EXT takes its exit code as
+/// operand if it isn't 0. natives are called by name and only read registers,
+/// `CALL_<name>[1] -> 2`. constants of the pool are written in full, `LIT[0.1_f] -> 2`
+pub mod asm {
+    use super::*;
+    use utils::{
+        asm::{self, AsmError, Labels, Operand},
+        native,
+    };
+
+    /// opcodes without a representation are written as comments, the words after the
+    /// first EXT are the constant pool
+    pub fn to_asm(bin: &[Opcode], labels: &Labels) -> String {
+        let mut text = labels.to_string();
+        let end = bin
+            .iter()
+            .position(|&o| op::get_op(o) == op::OP_EXT)
+            .map_or(bin.len(), |i| i + 1);
+
+        for &opcode in &bin[..end] {
+            let (op, lhs, rhs, out, imm) = op::decode(opcode);
+            let oprnd = |reg: usize| match reg {
+                0 => format!("{}_f", f32::from_bits(imm)),
+                _ => labels.reg(reg as u32),
+            };
+            let out = labels.reg(out as u32);
+            let name = op::op_to_str(op);
+
+            let line = match op {
+                op::OP_CALL => match NativeId::from_raw(imm) {
+                    Some(f) => format!("{name}_{f}[{}] -> {out}", labels.reg(lhs as u32)),
+                    None => format!("# unknown native {imm}"),
+                },
+                op::OP_LIT => match op::constant(bin, imm) {
+                    Some(v) => format!("{name}[{v:?}_f] -> {out}"),
+                    None => format!("# unknown constant {imm}"),
+                },
+                _ if op::is_binary(op) => {
+                    format!("{name}[{}, {}] -> {out}", oprnd(lhs), oprnd(rhs))
+                }
+                _ if op::is_unary(op) => format!("{name}[{}] -> {out}", oprnd(lhs)),
+                op::OP_OUT | op::OP_PSH | op::OP_RET => format!("{name}[{}]", oprnd(lhs)),
+                op::OP_POP => format!("{name} -> {out}"),
+                op::OP_EXT if imm != 0 => format!("{name}[{imm}]"),
+                op::OP_NOP | op::OP_EXT => name.to_string(),
+                _ => format!("# unknown opcode {opcode:#018x}"),
+            };
+            text.push_str(&line);
+            text.push('\n');
+        }
+
+        text
+    }
+
+    fn parse_op(op: &str) -> Option<u8> {
+        (0..op::NUM_OPS as u8).find(|&o| op::op_to_str(o) == op)
+    }
+
+    /// parses the output of [`to_asm`], the constant pool is appended to the program
+    pub fn from_asm(src: &str) -> Result<Vec<Opcode>, AsmError> {
+        let (lines, _) = asm::parse(src)?;
+        let mut bin = vec![];
+        let mut pool: Vec<float> = vec![];
+
+        for (line, l) in lines {
+            let asm::Line::Instr {
+                op: name,
+                args,
+                dst,
+            } = l
+            else {
+                return Err(AsmError::new(line, "vm programs have no directives"));
+            };
+
+            // natives are called by name, `CALL_<name>[reg] -> dst`
+            if let Some(native) = name.strip_prefix("CALL_") {
+                let f = native::lookup(native)
+                    .ok_or_else(|| AsmError::new(line, format!("unknown native `{native}`")))?;
+                let (&[Operand::Reg(lhs @ 1..)], Some(out)) = (args.as_slice(), dst) else {
+                    return Err(AsmError::new(
+                        line,
+                        format!("expected `{name}[reg] -> dst`"),
+                    ));
+                };
+                bin.push(op::CALL(f, check_reg(lhs, line)?, check_reg(out, line)?));
+                continue;
+            }
+            let op = parse_op(name)
+                .filter(|&op| op != op::OP_CALL)
+                .ok_or_else(|| AsmError::new(line, format!("unknown instruction `{name}`")))?;
+
+            let n_args = match op {
+                _ if op::is_binary(op) => 2,
+                op::OP_POP | op::OP_NOP => 0,
+                op::OP_EXT => args.len().min(1),
+                _ => 1,
+            };
+            let has_dst = !matches!(
+                op,
+                op::OP_OUT | op::OP_PSH | op::OP_RET | op::OP_NOP | op::OP_EXT
+            );
+            if args.len() != n_args {
+                return Err(AsmError::new(
+                    line,
+                    format!("wrong operand count for {name}"),
+                ));
+            }
+            let out = match (dst, has_dst) {
+                (Some(r), true) => r,
+                (None, false) => 0,
+                (None, true) => return Err(AsmError::new(line, "missing `-> dst`")),
+                (Some(_), false) => {
+                    return Err(AsmError::new(line, format!("{name} has no destination")));
+                }
+            };
+
+            if op == op::OP_EXT {
+                let code = args.first().map_or(Ok(0), |a| a.reg(line))?;
+                bin.push(op::EXT(code));
+                continue;
+            }
+            if op == op::OP_LIT {
+                let &[Operand::Imm(v)] = args.as_slice() else {
+                    return Err(AsmError::new(line, "LIT takes an immediate"));
+                };
+                let index = match pool.iter().position(|c| c.to_bits() == v.to_bits()) {
+                    Some(i) => i,
+                    None => {
+                        pool.push(v);
+                        pool.len() - 1
+                    }
+                };
+                bin.push(op::LIT(index as u32, check_reg(out, line)?));
+                continue;
+            }
+
+            let mut regs = [0u8; 3];
+            let mut imm = None;
+            for (reg, &arg) in regs.iter_mut().zip(&args) {
+                match arg {
+                    Operand::Reg(0) => {
+                        return Err(AsmError::new(
+                            line,
+                            "register 0 can't be read, use an immediate",
+                        ));
+                    }
+                    Operand::Reg(r) => *reg = check_reg(r, line)?,
+                    Operand::Imm(i) if imm.is_some_and(|j| j != i) => {
+                        return Err(AsmError::new(line, "operands share one immediate"));
+                    }
+                    Operand::Imm(i) => imm = Some(i),
+                }
+            }
+            regs[2] = check_reg(out, line)?;
+
+            let [lhs, rhs, out] = regs;
+            bin.push(op::build_opcode_float(
+                op,
+                lhs,
+                rhs,
+                out,
+                imm.unwrap_or(0.0),
+            ));
+        }
+
+        bin.extend(pool.iter().rev().map(|c| c.to_bits()));
+        Ok(bin)
+    }
+
+    fn check_reg(r: u32, line: usize) -> Result<u8, AsmError> {
+        u8::try_from(r)
+            .ok()
+            .filter(|&r| (r as usize) < REGISTER_COUNT)
+            .ok_or_else(|| AsmError::new(line, format!("register {r} out of range")))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -2935,4 +5175,598 @@ mod test {
         vm.eval(&pow);
         assert_eq!(vm.reg[1], (0.25, 4.0).into());
     }
+
+    #[test]
+    fn select() {
+        // x < y ? x : 2
+        let code = [
+            op::LT_REG_REG(1, 2, 3),
+            op::SEL_REG_IMM(1, 2.0, 3),
+            op::MOV(3, 1),
+            op::EXT(0),
+        ];
+
+        let mut vm = VM::with_instr_table(F64InstrTable);
+        assert_eq!(vm.call([1.0, 3.0], &code), [1.0]);
+        assert_eq!(vm.call([3.0, 1.0], &code), [2.0]);
+        assert_eq!(vm.call([3.0, 3.0], &code), [2.0]);
+
+        let mut vm = VM::with_instr_table(RangeInstrTable);
+        let mut eval = |x: (f64, f64), y: (f64, f64)| {
+            vm.reg[1] = x.into();
+            vm.reg[2] = y.into();
+            vm.eval(&code);
+            vm.reg[1]
+        };
+
+        assert_eq!(eval((0.0, 1.0), (3.0, 4.0)), (0.0, 1.0).into());
+        assert_eq!(eval((5.0, 6.0), (3.0, 4.0)), (2.0, 2.0).into());
+        // undecided, the hull of both branches
+        assert_eq!(eval((3.0, 6.0), (3.0, 4.0)), (2.0, 6.0).into());
+
+        let mut vm = VM::with_instr_table(F64VecInstrTable);
+        vm.reg[1] = F64Vec::from(vec![1.0, 3.0, 3.0]);
+        vm.reg[2] = F64Vec::from(vec![3.0, 1.0, 3.0]);
+        vm.eval(&code);
+        assert_eq!(vm.reg[1], F64Vec::from(vec![1.0, 2.0, 2.0]));
+
+        // a nan condition is not zero, the same as the jit select
+        let code = [
+            op::MOV_IMM(float::NAN, 3),
+            op::SEL_REG_IMM(1, 2.0, 3),
+            op::MOV(3, 1),
+            op::EXT(0),
+        ];
+        let mut vm = VM::with_instr_table(F64InstrTable);
+        assert_eq!(vm.call([1.0, 0.0], &code), [1.0]);
+
+        // jit selects reading dst or with two constants, 0.1 is not exact as f32
+        #[cfg(feature = "native-codegen")]
+        {
+            let program = compiler::jit::Program::from(
+                compiler::bytecode![
+                    LT[0, 1] -> 2,
+                    SEL[2, 0, 1] -> 0,
+                    SEL[2, imm(0.5), imm(0.1)] -> 2,
+                    ADD[0, 2] -> 0,
+                ]
+                .to_vec(),
+            );
+            let code = opt::from_bytecode(&program, false).unwrap();
+            assert_eq!(verify(&code), Ok(()));
+            for (x, y) in [(1.0, 2.0), (2.0, 1.0), (-1.0, -1.0)] {
+                let v = if x < y { x + 0.5 } else { y + 0.1 };
+                assert_eq!(vm.call([x, y], &code), [v]);
+            }
+        }
+    }
+
+    #[test]
+    fn sign_heaviside() {
+        // sign(x) + 2 heaviside(y)
+        let mut code = op::SIGN(1, 3, 4).to_vec();
+        code.extend([
+            op::HEAVISIDE(2, 5),
+            op::MUL_REG_IMM(5, 2.0, 5),
+            op::ADD_REG_REG(4, 5, 1),
+            op::EXT(0),
+        ]);
+        assert_eq!(verify(&code), Ok(()));
+
+        let mut vm = VM::with_instr_table(F64InstrTable);
+        for (x, y, v) in [
+            (-3.0, -1.0, -1.0),
+            (0.0, 0.0, 2.0),
+            (0.5, -0.0, 3.0),
+            (-0.0, 1e-300, 2.0),
+            (float::NAN, float::NAN, 0.0),
+        ] {
+            assert_eq!(vm.call([x, y], &code), [v], "sign({x}) + 2 heaviside({y})");
+        }
+
+        let mut vm = VM::with_instr_table(RangeInstrTable);
+        let mut eval = |x: (f64, f64), y: (f64, f64)| {
+            vm.reg[1] = x.into();
+            vm.reg[2] = y.into();
+            vm.eval(&code);
+            vm.reg[1]
+        };
+        assert_eq!(eval((1.0, 2.0), (-2.0, -1.0)), (1.0, 1.0).into());
+        assert_eq!(eval((-2.0, -1.0), (0.0, 1.0)), (1.0, 1.0).into());
+        // the steps at 0 give both values
+        assert_eq!(eval((-1.0, 1.0), (-1.0, 1.0)), (-1.0, 3.0).into());
+    }
+
+    #[test]
+    fn steps() {
+        // fract(x) + mod(y, 2) + round(x)
+        let code = [
+            op::FRACT(1, 3),
+            op::MOD_REG_IMM(2, 2.0, 4),
+            op::ADD_REG_REG(3, 4, 3),
+            op::ROUND(1, 4),
+            op::ADD_REG_REG(3, 4, 1),
+            op::EXT(0),
+        ];
+
+        let mut vm = VM::with_instr_table(F64InstrTable);
+        assert_eq!(vm.call([1.25, -1.0], &code), [0.25 + 1.0 + 1.0]);
+        // ties to even
+        assert_eq!(vm.call([2.5, 5.0], &code), [0.5 + 1.0 + 2.0]);
+
+        assert_eq!(Range::of_floor((0.5, 2.5).into()), (0.0, 2.0).into());
+        assert_eq!(Range::of_fract((1.25, 1.5).into()), (0.25, 0.5).into());
+        assert_eq!(Range::of_fract((0.5, 1.5).into()), (0.0, 1.0).into());
+        assert_eq!(
+            Range::of_mod((-1.5, -0.5).into(), Range::new_const(2.0)),
+            (0.5, 1.5).into()
+        );
+        assert_eq!(
+            Range::of_mod((1.5, 2.5).into(), Range::new_const(2.0)),
+            (0.0, 2.0).into()
+        );
+        assert!(Range::of_mod((1.0, 2.0).into(), (-1.0, 1.0).into()).is_empty());
+
+        let none = Range::UNDEF;
+        assert!(Range::has_step(op::OP_FLOOR, (0.5, 1.5).into(), none));
+        assert!(!Range::has_step(op::OP_FLOOR, (0.5, 0.9).into(), none));
+        assert!(Range::has_step(op::OP_ROUND, (0.4, 0.6).into(), none));
+        assert!(!Range::has_step(op::OP_CEIL, (0.5, 1.0).into(), none));
+        assert!(!Range::has_step(
+            op::OP_MOD,
+            (0.5, 1.5).into(),
+            Range::new_const(2.0)
+        ));
+        assert!(Range::has_step(
+            op::OP_MOD,
+            (0.5, 1.5).into(),
+            (1.0, 2.0).into()
+        ));
+
+        let mut vm = VM::with_instr_table(F64DerivInstrTable);
+        vm.reg[1] = F64Deriv::var(1.25);
+        vm.reg[2] = F64Deriv::cnst(-1.0);
+        vm.eval(&code);
+        assert_eq!(
+            vm.reg[1],
+            F64Deriv {
+                val: 2.25,
+                grad: 1.0
+            }
+        );
+    }
+
+    #[test]
+    fn special() {
+        // gamma(x) + j0(y)
+        let code = [
+            op::GAMMA(1, 3),
+            op::J0(2, 4),
+            op::ADD_REG_REG(3, 4, 1),
+            op::EXT(0),
+        ];
+        let (x, y) = (2.5, 1.5);
+        let expected = Special::Gamma.eval(x) + Special::J0.eval(y);
+
+        let mut vm = VM::with_instr_table(F64InstrTable);
+        assert_eq!(vm.call([x, y], &code), [expected]);
+
+        let mut vm = VM::with_instr_table(F64DerivInstrTable);
+        vm.reg[1] = F64Deriv::var(x);
+        vm.reg[2] = F64Deriv::cnst(y);
+        vm.eval(&code);
+        assert_eq!(
+            vm.reg[1],
+            F64Deriv {
+                val: expected,
+                grad: Special::Gamma.deriv(x),
+            }
+        );
+
+        let mut vm = VM::with_instr_table(RangeInstrTable);
+        vm.reg[1] = Range::new(2.0, 3.0);
+        vm.reg[2] = Range::new(1.0, 2.0);
+        vm.eval(&code);
+        assert!(vm.reg[1].l <= expected && expected <= vm.reg[1].u);
+
+        // the pole of gamma at 0
+        vm.reg[1] = Range::new(-0.5, 0.5);
+        vm.reg[2] = Range::new(1.0, 2.0);
+        vm.eval(&code);
+        assert!(vm.reg[1].is_empty());
+    }
+
+    #[test]
+    fn native() {
+        use utils::native::{self, NativeFn};
+
+        let cube = native::register(NativeFn::new(
+            "vm_test_cube",
+            |x| x * x * x,
+            |x| 3.0 * x * x,
+            |lo, hi| (lo * lo * lo, hi * hi * hi),
+        ));
+        // cube(x) + y
+        let code = [op::CALL(cube, 1, 3), op::ADD_REG_REG(3, 2, 1), op::EXT(0)];
+        assert_eq!(verify(&code), Ok(()));
+
+        let mut vm = VM::with_instr_table(F64InstrTable);
+        assert_eq!(vm.call([2.0, 0.5], &code), [8.5]);
+
+        let mut vm = VM::with_instr_table(F64DerivInstrTable);
+        vm.reg[1] = F64Deriv::var(2.0);
+        vm.reg[2] = F64Deriv::cnst(0.5);
+        vm.eval(&code);
+        assert_eq!(
+            vm.reg[1],
+            F64Deriv {
+                val: 8.5,
+                grad: 12.0
+            }
+        );
+
+        let mut vm = VM::with_instr_table(RangeInstrTable);
+        vm.reg[1] = Range::new(-1.0, 2.0);
+        vm.reg[2] = Range::new(0.0, 1.0);
+        vm.eval(&code);
+        assert_eq!((vm.reg[1].l, vm.reg[1].u), (-1.0, 9.0));
+
+        // without a derivative enclosure the gradient is unbounded
+        let mut vm = VM::with_instr_table(RangeDerivInstrTable);
+        vm.reg[1] = RangeDeriv::var(Range::new(1.0, 2.0));
+        vm.reg[2] = RangeDeriv::cnst(Range::new(0.0, 1.0));
+        vm.eval(&code);
+        assert_eq!((vm.reg[1].val.l, vm.reg[1].val.u), (1.0, 9.0));
+        assert!(vm.reg[1].grad.u.is_infinite());
+
+        let text = asm::to_asm(&code, &utils::asm::Labels::default());
+        assert!(text.starts_with("CALL_vm_test_cube[1] -> 3"), "{text}");
+        assert_eq!(asm::from_asm(&text).unwrap(), code);
+
+        let err = |src| asm::from_asm(src).unwrap_err().msg;
+        assert_eq!(
+            err("CALL_vm_test_none[1] -> 2"),
+            "unknown native `vm_test_none`"
+        );
+        assert_eq!(
+            err("CALL_vm_test_cube[1_f] -> 2"),
+            "expected `CALL_vm_test_cube[reg] -> dst`"
+        );
+        assert_eq!(err("CALL[1] -> 2"), "unknown instruction `CALL`");
+
+        assert_eq!(
+            verify(&[op::CALL(cube, 0, 1), op::EXT(0)]),
+            Err(VerifyError::CallImmediate { pc: 0 })
+        );
+        let unknown = op::build_opcode(op::OP_CALL, 1, 0, 1, u32::MAX);
+        assert_eq!(
+            verify(&[unknown, op::EXT(0)]),
+            Err(VerifyError::UnknownNative {
+                pc: 0,
+                id: u32::MAX
+            })
+        );
+
+        // constant arguments are moved into the destination
+        #[cfg(feature = "native-codegen")]
+        {
+            use compiler::jit::{Instr, Oprnd, Program, UnOp};
+
+            let (program, _) = opt::to_bytecode(&code).unwrap();
+            assert_eq!(opt::from_bytecode(&program, false).unwrap(), code);
+
+            let call = Instr::UnOp {
+                op: UnOp::CALL(cube),
+                val: Oprnd::Imm(2.0),
+                dst: 0,
+            };
+            let ops = opt::from_bytecode(&Program::from(vec![call]), false).unwrap();
+            assert_eq!(ops, [op::MOV_IMM(2.0, 1), op::CALL(cube, 1, 1), op::EXT(0)]);
+            assert_eq!(
+                VM::with_instr_table(F64InstrTable).call([0.0, 0.0], &ops),
+                [8.0]
+            );
+        }
+    }
+
+    #[test]
+    fn complex() {
+        // f(z) = z^2 + 1, roots at +-i
+        let code = [
+            op::POW_REG_IMM(1, 2.0, 1),
+            op::ADD_REG_IMM(1, 1.0, 1),
+            op::EXT(0),
+        ];
+
+        let mut vm = VM::with_instr_table(ComplexInstrTable);
+        vm.reg[1] = Complex::I;
+        vm.eval(&code);
+        assert_eq!(vm.reg[1], Complex::ZERO);
+
+        vm.reg[1] = Complex::new(1.0, 1.0);
+        vm.eval(&code);
+        assert_eq!(vm.reg[1], Complex::new(1.0, 2.0));
+
+        // e^(i pi) = -1, immediates are stored as f32
+        let code = [op::POW_IMM_REG(std::f64::consts::E, 1, 1), op::EXT(0)];
+        vm.reg[1] = Complex::new(0.0, std::f64::consts::PI);
+        vm.eval(&code);
+        assert!(vm.reg[1].sub(Complex::real(-1.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn multiple_outputs() {
+        // (x + y, x * y, 2)
+        let code = [
+            op::ADD_REG_REG(1, 2, 3),
+            op::MUL_REG_REG(1, 2, 4),
+            op::RET(3),
+            op::RET(4),
+            op::RET_IMM(2.0),
+            op::EXT(0),
+        ];
+
+        let mut vm = VM::with_instr_table(F64InstrTable);
+        assert_eq!(vm.call([2.0, 3.0], &code), [5.0, 6.0, 2.0]);
+        assert_eq!(vm.call([1.0, -1.0], &code), [0.0, -1.0, 2.0]);
+
+        // without RET the result is reg[1]
+        let code = [op::ADD_REG_REG(1, 2, 1), op::EXT(0)];
+        assert_eq!(vm.call([2.0, 3.0], &code), [5.0]);
+    }
+
+    #[cfg(feature = "native-codegen")]
+    #[test]
+    fn optimize() {
+        use crate::field::VectorProgram;
+        use crate::iso::Program;
+        use rand::{Rng, SeedableRng, rngs::StdRng};
+
+        let mut programs: Vec<_> = [
+            Program::X,
+            Program::XY,
+            Program::X2X,
+            Program::Union,
+            Program::Sin,
+            Program::OneDivX,
+            Program::Cos1DivX,
+            Program::Dense1,
+            Program::Dense2,
+            Program::Dense3,
+            Program::Piecewise,
+            Program::Floor,
+            Program::Gamma,
+        ]
+        .map(|p| p.opcode())
+        .to_vec();
+        programs.extend([VectorProgram::Rotation, VectorProgram::VanDerPol].map(|p| p.opcode()));
+        programs.push(vec![
+            op::ADD_IMM_IMM(2.0, 3),
+            op::POW_REG_IMM(1, 2.0, 4),
+            op::MUL_REG_REG(3, 4, 1),
+            op::MUL_REG_IMM(2, 1.0, 2),
+            op::ADD_REG_REG(1, 2, 1),
+            op::EXT(0),
+        ]);
+
+        // seeded, so a failure can be reproduced
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        let mut vm = VM::with_instr_table(F64InstrTable);
+        for code in programs {
+            let opt = opt::optimize(&code);
+            assert!(opt.len() <= code.len());
+
+            for _ in 0..16 {
+                let args = [rng.random_range(-3.0..3.0), rng.random_range(-3.0..3.0)];
+                let expected = vm.call(args, &code).to_vec();
+                let res = vm.call(args, &opt).to_vec();
+                for (a, b) in expected.iter().zip(&res) {
+                    assert!((a - b).abs() <= 1e-9 * a.abs().max(1.0), "{a} != {b}");
+                }
+            }
+        }
+
+        // sin(1/x) is computed once
+        let dense3 = opt::optimize(&Program::Dense3.opcode());
+        assert_eq!(dense3.len(), Program::Dense3.opcode().len() - 1);
+
+        // PSH / POP are not optimized
+        let code = [op::PSH(1), op::POP(2), op::EXT(0)];
+        assert_eq!(opt::optimize(&code), code);
+
+        // sin(1) is not exact as f32 and is kept in the pool
+        let cnst = 1.0f64.sin();
+        let code = [
+            op::SIN_IMM(1.0, 3),
+            op::MOV(3, 4),
+            op::ADD_REG_REG(1, 3, 1),
+            op::MUL_REG_REG(1, 4, 1),
+            op::EXT(0),
+        ];
+        let opt = opt::optimize(&code);
+        assert_eq!(verify(&opt), Ok(()));
+        assert!(opt.iter().any(|&o| op::get_op(o) == op::OP_LIT));
+        assert_eq!(opt.last(), Some(&cnst.to_bits()), "{opt:x?}");
+        let res = vm.call([2.0, 0.0], &opt)[0];
+        assert_eq!(res.to_bits(), ((2.0 + cnst) * cnst).to_bits());
+        // the pool survives a second round
+        assert_eq!(opt::optimize(&opt), opt);
+    }
+
+    #[test]
+    fn verify_bytecode() {
+        use crate::iso::Program;
+
+        for program in [Program::OneDivX, Program::Dense1, Program::Dense3] {
+            assert_eq!(verify(&program.opcode()), Ok(()));
+        }
+
+        let push_pop = [
+            op::PSH(1),
+            op::PSH_IMM(2.0),
+            op::POP(2),
+            op::POP(3),
+            op::EXT(0),
+        ];
+        assert_eq!(verify(&push_pop), Ok(()));
+        // instructions after EXT are not executed
+        assert_eq!(verify(&[op::EXT(0), op::POP(1)]), Ok(()));
+
+        let cases = [
+            (vec![op::ADD_REG_REG(1, 2, 1)], VerifyError::MissingExt),
+            (
+                vec![op::SIN(1, 1), op::MUL_REG_REG(1, 16, 1), op::EXT(0)],
+                VerifyError::RegisterOutOfBounds { pc: 1, reg: 16 },
+            ),
+            (
+                vec![op::COS(1, 200), op::EXT(0)],
+                VerifyError::RegisterOutOfBounds { pc: 0, reg: 200 },
+            ),
+            (
+                vec![op::PSH(1), op::POP(1), op::POP(1), op::EXT(0)],
+                VerifyError::StackUnderflow { pc: 2 },
+            ),
+            (
+                vec![op::PSH(1), op::PSH(2), op::POP(1), op::EXT(0)],
+                VerifyError::UnbalancedStack { pc: 3, depth: 1 },
+            ),
+            (
+                vec![op::MOV(1, 2), 0xff, op::EXT(0)],
+                VerifyError::UnknownOpcode { pc: 1, op: 0xff },
+            ),
+            (
+                vec![op::LIT(0, 1), op::LIT(1, 2), op::EXT(0), 0],
+                VerifyError::UnknownConstant { pc: 1, index: 1 },
+            ),
+        ];
+        for (code, err) in cases {
+            assert_eq!(verify(&code), Err(err));
+            let mut vm = VM::with_instr_table(F64InstrTable);
+            assert_eq!(vm.try_eval(&code), Err(err));
+            assert!(vm.try_call([1.0, 2.0], &code).is_err());
+        }
+
+        let stack = |pushes, pops| {
+            let code = std::iter::repeat_n(op::PSH(1), pushes)
+                .chain(std::iter::repeat_n(op::POP(1), pops))
+                .chain([op::EXT(0)]);
+            code.collect::<Vec<_>>()
+        };
+        assert_eq!(
+            verify(&stack(STACK_SIZE, STACK_SIZE)),
+            Err(VerifyError::StackOverflow { pc: STACK_SIZE - 1 })
+        );
+        // the largest stack the vm supports
+        let mut vm = VM::with_instr_table(F64InstrTable);
+        assert_eq!(vm.try_eval(&stack(STACK_SIZE - 1, STACK_SIZE - 1)), Ok(()));
+        assert_eq!(
+            verify(&stack(STACK_SIZE - 1, STACK_SIZE - 2)),
+            Err(VerifyError::UnbalancedStack {
+                pc: 2 * STACK_SIZE - 3,
+                depth: 1
+            })
+        );
+
+        assert_eq!(
+            vm.try_call([2.0, 3.0], &Program::XY.opcode()),
+            Ok(&[6.0][..])
+        );
+    }
+
+    #[test]
+    fn step() {
+        let code = [
+            op::LIT(0, 3),
+            op::MUL_REG_REG(1, 3, 1),
+            op::RET(1),
+            op::EXT(0),
+            0.1f64.to_bits(),
+        ];
+        let mut vm = VM::with_instr_table(F64InstrTable);
+        vm.reg[1] = 3.0;
+        let mut pcs = vec![];
+        let mut pc = Some(0);
+        while let Some(i) = pc {
+            pcs.push(i);
+            pc = vm.step(&code, i);
+        }
+        assert_eq!(pcs, [0, 1, 2, 3]);
+        assert_eq!(vm.out, [3.0 * 0.1]);
+        assert_eq!(vm.call([3.0], &code), [3.0 * 0.1]);
+    }
+
+    #[test]
+    fn asm_round_trip() {
+        use crate::iso::Program;
+        use utils::asm::Labels;
+
+        let fixture = asm::from_asm(include_str!("../fixtures/dense2.asm")).unwrap();
+        assert_eq!(fixture, Program::Dense2.opcode());
+
+        let labels = Labels::new([("x", 1), ("y", 2)]);
+        for program in [
+            Program::Dense1,
+            Program::Dense2,
+            Program::Dense3,
+            Program::XY,
+        ] {
+            let bin = program.opcode();
+            for labels in [&Labels::default(), &labels] {
+                let text = asm::to_asm(&bin, labels);
+                assert_eq!(asm::from_asm(&text).unwrap(), bin, "{text}");
+            }
+        }
+
+        let bin = [
+            op::PSH_IMM(0.1),
+            op::POP(3),
+            op::MAX_IMM_IMM(-2.5, 4),
+            op::MOV_IMM(f32::INFINITY as f64, 5),
+            op::RET(4),
+            op::OUT(3),
+            0,
+            op::EXT(7),
+        ];
+        let text = asm::to_asm(&bin, &labels);
+        assert_eq!(
+            text,
+            "reg x = 1\nreg y = 2\n\
+             PSH[0.1_f]\n\
+             POP -> 3\n\
+             MAX[-2.5_f, -2.5_f] -> 4\n\
+             MOV[inf_f] -> 5\n\
+             RET[4]\n\
+             OUT[3]\n\
+             NOP\n\
+             EXT[7]\n"
+        );
+        assert_eq!(asm::from_asm(&text).unwrap(), bin);
+
+        // the pool is written as LIT operands
+        let bin = [
+            op::LIT(0, 1),
+            op::LIT(1, 2),
+            op::LIT(0, 3),
+            op::EXT(0),
+            1e300f64.to_bits(),
+            0.1f64.to_bits(),
+        ];
+        let text = asm::to_asm(&bin, &Labels::default());
+        assert_eq!(
+            text,
+            "LIT[0.1_f] -> 1\nLIT[1e300_f] -> 2\nLIT[0.1_f] -> 3\nEXT\n"
+        );
+        assert_eq!(asm::from_asm(&text).unwrap(), bin);
+
+        let err = |src| asm::from_asm(src).unwrap_err().msg;
+        assert_eq!(
+            err("ADD[0, 1] -> 1"),
+            "register 0 can't be read, use an immediate"
+        );
+        assert_eq!(err("ADD[1_f, 2_f] -> 1"), "operands share one immediate");
+        assert_eq!(err("SIN[1] -> 300"), "register 300 out of range");
+        assert_eq!(err("PSH[1] -> 2"), "PSH has no destination");
+        assert_eq!(err("POP[1] -> 2"), "wrong operand count for POP");
+        assert_eq!(err("ret 1"), "vm programs have no directives");
+    }
 }
@@ -0,0 +1,440 @@
+//! records every instruction a [`VM`] executes, see [`trace`]
+//!
+//! there is no tracing `InstrTable` wrapper, [`trace`] drives the vm with [`VM::step`] and reads
+//! the registers between instructions, so it works with any instruction table. which registers
+//! an instruction reads comes from [`op::operands`], the same table [`vm::verify`] checks.
+//! used to find where an interval evaluation widens to INF / UNDEF. traces can be exported
+//! with [`to_csv`] and [`to_json`] or inspected in the settings panel by middle clicking a cell
+
+use std::{fmt::Write as _, io, path::Path};
+
+use egui_probe::EguiProbe;
+use glam::DVec2;
+
+use crate::vm::{self, Opcode, Range, VM, VerifyError, VmWord, op};
+use utils::Intrvl;
+
+/// word types whose values can be traced
+pub trait TraceWord: VmWord {
+    /// lower and upper bound, scalars return (v, v)
+    fn bounds(&self) -> (f64, f64);
+
+    /// the value was widened to INF or is UNDEF
+    fn is_degenerate(&self) -> bool {
+        let (l, u) = self.bounds();
+        !(l.is_finite() && u.is_finite())
+    }
+}
+
+impl TraceWord for f64 {
+    fn bounds(&self) -> (f64, f64) {
+        (*self, *self)
+    }
+}
+
+impl TraceWord for Range {
+    fn bounds(&self) -> (f64, f64) {
+        (self.l, self.u)
+    }
+}
+
+impl TraceWord for Intrvl {
+    fn bounds(&self) -> (f64, f64) {
+        (self.lo, self.hi)
+    }
+}
+
+/// one executed instruction
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceStep<WORD> {
+    pub pc: usize,
+    pub opcode: Opcode,
+    pub inputs: Vec<WORD>,
+    /// the written register, None for instructions like PSH that don't write one
+    pub out: Option<WORD>,
+}
+
+impl<WORD: TraceWord> TraceStep<WORD> {
+    /// the output is degenerate but none of the inputs are
+    pub fn widens(&self) -> bool {
+        self.out.as_ref().is_some_and(|o| o.is_degenerate())
+            && !self.inputs.iter().any(|i| i.is_degenerate())
+    }
+}
+
+/// evaluates `bin` like [`VM::try_eval`] one instruction at a time with [`VM::step`] and
+/// records every executed instruction
+pub fn trace<WORD: TraceWord>(
+    vm: &mut VM<WORD>,
+    bin: &[Opcode],
+) -> Result<Vec<TraceStep<WORD>>, VerifyError> {
+    vm::verify(bin)?;
+    vm.sp = 0;
+    vm.out.clear();
+
+    let mut trace = vec![];
+    let mut pc = 0;
+    loop {
+        let opcode = bin[pc];
+        let (op, l, r, out, imm) = op::decode(opcode);
+        if op == op::OP_EXT {
+            break;
+        }
+
+        // the fields were checked by verify
+        let (reads, writes) = op::operands(op).expect("unknown opcode, see verify");
+        let mut inputs: Vec<_> = reads
+            .iter()
+            .map(|field| match (field, l, r) {
+                (op::Field::Lhs, 0, _) | (op::Field::Rhs, _, 0) => WORD::from_imm(imm),
+                (op::Field::Lhs, l, _) => vm.reg[l].clone(),
+                (op::Field::Rhs, _, r) => vm.reg[r].clone(),
+                (op::Field::Out, ..) => vm.reg[out].clone(),
+            })
+            .collect();
+        if op == op::OP_LIT {
+            inputs.extend(op::constant(bin, imm).map(WORD::from_float));
+        }
+
+        let next = vm.step(bin, pc);
+        trace.push(TraceStep {
+            pc,
+            opcode,
+            inputs,
+            out: writes.then(|| vm.reg[out].clone()),
+        });
+
+        match next {
+            Some(next) => pc = next,
+            None => break,
+        }
+    }
+    Ok(trace)
+}
+
+/// index of the first step that widened its inputs to INF / UNDEF
+pub fn first_widening<WORD: TraceWord>(trace: &[TraceStep<WORD>]) -> Option<usize> {
+    trace.iter().position(|s| s.widens())
+}
+
+/// one row per step: `pc,instr,in0_lo,in0_hi,in1_lo,in1_hi,out_lo,out_hi,widens`, the
+/// condition of SEL is only part of the json export
+pub fn to_csv<WORD: TraceWord>(trace: &[TraceStep<WORD>]) -> String {
+    let mut csv = String::from("pc,instr,in0_lo,in0_hi,in1_lo,in1_hi,out_lo,out_hi,widens\n");
+    let first = first_widening(trace);
+
+    for (i, s) in trace.iter().enumerate() {
+        let mut bounds = s
+            .inputs
+            .iter()
+            .map(|w| Some(w.bounds()))
+            .collect::<Vec<_>>();
+        bounds.resize(2, None);
+        bounds.push(s.out.as_ref().map(|w| w.bounds()));
+
+        write!(csv, "{},\"{}\"", s.pc, vm::instr_to_str(s.opcode)).unwrap();
+        for b in bounds {
+            match b {
+                Some((l, u)) => write!(csv, ",{l},{u}").unwrap(),
+                None => csv.push_str(",,"),
+            }
+        }
+        writeln!(csv, ",{}", first == Some(i)).unwrap();
+    }
+
+    csv
+}
+
+/// json numbers can't be inf or nan, those are written as strings
+fn json_num(f: f64) -> String {
+    if f.is_finite() {
+        format!("{f:?}")
+    } else {
+        format!("\"{f}\"")
+    }
+}
+
+fn json_bounds<WORD: TraceWord>(w: &WORD) -> String {
+    let (l, u) = w.bounds();
+    format!("[{}, {}]", json_num(l), json_num(u))
+}
+
+/// `{"first_widening": i | null, "steps": [{"pc", "instr", "inputs", "out"}]}`
+pub fn to_json<WORD: TraceWord>(trace: &[TraceStep<WORD>]) -> String {
+    let first = first_widening(trace).map_or("null".into(), |i| i.to_string());
+    let steps: Vec<_> = trace
+        .iter()
+        .map(|s| {
+            let inputs: Vec<_> = s.inputs.iter().map(json_bounds).collect();
+            format!(
+                "{{\"pc\": {}, \"instr\": \"{}\", \"inputs\": [{}], \"out\": {}}}",
+                s.pc,
+                vm::instr_to_str(s.opcode),
+                inputs.join(", "),
+                s.out.as_ref().map_or("null".into(), json_bounds),
+            )
+        })
+        .collect();
+
+    format!(
+        "{{\"first_widening\": {first}, \"steps\": [\n  {}\n]}}\n",
+        steps.join(",\n  ")
+    )
+}
+
+/// traces the range evaluation of `ops` over the cell [min, max]
+pub fn trace_cell(
+    ops: &[Opcode],
+    min: DVec2,
+    max: DVec2,
+) -> Result<Vec<TraceStep<Range>>, VerifyError> {
+    let mut vm = VM::with_instr_table(vm::RangeInstrTable);
+    vm.reg[1] = (min.x, max.x).into();
+    vm.reg[2] = (min.y, max.y).into();
+    vm.reg[3] = (0.0, 0.0).into();
+    trace(&mut vm, ops)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, EguiProbe)]
+pub struct VmTraceConfig {
+    /// middle click a cell of the interval grid to trace its evaluation
+    #[egui_probe(toggle_switch)]
+    pub enabled: bool,
+
+    /// the traced cell
+    #[egui_probe(skip)]
+    pub cell: Option<(DVec2, DVec2)>,
+    #[egui_probe(skip)]
+    pub trace: Vec<TraceStep<Range>>,
+    /// the traced program was rejected by [`vm::verify`]
+    #[egui_probe(skip)]
+    pub error: Option<VerifyError>,
+}
+
+impl VmTraceConfig {
+    /// traces the cell of a `2^depth x 2^depth` grid over [min, max] containing p
+    pub fn select(&mut self, ops: &[Opcode], min: DVec2, max: DVec2, depth: u32, p: DVec2) {
+        let size = (max - min) / 2f64.powi(depth as i32);
+        let ij = ((p - min) / size).floor();
+        let cell_min = min + ij * size;
+        let cell_max = cell_min + size;
+
+        (self.trace, self.error) = match trace_cell(ops, cell_min, cell_max) {
+            Ok(trace) => (trace, None),
+            Err(err) => (vec![], Some(err)),
+        };
+        self.cell = Some((cell_min, cell_max));
+    }
+
+    /// writes the trace as csv if `path` ends in `.csv`, as json otherwise
+    pub fn export(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let content = match path.extension().and_then(|e| e.to_str()) {
+            Some("csv") => to_csv(&self.trace),
+            _ => to_json(&self.trace),
+        };
+        std::fs::write(path, content)
+    }
+
+    /// table of the traced steps, the first widening step is highlighted
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        let Some((min, max)) = self.cell else {
+            ui.weak("middle click a cell to trace it");
+            return;
+        };
+        ui.monospace(format!(
+            "cell [{:.4}, {:.4}] x [{:.4}, {:.4}]",
+            min.x, max.x, min.y, max.y
+        ));
+        if let Some(err) = self.error {
+            ui.colored_label(egui::Color32::from_rgb(243, 139, 168), err.to_string());
+        }
+
+        let first = first_widening(&self.trace);
+        let widen_col = egui::Color32::from_rgb(243, 139, 168);
+        egui::ScrollArea::vertical()
+            .max_height(300.0)
+            .show(ui, |ui| {
+                egui::Grid::new("vm_trace").striped(true).show(ui, |ui| {
+                    for (i, s) in self.trace.iter().enumerate() {
+                        let inputs: Vec<_> = s.inputs.iter().map(|w| w.to_string()).collect();
+                        let out = s.out.map_or(String::new(), |w| w.to_string());
+                        let row = [
+                            vm::instr_to_str(s.opcode),
+                            inputs.join(" "),
+                            format!("-> {out}"),
+                        ];
+                        for text in row {
+                            let text = egui::RichText::new(text).monospace();
+                            ui.label(if first == Some(i) {
+                                text.color(widen_col)
+                            } else {
+                                text
+                            });
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn trace_widening() {
+        // (x * y) / (x - 1) + sin(y)
+        let code = [
+            op::MUL_REG_REG(1, 2, 3),
+            op::SUB_REG_IMM(1, 1.0, 4),
+            op::DIV_REG_REG(3, 4, 3),
+            op::SIN(2, 2),
+            op::ADD_REG_REG(3, 2, 1),
+            op::EXT(0),
+        ];
+
+        let mut plain = VM::with_instr_table(vm::RangeInstrTable);
+        let mut traced = VM::with_instr_table(vm::RangeInstrTable);
+        for vm in [&mut plain, &mut traced] {
+            vm.reg[1] = (0.5, 2.0).into();
+            vm.reg[2] = (1.0, 3.0).into();
+        }
+        plain.eval(&code);
+        let trace = &trace(&mut traced, &code).unwrap();
+        // the division is UNDEF, compare the debug output since nan != nan
+        assert_eq!(
+            format!("{:?}", &plain.reg[1..5]),
+            format!("{:?}", &traced.reg[1..5])
+        );
+
+        assert_eq!(trace.len(), 5);
+        assert_eq!(trace[0].inputs, [(0.5, 2.0).into(), (1.0, 3.0).into()]);
+        assert_eq!(trace[0].out, Some((0.5, 6.0).into()));
+        assert_eq!(trace[1].inputs, [(0.5, 2.0).into(), Range::imm(1.0)]);
+        // x - 1 contains zero
+        assert_eq!(first_widening(trace), Some(2));
+
+        let csv = to_csv(trace);
+        assert_eq!(csv.lines().count(), 6);
+        assert!(csv.lines().nth(3).unwrap().ends_with(",true"));
+        let json = to_json(trace);
+        assert!(json.starts_with("{\"first_widening\": 2,"));
+        assert!(json.contains("\"NaN\""));
+
+        traced.reg[1] = (2.0, 3.0).into();
+        traced.reg[2] = (1.0, 3.0).into();
+        let trace = super::trace(&mut traced, &code).unwrap();
+        assert_eq!(trace.len(), 5);
+        assert_eq!(first_widening(&trace), None);
+    }
+
+    #[test]
+    fn trace_f64() {
+        let code = [
+            op::DIV_IMM_REG(1.0, 1, 1),
+            op::MUL_REG_IMM(1, 2.0, 1),
+            op::EXT(0),
+        ];
+        let mut vm = VM::with_instr_table(vm::F64InstrTable);
+        vm.reg[1] = 0.0;
+        let trace = trace(&mut vm, &code).unwrap();
+        assert_eq!(vm.reg[1], f64::INFINITY);
+        assert_eq!(first_widening(&trace), Some(0));
+    }
+
+    #[test]
+    fn trace_operands() {
+        use utils::native::{self, NativeFn};
+
+        let sqr = native::register(NativeFn::new(
+            "vm_trace_test_sqr",
+            |x| x * x,
+            |x| 2.0 * x,
+            |lo, hi| (0.0, lo.abs().max(hi.abs()).powi(2)),
+        ));
+        for op in 0..op::NUM_OPS as u8 {
+            let (reads, _) = op::operands(op).unwrap();
+            if reads.is_empty() {
+                continue;
+            }
+            // registers and the immediate in both operands
+            for (l, r) in [(1, 2), (0, 2), (1, 0)] {
+                let instr = match op {
+                    op::OP_CALL if l == 0 => continue,
+                    op::OP_CALL => op::CALL(sqr, l, 3),
+                    _ => op::build_opcode_float(op, l, r, 3, 0.5),
+                };
+                let code = match op {
+                    op::OP_PSH => vec![instr, op::POP(4), op::EXT(0)],
+                    _ => vec![instr, op::EXT(0)],
+                };
+                let read: Vec<usize> = reads
+                    .iter()
+                    .map(|field| match field {
+                        op::Field::Lhs => l as usize,
+                        op::Field::Rhs => r as usize,
+                        op::Field::Out => 3,
+                    })
+                    .collect();
+
+                // the vm must give the same result whatever the registers that are not
+                // inputs hold, 0 flips the condition of SEL
+                let run = |poison: Option<f64>| {
+                    let mut vm = VM::with_instr_table(vm::F64InstrTable);
+                    for reg in 1..5 {
+                        vm.reg[reg] = match poison {
+                            Some(v) if !read.contains(&reg) => v,
+                            _ => 0.25 * reg as f64,
+                        };
+                    }
+                    let trace = trace(&mut vm, &code).unwrap();
+                    let expected: Vec<_> = read
+                        .iter()
+                        .map(|&reg| if reg == 0 { 0.5 } else { 0.25 * reg as f64 })
+                        .collect();
+                    let name = op::op_to_str(op);
+                    assert_eq!(trace[0].inputs, expected, "{name} {l} {r}");
+                    format!("{:?}", (&trace.last().unwrap().out, &vm.out))
+                };
+                for poison in [f64::NAN, 0.0, -3.0] {
+                    let name = op::op_to_str(op);
+                    assert_eq!(run(None), run(Some(poison)), "{name} {l} {r}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn trace_unverified() {
+        let mut vm = VM::with_instr_table(vm::F64InstrTable);
+        let code = [op::ADD_REG_REG(1, 2, 1)];
+        assert_eq!(trace(&mut vm, &code), Err(VerifyError::MissingExt));
+        assert_eq!(trace(&mut vm, &[]), Err(VerifyError::MissingExt));
+
+        let mut config = VmTraceConfig::default();
+        config.select(&code, DVec2::splat(-1.0), DVec2::splat(1.0), 0, DVec2::ZERO);
+        assert!(config.trace.is_empty());
+        assert_eq!(config.error, Some(VerifyError::MissingExt));
+    }
+
+    #[test]
+    fn export() {
+        let mut config = VmTraceConfig::default();
+        let ops = [op::DIV_IMM_REG(1.0, 1, 1), op::EXT(0)];
+        config.select(&ops, DVec2::splat(-1.0), DVec2::splat(1.0), 0, DVec2::ZERO);
+
+        let dir = std::env::temp_dir();
+        let id = std::process::id();
+        for (ext, expected) in [
+            ("csv", to_csv(&config.trace)),
+            ("json", to_json(&config.trace)),
+        ] {
+            let path = dir.join(format!("atlas_trace_{id}.{ext}"));
+            config.export(&path).unwrap();
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), expected);
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! draws the zero set of the 2d program on the gpu with the shader from [`compiler::wgsl`]
+use bytemuck::Zeroable;
+use wgpu::util::DeviceExt;
+
+use crate::{iso::Iso2DConfig, vm};
+
+/// matches `View` in [`compiler::wgsl::fragment_shader`]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct View {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+    pub color: [f32; 4],
+    /// line thickness in pixels
+    pub thickness: f32,
+    pub _pad: [f32; 3],
+}
+
+pub struct Pipeline {
+    /// the pipeline of the bytecode the shader was generated for
+    pipeline: Option<(Vec<vm::Opcode>, wgpu::RenderPipeline)>,
+    layout: wgpu::BindGroupLayout,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pub visible: bool,
+}
+
+impl Pipeline {
+    pub fn init(wgpu: &crate::WGPU) -> Self {
+        let layout = wgpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("zero_set_bind_group_layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
+        let buffer = wgpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("zero_set_view_buffer"),
+                contents: bytemuck::bytes_of(&View::zeroed()),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+
+        let bind_group = wgpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("zero_set_bind_group"),
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            pipeline: None,
+            layout,
+            buffer,
+            bind_group,
+            visible: false,
+        }
+    }
+
+    /// regenerates the shader when the program changed and writes the visible region
+    ///
+    /// without native-codegen there is no shader and nothing is drawn
+    pub fn update(&mut self, wgpu: &crate::WGPU, config: &Iso2DConfig, color: glam::Vec4) {
+        if !self.visible {
+            return;
+        }
+
+        #[cfg(feature = "native-codegen")]
+        {
+            let opcode = config.opcode();
+            if self.pipeline.as_ref().is_none_or(|(o, _)| *o != opcode) {
+                let program = compiler::jit::Program::from(config.bytecode());
+                let src = compiler::wgsl::fragment_shader(&program);
+                let pipeline = load_pipeline(wgpu, &self.layout, &src);
+                self.pipeline = Some((opcode, pipeline));
+            }
+        }
+
+        let view = View {
+            min: config.min.as_vec2().into(),
+            max: config.max.as_vec2().into(),
+            color: color.into(),
+            thickness: config.line_thickness,
+            _pad: [0.0; 3],
+        };
+        wgpu.queue
+            .write_buffer(&self.buffer, 0, bytemuck::bytes_of(&view));
+    }
+
+    /// draws the zero set over the whole viewport
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass) {
+        let Some((_, pipeline)) = &self.pipeline else {
+            return;
+        };
+        if !self.visible {
+            return;
+        }
+
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+pub fn load_pipeline(
+    wgpu: &crate::WGPU,
+    layout: &wgpu::BindGroupLayout,
+    src: &str,
+) -> wgpu::RenderPipeline {
+    let shader_module = wgpu
+        .device
+        .create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("zero_set"),
+            source: wgpu::ShaderSource::Wgsl(src.into()),
+        });
+
+    let pipeline_layout = wgpu
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("zero_set_pipeline_layout"),
+            bind_group_layouts: &[layout],
+            push_constant_ranges: &[],
+        });
+
+    wgpu.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("zero_set_pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu.surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: None,
+                unclipped_depth: false,
+                front_face: wgpu::FrontFace::Ccw,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                strip_index_format: None,
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: crate::AtlasRenderer::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: crate::multisample_state(),
+            multiview: None,
+            cache: None,
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn view_layout() {
+        // vec4 alignment puts color at 16 and rounds the struct up to 48 bytes
+        assert_eq!(std::mem::offset_of!(View, color), 16);
+        assert_eq!(std::mem::offset_of!(View, thickness), 32);
+        assert_eq!(std::mem::size_of::<View>(), 48);
+    }
+}
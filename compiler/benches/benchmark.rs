@@ -1,6 +1,6 @@
 use compiler::{
     jit,
-    jit2::{self, F64X2, Intrvl},
+    jit2::{self, Intrvl},
 };
 
 use criterion::{BenchmarkId, Criterion, Throughput, black_box, criterion_group, criterion_main};
@@ -24,15 +24,13 @@ fn jit_native_vs_interpreter(c: &mut Criterion) {
 
     let jit = jit2::JIT::init();
 
-    let f1_intrvl = jit.compile_2intrvl_intrvl("f_intrvl", &prgrm);
+    let f1_intrvl = jit.compile::<Intrvl>("f_intrvl", &jit::Program::from(prgrm.to_vec()), 2);
 
-    let intrvl_x = F64X2(-0.1, 0.1);
-    let intrvl_y = F64X2(-0.1, 0.1);
-
-    let mut out = F64X2::ZERO;
+    let intrvl_x = Intrvl::new(-0.1, 0.1);
+    let intrvl_y = Intrvl::new(-0.1, 0.1);
 
     c.bench_function("intrvl_native", |b| {
-        b.iter(|| f1_intrvl(intrvl_x, intrvl_y, &mut out))
+        b.iter(|| f1_intrvl.eval(&[intrvl_x, intrvl_y]))
     });
     c.bench_function("intrvl_interpreter", |b| b.iter(|| f1_intrvl()));
 
@@ -0,0 +1,499 @@
+//! ahead of time compilation
+//!
+//! compiles programs with the code generator of [`crate::jit2::JIT`] into a relocatable object
+//! file, so hosts can link precompiled evaluators without cranelift at runtime:
+//! - word functions `void name(const W *args, W *out)`, see [`crate::jit2::Func`]
+//! - row kernels `void name(const row_params_t *params, double *out, uint64_t n)`, see
+//!   [`crate::jit2::RowFunc`]
+//!
+//! [`AotObject::header`] declares the exported symbols in c. compiled code may call runtime
+//! functions like `sin_f64`, these are undefined in the object and listed at the end of the
+//! header. with [`MathImpl::Inline`] f64 and f64xN functions don't call into the runtime.
+//! programs calling [`utils::native`] functions are refused, the jit calls them by their
+//! address in the compiling process, which is meaningless in any other.
+
+use std::{fmt::Write as _, io, path::Path, process};
+
+use cranelift_codegen::{ir, isa::OwnedTargetIsa};
+use cranelift_module::{FuncId, Linkage, Module};
+use cranelift_object::{ObjectBuilder, ObjectModule};
+use rustc_hash::FxHashMap;
+
+use crate::{
+    jit::{Instr, Program, UnOp},
+    jit2::{self, FnParam, JIT, Kernel, Lanes, MathImpl, Signature, Word, WordKind},
+};
+
+/// function exported by an [`AotObject`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub sig: Signature,
+    pub kernel: Kernel,
+    /// runtime functions called by the symbol
+    pub imports: Vec<&'static str>,
+}
+
+/// collects compiled functions into one object, see [`Aot::finish`]
+pub struct Aot {
+    module: ObjectModule,
+    /// declares all runtime functions, calls are moved to imports of `module` so unused runtime
+    /// functions don't end up as undefined symbols in the object
+    scratch: ObjectModule,
+    ctx: cranelift_codegen::Context,
+    glob_fns: FxHashMap<&'static str, FuncId>,
+    triple: String,
+    symbols: Vec<Symbol>,
+
+    pub math: MathImpl,
+}
+
+impl Aot {
+    /// compiles for the host machine
+    pub fn new(name: &str) -> Self {
+        let isa_builder = cranelift_native::builder().unwrap_or_else(|msg| {
+            panic!("host machine is not supported: {}", msg);
+        });
+        Self::with_isa(name, isa_builder.finish(JIT::flags(true)).unwrap())
+    }
+
+    /// compiles for `triple`, e.g. `aarch64-unknown-linux-gnu`
+    pub fn for_target(name: &str, triple: &str) -> anyhow::Result<Self> {
+        let isa_builder = cranelift_codegen::isa::lookup_by_name(triple)?;
+        Ok(Self::with_isa(name, isa_builder.finish(JIT::flags(true))?))
+    }
+
+    fn with_isa(name: &str, isa: OwnedTargetIsa) -> Self {
+        let triple = isa.triple().to_string();
+        let new_module = |isa| {
+            let builder =
+                ObjectBuilder::new(isa, name, cranelift_module::default_libcall_names()).unwrap();
+            ObjectModule::new(builder)
+        };
+        let module = new_module(isa.clone());
+        let mut scratch = new_module(isa);
+        let glob_fns = JIT::decl_glob_functions(&mut scratch);
+
+        Self {
+            ctx: module.make_context(),
+            module,
+            scratch,
+            glob_fns,
+            triple,
+            symbols: vec![],
+            math: MathImpl::default(),
+        }
+    }
+
+    /// exports `program` for `W` as `name`, taking the first `arity` registers as arguments
+    pub fn add<W: Word>(
+        &mut self,
+        name: &str,
+        program: &Program,
+        arity: usize,
+    ) -> anyhow::Result<()> {
+        let sig = Signature::words(W::KIND, program, arity);
+        self.define(name, program, sig, Kernel::Words)
+    }
+
+    /// exports a row kernel of `program` as `name`, see [`JIT::compile_row`]
+    pub fn add_row<W: Lanes>(&mut self, name: &str, program: &Program) -> anyhow::Result<()> {
+        let sig = Signature::row(W::KIND, program);
+        self.define(name, program, sig, Kernel::Row)
+    }
+
+    fn define(
+        &mut self,
+        name: &str,
+        program: &Program,
+        sig: Signature,
+        kernel: Kernel,
+    ) -> anyhow::Result<()> {
+        let native = program.bytecode.iter().find_map(|instr| match instr {
+            Instr::UnOp {
+                op: op @ UnOp::CALL(_),
+                ..
+            } => Some(*op),
+            _ => None,
+        });
+        if let Some(op) = native {
+            anyhow::bail!("{name} calls {op}, natives can't be compiled ahead of time");
+        }
+
+        JIT::build_function(
+            &mut self.scratch,
+            &mut self.ctx.func,
+            &self.glob_fns,
+            program,
+            sig,
+            kernel,
+            self.math,
+        );
+        let imports = self.import_called_functions();
+
+        let id = self
+            .module
+            .declare_function(name, Linkage::Export, &self.ctx.func.signature)
+            .unwrap_or_else(|e| panic!("failed to declare {name}: {e}"));
+        self.module
+            .define_function(id, &mut self.ctx)
+            .unwrap_or_else(|e| panic!("failed to define {name}: {e}"));
+        self.module.clear_context(&mut self.ctx);
+
+        self.symbols.push(Symbol {
+            name: name.to_string(),
+            sig,
+            kernel,
+            imports,
+        });
+        Ok(())
+    }
+
+    /// imports the runtime functions called by the function in `ctx` into `module`
+    fn import_called_functions(&mut self) -> Vec<&'static str> {
+        let func = &mut self.ctx.func;
+        let mut called = vec![];
+        for block in func.layout.blocks() {
+            for inst in func.layout.block_insts(block) {
+                let ir::InstructionData::Call { func_ref, .. } = func.dfg.insts[inst] else {
+                    continue;
+                };
+                let ir::ExternalName::User(name_ref) = func.dfg.ext_funcs[func_ref].name else {
+                    continue;
+                };
+                called.push(name_ref);
+            }
+        }
+        called.sort_unstable();
+        called.dedup();
+
+        let mut imports = vec![];
+        for name_ref in called {
+            let scratch_id = FuncId::from_u32(func.params.user_named_funcs()[name_ref].index);
+            let (&name, _) = self
+                .glob_fns
+                .iter()
+                .find(|&(_, &id)| id == scratch_id)
+                .expect("calls only target runtime functions");
+            let decl = self.scratch.declarations().get_function_decl(scratch_id);
+            let id = self
+                .module
+                .declare_function(name, Linkage::Import, &decl.signature)
+                .unwrap();
+            func.params.reset_user_func_name(
+                name_ref,
+                ir::UserExternalName {
+                    namespace: 0,
+                    index: id.as_u32(),
+                },
+            );
+            imports.push(name);
+        }
+        imports.sort_unstable();
+        imports
+    }
+
+    /// emits the object file
+    pub fn finish(self) -> AotObject {
+        AotObject {
+            bytes: self.module.finish().emit().unwrap(),
+            triple: self.triple,
+            symbols: self.symbols,
+        }
+    }
+}
+
+/// relocatable object produced by [`Aot`]
+#[derive(Debug, Clone)]
+pub struct AotObject {
+    pub bytes: Vec<u8>,
+    pub triple: String,
+    pub symbols: Vec<Symbol>,
+}
+
+fn c_type(word: WordKind) -> &'static str {
+    match word {
+        WordKind::F64 => "double",
+        WordKind::F64X2 => "f64x2_t",
+        WordKind::F64X4 => "f64x4_t",
+        WordKind::F64X8 => "f64x8_t",
+        WordKind::Intrvl => "intrvl_t",
+        WordKind::Complex => "complex_t",
+        WordKind::Dual => "dual_t",
+    }
+}
+
+fn c_param(param: FnParam) -> &'static str {
+    match param {
+        FnParam::I8 => "int8_t",
+        FnParam::F64 => "double",
+        FnParam::F64X2 => "const f64x2_t *",
+        FnParam::Ptr => "void *",
+    }
+}
+
+impl AotObject {
+    /// c declarations of the exported symbols and the runtime functions they call
+    pub fn header(&self) -> String {
+        let mut h = String::new();
+        writeln!(h, "/* generated by compiler::aot for {} */", self.triple).unwrap();
+        h.push_str(
+            "#pragma once\n\
+             #include <stdint.h>\n\
+             \n\
+             typedef struct { double lanes[2]; } f64x2_t;\n\
+             typedef struct { double lanes[4]; } f64x4_t;\n\
+             typedef struct { double lanes[8]; } f64x8_t;\n\
+             typedef struct { double lo, hi; } intrvl_t;\n\
+             typedef struct { double re, im; } complex_t;\n\
+             typedef struct { double v, d; } dual_t;\n\
+             /* points origin + k * step for k in 0..n */\n\
+             typedef struct { double origin[2]; double step[2]; } row_params_t;\n",
+        );
+
+        for Symbol {
+            name, sig, kernel, ..
+        } in &self.symbols
+        {
+            let ty = c_type(sig.word);
+            h.push('\n');
+            match kernel {
+                Kernel::Words => {
+                    writeln!(
+                        h,
+                        "/* {} {ty} arguments, {} {ty} outputs */\n\
+                         void {name}(const {ty} *args, {ty} *out);",
+                        sig.arity, sig.outputs
+                    )
+                }
+                Kernel::Row => {
+                    writeln!(
+                        h,
+                        "/* first output at n points, {ty} lanes */\n\
+                         void {name}(const row_params_t *params, double *out, uint64_t n);"
+                    )
+                }
+            }
+            .unwrap();
+        }
+
+        let mut imports: Vec<_> = self.symbols.iter().flat_map(|s| &s.imports).collect();
+        imports.sort_unstable();
+        imports.dedup();
+        if !imports.is_empty() {
+            h.push_str("\n/* runtime functions, provided by the host */\n");
+        }
+        for &(name, _, params, returns) in jit2::GLOB_FN_DECLS.iter().copied().flatten() {
+            if !imports.contains(&&name) {
+                continue;
+            }
+            let mut params: Vec<_> = params.iter().map(|&p| c_param(p)).collect();
+            let ret = match returns.first() {
+                // see FnParam::F64X2
+                Some(FnParam::F64X2) => {
+                    params.insert(0, "f64x2_t *");
+                    "void"
+                }
+                Some(&r) => c_param(r),
+                None => "void",
+            };
+            writeln!(h, "{ret} {name}({});", params.join(", ")).unwrap();
+        }
+
+        h
+    }
+
+    /// links the object into a shared library at `path` with the system c compiler
+    ///
+    /// the object is written next to the library with the extension `o`
+    pub fn write_shared(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let obj = path.with_extension("o");
+        std::fs::write(&obj, &self.bytes)?;
+
+        let status = process::Command::new("cc")
+            .arg("-shared")
+            .arg("-o")
+            .arg(path)
+            .arg(&obj)
+            .status()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("cc exited with {status}")));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::jit::Oprnd;
+    use crate::jit2::{F64X2, RowParams};
+    use utils::native::{self, NativeFn};
+
+    fn program() -> Program {
+        Program::from(
+            bytecode![
+                SIN[0] -> 2,
+                MUL[2, 1] -> 0,
+                POW[0, imm(2.)] -> 0,
+                COS[1] -> 1,
+                ADD[0, 1] -> 0,
+            ]
+            .to_vec(),
+        )
+    }
+
+    fn object(prog: &Program) -> AotObject {
+        let mut aot = Aot::new("test");
+        aot.math = MathImpl::Inline;
+        aot.add::<f64>("eval_inline", prog, 2).unwrap();
+        aot.add_row::<F64X2>("eval_row", prog).unwrap();
+        aot.math = MathImpl::Libm;
+        aot.add::<f64>("eval_libm", prog, 2).unwrap();
+        aot.finish()
+    }
+
+    #[test]
+    fn refuse_natives() {
+        let id = native::register(NativeFn::new(
+            "aot_test_neg",
+            |x| -x,
+            |_| -1.0,
+            |lo, hi| (-hi, -lo),
+        ));
+        let mut prog = program();
+        prog.bytecode.push(Instr::UnOp {
+            op: UnOp::CALL(id),
+            val: Oprnd::Reg(0),
+            dst: 0,
+        });
+
+        let mut aot = Aot::new("test");
+        let err = aot.add::<f64>("f", &prog, 2).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "f calls CALL_aot_test_neg, natives can't be compiled ahead of time"
+        );
+        assert!(aot.add_row::<F64X2>("row", &prog).is_err());
+        assert!(aot.add::<f64>("g", &program(), 2).is_ok());
+        assert_eq!(aot.finish().symbols.len(), 1);
+    }
+
+    #[test]
+    fn header() {
+        let obj = object(&program());
+        assert!(obj.symbols[0].imports.is_empty());
+        assert_eq!(obj.symbols[2].imports, ["cos_f64", "pow_f64", "sin_f64"]);
+
+        let h = obj.header();
+        assert!(h.contains("void eval_inline(const double *args, double *out);"));
+        assert!(h.contains("void eval_row(const row_params_t *params, double *out, uint64_t n);"));
+        assert!(h.contains("double pow_f64(double, double);"));
+    }
+
+    /// needs a c compiler, skipped if `cc` is not installed
+    #[cfg(unix)]
+    #[test]
+    fn link_and_call() {
+        if process::Command::new("cc")
+            .arg("--version")
+            .output()
+            .is_err()
+        {
+            eprintln!("cc not found, skipping");
+            return;
+        }
+
+        let dir = std::env::temp_dir().join(format!("aot_test_{}", process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let prog = program();
+        let obj = object(&prog);
+
+        let mut jit = JIT::init();
+        jit.math = MathImpl::Inline;
+        let f = jit.compile::<f64>("f", &prog, 2);
+        let row = jit.compile_row::<F64X2>("row", &prog);
+
+        let params = RowParams {
+            origin: [-1.25, 0.5],
+            step: [0.75, -0.3],
+        };
+        let n = 5;
+        let points: Vec<_> = (0..n)
+            .map(|k| {
+                let x = params.origin[0] + k as f64 * params.step[0];
+                let y = params.origin[1] + k as f64 * params.step[1];
+                format!("{{{x:?}, {y:?}}}")
+            })
+            .collect();
+        let mut expected_row = vec![0.0; n];
+        row.eval(&params, &mut expected_row);
+
+        std::fs::write(dir.join("test.h"), obj.header()).unwrap();
+        obj.write_shared(dir.join("libtest.so")).unwrap();
+        let main = format!(
+            r#"
+            #include <math.h>
+            #include <stdio.h>
+            #include "test.h"
+
+            double sin_f64(double x) {{ return sin(x); }}
+            double cos_f64(double x) {{ return cos(x); }}
+            double pow_f64(double b, double e) {{ return pow(b, e); }}
+
+            int main(void) {{
+                double points[{n}][2] = {{ {} }};
+                double row[{n}];
+                row_params_t params = {{ {{ {:?}, {:?} }}, {{ {:?}, {:?} }} }};
+                eval_row(&params, row, {n});
+                for (int k = 0; k < {n}; k++) {{
+                    double inline_out, libm_out;
+                    eval_inline(points[k], &inline_out);
+                    eval_libm(points[k], &libm_out);
+                    printf("%.17g %.17g %.17g\n", inline_out, libm_out, row[k]);
+                }}
+                return 0;
+            }}
+            "#,
+            points.join(", "),
+            params.origin[0],
+            params.origin[1],
+            params.step[0],
+            params.step[1],
+        );
+        std::fs::write(dir.join("main.c"), main).unwrap();
+
+        let exe = dir.join("main");
+        let status = process::Command::new("cc")
+            .arg(dir.join("main.c"))
+            .arg(dir.join("libtest.so"))
+            .arg("-lm")
+            .arg("-o")
+            .arg(&exe)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let out = process::Command::new(&exe).output().unwrap();
+        assert!(out.status.success());
+        let stdout = String::from_utf8(out.stdout).unwrap();
+        let lines: Vec<_> = stdout.lines().collect();
+        assert_eq!(lines.len(), n);
+
+        for (k, line) in lines.iter().enumerate() {
+            let x = params.origin[0] + k as f64 * params.step[0];
+            let y = params.origin[1] + k as f64 * params.step[1];
+            let expected = f.eval(&[x, y]);
+            let libm = (x.sin() * y).powf(2.0) + y.cos();
+
+            let vals: Vec<f64> = line.split(' ').map(|v| v.parse().unwrap()).collect();
+            assert_eq!(vals[0], expected, "k={k}");
+            assert!((vals[1] - libm).abs() < 1e-12, "k={k}");
+            assert_eq!(vals[2], expected_row[k], "k={k}");
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
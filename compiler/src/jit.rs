@@ -1,20 +1,10 @@
-use std::collections::HashMap;
-use std::fmt;
+use std::{fmt, hash, str::FromStr};
 
-use cranelift_codegen::ir::AbiParam;
-use cranelift_codegen::{
-    ir::{self, InstBuilder, condcodes::IntCC as IntCondCode},
-    isa,
+use utils::{
+    asm::{self, AsmError, Labels},
+    native::{self, NativeId},
+    special::Special,
 };
-use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable as JITVar};
-use cranelift_jit::{JITBuilder, JITModule};
-use cranelift_module::{FuncId, Linkage, Module};
-use macros::jit_fn;
-use paste::paste;
-
-use wide::{self, f64x2, f64x4};
-
-use utils::{ExplicitCopy, Intrvl};
 
 pub type Reg = u8;
 
@@ -33,6 +23,16 @@ impl fmt::Display for Oprnd {
     }
 }
 
+/// immediates are hashed by their bits, see [`Program`]
+impl hash::Hash for Oprnd {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        match self {
+            Oprnd::Reg(r) => (0u8, *r as u64).hash(state),
+            Oprnd::Imm(i) => (1u8, i.to_bits()).hash(state),
+        }
+    }
+}
+
 impl Oprnd {
     pub fn reg(reg: impl Into<Reg>) -> Self {
         Self::Reg(reg.into())
@@ -42,13 +42,20 @@ impl Oprnd {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Hash)]
 pub enum BinOp {
     ADD,
     SUB,
     MUL,
     DIV,
     POW,
+    MIN,
+    MAX,
+    /// 1 if lhs < rhs, else 0
+    LT,
+    LE,
+    /// lhs - rhs * floor(lhs / rhs)
+    MOD,
 }
 
 impl fmt::Display for BinOp {
@@ -57,30 +64,53 @@ impl fmt::Display for BinOp {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Hash)]
 pub enum UnOp {
     MOV,
 
     SIN,
     COS,
     TAN,
+
+    FLOOR,
+    CEIL,
+    /// rounds half to even
+    ROUND,
+    /// val - floor(val)
+    FRACT,
+
+    GAMMA,
+    ERF,
+    /// bessel functions of the first kind
+    J0,
+    J1,
+    /// principal branch of the inverse of w e^w
+    LAMBERTW,
+
+    /// a function registered with [`utils::native`], written `CALL_<name>`
+    CALL(NativeId),
 }
 
 impl fmt::Display for UnOp {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{self:?}")
+        match self {
+            UnOp::CALL(id) => write!(f, "CALL_{id}"),
+            _ => write!(f, "{self:?}"),
+        }
     }
 }
 
 impl UnOp {
-    fn c_fn_name(&self) -> Option<&'static str> {
+    /// the special function computed by the op, evaluated through runtime calls
+    pub const fn special(self) -> Option<Special> {
         match self {
-            UnOp::SIN => "sin",
-            UnOp::COS => "cos",
-            UnOp::TAN => "tan",
-            _ => return None,
+            UnOp::GAMMA => Some(Special::Gamma),
+            UnOp::ERF => Some(Special::Erf),
+            UnOp::J0 => Some(Special::J0),
+            UnOp::J1 => Some(Special::J1),
+            UnOp::LAMBERTW => Some(Special::LambertW),
+            _ => None,
         }
-        .into()
     }
 }
 
@@ -90,6 +120,15 @@ macro_rules! bytecode {
     (@oprnd: imm($val:literal)) => { $crate::jit::Oprnd::Imm($val.into()) };
     (@oprnd: $reg:literal) => { $crate::jit::Oprnd::Reg($reg.into()) };
 
+    (@instr: SEL [$($coprnd_typ: ident)? $(($cval:literal))? $($creg:literal)?, $($loprnd_typ: ident)? $(($lval:literal))? $($lreg:literal)?, $($roprnd_typ: ident)? $(($rval:literal))? $($rreg:literal)? ] -> $dst:literal) => {
+        $crate::jit::Instr::Select {
+            cond: $crate::bytecode!(@oprnd: $($coprnd_typ)? $(($cval))? $($creg)?),
+            lhs: $crate::bytecode!(@oprnd: $($loprnd_typ)? $(($lval))? $($lreg)?),
+            rhs: $crate::bytecode!(@oprnd: $($roprnd_typ)? $(($rval))? $($rreg)?),
+            dst: $dst,
+        }
+    };
+
     (@instr: $op: ident [$($loprnd_typ: ident)? $(($lval:literal))? $($lreg:literal)?, $($roprnd_typ: ident)? $(($rval:literal))? $($rreg:literal)? ] -> $dst:literal) => {
         $crate::jit::Instr::BinOp {
             op: $crate::jit::BinOp::$op,
@@ -119,297 +158,184 @@ macro_rules! bytecode {
 }
 //pub use bytecode;
 
-macro_rules! oprnd_val {
-    ($oprnd:expr, $reg_vars: expr, $fn_ctx: expr) => {
-        match $oprnd {
-            Oprnd::Reg(reg) => $fn_ctx.use_var($reg_vars[reg.copy() as usize]),
-            Oprnd::Imm(imm) => $fn_ctx.ins().f64const(imm.copy()),
-        }
-    };
-}
-
-macro_rules! set_reg {
-    ($val:expr, $reg: expr, $reg_vars: expr, $fn_ctx: expr) => {
-        let reg_var = $reg_vars[$reg.copy() as usize];
-        $fn_ctx.def_var(reg_var, $val.copy())
-    };
-}
-
-macro_rules! make_sig {
-
-    (@param: # $p:expr) => {
-        $p
-    };
-    (@param: $p:expr) => {
-        paste!(cranelift_codegen::ir::types::$p)
-    };
-
-    ($mod:expr, ($($p_typ:ident)? $(#$p_var:expr)? $(,$($pr_typ:ident)? $(#$pr_var:expr)?)* $(,)?) -> ($($ret_typ:ident)? $(#$ret_var:expr)?)) => {{
-        #[allow(unused_mut)]
-        let mut sig = $mod.make_signature();
-        $(sig.params.push(ir::AbiParam::new(make_sig!(@param: #$p_var)));)?
-        $(sig.params.push(ir::AbiParam::new(make_sig!(@param: $p_typ)));)?
-        $($(sig.params.push(ir::AbiParam::new(make_sig!(@param: $pr_typ)));)?)*
-        $($(sig.params.push(ir::AbiParam::new(make_sig!(@param: #$pr_var)));)?)*
-        $(sig.returns.push(ir::AbiParam::new(make_sig!(@param: #$ret_var)));)?
-        $(sig.returns.push(ir::AbiParam::new(make_sig!(@param: $ret_typ)));)?
-        sig
-    }};
-}
-
-macro_rules! extrn_sig {
-
-    (@param: # $p:expr) => {
-        $p
-    };
-    (@param: $p:expr) => {
-        paste!(cranelift_codegen::ir::types::$p)
-    };
-
-    (($($p_typ:ident)? $(#$p_var:expr)? $(,$($pr_typ:ident)? $(#$pr_var:expr)?)* $(,)?) ->
-     ($($ret_typ:ident)? $(#$ret_var:expr)? $(,$($retr_typ:ident)? $(#$retr_var:expr)?)* $(,)?)) => {{
-     //($($ret_typ:ident)? $(#$ret_var:expr)?)) =>
-        #[allow(unused_mut)]
-        let mut params = vec![];
-        #[allow(unused_mut)]
-        let mut returns = vec![];
-
-        $(params.push(ir::AbiParam::new(make_sig!(@param: #$p_var)));)?
-        $(params.push(ir::AbiParam::new(make_sig!(@param: $p_typ)));)?
-        $($(params.push(ir::AbiParam::new(make_sig!(@param: $pr_typ)));)?)*
-        $($(params.push(ir::AbiParam::new(make_sig!(@param: #$pr_var)));)?)*
-
-        $(returns.push(ir::AbiParam::new(make_sig!(@param: #$ret_var)));)?
-        $(returns.push(ir::AbiParam::new(make_sig!(@param: $ret_typ)));)?
-        $($(returns.push(ir::AbiParam::new(make_sig!(@param: $retr_typ)));)?)*
-        $($(returns.push(ir::AbiParam::new(make_sig!(@param: #$retr_var)));)?)*
-
-        let mut sig = ir::Signature::new(isa::CallConv::triple_default(&target_lexicon::Triple::host()));
-        sig.params = params;
-        sig.returns = returns;
-        sig
-    }};
-}
-
-macro_rules! decl_var {
-    ($fb:expr, $idx:tt: $typ:ident) => {{
-        paste! {
-        let var = cranelift_frontend::Variable::from_u32($idx);
-        let typ = cranelift_codegen::ir::types::[<$typ:upper>];
-        let val = $fb.ins().[<$typ:lower const>]($val);
-        $fb.declare_var(var, typ);
-        }
-    }};
-
-    ($fb:expr, $idx:tt: $typ:ident [$const_ty:ident]) => {{
-        paste! {
-        let var = cranelift_frontend::Variable::from_u32($idx);
-        let typ = cranelift_codegen::ir::types::[<$typ:upper>];
-        let val = $fb.ins().[<$const_ty:lower const>]($val);
-        $fb.declare_var(var, typ);
-        }
-    }};
+#[derive(Debug, Clone, PartialEq, PartialOrd, Hash)]
+pub struct Program {
+    pub bytecode: Vec<Instr>,
+    /// registers returned by the program in order, only register 0 by default
+    pub outputs: Vec<Reg>,
 }
 
-macro_rules! init_var {
-    ($fb:expr, $idx:tt: $typ:ident [$const_ty:ident] = $val:literal) => {{
-        paste! {
-        let var = cranelift_frontend::Variable::from_u32($idx);
-        let typ = cranelift_codegen::ir::types::[<$typ:upper>];
-        let val = $fb.ins().[<$const_ty:lower const>](typ, $val);
-        $fb.declare_var(var, typ);
-        $fb.def_var(var, val);
-        var
-        }
-    }};
-    ($fb:expr, $idx:tt: $typ:ident = $val:literal) => {{
-        paste! {
-        let var = cranelift_frontend::Variable::from_u32($idx);
-        let typ = cranelift_codegen::ir::types::[<$typ:upper>];
-        let val = $fb.ins().[<$typ:lower const>]($val);
-        $fb.declare_var(var, typ);
-        $fb.def_var(var, val);
-        var
-        }
-    }};
-}
+impl Program {
+    pub fn with_outputs(bytecode: Vec<Instr>, outputs: Vec<Reg>) -> Self {
+        Self { bytecode, outputs }
+    }
 
-macro_rules! rep_count {
-    (@ident: ) => ( 0 );
-    (@ident: $x:ident $($xs:ident)*) => ( 1 + rep_count!(@ident: $($xs)*) );
+    pub fn n_outputs(&self) -> usize {
+        self.outputs.len()
+    }
 }
 
-macro_rules! extern_c_fns {
-    //($($c_fn:ident $(,)?)*) => {
-    ($($c_fn:ident),* $(,)?) => {
-        struct ExternCFnTable {
-            $($c_fn: cranelift_module::FuncId,)*
-        }
-
-        impl ExternCFnTable {
+impl Program {
+    /// the text format of [`utils::asm`], registers in `labels` are printed by name
+    pub fn to_asm(&self, labels: &Labels) -> String {
+        let oprnd = |o: Oprnd| match o {
+            Oprnd::Reg(r) => labels.reg(r as u32),
+            Oprnd::Imm(i) => format!("{i}_f"),
+        };
+        let reg = |r: Reg| labels.reg(r as u32);
 
-            fn import<F>(mut import_fn: F) -> Self
-            where F: FnMut(&'static str) -> cranelift_module::FuncId
-            {
-                Self {
-                    $($c_fn: import_fn(stringify!($c_fn)),)*
+        let mut asm = labels.to_string();
+        for instr in &self.bytecode {
+            let line = match *instr {
+                Instr::UnOp { op, val, dst } => format!("{op}[{}] -> {}", oprnd(val), reg(dst)),
+                Instr::BinOp { op, lhs, rhs, dst } => {
+                    format!("{op}[{}, {}] -> {}", oprnd(lhs), oprnd(rhs), reg(dst))
                 }
-            }
-
-        }
+                Instr::Select {
+                    cond,
+                    lhs,
+                    rhs,
+                    dst,
+                } => format!(
+                    "SEL[{}, {}, {}] -> {}",
+                    oprnd(cond),
+                    oprnd(lhs),
+                    oprnd(rhs),
+                    reg(dst)
+                ),
+            };
+            asm.push_str(&line);
+            asm.push('\n');
+        }
+        if self.outputs != [0] {
+            let outputs: Vec<_> = self.outputs.iter().map(|&r| reg(r)).collect();
+            asm.push_str(&format!("ret {}\n", outputs.join(", ")));
+        }
+        asm
     }
-}
-
-extern_c_fns!(sin, cos, tan);
-
-pub trait AsJITType {
-    const TYPE: ir::Type;
-}
-impl AsJITType for i32 {
-    const TYPE: ir::Type = ir::types::I32;
-}
-impl AsJITType for i64 {
-    const TYPE: ir::Type = ir::types::I64;
-}
-impl AsJITType for f32 {
-    const TYPE: ir::Type = ir::types::F32;
-}
-impl AsJITType for f64 {
-    const TYPE: ir::Type = ir::types::F64;
-}
-
-pub trait IRSignature {
-    fn ir_signature(module: &JITModule) -> ir::Signature;
-}
-
-#[jit_fn]
-fn print_f64(f: f64) {
-    println!("{f}");
-}
-
-#[jit_fn]
-fn print_f64x2(f: f64x2) {
-    println!("{f}");
-}
-
-#[inline]
-fn pack_f64x2(l: f64x2, u: f64x2) -> f64x4 {
-    let [ll, lu] = l.to_array();
-    let [ul, uu] = u.to_array();
-    f64x4::new([ll, lu, ul, uu])
-}
-
-#[inline]
-fn unpack_f64x4(val: f64x4) -> [f64x2; 2] {
-    let [ll, lu, ul, uu] = val.to_array();
-    [f64x2::new([ll, lu]), f64x2::new([ul, uu])]
-}
-
-#[jit_fn]
-fn sin_f64x2x2(l: f64x2, u: f64x2) -> [f64x2; 2] {
-    let val = pack_f64x2(l, u);
-    unpack_f64x4(val.sin())
-}
-
-#[jit_fn]
-fn div_f64x2x2(lhsl: f64x2, lhsu: f64x2, rhsl: f64x2, rhsu: f64x2) -> [f64x2; 2] {
-    let lhs = pack_f64x2(lhsl, lhsu);
-    let rhs = pack_f64x2(rhsl, rhsu);
-    unpack_f64x4(lhs / rhs)
-}
-
-#[jit_fn]
-fn pow_f64(b: f64, e: f64) -> f64 {
-    b.powf(e)
-}
 
-#[jit_fn]
-fn pow_f64x2(b: f64x2, e: f64x2) -> f64x2 {
-    b.pow_f64x2(e)
-}
-
-#[jit_fn]
-fn pow_f64x2x2(lhsl: f64x2, lhsu: f64x2, rhsl: f64x2, rhsu: f64x2) -> [f64x2; 2] {
-    let lhs = pack_f64x2(lhsl, lhsu);
-    let rhs = pack_f64x2(rhsl, rhsu);
-    unpack_f64x4(lhs.pow_f64x4(rhs))
-}
+    /// parses the output of [`Program::to_asm`] or [`Program`]'s Display
+    pub fn from_asm(src: &str) -> Result<Self, AsmError> {
+        let (lines, _) = asm::parse(src)?;
+        let mut bytecode = vec![];
+        let mut outputs = vec![0];
 
-macro_rules! impl_unop_f64x2x4 {
-    ($name:ident ($val:ident) => $block:block) => {
-        paste::paste! {
-            #[jit_fn]
-            fn [<$name _f64x2x4>](parts: [f64x2; 4]) -> [f64x2; 4] {
-                let mut out = [f64x2::ZERO; 4];
-                let mut i = 0;
-                while i < 4 {
-                    // let res = unpack_f64x4(pack_f64x2(parts[i], parts[i+1]).sin());
-                    // let $val = pack_f64x2(parts[i], parts[i+1]);
-                    // let res = unpack_f64x4($block);
-                    // out[i] = res[0];
-                    // out[i+1] = res[1];
+        let to_reg = |r: u32, line| {
+            Reg::try_from(r).map_err(|_| AsmError::new(line, format!("register {r} out of range")))
+        };
+        let to_oprnd = |o: asm::Operand, line| match o {
+            asm::Operand::Reg(r) => to_reg(r, line).map(Oprnd::Reg),
+            asm::Operand::Imm(i) => Ok(Oprnd::Imm(i)),
+        };
 
-                    let $val = parts[i];
-                    out[i] = $block;
-                    let $val = parts[i+1];
-                    out[i+1] = $block;
-                    i += 2;
+        for (line, l) in lines {
+            match l {
+                asm::Line::Instr { op, args, dst } => {
+                    let dst = dst.ok_or_else(|| AsmError::new(line, "missing `-> dst`"))?;
+                    let dst = to_reg(dst, line)?;
+                    let instr = match (parse_op(op), args.as_slice()) {
+                        (None, &[cond, lhs, rhs]) if op == "SEL" => Instr::Select {
+                            cond: to_oprnd(cond, line)?,
+                            lhs: to_oprnd(lhs, line)?,
+                            rhs: to_oprnd(rhs, line)?,
+                            dst,
+                        },
+                        (Some(Ok(op)), &[lhs, rhs]) => Instr::BinOp {
+                            op,
+                            lhs: to_oprnd(lhs, line)?,
+                            rhs: to_oprnd(rhs, line)?,
+                            dst,
+                        },
+                        (Some(Err(op)), &[val]) => Instr::UnOp {
+                            op,
+                            val: to_oprnd(val, line)?,
+                            dst,
+                        },
+                        (None, _) if op != "SEL" => {
+                            return Err(AsmError::new(line, format!("unknown instruction `{op}`")));
+                        }
+                        _ => {
+                            return Err(AsmError::new(
+                                line,
+                                format!("wrong operand count for {op}"),
+                            ));
+                        }
+                    };
+                    bytecode.push(instr);
+                }
+                asm::Line::Directive { name: "ret", args } => {
+                    outputs = args
+                        .into_iter()
+                        .map(|a| to_reg(a.reg(line)?, line))
+                        .collect::<Result<_, _>>()?;
+                }
+                asm::Line::Directive { name, .. } => {
+                    return Err(AsmError::new(line, format!("unknown directive `{name}`")));
                 }
-                out
-            }
-        }
-    };
-}
-
-impl_unop_f64x2x4!(sin(val) => { val.sin() });
-impl_unop_f64x2x4!(cos(val) => { val.cos() });
-impl_unop_f64x2x4!(tan(val) => { val.tan() });
-
-macro_rules! impl_unop_f64x2 {
-    ($name:ident ($val:ident) => $block:block) => {
-        paste::paste! {
-            #[jit_fn]
-            fn [<$name _f64x2>]($val: f64x2) -> f64x2 {
-                $block
             }
         }
-    };
-}
-
-impl_unop_f64x2!(sin(val) => { val.sin() });
-impl_unop_f64x2!(cos(val) => { val.cos() });
-impl_unop_f64x2!(tan(val) => { val.tan() });
 
-impl ExternCFnTable {
-    fn unop_id(&self, unop: UnOp) -> FuncId {
-        match unop {
-            UnOp::SIN => self.sin,
-            UnOp::COS => self.cos,
-            UnOp::TAN => self.tan,
-            UnOp::MOV => panic!("not a c function: {unop:?}"),
-        }
+        Ok(Self::with_outputs(bytecode, outputs))
     }
 }
 
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
-pub struct Program {
-    pub bytecode: Vec<Instr>,
+fn parse_op(op: &str) -> Option<Result<BinOp, UnOp>> {
+    use {BinOp::*, UnOp::*};
+    Some(match op {
+        "ADD" => Ok(ADD),
+        "SUB" => Ok(SUB),
+        "MUL" => Ok(MUL),
+        "DIV" => Ok(DIV),
+        "POW" => Ok(POW),
+        "MIN" => Ok(MIN),
+        "MAX" => Ok(MAX),
+        "LT" => Ok(LT),
+        "LE" => Ok(LE),
+        "MOD" => Ok(MOD),
+        "MOV" => Err(MOV),
+        "SIN" => Err(SIN),
+        "COS" => Err(COS),
+        "TAN" => Err(TAN),
+        "FLOOR" => Err(FLOOR),
+        "CEIL" => Err(CEIL),
+        "ROUND" => Err(ROUND),
+        "FRACT" => Err(FRACT),
+        "GAMMA" => Err(GAMMA),
+        "ERF" => Err(ERF),
+        "J0" => Err(J0),
+        "J1" => Err(J1),
+        "LAMBERTW" => Err(LAMBERTW),
+        _ => Err(CALL(native::lookup(op.strip_prefix("CALL_")?)?)),
+    })
+}
+
+impl FromStr for Program {
+    type Err = AsmError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_asm(s)
+    }
 }
 
 impl From<Vec<Instr>> for Program {
     fn from(bytecode: Vec<Instr>) -> Self {
-        Self { bytecode }
+        Self::with_outputs(bytecode, vec![0])
     }
 }
 
 impl fmt::Display for Program {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let instrs: Vec<_> = self.bytecode.iter().map(|i| i.to_string()).collect();
-        write!(f, "{}", instrs.join("\n"))
+        write!(f, "{}", instrs.join("\n"))?;
+        if self.outputs != [0] {
+            let outputs: Vec<_> = self.outputs.iter().map(|r| r.to_string()).collect();
+            write!(f, "\nret {}", outputs.join(", "))?;
+        }
+        Ok(())
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Hash)]
 pub enum Instr {
     UnOp {
         op: UnOp,
@@ -422,6 +348,13 @@ pub enum Instr {
         rhs: Oprnd,
         dst: Reg,
     },
+    /// dst = lhs if cond != 0, else rhs
+    Select {
+        cond: Oprnd,
+        lhs: Oprnd,
+        rhs: Oprnd,
+        dst: Reg,
+    },
 }
 
 impl fmt::Display for Instr {
@@ -429,953 +362,101 @@ impl fmt::Display for Instr {
         match self {
             Instr::UnOp { op, val, dst } => write!(f, "{op}[{val}] -> {dst}"),
             Instr::BinOp { op, lhs, rhs, dst } => write!(f, "{op}[{lhs}, {rhs}] -> {dst}"),
+            Instr::Select {
+                cond,
+                lhs,
+                rhs,
+                dst,
+            } => write!(f, "SEL[{cond}, {lhs}, {rhs}] -> {dst}"),
         }
     }
 }
 
-extern "C" fn print_hello() {
-    println!("Hello World");
-}
-
-struct FnTable(HashMap<String, FuncId>);
-
-macro_rules! min_max {
-    ($a:expr, $b:expr $(,)?) => {
-        if $a < $b { ($a, $b) } else { ($b, $a) }
-    };
-
-    ($a:expr, $b:expr, $c:expr, $d:expr $(,)?) => {
-        (min_max!($a, $b).0, min_max!($c, $d).1)
-    };
-}
-
-#[unsafe(no_mangle)]
-extern "C" fn sin_intrvl(v: f64x2) -> f64x2 {
-    let [lo, hi] = v.to_array();
-    let intrvl = Intrvl::new(lo, hi).sin();
-    f64x2::new([intrvl.lo, intrvl.hi])
-}
-
-#[unsafe(no_mangle)]
-extern "C" fn cos_intrvl(v: f64x2) -> f64x2 {
-    let [lo, hi] = v.to_array();
-    let intrvl = Intrvl::new(lo, hi).cos();
-    f64x2::new([intrvl.lo, intrvl.hi])
-}
-#[unsafe(no_mangle)]
-extern "C" fn tan_intrvl(v: f64x2) -> f64x2 {
-    let [lo, hi] = v.to_array();
-    let intrvl = Intrvl::new(lo, hi).tan();
-    f64x2::new([intrvl.lo, intrvl.hi])
-}
-
-#[unsafe(no_mangle)]
-extern "C" fn pow_intrvl(b: f64x2, e: f64x2) -> f64x2 {
-    let [b_lo, b_hi] = b.to_array();
-    let [e_lo, e_hi] = e.to_array();
-    let intrvl = Intrvl::new(b_lo, b_hi).pow(Intrvl::new(e_lo, e_hi));
-    f64x2::new([intrvl.lo, intrvl.hi])
-}
-
-#[unsafe(no_mangle)]
-extern "C" fn add_intrvl(l: f64x2, r: f64x2) -> f64x2 {
-    let [l_lo, l_hi] = l.to_array();
-    let l = Intrvl::new(l_lo, l_hi);
-    let [r_lo, r_hi] = r.to_array();
-    let r = Intrvl::new(r_lo, r_hi);
-    let res = l.add(r);
-    f64x2::new([res.lo, res.hi])
-}
-#[unsafe(no_mangle)]
-extern "C" fn sub_intrvl(l: f64x2, r: f64x2) -> f64x2 {
-    let [l_lo, l_hi] = l.to_array();
-    let [r_lo, r_hi] = r.to_array();
-    let l = Intrvl::new(l_lo, l_hi);
-    let r = Intrvl::new(r_lo, r_hi);
-    let res = l.sub(r);
-    f64x2::new([res.lo, res.hi])
-}
-#[unsafe(no_mangle)]
-extern "C" fn mul_intrvl(l: f64x2, r: f64x2) -> f64x2 {
-    let [l_lo, l_hi] = l.to_array();
-    let [r_lo, r_hi] = r.to_array();
-    let l = Intrvl::new(l_lo, l_hi);
-    let r = Intrvl::new(r_lo, r_hi);
-    let res = l.mul(r);
-
-    let (lo, hi) = min_max![l.lo * r.lo, l.lo * r.hi, l.hi * r.lo, l.hi * r.hi,];
-    // println!("{l} * {r} -> ({lo}, {hi})");
-    f64x2::new([lo, hi])
-}
-#[unsafe(no_mangle)]
-extern "C" fn div_intrvl(l: f64x2, r: f64x2) -> f64x2 {
-    let [l_lo, l_hi] = l.to_array();
-    let [r_lo, r_hi] = r.to_array();
-    let intrvl = Intrvl::new(l_lo, l_hi).div(Intrvl::new(r_lo, r_hi));
-    f64x2::new([intrvl.lo, intrvl.hi])
-}
-
-/*
-export function cos(x: Interval): Interval {
-  if (utils.isEmpty(x) || onlyInfinity(x)) {
-    return constants.EMPTY
-  }
-
-  // create a clone of `x` because the clone is going to be modified
-  const cache = new Interval().set(x.lo, x.hi)
-  handleNegative(cache)
-
-  const pi2 = constants.PI_TWICE
-  const t = algebra.fmod(cache, pi2)
-  if (misc.width(t) >= pi2.lo) {
-    return new Interval(-1, 1)
-  }
-
-  // when t.lo > pi it's the same as
-  // -cos(t - pi)
-  if (t.lo >= constants.PI_HIGH) {
-    const cosv = cos(arithmetic.sub(t, constants.PI))
-    return arithmetic.negative(cosv)
-  }
-
-  const lo = t.lo
-  const hi = t.hi
-  const rlo = rmath.cosLo(hi)
-  const rhi = rmath.cosHi(lo)
-  // it's ensured that t.lo < pi and that t.lo >= 0
-  if (hi <= constants.PI_LOW) {
-    // when t.hi < pi
-    // [cos(t.lo), cos(t.hi)]
-    return new Interval(rlo, rhi)
-  } else if (hi <= pi2.lo) {
-    // when t.hi < 2pi
-    // [-1, max(cos(t.lo), cos(t.hi))]
-    return new Interval(-1, Math.max(rlo, rhi))
-  } else {
-    // t.lo < pi and t.hi > 2pi
-    return new Interval(-1, 1)
-  }
-}
-*/
-
-impl FnTable {
-    fn get(&self, name: &str) -> FuncId {
-        self.0[name]
-    }
-
-    fn get_unop(&self, unop: UnOp) -> FuncId {
-        let name = match unop {
-            UnOp::SIN => "sin",
-            UnOp::COS => "cos",
-            UnOp::TAN => "tan",
-            UnOp::MOV => panic!("not a function: {unop:?}"),
-        };
-        self.get(name)
-    }
-
-    fn insert(&mut self, name: &str, id: FuncId) {
-        self.0.insert(name.to_string(), id);
-    }
-
-    fn decl_in_func(
-        &self,
-        module: &mut JITModule,
-        func: &mut ir::Function,
-    ) -> HashMap<String, ir::FuncRef> {
-        self.0
-            .iter()
-            .map(|(name, id)| (name.to_owned(), module.declare_func_in_func(*id, func)))
-            .collect()
-    }
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-pub enum ExternFnPtr {
-    Rust(*const u8),
-    C(&'static str),
-}
-
-type ExternFnSig = ir::Signature;
-
-#[derive(Debug, Clone, PartialEq)]
-pub struct ExternFn {
-    ptr: ExternFnPtr,
-    sig: ExternFnSig,
-    name: String,
-}
-
-impl ExternFn {
-    fn c_fn(name: &'static str, sig: &ExternFnSig) -> Self {
-        Self {
-            ptr: ExternFnPtr::C(name),
-            sig: sig.clone(),
-            name: name.into(),
-        }
-    }
-
-    fn rust(name: &str, ptr: *const u8, sig: &ExternFnSig) -> Self {
-        Self {
-            ptr: ExternFnPtr::Rust(ptr),
-            sig: sig.clone(),
-            name: name.into(),
-        }
-    }
-}
-
-#[derive(Debug, Clone, Copy, Default)]
-pub struct CompConfig {
-    pub emit_asm: bool,
-}
-
-pub struct CompOutput<FN> {
-    pub asm: Option<String>,
-    pub fn_id: FuncId,
-    pub fn_ptr: FN,
-}
-
-pub struct JITCompiler {
-    module: JITModule,
-    ctx: cranelift_codegen::Context,
-    fn_ctx: FunctionBuilderContext,
-    // c_fn_table: HashMap<,
-    glob_fn_table: FnTable,
-}
-
-fn simd_bit_width() -> usize {
-    if is_x86_feature_detected!("avx512f") {
-        512
-    } else if is_x86_feature_detected!("avx") {
-        256
-    } else if is_x86_feature_detected!("sse2") {
-        128
-    } else {
-        0
-    }
-}
-
-impl JITCompiler {
-    pub fn init() -> Self {
-        // let isa = {
-        //     use cranelift_codegen::settings;
-        //     use cranelift_codegen::settings::Configurable;
-        //     let mut flags = settings::builder();
-
-        //     flags.set("use_colocated_libcalls", "false").unwrap();
-        //     flags.set("is_pic", "false").unwrap();
-        //     let mut isa_builder = cranelift_native::builder().unwrap_or_else(|msg| {
-        //         panic!("host machine is not supported: {msg}");
-        //     });
-        //     isa_builder.enable("has_sse3").unwrap();
-        //     isa_builder.enable("has_ssse3").unwrap();
-        //     isa_builder.enable("has_sse41").unwrap();
-        //     isa_builder.enable("has_sse42").unwrap();
-        //     isa_builder.enable("has_avx").unwrap();
-        //     isa_builder.enable("has_avx512f").unwrap();
-        //     isa_builder.enable("has_avx512dq").unwrap();
-        //     isa_builder.enable("has_avx512vl").unwrap();
-        //     let isa = isa_builder.finish(settings::Flags::new(flags)).unwrap();
-        //     isa
-        // };
-
-        let isa = {
-            use cranelift_codegen::settings::Configurable;
-            let mut flag_builder = cranelift_codegen::settings::builder();
-            flag_builder.set("opt_level", "speed").unwrap();
-            let isa_builder = cranelift_native::builder().ok().unwrap();
-            isa_builder
-                .finish(cranelift_codegen::settings::Flags::new(flag_builder))
-                .unwrap()
-        };
-
-        // let mut builder = JITBuilder::with_isa(isa, cranelift_module::default_libcall_names());
-        let unop_sig = extrn_sig!((F64) -> (F64));
-        let imports = [
-            ExternFn::c_fn("sin", &unop_sig),
-            ExternFn::c_fn("cos", &unop_sig),
-            ExternFn::c_fn("tan", &unop_sig),
-            ExternFn::rust(
-                "print_f64",
-                print_f64 as *const u8,
-                &extrn_sig!((F64) -> ()),
-            ),
-            ExternFn::rust(
-                "print_f64x2",
-                print_f64x2 as *const u8,
-                &extrn_sig!((F64X2) -> ()),
-            ),
-        ];
-        //ExternFn::rust("sin_f64x2x4", sin_f64x2x4 as *const u8, &extrn_sig!((F64X2, F64X2) -> (F64X2, F64X2))),
-
-        let mut builder = JITBuilder::with_isa(isa, cranelift_module::default_libcall_names());
-        // let mut builder = JITBuilder::with_flags(
-        //     &[
-        //         // ("opt_level", "speed_and_size")
-        //         #[cfg(debug_assertions)]
-        //         ("enable_verifier", "true"),
-        //     ],
-        //     cranelift_module::default_libcall_names(),
-        // )
-        // .unwrap();
-        // builder.symbol("print_f64", print_f64 as *const u8);
-        builder.symbol("pow_f64", pow_f64 as *const u8);
-        builder.symbol("pow_f64x2", pow_f64x2 as *const u8);
-        builder.symbol("pow_f64x2x2", pow_f64x2x2 as *const u8);
-
-        builder.symbol("sin_f64x2x2", sin_f64x2x2 as *const u8);
-        builder.symbol("div_f64x2x2", div_f64x2x2 as *const u8);
-
-        builder.symbol("sin_f64x2x4", sin_f64x2x4 as *const u8);
-        builder.symbol("cos_f64x2x4", cos_f64x2x4 as *const u8);
-        builder.symbol("tan_f64x2x4", tan_f64x2x4 as *const u8);
-
-        builder.symbol("sin_f64x2", sin_f64x2 as *const u8);
-        builder.symbol("cos_f64x2", cos_f64x2 as *const u8);
-        builder.symbol("tan_f64x2", tan_f64x2 as *const u8);
-
-        builder.symbol("sin_intrvl", sin_intrvl as *const u8);
-        builder.symbol("cos_intrvl", cos_intrvl as *const u8);
-        builder.symbol("tan_intrvl", tan_intrvl as *const u8);
-        builder.symbol("add_intrvl", add_intrvl as *const u8);
-        builder.symbol("sub_intrvl", sub_intrvl as *const u8);
-        builder.symbol("mul_intrvl", mul_intrvl as *const u8);
-        builder.symbol("div_intrvl", div_intrvl as *const u8);
-        builder.symbol("pow_intrvl", pow_intrvl as *const u8);
-
-        imports.iter().for_each(|ex_fn| {
-            if let ExternFnPtr::Rust(ptr) = ex_fn.ptr {
-                builder.symbol(&ex_fn.name, ptr);
-            }
-        });
-
-        let mut module = JITModule::new(builder);
-        let mut ctx = module.make_context();
-        let fn_ctx = FunctionBuilderContext::new();
-
-        let mut glob_fn_table = FnTable(HashMap::default());
-
-        imports.into_iter().for_each(|mut ex_fn| {
-            ex_fn.sig.call_conv = module.isa().default_call_conv();
-
-            glob_fn_table.insert(
-                &ex_fn.name,
-                module
-                    .declare_function(&ex_fn.name, Linkage::Import, &ex_fn.sig)
-                    .unwrap(),
-            );
-        });
-
-        {
-            let name = "pow_f64";
-            let mut sig = module.make_signature();
-            sig.params.push(AbiParam::new(ir::types::F64));
-            sig.params.push(AbiParam::new(ir::types::F64));
-            sig.returns.push(AbiParam::new(ir::types::F64));
-            let id = module
-                .declare_function(name, Linkage::Import, &sig)
-                .unwrap();
-            glob_fn_table.insert(name, id);
-        }
-
-        {
-            let name = "sin_f64x2x2";
-            let mut sig = module.make_signature();
-
-            let ptr_ty = module.target_config().pointer_type();
-            let mut sret_param = AbiParam::new(ptr_ty);
-            sret_param.purpose = ir::ArgumentPurpose::StructReturn;
-            sig.params.push(sret_param);
-
-            sig.params.push(AbiParam::new(ir::types::F64X2));
-            sig.params.push(AbiParam::new(ir::types::F64X2));
-            let id = module
-                .declare_function(name, Linkage::Import, &sig)
-                .unwrap();
-            glob_fn_table.insert(name, id);
-        }
-        {
-            let name = "div_f64x2x2";
-            let mut sig = module.make_signature();
-
-            let ptr_ty = module.target_config().pointer_type();
-            let mut sret_param = AbiParam::new(ptr_ty);
-            sret_param.purpose = ir::ArgumentPurpose::StructReturn;
-            sig.params.push(sret_param);
-
-            sig.params.push(AbiParam::new(ir::types::F64X2));
-            sig.params.push(AbiParam::new(ir::types::F64X2));
-            sig.params.push(AbiParam::new(ir::types::F64X2));
-            sig.params.push(AbiParam::new(ir::types::F64X2));
-            let id = module
-                .declare_function(name, Linkage::Import, &sig)
-                .unwrap();
-            glob_fn_table.insert(name, id);
-        }
-        {
-            let name = "pow_f64x2";
-            let mut sig = module.make_signature();
-
-            let ptr_ty = module.target_config().pointer_type();
-            let mut sret_param = AbiParam::new(ptr_ty);
-            sret_param.purpose = ir::ArgumentPurpose::StructReturn;
-
-            sig.params.push(sret_param);
-            sig.params.push(AbiParam::new(ir::types::F64X2));
-            sig.params.push(AbiParam::new(ir::types::F64X2));
-
-            let id = module
-                .declare_function(name, Linkage::Import, &sig)
-                .unwrap();
-            glob_fn_table.insert(name, id);
-        }
-        {
-            let name = "pow_f64x2x2";
-            let mut sig = module.make_signature();
-
-            let ptr_ty = module.target_config().pointer_type();
-            let mut sret_param = AbiParam::new(ptr_ty);
-            sret_param.purpose = ir::ArgumentPurpose::StructReturn;
-            sig.params.push(sret_param);
-
-            sig.params.push(AbiParam::new(ir::types::F64X2));
-            sig.params.push(AbiParam::new(ir::types::F64X2));
-            sig.params.push(AbiParam::new(ir::types::F64X2));
-            sig.params.push(AbiParam::new(ir::types::F64X2));
-            let id = module
-                .declare_function(name, Linkage::Import, &sig)
-                .unwrap();
-            glob_fn_table.insert(name, id);
-        }
-
-        for name in ["sin_f64x2", "cos_f64x2", "tan_f64x2"] {
-            let mut sig = module.make_signature();
-            let ptr_ty = module.target_config().pointer_type();
-            // let mut sret_param = AbiParam::new(ptr_ty);
-            // sret_param.purpose = ir::ArgumentPurpose::StructReturn;
-            sig.params
-                .push(AbiParam::special(ptr_ty, ir::ArgumentPurpose::StructReturn));
-            sig.params.push(AbiParam::new(ir::types::F64X2));
-
-            let id = module
-                .declare_function(name, Linkage::Import, &sig)
-                .unwrap();
-            glob_fn_table.insert(name, id);
-        }
-
-        for name in ["sin_f64x2x4", "cos_f64x2x4", "tan_f64x2x4"] {
-            let mut sig = module.make_signature();
-            let ptr_ty = module.target_config().pointer_type();
-            let mut sret_param = AbiParam::new(ptr_ty);
-            sret_param.purpose = ir::ArgumentPurpose::StructReturn;
-            sig.params.push(sret_param);
-
-            sig.params.push(AbiParam::new(ir::types::F64X2));
-            sig.params.push(AbiParam::new(ir::types::F64X2));
-            sig.params.push(AbiParam::new(ir::types::F64X2));
-            sig.params.push(AbiParam::new(ir::types::F64X2));
-            let id = module
-                .declare_function(name, Linkage::Import, &sig)
-                .unwrap();
-            glob_fn_table.insert(name, id);
-        }
-
-        for name in ["sin_intrvl", "cos_intrvl", "tan_intrvl"] {
-            let mut sig = module.make_signature();
-            let ptr_ty = module.target_config().pointer_type();
-            // let mut sret_param = AbiParam::new(ptr_ty);
-            // sret_param.purpose = ir::ArgumentPurpose::StructReturn;
-            sig.params
-                .push(AbiParam::special(ptr_ty, ir::ArgumentPurpose::StructReturn));
-            sig.params.push(AbiParam::new(ir::types::F64X2));
-
-            let id = module
-                .declare_function(name, Linkage::Import, &sig)
-                .unwrap();
-            glob_fn_table.insert(name, id);
-        }
-        for name in [
-            "pow_intrvl",
-            "add_intrvl",
-            "mul_intrvl",
-            "div_intrvl",
-            "sub_intrvl",
-        ] {
-            // let name = "pow_intrvl";
-            let mut sig = module.make_signature();
-
-            let ptr_ty = module.target_config().pointer_type();
-            let mut sret_param = AbiParam::new(ptr_ty);
-            sret_param.purpose = ir::ArgumentPurpose::StructReturn;
-
-            sig.params.push(sret_param);
-            sig.params.push(AbiParam::new(ir::types::F64X2));
-            sig.params.push(AbiParam::new(ir::types::F64X2));
-
-            let id = module
-                .declare_function(name, Linkage::Import, &sig)
-                .unwrap();
-            glob_fn_table.insert(name, id);
-        }
-
-        // let unop_sig = make_sig!(module, (F64) -> (F64));
-
-        // ["sin", "cos", "tan"].into_iter().for_each(|name| {
-        //     let id = module
-        //         .declare_function(name, Linkage::Import, &unop_sig)
-        //         .expect("import: {name}");
-        //     glob_fn_table.insert(name, id);
-        // });
-        // let c_fn_table = ExternCFnTable::import(|name| {
-        //     module.declare_function(name, Linkage::Import, &unop_sig)
-        //         .expect("import: {name}")
-        // });
+/// random programs for the differential tests of the backends and passes
+#[cfg(test)]
+pub(crate) mod random {
+    use rand::{Rng, seq::IndexedRandom};
 
-        // let name = "print_f64";
-        // glob_fn_table.insert(
-        //     name,
-        //     module
-        //         .declare_function(name, Linkage::Import, &sig)
-        //         .unwrap(),
-        // );
+    use super::*;
 
-        Self {
-            module,
-            ctx,
-            fn_ctx,
-            glob_fn_table,
+    pub const UN_OPS: [UnOp; 13] = [
+        UnOp::MOV,
+        UnOp::SIN,
+        UnOp::COS,
+        UnOp::TAN,
+        UnOp::FLOOR,
+        UnOp::CEIL,
+        UnOp::ROUND,
+        UnOp::FRACT,
+        UnOp::GAMMA,
+        UnOp::ERF,
+        UnOp::J0,
+        UnOp::J1,
+        UnOp::LAMBERTW,
+    ];
+
+    pub const BIN_OPS: [BinOp; 10] = [
+        BinOp::ADD,
+        BinOp::SUB,
+        BinOp::MUL,
+        BinOp::DIV,
+        BinOp::POW,
+        BinOp::MIN,
+        BinOp::MAX,
+        BinOp::LT,
+        BinOp::LE,
+        BinOp::MOD,
+    ];
+
+    /// mostly registers below n_regs, otherwise a small or a round immediate
+    pub fn oprnd(rng: &mut impl Rng, n_regs: Reg) -> Oprnd {
+        match rng.random_range(0..6) {
+            0 => Oprnd::Imm(*[0.0, 1.0, 2.0, -1.0].choose(rng).unwrap()),
+            1 => Oprnd::Imm(rng.random_range(-5.0..5.0)),
+            _ => Oprnd::Reg(rng.random_range(0..n_regs)),
         }
     }
 
-    fn clear_ctx(&mut self) {
-        self.module.clear_context(&mut self.ctx);
-    }
-
-    pub fn compile_for_f64(
-        &mut self,
-        fn_name: &str,
-        bytecode: &[Instr],
-        config: &CompConfig,
-    ) -> CompOutput<extern "C" fn(f64, f64) -> f64> {
-        self.ctx.set_disasm(config.emit_asm);
-        self.ctx.func.signature = make_sig!(self.module, (F64, F64) -> (F64));
-
-        let mut fb = FunctionBuilder::new(&mut self.ctx.func, &mut self.fn_ctx);
-        let entry = fb.create_block();
-        fb.append_block_params_for_function_params(entry);
-        fb.switch_to_block(entry);
-        fb.seal_block(entry);
-
-        let loc_fns = self.glob_fn_table.decl_in_func(&mut self.module, fb.func);
-
-        let mut regs = vec![];
-        for i in 0..16 {
-            let v = init_var!(fb, i: F64 = 0.0);
-            regs.push(v);
-        }
-
-        let param = fb.block_params(entry)[0];
-        fb.def_var(regs[0], param);
-        let param = fb.block_params(entry)[1];
-        fb.def_var(regs[1], param);
-
-        for instr in bytecode {
-            match *instr {
-                Instr::UnOp { op, val, dst } => {
-                    let val = oprnd_val!(val, regs, fb);
-                    match op {
-                        UnOp::MOV => {
-                            fb.def_var(regs[dst as usize], val);
-                            // continue;
-                        }
-                        c_op => {
-                            let name = c_op.c_fn_name().unwrap();
-                            let fn_ref = loc_fns[name];
-                            let call = fb.ins().call(fn_ref, &[val]);
-                            let res = fb.inst_results(call)[0];
-                            fb.def_var(regs[dst as usize], res);
-                        } // UnOp::SIN => "sin",
-                          // UnOp::COS => "cos",
-                          // UnOp::TAN => "tan",
-                    };
-                    // let fn_id = self.glob_fn_table.get_unop(op);
-                }
-                Instr::BinOp { op, lhs, rhs, dst } => {
-                    let lhs = oprnd_val!(lhs, regs, fb);
-                    let rhs = oprnd_val!(rhs, regs, fb);
-                    let res = match op {
-                        BinOp::ADD => fb.ins().fadd(lhs, rhs),
-                        BinOp::SUB => fb.ins().fsub(lhs, rhs),
-                        BinOp::MUL => fb.ins().fmul(lhs, rhs),
-                        BinOp::DIV => fb.ins().fdiv(lhs, rhs),
-                        BinOp::POW => {
-                            let fn_ref = loc_fns["pow_f64"];
-                            let call = fb.ins().call(fn_ref, &[lhs, rhs]);
-                            fb.inst_results(call)[0]
-                        }
-                    };
-                    fb.def_var(regs[dst as usize], res);
-                }
+    pub fn instr(rng: &mut impl Rng, n_regs: Reg, dst: Reg) -> Instr {
+        if rng.random_bool(0.1) {
+            Instr::Select {
+                cond: oprnd(rng, n_regs),
+                lhs: oprnd(rng, n_regs),
+                rhs: oprnd(rng, n_regs),
+                dst,
             }
-        }
-
-        let ret = fb.use_var(regs[0]);
-
-        fb.ins().return_(&[ret]);
-        fb.finalize();
-
-        let fn_id = self
-            .module
-            .declare_function(fn_name, Linkage::Local, &self.ctx.func.signature)
-            .unwrap();
-        self.module.define_function(fn_id, &mut self.ctx).unwrap();
-        self.module.finalize_definitions().unwrap();
-        let asm = self.ctx.compiled_code().unwrap().vcode.clone();
-
-        self.clear_ctx();
-
-        let fn_ptr = self.module.get_finalized_function(fn_id);
-
-        CompOutput {
-            fn_id,
-            fn_ptr: unsafe { std::mem::transmute(fn_ptr) },
-            asm,
-        }
-    }
-
-    pub fn compile_for_intrvl(
-        &mut self,
-        fn_name: &str,
-        bytecode: &[Instr],
-        config: &CompConfig,
-    ) -> CompOutput<extern "C" fn(*mut [f64; 2], [f64; 2], [f64; 2])> {
-        self.ctx.set_disasm(config.emit_asm);
-        let vec_ty = ir::types::F64X2;
-        let ptr_ty = self.module.target_config().pointer_type();
-
-        let mut variable_id = 0;
-        let mut new_var = || {
-            variable_id += 1;
-            JITVar::from_u32(variable_id)
-        };
-
-        let mut sig = self.module.make_signature();
-        sig.call_conv = isa::CallConv::SystemV;
-        let triple = target_lexicon::Triple::host();
-        // sig.call_conv = isa::CallConv::triple_default(&triple);
-        sig.params.push(ir::AbiParam::special(
-            ptr_ty,
-            ir::ArgumentPurpose::StructReturn,
-        ));
-        sig.params.push(ir::AbiParam::new(vec_ty));
-        sig.params.push(ir::AbiParam::new(vec_ty));
-        // sig.returns.push(ir::AbiParam::new(vec_ty));
-        self.ctx.func.signature = sig;
-
-        let mut fb = FunctionBuilder::new(&mut self.ctx.func, &mut self.fn_ctx);
-        let entry = fb.create_block();
-        fb.append_block_params_for_function_params(entry);
-        fb.switch_to_block(entry);
-        fb.seal_block(entry);
-
-        let loc_fns = self.glob_fn_table.decl_in_func(&mut self.module, fb.func);
-
-        let out_ptr = fb.block_params(entry)[0];
-        let a = fb.block_params(entry)[1];
-        let b = fb.block_params(entry)[2];
-
-        let zero_f64 = fb.ins().f64const(0.0);
-        let zero_f64x2 = fb.ins().splat(vec_ty, zero_f64);
-
-        let mut regs = vec![];
-        for _ in 0..16 {
-            let r = new_var();
-            fb.declare_var(r, vec_ty);
-            fb.def_var(r, zero_f64x2);
-            regs.push(r);
-        }
-
-        let use_oprnd = |oprnd: Oprnd, fb: &mut FunctionBuilder| match oprnd {
-            Oprnd::Reg(indx) => fb.use_var(regs[indx as usize]),
-            Oprnd::Imm(imm) => {
-                let imm = fb.ins().f64const(imm);
-                fb.ins().splat(vec_ty, imm)
+        } else if rng.random_bool(0.3) {
+            Instr::UnOp {
+                op: *UN_OPS.choose(rng).unwrap(),
+                val: oprnd(rng, n_regs),
+                dst,
             }
-        };
-
-        let print_fn = loc_fns["print_f64"];
-        let print2_fn = loc_fns["print_f64x2"];
-
-        let print = |v: f64, fb: &mut FunctionBuilder| {
-            let v = fb.ins().f64const(v);
-            let _ = fb.ins().call(print_fn, &[v]);
-        };
-
-        let print2 = |a: ir::Value, fb: &mut FunctionBuilder| {
-            let _ = fb.ins().call(print2_fn, &[a]);
-        };
-
-        fb.def_var(regs[0], a);
-        fb.def_var(regs[1], b);
-
-        print(1.0, &mut fb);
-        print2(a, &mut fb);
-        print2(b, &mut fb);
-
-        let ret_slot = fb.create_sized_stack_slot(ir::StackSlotData {
-            kind: ir::StackSlotKind::ExplicitSlot,
-            size: 8 * 2 as u32,
-            align_shift: 4,
-        });
-
-        for instr in bytecode {
-            match *instr {
-                Instr::UnOp { op, val, dst } => {
-                    let dst = dst as usize;
-                    let val = use_oprnd(val, &mut fb);
-                    print(2.0, &mut fb);
-                    print2(val, &mut fb);
-
-                    match op {
-                        UnOp::MOV => {
-                            fb.def_var(regs[dst], val);
-                        }
-                        op => {
-                            let name = op.c_fn_name().unwrap();
-                            let fn_ref = loc_fns[&format!("{name}_intrvl")];
-                            let ret_addr = fb.ins().stack_addr(ptr_ty, ret_slot, 0);
-                            let _ = fb.ins().call(fn_ref, &[ret_addr, val]);
-                            let res = fb.ins().load(vec_ty, ir::MemFlags::new(), ret_addr, 0);
-                            print(2.5, &mut fb);
-                            print2(res, &mut fb);
-                            fb.def_var(regs[dst], res);
-                        }
-                    }
-                }
-                Instr::BinOp { op, lhs, rhs, dst } => {
-                    let dst = dst as usize;
-                    let lhs = use_oprnd(lhs, &mut fb);
-                    let rhs = use_oprnd(rhs, &mut fb);
-
-                    print(3.0, &mut fb);
-                    print2(lhs, &mut fb);
-                    print2(rhs, &mut fb);
-
-                    let fn_name = match op {
-                        BinOp::ADD => "add_intrvl",
-                        BinOp::SUB => "sub_intrvl",
-                        BinOp::MUL => "mul_intrvl",
-                        BinOp::DIV => "div_intrvl",
-                        BinOp::POW => "pow_intrvl",
-                    };
-
-                    let fn_ref = loc_fns[fn_name];
-                    let ret_addr = fb.ins().stack_addr(ptr_ty, ret_slot, 0);
-                    let _ = fb.ins().call(fn_ref, &[ret_addr, lhs, rhs]);
-                    let res = fb.ins().load(vec_ty, ir::MemFlags::new(), ret_addr, 0);
-
-                    print(3.5, &mut fb);
-                    print2(res, &mut fb);
-                    // let res = match op {
-                    //     BinOp::ADD => fb.ins().fadd(lhs, rhs),
-                    //     BinOp::SUB => fb.ins().fsub(lhs, rhs),
-                    //     BinOp::MUL => fb.ins().fmul(lhs, rhs),
-                    //     BinOp::DIV => fb.ins().fdiv(lhs, rhs),
-                    //     BinOp::POW => {
-                    //         let fn_ref = loc_fns["pow_f64x2"];
-                    //         let ret_addr = fb.ins().stack_addr(ptr_ty, ret_slot, 0);
-                    //         let _ = fb.ins().call(fn_ref, &[ret_addr, lhs, rhs]);
-                    //         fb.ins()
-                    //             .load(vec_ty, ir::MemFlags::new().with_aligned(), ret_addr, 0)
-                    //     }
-                    // };
-                    fb.def_var(regs[dst], res);
-                }
+        } else {
+            Instr::BinOp {
+                op: *BIN_OPS.choose(rng).unwrap(),
+                lhs: oprnd(rng, n_regs),
+                rhs: oprnd(rng, n_regs),
+                dst,
             }
         }
-
-        let ret = fb.use_var(regs[0]);
-
-        print(4.0, &mut fb);
-        print2(ret, &mut fb);
-
-        let store_flags = ir::MemFlags::new();
-        fb.ins().store(store_flags, ret, out_ptr, 0);
-        // fb.ins().return_(&[ret]);
-        fb.ins().return_(&[]);
-
-        fb.finalize();
-
-        let fn_id = self
-            .module
-            .declare_function(fn_name, Linkage::Local, &self.ctx.func.signature)
-            .unwrap();
-        self.module.define_function(fn_id, &mut self.ctx).unwrap();
-        self.module.finalize_definitions().unwrap();
-        let asm = self.ctx.compiled_code().unwrap().vcode.clone();
-
-        self.clear_ctx();
-        let fn_ptr = self.module.get_finalized_function(fn_id);
-
-        CompOutput {
-            fn_id,
-            fn_ptr: unsafe { std::mem::transmute(fn_ptr) },
-            asm,
-        }
     }
 
-    pub fn compile_for_f64x2(
-        &mut self,
-        fn_name: &str,
-        bytecode: &[Instr],
-        config: &CompConfig,
-    ) -> CompOutput<extern "C" fn(&mut [f64; 2], [f64; 2], [f64; 2])> {
-        self.ctx.set_disasm(config.emit_asm);
-        let vec_ty = ir::types::F64X2;
-        let ptr_ty = self.module.target_config().pointer_type();
-
-        let mut variable_id = 0;
-        let mut new_var = || {
-            variable_id += 1;
-            JITVar::from_u32(variable_id)
-        };
-
-        let mut sig = self.module.make_signature();
-        // let triple = target_lexicon::Triple::host();
-        // sig.call_conv = isa::CallConv::triple_default(&triple);
-
-        sig.params.push(ir::AbiParam::special(
-            ptr_ty,
-            ir::ArgumentPurpose::StructReturn,
-        ));
-        sig.params.push(ir::AbiParam::new(vec_ty));
-        sig.params.push(ir::AbiParam::new(vec_ty));
-        // sig.params.push(ir::AbiParam::new(vec_ty));
-        self.ctx.func.signature = sig;
-
-        let mut fb = FunctionBuilder::new(&mut self.ctx.func, &mut self.fn_ctx);
-        let entry = fb.create_block();
-        fb.append_block_params_for_function_params(entry);
-        fb.switch_to_block(entry);
-        fb.seal_block(entry);
-
-        let loc_fns = self.glob_fn_table.decl_in_func(&mut self.module, fb.func);
-
-        let out_ptr = fb.block_params(entry)[0];
-        let a = fb.block_params(entry)[1];
-        let b = fb.block_params(entry)[2];
-
-        let zero_f64 = fb.ins().f64const(0.0);
-        let zero_f64x2 = fb.ins().splat(vec_ty, zero_f64);
-
-        let mut regs = vec![];
-        for _ in 0..16 {
-            let r = new_var();
-            fb.declare_var(r, vec_ty);
-            fb.def_var(r, zero_f64x2);
-            regs.push(r);
-        }
-
-        let use_oprnd = |oprnd: Oprnd, fb: &mut FunctionBuilder| match oprnd {
-            Oprnd::Reg(indx) => fb.use_var(regs[indx as usize]),
-            Oprnd::Imm(imm) => {
-                let imm = fb.ins().f64const(imm);
-                fb.ins().splat(vec_ty, imm)
-            }
-        };
-
-        fb.def_var(regs[0], a);
-        fb.def_var(regs[1], b);
-
-        let ret_slot = fb.create_sized_stack_slot(ir::StackSlotData {
-            kind: ir::StackSlotKind::ExplicitSlot,
-            size: 8 * vec_ty.lane_count() as u32,
-            align_shift: 0,
-        });
-
-        for instr in bytecode {
-            match *instr {
-                Instr::UnOp { op, val, dst } => {
-                    let dst = dst as usize;
-                    let val = use_oprnd(val, &mut fb);
-                    match op {
-                        UnOp::MOV => {
-                            fb.def_var(regs[dst], val);
-                        }
-                        op => {
-                            let name = op.c_fn_name().unwrap();
-                            let fn_ref = loc_fns[&format!("{name}_f64x2")];
-                            let ret_addr = fb.ins().stack_addr(ptr_ty, ret_slot, 0);
-                            let _ = fb.ins().call(fn_ref, &[ret_addr, val]);
-                            let res = fb.ins().load(vec_ty, ir::MemFlags::new(), ret_addr, 0);
-                            fb.def_var(regs[dst], res);
-                        }
-                    }
-                }
-                Instr::BinOp { op, lhs, rhs, dst } => {
-                    let dst = dst as usize;
-                    let lhs = use_oprnd(lhs, &mut fb);
-                    let rhs = use_oprnd(rhs, &mut fb);
-                    let res = match op {
-                        BinOp::ADD => fb.ins().fadd(lhs, rhs),
-                        BinOp::SUB => fb.ins().fsub(lhs, rhs),
-                        BinOp::MUL => fb.ins().fmul(lhs, rhs),
-                        BinOp::DIV => fb.ins().fdiv(lhs, rhs),
-                        BinOp::POW => {
-                            let fn_ref = loc_fns["pow_f64x2"];
-                            let ret_addr = fb.ins().stack_addr(ptr_ty, ret_slot, 0);
-                            let _ = fb.ins().call(fn_ref, &[ret_addr, lhs, rhs]);
-                            fb.ins().load(vec_ty, ir::MemFlags::new(), ret_addr, 0)
-                        }
-                    };
-                    fb.def_var(regs[dst], res);
-                }
-            }
-        }
-
-        let ret = fb.use_var(regs[0]);
-
-        // fb.ins().return_(&[ret]);
-        let store_flags = ir::MemFlags::new();
-        fb.ins().store(store_flags, ret, out_ptr, 0);
-        fb.ins().return_(&[]);
-
-        fb.finalize();
-
-        let fn_id = self
-            .module
-            .declare_function(fn_name, Linkage::Local, &self.ctx.func.signature)
-            .unwrap();
-        self.module.define_function(fn_id, &mut self.ctx).unwrap();
-        self.module.finalize_definitions().unwrap();
-        let asm = self.ctx.compiled_code().unwrap().vcode.clone();
-
-        self.clear_ctx();
-        let fn_ptr = self.module.get_finalized_function(fn_id);
-
-        CompOutput {
-            fn_id,
-            fn_ptr: unsafe { std::mem::transmute(fn_ptr) },
-            asm,
-        }
-    }
-
-    // TODO: config kernel, slice, name, flags, etc...
-
-    fn supported_f64_simd_typ(&self) -> ir::Type {
-        let n_lanes = self.module.isa().dynamic_vector_bytes(ir::types::F64) / 8;
-        match n_lanes {
-            1 => ir::types::F64,
-            2 => ir::types::F64X2,
-            4 => ir::types::F64X4,
-            8 => ir::types::F64X8,
-            _ => panic!(),
-        }
+    /// len instructions on the registers below n_regs returning register 0
+    pub fn program(rng: &mut impl Rng, len: usize, n_regs: Reg) -> Program {
+        let bytecode: Vec<_> = (0..len)
+            .map(|_| {
+                let dst = rng.random_range(0..n_regs);
+                instr(rng, n_regs, dst)
+            })
+            .collect();
+        Program::from(bytecode)
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use rand;
+    use crate::jit2::{F64X2, JIT};
 
     #[test]
     fn compile_f64() {
@@ -1385,64 +466,12 @@ mod test {
             POW[0, 0] -> 0,
         };
 
-        let mut jit = JITCompiler::init();
-        let config = CompConfig::default();
-        let f = jit.compile_for_f64("jit_fn", &program, &config).fn_ptr;
-        // let f: extern "C" fn(f64, f64) -> f64 = unsafe { std::mem::transmute(fn_ptr) };
-        let res = f(2.0, 3.0);
+        let jit = JIT::init();
+        let f = jit.compile::<f64>("jit_fn", &Program::from(program.to_vec()), 2);
+        let res = f.eval(&[2.0, 3.0]);
         assert_eq!(res, 5.1f64.powf(5.1));
     }
 
-    // #[test]
-    // fn compile_f64x2x4() {
-    //     // let program = bytecode! {
-    //     //     ADD[0, 1] -> 0,
-    //     //     SIN[0] -> 0,
-    //     //     ADD[imm(0.1), 0] -> 0,
-    //     // };
-    //     let program = bytecode! [
-    //         DIV[imm(1.0), 0] -> 0,
-    //         DIV[imm(1.0), 1] -> 1,
-    //         SIN[0] -> 2,
-    //         SIN[1] -> 3,
-    //         ADD[2, 3] -> 2,
-    //         SIN[2] -> 2,
-    //         MUL[0, 1] -> 4,
-    //         SIN[4] -> 4,
-    //         SIN[0] -> 5,
-    //         ADD[4, 5] -> 4,
-    //         SIN[4] -> 4,
-    //         SUB[2, 4] -> 0,
-    //     ];
-
-    //     let mut jit = JITCompiler::init();
-    //     let config = CompConfig::default();
-    //     let f = jit.compile_for_f64("jit_fn", &program, &config).fn_ptr;
-    //     let f_simd = jit
-    //         .compile_for_f64x2x4("jit_fn_simd", &program, &config)
-    //         .fn_ptr;
-
-    //     let a = [0.0; 1024].map(|_| rand::random());
-    //     let b = [0.0; 1024].map(|_| rand::random());
-    //     let mut out = [0.0; 1024];
-
-    //     for i in (0..a.len()).step_by(8) {
-    //         let x: [f64; 8] = a[i..i + 8].try_into().unwrap();
-    //         let y: [f64; 8] = b[i..i + 8].try_into().unwrap();
-    //         let mut o = [0.0; 8];
-    //         f_simd(&x, &y, &mut o);
-    //         out[i..i + 8].copy_from_slice(&o);
-    //     }
-
-    //     for i in 0..a.len() {
-    //         let o1 = out[i];
-    //         let o2 = f(a[i], b[i]);
-    //         assert!((o1 - o2).abs() > f32::EPSILON as f64, "{o1} == {o2}");
-    //     }
-    //     // let f: extern "C" fn(f64, f64) -> f64 = unsafe { std::mem::transmute(fn_ptr) };
-    //     // assert_eq!(res, 5.1);
-    // }
-
     #[test]
     fn compile_f64x2() {
         let program = bytecode! [
@@ -1461,29 +490,94 @@ mod test {
             SUB[2, 4] -> 0,
         ];
 
-        let config = CompConfig::default();
+        let program = Program::from(program.to_vec());
 
-        let mut jit = JITCompiler::init();
-        let f_f64 = jit.compile_for_f64("f_f64", &program, &config).fn_ptr;
-        let f_f64x2 = jit.compile_for_f64x2("f_f64x2", &program, &config).fn_ptr;
+        let jit = JIT::init();
+        let f_f64 = jit.compile::<f64>("f_f64", &program, 2);
+        let f_f64x2 = jit.compile::<F64X2>("f_f64x2", &program, 2);
 
         let a = [0.0; 1028].map(|_| rand::random());
         let b = [0.0; 1028].map(|_| rand::random());
         let mut out = [0.0; 1028];
 
         for i in (0..a.len()).step_by(2) {
-            let x = [a[i], a[i + 1]];
-            let y = [b[i], b[i + 1]];
-            let mut o = [0.0; 2];
-            f_f64x2(&mut o, x, y);
-            out[i] = o[0];
-            out[i + 1] = o[1];
+            let x = F64X2(a[i], a[i + 1]);
+            let y = F64X2(b[i], b[i + 1]);
+            let o = f_f64x2.eval(&[x, y]);
+            out[i] = o.0;
+            out[i + 1] = o.1;
         }
 
         for ((x, y), o) in a.into_iter().zip(b).zip(out) {
             let o1 = o;
-            let o2 = f_f64(x, y);
+            let o2 = f_f64.eval(&[x, y]);
             assert!((o1 - o2).abs() < f32::EPSILON as f64, "{o1} vs {o2}");
         }
     }
+
+    #[test]
+    fn compile_select() {
+        // x < y ? x : y - 1
+        let program = Program::from(
+            bytecode! [
+                LT[0, 1] -> 2,
+                SUB[1, imm(1.0)] -> 3,
+                SEL[2, 0, 3] -> 0,
+            ]
+            .to_vec(),
+        );
+        assert_eq!(
+            program.to_string().parse::<Program>().unwrap(),
+            program,
+            "{program}"
+        );
+
+        let jit = JIT::init();
+        let f_f64 = jit.compile::<f64>("f_f64", &program, 2);
+        let f_f64x2 = jit.compile::<F64X2>("f_f64x2", &program, 2);
+
+        assert_eq!(f_f64.eval(&[1.0, 3.0]), 1.0);
+        assert_eq!(f_f64.eval(&[3.0, 1.0]), 0.0);
+        assert_eq!(f_f64.eval(&[3.0, 3.0]), 2.0);
+        let res = f_f64x2.eval(&[F64X2(1.0, 3.0), F64X2(3.0, 1.0)]);
+        assert_eq!((res.0, res.1), (1.0, 0.0));
+    }
+
+    #[test]
+    fn asm_round_trip() {
+        let program = Program::with_outputs(
+            bytecode! [
+                MUL[0, 1] -> 2,
+                ADD[2, imm(-0.1)] -> 2,
+                SIN[2] -> 0,
+                POW[imm(2.5), imm(1e-7)] -> 1,
+            ]
+            .to_vec(),
+            vec![0, 2],
+        );
+
+        let text = program.to_string();
+        assert_eq!(text.parse::<Program>().unwrap(), program);
+        assert_eq!(program.to_asm(&Labels::default()), text + "\n");
+
+        let labels = Labels::new([("x", 0), ("y", 1), ("xy", 2)]);
+        let asm = program.to_asm(&labels);
+        assert_eq!(
+            asm,
+            "reg x = 0\nreg y = 1\nreg xy = 2\n\
+             MUL[x, y] -> xy\n\
+             ADD[xy, -0.1_f] -> xy\n\
+             SIN[xy] -> x\n\
+             POW[2.5_f, 0.0000001_f] -> y\n\
+             ret x, xy\n"
+        );
+        assert_eq!(Program::from_asm(&asm).unwrap(), program);
+
+        let err = |src| Program::from_asm(src).unwrap_err().msg;
+        assert_eq!(err("SIN[0, 1] -> 2"), "wrong operand count for SIN");
+        assert_eq!(err("ABS[0] -> 2"), "unknown instruction `ABS`");
+        assert_eq!(err("SIN[256] -> 2"), "register 256 out of range");
+        assert_eq!(err("SIN[0]"), "missing `-> dst`");
+        assert_eq!(err("ret 1_f"), "expected register, got 1_f");
+    }
 }
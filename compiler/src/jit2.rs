@@ -1,7 +1,11 @@
 use std::{
-    cell::{RefCell, RefMut},
-    fmt, ops,
-    sync::Arc,
+    cell::{Ref, RefCell},
+    fmt, fs,
+    hash::{Hash, Hasher},
+    io::{self, Write as _},
+    ops,
+    path::{Path, PathBuf},
+    sync::{OnceLock, atomic},
 };
 
 use cranelift::prelude::*;
@@ -9,9 +13,10 @@ use cranelift_codegen::ir;
 use cranelift_jit::{JITBuilder, JITModule};
 use cranelift_module::{FuncId, Linkage, Module};
 
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHasher};
 
-use crate::jit::{BinOp, Instr, Oprnd, UnOp};
+use crate::jit::{BinOp, Instr, Oprnd, Program, UnOp};
+use utils::Complex;
 
 macro_rules! extrn {
     ($($tt:tt)*) => {
@@ -34,6 +39,7 @@ const fn min_max_4(a: f64, b: f64, c: f64, d: f64) -> (f64, f64) {
 }
 
 #[derive(Debug, Default, PartialEq, Copy, Clone)]
+#[repr(C)]
 pub struct Intrvl {
     pub lo: f64,
     pub hi: f64,
@@ -127,6 +133,14 @@ impl Intrvl {
     pub fn ln(self) -> Self {
         Self::of_ln(self)
     }
+    #[inline(always)]
+    pub fn fract(self) -> Self {
+        Self::of_fract(self)
+    }
+    #[inline(always)]
+    pub fn modulo(self, other: Self) -> Self {
+        Self::of_mod(self, other)
+    }
 
     #[inline(always)]
     pub fn of_sin(a: Intrvl) -> Self {
@@ -329,6 +343,37 @@ impl Intrvl {
         (a.lo.ln(), a.hi.ln()).into()
     }
 
+    /// x - floor(x), [0, 1] if an integer lies inside
+    #[inline(always)]
+    pub fn of_fract(a: Intrvl) -> Self {
+        if a.is_empty() {
+            return a;
+        }
+
+        let k = a.lo.floor();
+        if k == a.hi.floor() {
+            (a.lo - k, a.hi - k).into()
+        } else {
+            (0.0, 1.0).into()
+        }
+    }
+
+    /// a - b floor(a / b), between 0 and b if a / b crosses an integer
+    #[inline(always)]
+    pub fn of_mod(a: Intrvl, b: Intrvl) -> Self {
+        if a.is_empty() || b.is_empty() || b.contains_zero() {
+            return Self::UNDEF;
+        }
+
+        if b.lo == b.hi {
+            let k = (a.lo / b.lo).floor();
+            if k == (a.hi / b.lo).floor() {
+                return (a.lo - b.lo * k, a.hi - b.lo * k).into();
+            }
+        }
+        (b.lo.min(0.0), b.hi.max(0.0)).into()
+    }
+
     #[inline(always)]
     pub fn from_tuple(b: (f64, f64)) -> Self {
         Self::new(b.0, b.1)
@@ -397,6 +442,18 @@ pub(crate) type FnDecl = (
     &'static [FnParam],
 );
 
+/// defines `extern "C" fn $name(out: *mut F64X2, $arg: *const F64X2, ..)`, see [`FnParam::F64X2`]
+macro_rules! f64x2_fn {
+    ($vis:vis fn $name:ident($($arg:ident),*) $body:block) => {
+        $vis unsafe extern "C" fn $name(out: *mut F64X2, $($arg: *const F64X2),*) {
+            unsafe {
+                $(let $arg = $arg.read();)*
+                out.write($body)
+            }
+        }
+    };
+}
+
 mod f64_util {
     use super::*;
 
@@ -427,6 +484,12 @@ mod f64_util {
             &[FnParam::F64, FnParam::F64],
             &[FnParam::F64],
         ),
+        (
+            "ln_f64",
+            f64::ln as *const u8,
+            &[FnParam::F64],
+            &[FnParam::F64],
+        ),
         (
             "sin_f64",
             f64::sin as *const u8,
@@ -543,94 +606,116 @@ mod intrvl_util {
 
     use super::*;
 
-    pub extern "C" fn add(l: F64X2, r: F64X2) -> F64X2 {
+    f64x2_fn!(pub fn add(l, r) {
         let intrvl = Intrvl::new(l.0, l.1).add(Intrvl::new(r.0, r.1));
         F64X2(intrvl.lo, intrvl.hi)
-    }
+    });
 
-    pub extern "C" fn sub(l: F64X2, r: F64X2) -> F64X2 {
+    f64x2_fn!(pub fn sub(l, r) {
         let intrvl = Intrvl::new(l.0, l.1).sub(Intrvl::new(r.0, r.1));
         F64X2(intrvl.lo, intrvl.hi)
-    }
+    });
 
-    pub extern "C" fn mul(l: F64X2, r: F64X2) -> F64X2 {
+    f64x2_fn!(pub fn mul(l, r) {
         let intrvl = Intrvl::new(l.0, l.1).mul(Intrvl::new(r.0, r.1));
         F64X2(intrvl.lo, intrvl.hi)
-    }
+    });
 
-    pub extern "C" fn div(l: F64X2, r: F64X2) -> F64X2 {
+    f64x2_fn!(pub fn div(l, r) {
         let intrvl = Intrvl::new(l.0, l.1).div(Intrvl::new(r.0, r.1));
         F64X2(intrvl.lo, intrvl.hi)
-    }
+    });
 
-    pub extern "C" fn pow(b: F64X2, e: F64X2) -> F64X2 {
+    f64x2_fn!(pub fn pow(b, e) {
         let intrvl = Intrvl::new(b.0, b.1).pow(Intrvl::new(e.0, e.1));
         F64X2(intrvl.lo, intrvl.hi)
-    }
+    });
 
-    pub extern "C" fn sin(v: F64X2) -> F64X2 {
+    f64x2_fn!(pub fn sin(v) {
         let intrvl = Intrvl::new(v.0, v.1).sin();
         F64X2(intrvl.lo, intrvl.hi)
-    }
+    });
 
-    pub extern "C" fn cos(v: F64X2) -> F64X2 {
+    f64x2_fn!(pub fn cos(v) {
         let intrvl = Intrvl::new(v.0, v.1).cos();
         F64X2(intrvl.lo, intrvl.hi)
-    }
+    });
 
-    pub extern "C" fn tan(v: F64X2) -> F64X2 {
+    f64x2_fn!(pub fn tan(v) {
         let intrvl = Intrvl::new(v.0, v.1).tan();
         F64X2(intrvl.lo, intrvl.hi)
-    }
+    });
+
+    f64x2_fn!(pub fn fract(v) {
+        let intrvl = Intrvl::new(v.0, v.1).fract();
+        F64X2(intrvl.lo, intrvl.hi)
+    });
+
+    f64x2_fn!(pub fn modulo(l, r) {
+        let intrvl = Intrvl::new(l.0, l.1).modulo(Intrvl::new(r.0, r.1));
+        F64X2(intrvl.lo, intrvl.hi)
+    });
 
     pub const GLOB_FN_DECLS: &'static [FnDecl] = &[
         (
             "add_intrvl",
             add as *const u8,
-            &[FnParam::SRet, FnParam::F64X2, FnParam::F64X2],
-            &[],
+            &[FnParam::F64X2, FnParam::F64X2],
+            &[FnParam::F64X2],
         ),
         (
             "sub_intrvl",
             sub as *const u8,
-            &[FnParam::SRet, FnParam::F64X2, FnParam::F64X2],
-            &[],
+            &[FnParam::F64X2, FnParam::F64X2],
+            &[FnParam::F64X2],
         ),
         (
             "mul_intrvl",
             mul as *const u8,
-            &[FnParam::SRet, FnParam::F64X2, FnParam::F64X2],
-            &[],
+            &[FnParam::F64X2, FnParam::F64X2],
+            &[FnParam::F64X2],
         ),
         (
             "div_intrvl",
             div as *const u8,
-            &[FnParam::SRet, FnParam::F64X2, FnParam::F64X2],
-            &[],
+            &[FnParam::F64X2, FnParam::F64X2],
+            &[FnParam::F64X2],
         ),
         (
             "pow_intrvl",
             pow as *const u8,
-            &[FnParam::SRet, FnParam::F64X2, FnParam::F64X2],
-            &[],
+            &[FnParam::F64X2, FnParam::F64X2],
+            &[FnParam::F64X2],
         ),
         (
             "sin_intrvl",
             sin as *const u8,
-            &[FnParam::SRet, FnParam::F64X2],
-            &[],
+            &[FnParam::F64X2],
+            &[FnParam::F64X2],
         ),
         (
             "cos_intrvl",
             cos as *const u8,
-            &[FnParam::SRet, FnParam::F64X2],
-            &[],
+            &[FnParam::F64X2],
+            &[FnParam::F64X2],
         ),
         (
             "tan_intrvl",
             tan as *const u8,
-            &[FnParam::SRet, FnParam::F64X2],
-            &[],
+            &[FnParam::F64X2],
+            &[FnParam::F64X2],
+        ),
+        (
+            "fract_intrvl",
+            fract as *const u8,
+            &[FnParam::F64X2],
+            &[FnParam::F64X2],
+        ),
+        (
+            "mod_intrvl",
+            modulo as *const u8,
+            &[FnParam::F64X2, FnParam::F64X2],
+            &[FnParam::F64X2],
         ),
     ];
 }
@@ -638,286 +723,1330 @@ mod intrvl_util {
 mod f64x2_util {
     use super::*;
 
-    pub extern "C" fn pow_f64x2(b: F64X2, e: F64X2) -> F64X2 {
+    f64x2_fn!(pub fn pow_f64x2(b, e) {
         b.pow(&e)
-    }
+    });
 
-    pub extern "C" fn sin_f64x2(v: F64X2) -> F64X2 {
+    f64x2_fn!(pub fn sin_f64x2(v) {
         v.sin()
-    }
+    });
 
-    pub extern "C" fn cos_f64x2(v: F64X2) -> F64X2 {
+    f64x2_fn!(pub fn cos_f64x2(v) {
         v.cos()
-    }
+    });
 
-    pub extern "C" fn tan_f64x2(v: F64X2) -> F64X2 {
+    f64x2_fn!(pub fn tan_f64x2(v) {
         v.tan()
-    }
+    });
 
     pub const GLOB_FN_DECLS: &'static [FnDecl] = &[
         (
             "pow_f64x2",
             pow_f64x2 as *const u8,
-            &[FnParam::SRet, FnParam::F64X2, FnParam::F64X2],
-            &[],
+            &[FnParam::F64X2, FnParam::F64X2],
+            &[FnParam::F64X2],
         ),
         (
             "sin_f64x2",
             sin_f64x2 as *const u8,
-            &[FnParam::SRet, FnParam::F64X2],
-            &[],
+            &[FnParam::F64X2],
+            &[FnParam::F64X2],
         ),
         (
             "cos_f64x2",
             cos_f64x2 as *const u8,
-            &[FnParam::SRet, FnParam::F64X2],
-            &[],
+            &[FnParam::F64X2],
+            &[FnParam::F64X2],
         ),
         (
             "tan_f64x2",
             tan_f64x2 as *const u8,
-            &[FnParam::SRet, FnParam::F64X2],
-            &[],
+            &[FnParam::F64X2],
+            &[FnParam::F64X2],
         ),
     ];
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-enum FnParam {
-    I8,
-    F64,
-    F64X2,
-    SRet,
-}
-
-type FnRefTable = FxHashMap<&'static str, ir::FuncRef>;
-
-pub struct JIT<'a> {
-    pub builder_ctx: FunctionBuilderContext,
-    pub ctx: RefCell<cranelift_codegen::Context>,
-    pub module: RefCell<JITModule>,
-    pub emit_asm: bool,
-
-    pub glob_fns: FxHashMap<&'static str, FuncId>,
-    pub asm: RefCell<Option<String>>,
+mod complex_util {
+    use super::*;
 
-    // calling a compiled function after dropping this struct would be invalid
-    _fn_ptr_lifetime: std::marker::PhantomData<&'a ()>,
-}
+    pub unsafe extern "C" fn pow(out: *mut Complex, b_re: f64, b_im: f64, e_re: f64, e_im: f64) {
+        let z = Complex::new(b_re, b_im).pow(Complex::new(e_re, e_im));
+        unsafe { out.write(z) }
+    }
 
-impl<'a> ops::Drop for JIT<'a> {
-    fn drop(&mut self) {
-        // we restrict the lifetime of returned pointers or require unsafe from the user
-        let module = self.module.replace(JITModule::new(
-            JITBuilder::new(cranelift_module::default_libcall_names()).unwrap(),
-        ));
-        unsafe { module.free_memory() }
+    pub unsafe extern "C" fn sin(out: *mut Complex, re: f64, im: f64) {
+        unsafe { out.write(Complex::new(re, im).sin()) }
     }
-}
 
-/// Bind a function pointer to some lifetime by its return argument
-///
-/// https://internals.rust-lang.org/t/lt-a-lang-item-because-we-dont-have-derefmove-lifetimes-for-fn/15169
-#[repr(transparent)]
-pub struct Lt<'a, T = ()>(pub T, pub core::marker::PhantomData<&'a ()>);
+    pub unsafe extern "C" fn cos(out: *mut Complex, re: f64, im: f64) {
+        unsafe { out.write(Complex::new(re, im).cos()) }
+    }
 
-impl<'a, T> ops::Deref for Lt<'a, T> {
-    type Target = T;
+    pub unsafe extern "C" fn tan(out: *mut Complex, re: f64, im: f64) {
+        unsafe { out.write(Complex::new(re, im).tan()) }
+    }
 
-    fn deref(&self) -> &Self::Target {
-        &self.0
+    pub unsafe extern "C" fn modulo(out: *mut Complex, a_re: f64, a_im: f64, b_re: f64, b_im: f64) {
+        let z = Complex::new(a_re, a_im).modulo(Complex::new(b_re, b_im));
+        unsafe { out.write(z) }
     }
+
+    // complex values are passed as two f64 and returned through a pointer
+    pub const GLOB_FN_DECLS: &[FnDecl] = &[
+        (
+            "pow_complex",
+            pow as *const u8,
+            &[
+                FnParam::Ptr,
+                FnParam::F64,
+                FnParam::F64,
+                FnParam::F64,
+                FnParam::F64,
+            ],
+            &[],
+        ),
+        (
+            "sin_complex",
+            sin as *const u8,
+            &[FnParam::Ptr, FnParam::F64, FnParam::F64],
+            &[],
+        ),
+        (
+            "cos_complex",
+            cos as *const u8,
+            &[FnParam::Ptr, FnParam::F64, FnParam::F64],
+            &[],
+        ),
+        (
+            "tan_complex",
+            tan as *const u8,
+            &[FnParam::Ptr, FnParam::F64, FnParam::F64],
+            &[],
+        ),
+        (
+            "mod_complex",
+            modulo as *const u8,
+            &[
+                FnParam::Ptr,
+                FnParam::F64,
+                FnParam::F64,
+                FnParam::F64,
+                FnParam::F64,
+            ],
+            &[],
+        ),
+    ];
 }
 
-impl<'a, T> ops::DerefMut for Lt<'a, T> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+mod special_util {
+    use super::*;
+    use utils::special::Special;
+
+    macro_rules! special_fns {
+        ($($name:ident: $f:expr),* $(,)?) => {
+            paste::paste! {
+                $(
+                    pub extern "C" fn [<$name _f64>](x: f64) -> f64 {
+                        $f.eval(x)
+                    }
+
+                    pub extern "C" fn [<$name _deriv_f64>](x: f64) -> f64 {
+                        $f.deriv(x)
+                    }
+
+                    f64x2_fn!(pub fn [<$name _f64x2>](v) {
+                        F64X2($f.eval(v.0), $f.eval(v.1))
+                    });
+
+                    f64x2_fn!(pub fn [<$name _intrvl>](v) {
+                        let (lo, hi) = $f.intrvl(v.0, v.1);
+                        F64X2(lo, hi)
+                    });
+
+                    pub unsafe extern "C" fn [<$name _complex>](out: *mut Complex, re: f64, im: f64) {
+                        unsafe { out.write(Complex::new(re, im).special($f)) }
+                    }
+                )*
+
+                pub const GLOB_FN_DECLS: &[FnDecl] = &[
+                    $(
+                        (
+                            concat!(stringify!($name), "_f64"),
+                            [<$name _f64>] as *const u8,
+                            &[FnParam::F64],
+                            &[FnParam::F64],
+                        ),
+                        (
+                            concat!(stringify!($name), "_deriv_f64"),
+                            [<$name _deriv_f64>] as *const u8,
+                            &[FnParam::F64],
+                            &[FnParam::F64],
+                        ),
+                        (
+                            concat!(stringify!($name), "_f64x2"),
+                            [<$name _f64x2>] as *const u8,
+                            &[FnParam::F64X2],
+                            &[FnParam::F64X2],
+                        ),
+                        (
+                            concat!(stringify!($name), "_intrvl"),
+                            [<$name _intrvl>] as *const u8,
+                            &[FnParam::F64X2],
+                            &[FnParam::F64X2],
+                        ),
+                        (
+                            concat!(stringify!($name), "_complex"),
+                            [<$name _complex>] as *const u8,
+                            &[FnParam::Ptr, FnParam::F64, FnParam::F64],
+                            &[],
+                        ),
+                    )*
+                ];
+            }
+        };
     }
-}
 
-impl<'a> JIT<'a> {
-    pub fn init() -> Self {
-        let mut flag_builder = settings::builder();
-        flag_builder.set("use_colocated_libcalls", "false").unwrap();
-        flag_builder.set("is_pic", "false").unwrap();
-        flag_builder.set("opt_level", "speed").unwrap();
-        let isa_builder = cranelift_native::builder().unwrap_or_else(|msg| {
-            panic!("host machine is not supported: {}", msg);
-        });
-        let isa = isa_builder
-            .finish(settings::Flags::new(flag_builder))
-            .unwrap();
+    // the names match Special::name
+    special_fns!(
+        gamma: Special::Gamma,
+        erf: Special::Erf,
+        j0: Special::J0,
+        j1: Special::J1,
+        lambert_w: Special::LambertW,
+    );
+}
 
-        let mut builder = JITBuilder::with_isa(isa, cranelift_module::default_libcall_names());
-        {
-            JIT::def_function_symbols(&mut builder, f64_util::GLOB_FN_DECLS);
-            JIT::def_function_symbols(&mut builder, f64x2_util::GLOB_FN_DECLS);
-            JIT::def_function_symbols(&mut builder, intrvl_util::GLOB_FN_DECLS);
-        }
+/// calls of [`utils::native`] functions, the callee is resolved when compiling and passed as
+/// first argument, so evaluation doesn't lock the registry
+// the callee is a rust fn pointer passed back unchanged by compiled code
+#[allow(improper_ctypes_definitions)]
+mod native_util {
+    use super::*;
+    use utils::native::IntrvlFn;
 
-        let mut module = JITModule::new(builder);
+    pub extern "C" fn native_f64(f: fn(f64) -> f64, x: f64) -> f64 {
+        f(x)
+    }
 
-        let mut glob_fns = FxHashMap::default();
-        {
-            glob_fns.extend(JIT::decl_functions(&mut module, f64_util::GLOB_FN_DECLS));
-            glob_fns.extend(JIT::decl_functions(&mut module, f64x2_util::GLOB_FN_DECLS));
-            glob_fns.extend(JIT::decl_functions(&mut module, intrvl_util::GLOB_FN_DECLS));
-        }
+    pub unsafe extern "C" fn native_f64x2(out: *mut F64X2, f: fn(f64) -> f64, v: *const F64X2) {
+        let v = unsafe { v.read() };
+        unsafe { out.write(F64X2(f(v.0), f(v.1))) }
+    }
 
-        Self {
-            builder_ctx: FunctionBuilderContext::new(),
-            ctx: RefCell::new(module.make_context()),
-            module: RefCell::new(module),
-            emit_asm: false,
-            glob_fns,
-            asm: RefCell::new(None),
-            _fn_ptr_lifetime: std::marker::PhantomData,
-        }
+    pub unsafe extern "C" fn native_intrvl(out: *mut F64X2, f: IntrvlFn, v: *const F64X2) {
+        let v = unsafe { v.read() };
+        let (lo, hi) = f(v.0, v.1);
+        unsafe { out.write(F64X2(lo, hi)) }
     }
 
-    fn def_function_symbols(b: &mut JITBuilder, fn_decls: &[FnDecl]) {
-        for (s, ptr, _, _) in fn_decls {
-            b.symbol(*s, *ptr);
-        }
+    /// like [`Complex::native`], only evaluated on the real axis
+    pub unsafe extern "C" fn native_complex(
+        out: *mut Complex,
+        f: fn(f64) -> f64,
+        re: f64,
+        im: f64,
+    ) {
+        let z = Complex::new(re, im);
+        let res = match z.is_real() {
+            true => Complex::real(f(re)),
+            false => Complex::UNDEF,
+        };
+        unsafe { out.write(res) }
     }
 
-    fn decl_functions(
-        module: &mut JITModule,
-        fn_decls: &[FnDecl],
-    ) -> FxHashMap<&'static str, FuncId> {
-        let mut decls = FxHashMap::default();
-        let ptr_ty = module.target_config().pointer_type();
+    pub const GLOB_FN_DECLS: &[FnDecl] = &[
+        (
+            "native_f64",
+            native_f64 as *const u8,
+            &[FnParam::Ptr, FnParam::F64],
+            &[FnParam::F64],
+        ),
+        (
+            "native_f64x2",
+            native_f64x2 as *const u8,
+            &[FnParam::Ptr, FnParam::F64X2],
+            &[FnParam::F64X2],
+        ),
+        (
+            "native_intrvl",
+            native_intrvl as *const u8,
+            &[FnParam::Ptr, FnParam::F64X2],
+            &[FnParam::F64X2],
+        ),
+        (
+            "native_complex",
+            native_complex as *const u8,
+            &[FnParam::Ptr, FnParam::Ptr, FnParam::F64, FnParam::F64],
+            &[],
+        ),
+    ];
+}
 
-        for (name, _, params, returns) in fn_decls {
-            let mut sig = module.make_signature();
+/// runtime functions compiled code may call
+pub(crate) const GLOB_FN_DECLS: [&[FnDecl]; 6] = [
+    f64_util::GLOB_FN_DECLS,
+    f64x2_util::GLOB_FN_DECLS,
+    intrvl_util::GLOB_FN_DECLS,
+    complex_util::GLOB_FN_DECLS,
+    special_util::GLOB_FN_DECLS,
+    native_util::GLOB_FN_DECLS,
+];
+
+/// the runtime function of [`special_util`] computing the special function of `op` for `ty`
+fn special_fn_name(op: UnOp, ty: &str) -> String {
+    let f = op.special().expect("not a special function");
+    format!("{}_{ty}", f.name())
+}
 
-            for param in *params {
-                match param {
-                    FnParam::I8 => sig.params.push(AbiParam::new(types::I8)),
-                    FnParam::F64 => sig.params.push(AbiParam::new(types::F64)),
-                    FnParam::F64X2 => sig.params.push(AbiParam::new(types::F64X2)),
-                    FnParam::SRet => sig
-                        .params
-                        .push(AbiParam::special(ptr_ty, ir::ArgumentPurpose::StructReturn)),
-                }
-            }
+/// the callee `f` of a CALL as first argument of the [`native_util`] function `name`
+fn native_fn(f: *const u8, name: &str, fn_refs: &FnRefTable, fb: &mut FunctionBuilder) -> Value {
+    // the native_util functions take a pointer first, the callee or the out pointer
+    let sig = fb.func.dfg.ext_funcs[fn_refs[name]].signature;
+    let ptr_ty = fb.func.dfg.signatures[sig].params[0].value_type;
+    fb.ins().iconst(ptr_ty, f as i64)
+}
 
-            for ret in *returns {
-                match ret {
-                    FnParam::I8 => sig.returns.push(AbiParam::new(types::I8)),
-                    FnParam::F64 => sig.returns.push(AbiParam::new(types::F64)),
-                    FnParam::F64X2 => sig.returns.push(AbiParam::new(types::F64X2)),
-                    FnParam::SRet => panic!("sret is only allowed as argument"),
-                }
-            }
+/// number of registers available to compiled programs
+pub const N_REGS: usize = 16;
 
-            let id = module
-                .declare_function(name, Linkage::Import, &sig)
-                .unwrap();
-            decls.insert(*name, id);
-        }
+/// element type a program is compiled for, see [`Word`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum WordKind {
+    F64,
+    F64X2,
+    F64X4,
+    F64X8,
+    Intrvl,
+    Complex,
+    Dual,
+}
 
-        decls
-    }
+/// types [`JIT::compile`] can generate code for
+pub trait Word: Copy + Send + Sync + 'static {
+    const KIND: WordKind;
+    const UNDEF: Self;
+}
 
-    fn decl_functions_in_function(
-        module: &mut JITModule,
-        func: &mut ir::Function,
-        fn_decls: &FxHashMap<&'static str, FuncId>,
-    ) -> FnRefTable {
-        fn_decls
-            .iter()
-            .map(|(name, id)| (*name, module.declare_func_in_func(*id, func)))
-            .collect()
-    }
+impl Word for f64 {
+    const KIND: WordKind = WordKind::F64;
+    const UNDEF: Self = f64::NAN;
+}
 
-    pub unsafe fn compile_static_2f64_f64(
-        &self,
-        fn_name: &str,
-        bytecode: &[Instr],
-    ) -> extern "C" fn(f64, f64) -> f64 {
-        let non_static = self.compile_2f64_f64(fn_name, bytecode);
-        unsafe { std::mem::transmute(non_static) }
-    }
+impl Word for F64X2 {
+    const KIND: WordKind = WordKind::F64X2;
+    const UNDEF: Self = F64X2(f64::NAN, f64::NAN);
+}
 
-    pub fn compile_2f64_f64(
-        &self,
-        fn_name: &str,
-        bytecode: &[Instr],
-    ) -> extern "C" fn(f64, f64) -> Lt<'a, f64> {
-        let mut ctx_mut = self.ctx.borrow_mut();
-        let mut module_mut = self.module.borrow_mut();
+impl Word for F64X4 {
+    const KIND: WordKind = WordKind::F64X4;
+    const UNDEF: Self = F64X4([f64::NAN; 4]);
+}
 
-        ctx_mut.set_disasm(self.emit_asm);
+impl Word for F64X8 {
+    const KIND: WordKind = WordKind::F64X8;
+    const UNDEF: Self = F64X8([f64::NAN; 8]);
+}
 
-        // Signature
+impl Word for Intrvl {
+    const KIND: WordKind = WordKind::Intrvl;
+    const UNDEF: Self = Intrvl::UNDEF;
+}
 
-        let mut sig = module_mut.make_signature();
+impl Word for Complex {
+    const KIND: WordKind = WordKind::Complex;
+    const UNDEF: Self = Complex::UNDEF;
+}
 
-        for _ in 0..2 {
-            sig.params.push(AbiParam::new(types::F64));
-        }
+impl Word for Dual {
+    const KIND: WordKind = WordKind::Dual;
+    const UNDEF: Self = Dual::new(f64::NAN, f64::NAN);
+}
 
-        sig.returns.push(AbiParam::new(types::F64));
-        ctx_mut.func.signature = sig;
+/// 4 f64 lanes, compiled as two f64x2 streams
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct F64X4(pub [f64; 4]);
 
-        let mut fn_ctx = FunctionBuilderContext::new();
-        let mut fb = FunctionBuilder::new(&mut ctx_mut.func, &mut fn_ctx);
+/// 8 f64 lanes, compiled as four f64x2 streams
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct F64X8(pub [f64; 8]);
+
+/// words made of independent f64 lanes, see [`Func::eval_rows`]
+pub trait Lanes: Word {
+    const LANES: usize;
+    /// missing lanes are nan
+    fn from_lanes(lanes: &[f64]) -> Self;
+    /// writes the first `out.len()` lanes
+    fn to_lanes(self, out: &mut [f64]);
+}
 
-        // entry block
+impl Lanes for f64 {
+    const LANES: usize = 1;
+    fn from_lanes(lanes: &[f64]) -> Self {
+        lanes.first().copied().unwrap_or(f64::NAN)
+    }
+    fn to_lanes(self, out: &mut [f64]) {
+        out[..1].copy_from_slice(&[self]);
+    }
+}
 
-        let entry = fb.create_block();
-        fb.append_block_params_for_function_params(entry);
-        fb.switch_to_block(entry);
-        fb.seal_block(entry);
+impl Lanes for F64X2 {
+    const LANES: usize = 2;
+    fn from_lanes(lanes: &[f64]) -> Self {
+        let [a, b] = std::array::from_fn(|i| lanes.get(i).copied().unwrap_or(f64::NAN));
+        F64X2(a, b)
+    }
+    fn to_lanes(self, out: &mut [f64]) {
+        let n = out.len().min(2);
+        out[..n].copy_from_slice(&[self.0, self.1][..n]);
+    }
+}
 
-        let fn_refs = Self::decl_functions_in_function(&mut *module_mut, fb.func, &self.glob_fns);
+macro_rules! impl_lanes {
+    ($($typ:ident: $n:literal),*) => {$(
+        impl Lanes for $typ {
+            const LANES: usize = $n;
+            fn from_lanes(lanes: &[f64]) -> Self {
+                $typ(std::array::from_fn(|i| lanes.get(i).copied().unwrap_or(f64::NAN)))
+            }
+            fn to_lanes(self, out: &mut [f64]) {
+                let n = out.len().min($n);
+                out[..n].copy_from_slice(&self.0[..n]);
+            }
+        }
+    )*};
+}
 
-        let nan = fb.ins().f64const(f64::NAN);
+impl_lanes!(F64X4: 4, F64X8: 8);
 
-        // registers alloc
+/// number of f64 lanes of the widest word worth compiling for the host
+///
+/// cranelift only emits 128-bit vectors, [`F64X4`] and [`F64X8`] interleave independent f64x2
+/// streams to keep the wider avx2 / avx512 units busy. 1 means only scalar code is supported
+pub fn simd_lanes() -> usize {
+    static LANES: OnceLock<usize> = OnceLock::new();
+    *LANES.get_or_init(|| {
+        let Ok(isa_builder) = cranelift_native::builder() else {
+            return 1;
+        };
+        let Ok(isa) = isa_builder.finish(settings::Flags::new(settings::builder())) else {
+            return 1;
+        };
+        let flags = isa.isa_flags();
+        let has = |name: &str| {
+            flags
+                .iter()
+                .any(|f| f.name == name && f.as_bool() == Some(true))
+        };
 
-        let mut vars = vec![];
-        for i in 0..16 {
-            let v = Variable::from_u32(i);
-            fb.declare_var(v, types::F64);
-            fb.def_var(v, nan);
-            vars.push(v);
+        if has("has_avx512f") {
+            8
+        } else if has("has_avx2") {
+            4
+        } else if has("has_sse41") || cfg!(target_arch = "aarch64") {
+            2
+        } else {
+            1
         }
+    })
+}
 
-        for i in 0..2 {
-            let x = fb.block_params(entry)[i as usize];
-            fb.def_var(vars[i as usize], x);
-        }
+/// value together with its derivative along one direction
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[repr(C)]
+pub struct Dual {
+    pub v: f64,
+    pub d: f64,
+}
 
-        Self::asmbl_f64_body(bytecode, &mut fb, &fn_refs, &vars);
+impl Dual {
+    pub const fn new(v: f64, d: f64) -> Self {
+        Self { v, d }
+    }
 
-        // return
-        let ret = fb.use_var(vars[0]);
-        fb.ins().return_(&[ret]);
-        fb.finalize();
+    /// the variable we differentiate by
+    pub const fn var(v: f64) -> Self {
+        Self::new(v, 1.0)
+    }
+
+    pub const fn cnst(v: f64) -> Self {
+        Self::new(v, 0.0)
+    }
+}
+
+/// signature of a compiled function: `arity` words in, `outputs` words out
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Signature {
+    pub word: WordKind,
+    pub arity: usize,
+    pub outputs: usize,
+}
+
+impl Signature {
+    /// signature of `program` taking the first `arity` registers as arguments
+    pub(crate) fn words(word: WordKind, program: &Program, arity: usize) -> Self {
+        let sig = Signature {
+            word,
+            arity,
+            outputs: program.outputs.len(),
+        };
+        assert!(
+            arity <= N_REGS,
+            "{arity} arguments, expected at most {N_REGS}"
+        );
+        assert!(
+            sig.outputs <= N_REGS,
+            "{} outputs, expected at most {N_REGS}",
+            sig.outputs
+        );
+        assert!(
+            program.outputs.iter().all(|&r| (r as usize) < N_REGS),
+            "output registers must be < {N_REGS}: {:?}",
+            program.outputs
+        );
+        sig
+    }
+
+    /// signature of a row kernel sampling the first output of `program`
+    pub(crate) fn row(word: WordKind, program: &Program) -> Self {
+        assert!(
+            program
+                .outputs
+                .first()
+                .is_some_and(|&r| (r as usize) < N_REGS),
+            "output registers must be < {N_REGS}: {:?}",
+            program.outputs
+        );
+        Signature {
+            word,
+            arity: 2,
+            outputs: 1,
+        }
+    }
+}
+
+/// compiled function, can't outlive the [`JIT`] it was compiled by
+#[derive(Debug)]
+pub struct Func<'a, W> {
+    entry: Entry<W>,
+    _jit: std::marker::PhantomData<&'a ()>,
+}
+
+impl<W> Clone for Func<'_, W> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<W> Copy for Func<'_, W> {}
+
+impl<W: Word> Func<'_, W> {
+    pub fn signature(&self) -> Signature {
+        self.entry.sig
+    }
+
+    /// the function without its lifetime, see [`JIT::get`]
+    pub fn entry(&self) -> Entry<W> {
+        self.entry
+    }
+
+    /// start of the machine code, see [`JIT::fn_info`]
+    pub fn addr(&self) -> *const u8 {
+        self.entry.ptr as *const u8
+    }
+
+    /// writes all outputs to `out`
+    pub fn call(&self, args: &[W], out: &mut [W]) {
+        let sig = self.entry.sig;
+        assert_eq!(args.len(), sig.arity, "wrong number of arguments");
+        assert_eq!(out.len(), sig.outputs, "wrong number of outputs");
+        (self.entry.ptr)(args.as_ptr(), out.as_mut_ptr())
+    }
+
+    /// returns the first output
+    #[inline]
+    pub fn eval(&self, args: &[W]) -> W {
+        let sig = self.entry.sig;
+        assert_eq!(args.len(), sig.arity, "wrong number of arguments");
+        if sig.outputs == 1 {
+            let mut out = W::UNDEF;
+            (self.entry.ptr)(args.as_ptr(), &mut out);
+            out
+        } else {
+            let mut out = [W::UNDEF; N_REGS];
+            (self.entry.ptr)(args.as_ptr(), out.as_mut_ptr());
+            out[0]
+        }
+    }
+}
+
+impl<W: Lanes> Func<'_, W> {
+    /// evaluates the first output at every column of `args`, `W::LANES` columns per call
+    pub fn eval_rows(&self, args: &[&[f64]], out: &mut [f64]) {
+        assert_eq!(
+            args.len(),
+            self.entry.sig.arity,
+            "wrong number of arguments"
+        );
+        assert!(
+            args.iter().all(|a| a.len() == out.len()),
+            "rows must have the same length"
+        );
+
+        let mut words = [W::UNDEF; N_REGS];
+        for (c, out) in out.chunks_mut(W::LANES).enumerate() {
+            let cols = c * W::LANES..c * W::LANES + out.len();
+            for (w, arg) in words.iter_mut().zip(args) {
+                *w = W::from_lanes(&arg[cols.clone()]);
+            }
+            self.eval(&words[..args.len()]).to_lanes(out);
+        }
+    }
+}
+
+/// row of sample points `origin + k * step` for k in 0..n, see [`JIT::compile_row`]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[repr(C)]
+pub struct RowParams {
+    pub origin: [f64; 2],
+    pub step: [f64; 2],
+}
+
+/// compiled row kernel, can't outlive the [`JIT`] it was compiled by
+#[derive(Debug, Copy, Clone)]
+pub struct RowFunc<'a> {
+    entry: RowEntry,
+    _jit: std::marker::PhantomData<&'a ()>,
+}
+
+impl RowFunc<'_> {
+    /// the kernel without its lifetime, see [`JIT::get_row`]
+    pub fn entry(&self) -> RowEntry {
+        self.entry
+    }
+
+    /// start of the machine code, see [`JIT::fn_info`]
+    pub fn addr(&self) -> *const u8 {
+        self.entry.ptr as *const u8
+    }
+
+    /// samples the row at `out.len()` points
+    pub fn eval(&self, params: &RowParams, out: &mut [f64]) {
+        (self.entry.ptr)(params, out.as_mut_ptr(), out.len() as u64)
+    }
+}
+
+/// [`RowFunc`] detached from the lifetime of its [`JIT`]
+#[derive(Debug, Copy, Clone)]
+pub struct RowEntry {
+    ptr: extern "C" fn(*const RowParams, *mut f64, u64),
+    jit_id: u64,
+}
+
+/// [`Func`] detached from the lifetime of its [`JIT`]
+///
+/// can only be called after reattaching it with [`JIT::get`]
+#[derive(Debug)]
+pub struct Entry<W> {
+    ptr: extern "C" fn(*const W, *mut W),
+    sig: Signature,
+    jit_id: u64,
+}
+
+impl<W> Clone for Entry<W> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<W> Copy for Entry<W> {}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum FnParam {
+    I8,
+    F64,
+    /// passed by pointer, a returned F64X2 is written through a pointer before the other
+    /// arguments. the c abi of small structs differs between platforms
+    F64X2,
+    Ptr,
+}
+
+type FnRefTable = FxHashMap<&'static str, ir::FuncRef>;
+
+/// finalized functions keyed by the hash of their program, see [`JIT::compile`]
+type FnCache = FxHashMap<(u64, Signature, MathImpl, Kernel), Vec<(Program, *const u8)>>;
+
+/// calling convention of a compiled function
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Kernel {
+    /// `extern "C" fn(args: *const W, out: *mut W)`
+    Words,
+    /// `extern "C" fn(params: *const RowParams, out: *mut f64, n: u64)`
+    Row,
+}
+
+/// how SIN, COS, TAN and POW are compiled for f64, f64x2, interval and dual words
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum MathImpl {
+    /// calls into the rust standard library
+    #[default]
+    Libm,
+    /// range reduction and polynomial kernels emitted inline. max error for f64,
+    /// measured against libm (see `test::inline_math_ulp`):
+    /// - sin, cos: 1 ulp for |x| < 10, 2 ulp for |x| < 1e6. the range reduction loses
+    ///   accuracy for larger |x|, intervals beyond are [-1, 1]
+    /// - tan: 3 ulp for |x| < 1e6, intervals beyond are the whole line
+    /// - pow: 2 + 2 * |y * ln(x)| ulp, small integer constant exponents are expanded into
+    ///   products
+    ///
+    /// interval bounds are widened outward by the kernel error, interval POW always calls
+    /// into rust
+    Inline,
+}
+
+static NEXT_JIT_ID: atomic::AtomicU64 = atomic::AtomicU64::new(0);
+
+/// machine code of a finalized function, see [`JIT::functions`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FnInfo {
+    pub name: String,
+    pub addr: usize,
+    pub size: usize,
+    /// disassembly, if the function was compiled with [`JIT::emit_asm`]
+    pub asm: Option<String>,
+}
+
+impl FnInfo {
+    /// `/tmp/perf-<pid>.map`, where `perf report` looks up symbols of jit compiled code
+    pub fn perf_map_path() -> PathBuf {
+        PathBuf::from(format!("/tmp/perf-{}.map", std::process::id()))
+    }
+
+    /// appends the function to the perf map at `path`, see [`FnInfo::perf_map_path`]
+    fn write_perf_map(&self, path: &Path) -> io::Result<()> {
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        writeln!(file, "{:x} {:x} {}", self.addr, self.size, self.name)
+    }
+}
+
+pub struct JIT {
+    pub builder_ctx: FunctionBuilderContext,
+    pub ctx: RefCell<cranelift_codegen::Context>,
+    pub module: RefCell<JITModule>,
+    pub emit_asm: bool,
+    /// write every finalized function to this perf map, see [`FnInfo::write_perf_map`].
+    /// defaults to [`FnInfo::perf_map_path`] if the `ATHENA_PERF_MAP` environment variable is set
+    pub perf_map: Option<PathBuf>,
+    pub math: MathImpl,
+
+    pub glob_fns: FxHashMap<&'static str, FuncId>,
+    /// disassembly of the last compiled function, see [`JIT::fn_info`] for older ones
+    pub asm: RefCell<Option<String>>,
+
+    /// identifies the module compiled functions belong to, see [`JIT::get`]
+    id: u64,
+    cache: RefCell<FnCache>,
+    /// functions defined in the module, their number makes symbol names unique
+    functions: RefCell<Vec<FnInfo>>,
+}
+
+impl ops::Drop for JIT {
+    fn drop(&mut self) {
+        // we restrict the lifetime of returned pointers or require unsafe from the user
+        let module = self.module.replace(JITModule::new(
+            JITBuilder::new(cranelift_module::default_libcall_names()).unwrap(),
+        ));
+        unsafe { module.free_memory() }
+    }
+}
+
+impl JIT {
+    pub fn init() -> Self {
+        let (module, glob_fns) = Self::new_module();
+
+        Self {
+            builder_ctx: FunctionBuilderContext::new(),
+            ctx: RefCell::new(module.make_context()),
+            module: RefCell::new(module),
+            emit_asm: false,
+            perf_map: std::env::var_os("ATHENA_PERF_MAP").map(|_| FnInfo::perf_map_path()),
+            math: MathImpl::default(),
+            glob_fns,
+            asm: RefCell::new(None),
+            id: NEXT_JIT_ID.fetch_add(1, atomic::Ordering::Relaxed),
+            cache: RefCell::new(FxHashMap::default()),
+            functions: RefCell::new(vec![]),
+        }
+    }
+
+    /// releases the code of all functions compiled so far
+    ///
+    /// entries of released functions panic in [`JIT::get`]
+    pub fn free_memory(&mut self) {
+        let (module, glob_fns) = Self::new_module();
+        let old = self.module.replace(module);
+        unsafe { old.free_memory() }
+
+        *self.ctx.get_mut() = self.module.get_mut().make_context();
+        self.glob_fns = glob_fns;
+        self.id = NEXT_JIT_ID.fetch_add(1, atomic::Ordering::Relaxed);
+        self.cache.get_mut().clear();
+        self.functions.get_mut().clear();
+    }
+
+    /// number of functions with finalized code in the module
+    pub fn n_functions(&self) -> usize {
+        self.functions.borrow().len()
+    }
+
+    /// all functions with finalized code in the module
+    pub fn functions(&self) -> Ref<'_, [FnInfo]> {
+        Ref::map(self.functions.borrow(), |f| f.as_slice())
+    }
+
+    /// the function whose code starts at `addr`, see [`Func::addr`]
+    pub fn fn_info(&self, addr: *const u8) -> Option<FnInfo> {
+        self.functions
+            .borrow()
+            .iter()
+            .find(|f| f.addr == addr as usize)
+            .cloned()
+    }
+
+    /// code generation flags shared by [`JIT`] and [`crate::aot::Aot`]
+    pub(crate) fn flags(is_pic: bool) -> settings::Flags {
+        let mut flag_builder = settings::builder();
+        flag_builder.set("use_colocated_libcalls", "false").unwrap();
+        flag_builder
+            .set("is_pic", if is_pic { "true" } else { "false" })
+            .unwrap();
+        flag_builder.set("opt_level", "speed").unwrap();
+        settings::Flags::new(flag_builder)
+    }
+
+    fn new_module() -> (JITModule, FxHashMap<&'static str, FuncId>) {
+        let isa_builder = cranelift_native::builder().unwrap_or_else(|msg| {
+            panic!("host machine is not supported: {}", msg);
+        });
+        let isa = isa_builder.finish(Self::flags(false)).unwrap();
+
+        let mut builder = JITBuilder::with_isa(isa, cranelift_module::default_libcall_names());
+        for decls in GLOB_FN_DECLS {
+            JIT::def_function_symbols(&mut builder, decls);
+        }
+
+        let mut module = JITModule::new(builder);
+        let glob_fns = JIT::decl_glob_functions(&mut module);
+        (module, glob_fns)
+    }
+
+    /// imports the runtime functions compiled code may call
+    pub(crate) fn decl_glob_functions(module: &mut impl Module) -> FxHashMap<&'static str, FuncId> {
+        let mut glob_fns = FxHashMap::default();
+        for decls in GLOB_FN_DECLS {
+            glob_fns.extend(JIT::decl_functions(module, decls));
+        }
+        glob_fns
+    }
+
+    fn def_function_symbols(b: &mut JITBuilder, fn_decls: &[FnDecl]) {
+        for (s, ptr, _, _) in fn_decls {
+            b.symbol(*s, *ptr);
+        }
+    }
+
+    fn decl_functions(
+        module: &mut impl Module,
+        fn_decls: &[FnDecl],
+    ) -> FxHashMap<&'static str, FuncId> {
+        let mut decls = FxHashMap::default();
+        let ptr_ty = module.target_config().pointer_type();
+
+        for (name, _, params, returns) in fn_decls {
+            let mut sig = module.make_signature();
+
+            for param in *params {
+                match param {
+                    FnParam::I8 => sig.params.push(AbiParam::new(types::I8)),
+                    FnParam::F64 => sig.params.push(AbiParam::new(types::F64)),
+                    FnParam::F64X2 | FnParam::Ptr => sig.params.push(AbiParam::new(ptr_ty)),
+                }
+            }
+
+            for ret in *returns {
+                match ret {
+                    FnParam::I8 => sig.returns.push(AbiParam::new(types::I8)),
+                    FnParam::F64 => sig.returns.push(AbiParam::new(types::F64)),
+                    // written through a leading out pointer
+                    FnParam::F64X2 => sig.params.insert(0, AbiParam::new(ptr_ty)),
+                    FnParam::Ptr => sig.returns.push(AbiParam::new(ptr_ty)),
+                }
+            }
+
+            let id = module
+                .declare_function(name, Linkage::Import, &sig)
+                .unwrap();
+            decls.insert(*name, id);
+        }
+
+        decls
+    }
+
+    fn decl_functions_in_function(
+        module: &mut impl Module,
+        func: &mut ir::Function,
+        fn_decls: &FxHashMap<&'static str, FuncId>,
+    ) -> FnRefTable {
+        fn_decls
+            .iter()
+            .map(|(name, id)| (*name, module.declare_func_in_func(*id, func)))
+            .collect()
+    }
+
+    /// compiles `program` for `W` taking the first `arity` registers as arguments
+    ///
+    /// the function returns the registers in `program.outputs`, unused registers start as nan.
+    /// compiling the same program for the same signature again returns the cached function
+    pub fn compile<W: Word>(&self, fn_name: &str, program: &Program, arity: usize) -> Func<'_, W> {
+        let sig = Signature::words(W::KIND, program, arity);
+        let ptr = self.compile_cached(fn_name, program, sig, Kernel::Words);
+        Func {
+            entry: Entry {
+                ptr: unsafe {
+                    std::mem::transmute::<*const u8, extern "C" fn(*const W, *mut W)>(ptr)
+                },
+                sig,
+                jit_id: self.id,
+            },
+            _jit: std::marker::PhantomData,
+        }
+    }
+
+    /// reattaches an [`Entry`] to the lifetime of this JIT
+    ///
+    /// panics if the entry was compiled by a different JIT
+    pub fn get<W: Word>(&self, entry: Entry<W>) -> Func<'_, W> {
+        assert_eq!(entry.jit_id, self.id, "entry was compiled by another JIT");
+        Func {
+            entry,
+            _jit: std::marker::PhantomData,
+        }
+    }
+
+    /// compiles `program` into a kernel sampling the first output along a row of points,
+    /// see [`RowParams`]. the loop handles `W::LANES` points per iteration
+    pub fn compile_row<W: Lanes>(&self, fn_name: &str, program: &Program) -> RowFunc<'_> {
+        let sig = Signature::row(W::KIND, program);
+        let ptr = self.compile_cached(fn_name, program, sig, Kernel::Row);
+        RowFunc {
+            entry: RowEntry {
+                ptr: unsafe {
+                    std::mem::transmute::<*const u8, extern "C" fn(*const RowParams, *mut f64, u64)>(
+                        ptr,
+                    )
+                },
+                jit_id: self.id,
+            },
+            _jit: std::marker::PhantomData,
+        }
+    }
+
+    /// reattaches a [`RowEntry`] to the lifetime of this JIT
+    ///
+    /// panics if the entry was compiled by a different JIT
+    pub fn get_row(&self, entry: RowEntry) -> RowFunc<'_> {
+        assert_eq!(entry.jit_id, self.id, "entry was compiled by another JIT");
+        RowFunc {
+            entry,
+            _jit: std::marker::PhantomData,
+        }
+    }
+
+    fn compile_cached(
+        &self,
+        fn_name: &str,
+        program: &Program,
+        sig: Signature,
+        kernel: Kernel,
+    ) -> *const u8 {
+        let mut hasher = FxHasher::default();
+        program.hash(&mut hasher);
+        let key = (hasher.finish(), sig, self.math, kernel);
+
+        let cached = self
+            .cache
+            .borrow()
+            .get(&key)
+            .and_then(|fns| fns.iter().find(|(p, _)| p == program).map(|&(_, ptr)| ptr));
+
+        match cached {
+            Some(ptr) => ptr,
+            None => {
+                let n = self.n_functions();
+                let ptr = self.compile_impl(&format!("{fn_name}.{n}"), program, sig, kernel);
+                self.cache
+                    .borrow_mut()
+                    .entry(key)
+                    .or_default()
+                    .push((program.clone(), ptr));
+                ptr
+            }
+        }
+    }
+
+    /// generates a function with the calling convention of `kernel`
+    fn compile_impl(
+        &self,
+        fn_name: &str,
+        program: &Program,
+        sig: Signature,
+        kernel: Kernel,
+    ) -> *const u8 {
+        let mut ctx_mut = self.ctx.borrow_mut();
+        let mut module_mut = self.module.borrow_mut();
+
+        ctx_mut.set_disasm(self.emit_asm);
+        Self::build_function(
+            &mut *module_mut,
+            &mut ctx_mut.func,
+            &self.glob_fns,
+            program,
+            sig,
+            kernel,
+            self.math,
+        );
 
         let fn_id = module_mut
             .declare_function(fn_name, Linkage::Local, &ctx_mut.func.signature)
             .unwrap();
-        module_mut.define_function(fn_id, &mut *ctx_mut).unwrap();
+        module_mut.define_function(fn_id, &mut ctx_mut).unwrap();
         module_mut.finalize_definitions().unwrap();
 
-        if self.emit_asm {
-            *self.asm.borrow_mut() = Some(
-                ctx_mut
-                    .compiled_code()
-                    .unwrap()
-                    .vcode
-                    .clone()
-                    .unwrap()
-                    .to_string(),
-            );
+        let code = ctx_mut.compiled_code().unwrap();
+        let ptr = module_mut.get_finalized_function(fn_id);
+        let info = FnInfo {
+            name: fn_name.to_string(),
+            addr: ptr as usize,
+            size: code.code_info().total_size as usize,
+            asm: self.emit_asm.then(|| code.vcode.clone().unwrap()),
+        };
+        module_mut.clear_context(&mut ctx_mut);
+
+        if let Some(path) = &self.perf_map {
+            info.write_perf_map(path)
+                .unwrap_or_else(|e| log::warn!("failed to write perf map: {e}"));
+        }
+        *self.asm.borrow_mut() = info.asm.clone();
+        self.functions.borrow_mut().push(info);
+        ptr
+    }
+
+    /// writes the ir of `program` into `func`, independent of the module the code ends up in
+    pub(crate) fn build_function(
+        module: &mut impl Module,
+        func: &mut ir::Function,
+        glob_fns: &FxHashMap<&'static str, FuncId>,
+        program: &Program,
+        sig: Signature,
+        kernel: Kernel,
+        math: MathImpl,
+    ) {
+        let ptr_ty = module.target_config().pointer_type();
+
+        // Signature
+
+        let mut ir_sig = module.make_signature();
+        ir_sig.params.push(AbiParam::new(ptr_ty));
+        ir_sig.params.push(AbiParam::new(ptr_ty));
+        if kernel == Kernel::Row {
+            ir_sig.params.push(AbiParam::new(types::I64));
+        }
+        func.signature = ir_sig;
+
+        let mut fn_ctx = FunctionBuilderContext::new();
+        let mut fb = FunctionBuilder::new(func, &mut fn_ctx);
+
+        // entry block
+
+        let entry = fb.create_block();
+        fb.append_block_params_for_function_params(entry);
+        fb.switch_to_block(entry);
+        fb.seal_block(entry);
+
+        let fn_refs = Self::decl_functions_in_function(module, fb.func, glob_fns);
+
+        let args_ptr = fb.block_params(entry)[0];
+        let out_ptr = fb.block_params(entry)[1];
+        let flags = ir::MemFlags::trusted();
+        let bytecode = &program.bytecode;
+
+        let nan = fb.ins().f64const(f64::NAN);
+
+        if kernel == Kernel::Row {
+            Self::asmbl_row(program, sig.word, &mut fb, &fn_refs, math, entry);
+        } else {
+            match sig.word {
+                WordKind::F64
+                | WordKind::F64X2
+                | WordKind::F64X4
+                | WordKind::F64X8
+                | WordKind::Intrvl => {
+                    let (ty, size, init) = match sig.word {
+                        WordKind::F64 => (types::F64, 8, nan),
+                        _ => (types::F64X2, 16, fb.ins().splat(types::F64X2, nan)),
+                    };
+
+                    // wider words are split into interleaved f64x2 streams, see [`simd_lanes`]
+                    let streams: usize = match sig.word {
+                        WordKind::F64X4 => 2,
+                        WordKind::F64X8 => 4,
+                        _ => 1,
+                    };
+                    let word_size = size * streams as i32;
+
+                    // registers alloc
+
+                    let mut vars = vec![];
+                    for s in 0..streams {
+                        let mut stream = vec![];
+                        for i in 0..N_REGS {
+                            let v = Variable::from_u32((s * N_REGS + i) as u32);
+                            fb.declare_var(v, ty);
+                            fb.def_var(v, init);
+                            stream.push(v);
+                        }
+                        vars.push(stream);
+                    }
+
+                    for (s, stream) in vars.iter().enumerate() {
+                        for (i, &v) in stream.iter().enumerate().take(sig.arity) {
+                            let offset = word_size * i as i32 + size * s as i32;
+                            let arg = fb.ins().load(ty, flags, args_ptr, offset);
+                            fb.def_var(v, arg);
+                        }
+                    }
+
+                    match sig.word {
+                        WordKind::F64 => {
+                            Self::asmbl_f64_body(bytecode, &mut fb, &fn_refs, &vars[0], math)
+                        }
+                        WordKind::Intrvl => {
+                            Self::asmbl_intrvl_body2(bytecode, &mut fb, &fn_refs, &vars[0], math)
+                        }
+                        _ => {
+                            for instr in bytecode {
+                                for stream in &vars {
+                                    let instr = std::slice::from_ref(instr);
+                                    Self::asmbl_f64x2_body(instr, &mut fb, &fn_refs, stream, math);
+                                }
+                            }
+                        }
+                    }
+
+                    for (s, stream) in vars.iter().enumerate() {
+                        for (i, &reg) in program.outputs.iter().enumerate() {
+                            let ret = fb.use_var(stream[reg as usize]);
+                            let offset = word_size * i as i32 + size * s as i32;
+                            fb.ins().store(flags, ret, out_ptr, offset);
+                        }
+                    }
+                }
+                WordKind::Complex | WordKind::Dual => {
+                    // registers alloc, every register is a pair of f64 variables
+
+                    let mut vars = vec![];
+                    for i in 0..N_REGS as u32 {
+                        let a = Variable::from_u32(2 * i);
+                        let b = Variable::from_u32(2 * i + 1);
+                        fb.declare_var(a, types::F64);
+                        fb.declare_var(b, types::F64);
+                        fb.def_var(a, nan);
+                        fb.def_var(b, nan);
+                        vars.push((a, b));
+                    }
+
+                    for (i, &(a, b)) in vars.iter().enumerate().take(sig.arity) {
+                        let offset = 16 * i as i32;
+                        let arg_a = fb.ins().load(types::F64, flags, args_ptr, offset);
+                        let arg_b = fb.ins().load(types::F64, flags, args_ptr, offset + 8);
+                        fb.def_var(a, arg_a);
+                        fb.def_var(b, arg_b);
+                    }
+
+                    match sig.word {
+                        WordKind::Complex => {
+                            Self::asmbl_complex_body(bytecode, &mut fb, &fn_refs, &vars, ptr_ty)
+                        }
+                        _ => Self::asmbl_dual_body(bytecode, &mut fb, &fn_refs, &vars, math),
+                    }
+
+                    for (i, &reg) in program.outputs.iter().enumerate() {
+                        let (a, b) = vars[reg as usize];
+                        let (a, b) = (fb.use_var(a), fb.use_var(b));
+                        fb.ins().store(flags, a, out_ptr, 16 * i as i32);
+                        fb.ins().store(flags, b, out_ptr, 16 * i as i32 + 8);
+                    }
+                }
+            }
         }
 
-        module_mut.clear_context(&mut *ctx_mut);
-        let fn_ptr = module_mut.get_finalized_function(fn_id);
-        unsafe { std::mem::transmute(fn_ptr) }
+        fb.ins().return_(&[]);
+        fb.finalize();
+    }
+
+    /// loops over `n` points `origin + k * step`, first with the vector streams of `word` and
+    /// then one point at a time for the remainder
+    fn asmbl_row(
+        program: &Program,
+        word: WordKind,
+        fb: &mut FunctionBuilder,
+        fn_refs: &FnRefTable,
+        math: MathImpl,
+        entry: Block,
+    ) {
+        let streams: usize = match word {
+            WordKind::F64X2 => 1,
+            WordKind::F64X4 => 2,
+            WordKind::F64X8 => 4,
+            _ => 0,
+        };
+        let lanes = 2 * streams as i64;
+        let bytecode = &program.bytecode;
+        let output = program.outputs[0] as usize;
+
+        let params_ptr = fb.block_params(entry)[0];
+        let out_ptr = fb.block_params(entry)[1];
+        let n = fb.block_params(entry)[2];
+        let flags = ir::MemFlags::trusted();
+
+        let [ox, oy, sx, sy] =
+            [0, 8, 16, 24].map(|offset| fb.ins().load(types::F64, flags, params_ptr, offset));
+
+        // registers alloc, one f64x2 set per stream and one f64 set for the remainder
+
+        let mut var_id = 0;
+        let mut declare_regs = |ty, fb: &mut FunctionBuilder| {
+            (0..N_REGS)
+                .map(|_| {
+                    let v = Variable::from_u32(var_id);
+                    var_id += 1;
+                    fb.declare_var(v, ty);
+                    v
+                })
+                .collect::<Vec<_>>()
+        };
+        let vec_vars: Vec<_> = (0..streams)
+            .map(|_| declare_regs(types::F64X2, fb))
+            .collect();
+        let vars = declare_regs(types::F64, fb);
+
+        let head = fb.create_block();
+        let body = fb.create_block();
+        let exit = fb.create_block();
+        fb.append_block_param(head, types::I64);
+        let zero = fb.ins().iconst(types::I64, 0);
+
+        if streams == 0 {
+            fb.ins().jump(head, &[zero]);
+        } else {
+            let vec_head = fb.create_block();
+            let vec_body = fb.create_block();
+            fb.append_block_param(vec_head, types::I64);
+            fb.ins().jump(vec_head, &[zero]);
+
+            // while k + lanes <= n
+
+            fb.switch_to_block(vec_head);
+            let k = fb.block_params(vec_head)[0];
+            let k_end = fb.ins().iadd_imm(k, lanes);
+            let cond = fb.ins().icmp(IntCC::UnsignedLessThanOrEqual, k_end, n);
+            fb.ins().brif(cond, vec_body, &[], head, &[k]);
+
+            fb.switch_to_block(vec_body);
+            fb.seal_block(vec_body);
+            let kf = fb.ins().fcvt_from_uint(types::F64, k);
+            let nan = fb.ins().f64const(f64::NAN);
+            let nan = fb.ins().splat(types::F64X2, nan);
+
+            for (s, stream) in vec_vars.iter().enumerate() {
+                let l0 = fb.ins().f64const(2.0 * s as f64);
+                let l1 = fb.ins().f64const(2.0 * s as f64 + 1.0);
+                let offsets = Self::asmbl_f64x2(l0, l1, fb);
+                let kf = fb.ins().splat(types::F64X2, kf);
+                let idx = fb.ins().fadd(kf, offsets);
+
+                let [x, y] = [(ox, sx), (oy, sy)].map(|(o, step)| {
+                    let o = fb.ins().splat(types::F64X2, o);
+                    let step = fb.ins().splat(types::F64X2, step);
+                    let d = fb.ins().fmul(idx, step);
+                    fb.ins().fadd(o, d)
+                });
+
+                // unused registers start as nan in every iteration
+                for &v in stream {
+                    fb.def_var(v, nan);
+                }
+                fb.def_var(stream[0], x);
+                fb.def_var(stream[1], y);
+            }
+
+            for instr in bytecode {
+                for stream in &vec_vars {
+                    let instr = std::slice::from_ref(instr);
+                    Self::asmbl_f64x2_body(instr, fb, fn_refs, stream, math);
+                }
+            }
+
+            let offset = fb.ins().imul_imm(k, 8);
+            let addr = fb.ins().iadd(out_ptr, offset);
+            for (s, stream) in vec_vars.iter().enumerate() {
+                let res = fb.use_var(stream[output]);
+                fb.ins().store(flags, res, addr, 16 * s as i32);
+            }
+
+            let k_next = fb.ins().iadd_imm(k, lanes);
+            fb.ins().jump(vec_head, &[k_next]);
+            fb.seal_block(vec_head);
+        }
+
+        // while k < n
+
+        fb.switch_to_block(head);
+        let k = fb.block_params(head)[0];
+        let cond = fb.ins().icmp(IntCC::UnsignedLessThan, k, n);
+        fb.ins().brif(cond, body, &[], exit, &[]);
+
+        fb.switch_to_block(body);
+        fb.seal_block(body);
+        let kf = fb.ins().fcvt_from_uint(types::F64, k);
+        let x = fb.ins().fmul(kf, sx);
+        let x = fb.ins().fadd(ox, x);
+        let y = fb.ins().fmul(kf, sy);
+        let y = fb.ins().fadd(oy, y);
+
+        let nan = fb.ins().f64const(f64::NAN);
+        for &v in &vars {
+            fb.def_var(v, nan);
+        }
+        fb.def_var(vars[0], x);
+        fb.def_var(vars[1], y);
+        Self::asmbl_f64_body(bytecode, fb, fn_refs, &vars, math);
+
+        let res = fb.use_var(vars[output]);
+        let offset = fb.ins().imul_imm(k, 8);
+        let addr = fb.ins().iadd(out_ptr, offset);
+        fb.ins().store(flags, res, addr, 0);
+        let k_next = fb.ins().iadd_imm(k, 1);
+        fb.ins().jump(head, &[k_next]);
+        fb.seal_block(head);
+
+        fb.switch_to_block(exit);
+        fb.seal_block(exit);
     }
 
     fn asmbl_f64_body(
@@ -925,6 +2054,7 @@ impl<'a> JIT<'a> {
         fb: &mut FunctionBuilder,
         fn_refs: &FnRefTable,
         vars: &[Variable],
+        math: MathImpl,
     ) {
         let use_oprnd = |oprnd: Oprnd, fb: &mut FunctionBuilder| match oprnd {
             Oprnd::Reg(indx) => fb.use_var(vars[indx as usize]),
@@ -943,17 +2073,33 @@ impl<'a> JIT<'a> {
                     let dst = dst as usize;
                     let val = use_oprnd(val, fb);
 
-                    let res = match op {
-                        UnOp::MOV => val,
-                        UnOp::SIN => call_fn("sin_f64", &[val], fb),
-                        UnOp::COS => call_fn("cos_f64", &[val], fb),
-                        UnOp::TAN => call_fn("tan_f64", &[val], fb),
+                    let res = match (op, math) {
+                        (UnOp::MOV, _) => val,
+                        (UnOp::SIN, MathImpl::Libm) => call_fn("sin_f64", &[val], fb),
+                        (UnOp::COS, MathImpl::Libm) => call_fn("cos_f64", &[val], fb),
+                        (UnOp::TAN, MathImpl::Libm) => call_fn("tan_f64", &[val], fb),
+                        (UnOp::FLOOR, _) => fb.ins().floor(val),
+                        (UnOp::CEIL, _) => fb.ins().ceil(val),
+                        (UnOp::ROUND, _) => fb.ins().nearest(val),
+                        (UnOp::FRACT, _) => Self::asmbl_fract(val, fb),
+                        (UnOp::SIN, MathImpl::Inline) => Self::asmbl_sin(val, fb),
+                        (UnOp::COS, MathImpl::Inline) => Self::asmbl_cos(val, fb),
+                        (UnOp::TAN, MathImpl::Inline) => Self::asmbl_tan(val, fb),
+                        (
+                            op @ (UnOp::GAMMA | UnOp::ERF | UnOp::J0 | UnOp::J1 | UnOp::LAMBERTW),
+                            _,
+                        ) => call_fn(&special_fn_name(op, "f64"), &[val], fb),
+                        (UnOp::CALL(f), _) => {
+                            let f = native_fn(f.get().f64 as *const u8, "native_f64", fn_refs, fb);
+                            call_fn("native_f64", &[f, val], fb)
+                        }
                     };
 
                     fb.def_var(vars[dst], res);
                 }
                 Instr::BinOp { op, lhs, rhs, dst } => {
                     let dst = dst as usize;
+                    let exp = Self::small_int_exponent(rhs);
                     let lhs = use_oprnd(lhs, fb);
                     let rhs = use_oprnd(rhs, fb);
 
@@ -962,103 +2108,174 @@ impl<'a> JIT<'a> {
                         BinOp::SUB => fb.ins().fsub(lhs, rhs),
                         BinOp::MUL => fb.ins().fmul(lhs, rhs),
                         BinOp::DIV => fb.ins().fdiv(lhs, rhs),
-                        BinOp::POW => call_fn("pow_f64", &[lhs, rhs], fb),
+                        BinOp::POW => match (math, exp) {
+                            (MathImpl::Libm, _) => call_fn("pow_f64", &[lhs, rhs], fb),
+                            (MathImpl::Inline, Some(n)) => Self::asmbl_powi(lhs, n, fb),
+                            (MathImpl::Inline, None) => Self::asmbl_pow(lhs, rhs, fb),
+                        },
+                        BinOp::MIN | BinOp::MAX => Self::asmbl_min_max(op, lhs, rhs, fb),
+                        BinOp::LT => Self::asmbl_cmp(FloatCC::LessThan, lhs, rhs, fb),
+                        BinOp::LE => Self::asmbl_cmp(FloatCC::LessThanOrEqual, lhs, rhs, fb),
+                        BinOp::MOD => Self::asmbl_mod(lhs, rhs, fb),
                     };
 
                     fb.def_var(vars[dst], res);
                 }
+                Instr::Select {
+                    cond,
+                    lhs,
+                    rhs,
+                    dst,
+                } => {
+                    let dst = dst as usize;
+                    let cond = use_oprnd(cond, fb);
+                    let lhs = use_oprnd(lhs, fb);
+                    let rhs = use_oprnd(rhs, fb);
+                    let res = Self::asmbl_select_nonzero(cond, lhs, rhs, fb);
+                    fb.def_var(vars[dst], res);
+                }
             }
         }
     }
 
-    pub unsafe fn compile_static_2f64x2_f64(
-        &self,
-        fn_name: &str,
+    fn asmbl_complex_body(
         bytecode: &[Instr],
-    ) -> extern "C" fn(F64X2, F64X2, *mut F64X2) {
-        let non_static = self.compile_2f64x2_f64(fn_name, bytecode);
-        unsafe { std::mem::transmute(non_static) }
-    }
-
-    pub fn compile_2f64x2_f64(
-        &self,
-        fn_name: &str,
-        bytecode: &[Instr],
-    ) -> extern "C" fn(F64X2, F64X2, *mut F64X2) -> Lt<'a> {
-        let mut ctx_mut = self.ctx.borrow_mut();
-        let mut module_mut = self.module.borrow_mut();
-
-        ctx_mut.set_disasm(self.emit_asm);
-        let ptr_ty = module_mut.target_config().pointer_type();
-
-        let mut sig = module_mut.make_signature();
-
-        for _ in 0..2 {
-            sig.params.push(AbiParam::new(types::F64X2));
-        }
-
-        sig.params
-            .push(AbiParam::special(ptr_ty, ir::ArgumentPurpose::StructReturn));
-
-        ctx_mut.func.signature = sig;
-
-        let mut fn_ctx = FunctionBuilderContext::new();
-        let mut fb = FunctionBuilder::new(&mut ctx_mut.func, &mut fn_ctx);
-
-        let entry = fb.create_block();
-        fb.append_block_params_for_function_params(entry);
-        fb.switch_to_block(entry);
-        fb.seal_block(entry);
-
-        let fn_refs = Self::decl_functions_in_function(&mut *module_mut, fb.func, &self.glob_fns);
-
-        let nan = fb.ins().f64const(f64::NAN);
-        let nan = fb.ins().splat(types::F64X2, nan);
+        fb: &mut FunctionBuilder,
+        fn_refs: &FnRefTable,
+        vars: &[(Variable, Variable)],
+        ptr_ty: Type,
+    ) {
+        let use_oprnd = |oprnd: Oprnd, fb: &mut FunctionBuilder| match oprnd {
+            Oprnd::Reg(indx) => {
+                let (re, im) = vars[indx as usize];
+                (fb.use_var(re), fb.use_var(im))
+            }
+            Oprnd::Imm(imm) => (fb.ins().f64const(imm), fb.ins().f64const(0.0)),
+        };
 
-        let mut vars = vec![];
-        for i in 0..16 {
-            let v = Variable::from_u32(i);
-            fb.declare_var(v, types::F64X2);
-            fb.def_var(v, nan);
-            vars.push(v);
-        }
+        let call_fn = |name: &str, v: &[Value], fb: &mut FunctionBuilder| {
+            let ret_slot = fb.create_sized_stack_slot(ir::StackSlotData {
+                kind: ir::StackSlotKind::ExplicitSlot,
+                size: 2 * 8,
+                align_shift: 3,
+            });
 
-        for i in 0..2 {
-            let x = fb.block_params(entry)[i as usize];
-            fb.def_var(vars[i as usize], x);
-        }
+            let fn_ref = fn_refs[name];
+            let ret_addr = fb.ins().stack_addr(ptr_ty, ret_slot, 0);
+            let mut args = vec![ret_addr];
+            args.extend_from_slice(v);
 
-        let out_ptr = fb.block_params(entry)[2];
+            let _ = fb.ins().call(fn_ref, &args);
+            let re = fb.ins().stack_load(types::F64, ret_slot, 0);
+            let im = fb.ins().stack_load(types::F64, ret_slot, 8);
+            (re, im)
+        };
 
-        Self::asmbl_f64x2_body(bytecode, &mut fb, &fn_refs, &vars, ptr_ty);
+        for &instr in bytecode {
+            match instr {
+                Instr::UnOp { op, val, dst } => {
+                    let dst = dst as usize;
+                    let (re, im) = use_oprnd(val, fb);
+
+                    let (re, im) = match op {
+                        UnOp::MOV => (re, im),
+                        UnOp::SIN => call_fn("sin_complex", &[re, im], fb),
+                        UnOp::COS => call_fn("cos_complex", &[re, im], fb),
+                        UnOp::TAN => call_fn("tan_complex", &[re, im], fb),
+                        UnOp::FLOOR => (fb.ins().floor(re), fb.ins().floor(im)),
+                        UnOp::CEIL => (fb.ins().ceil(re), fb.ins().ceil(im)),
+                        UnOp::ROUND => (fb.ins().nearest(re), fb.ins().nearest(im)),
+                        UnOp::FRACT => (Self::asmbl_fract(re, fb), Self::asmbl_fract(im, fb)),
+                        op @ (UnOp::GAMMA | UnOp::ERF | UnOp::J0 | UnOp::J1 | UnOp::LAMBERTW) => {
+                            call_fn(&special_fn_name(op, "complex"), &[re, im], fb)
+                        }
+                        UnOp::CALL(f) => {
+                            let f = f.get().f64 as *const u8;
+                            let f = native_fn(f, "native_complex", fn_refs, fb);
+                            call_fn("native_complex", &[f, re, im], fb)
+                        }
+                    };
 
-        let ret = fb.use_var(vars[0]);
-        fb.ins().store(ir::MemFlags::new(), ret, out_ptr, 0);
-        fb.ins().return_(&[]);
-        fb.finalize();
+                    fb.def_var(vars[dst].0, re);
+                    fb.def_var(vars[dst].1, im);
+                }
+                Instr::BinOp { op, lhs, rhs, dst } => {
+                    let dst = dst as usize;
+                    let (a, b) = use_oprnd(lhs, fb);
+                    let (c, d) = use_oprnd(rhs, fb);
+
+                    let (re, im) = match op {
+                        BinOp::ADD => (fb.ins().fadd(a, c), fb.ins().fadd(b, d)),
+                        BinOp::SUB => (fb.ins().fsub(a, c), fb.ins().fsub(b, d)),
+                        // (a + bi)(c + di) = (ac - bd) + (ad + bc)i
+                        BinOp::MUL => {
+                            let ac = fb.ins().fmul(a, c);
+                            let bd = fb.ins().fmul(b, d);
+                            let ad = fb.ins().fmul(a, d);
+                            let bc = fb.ins().fmul(b, c);
+                            (fb.ins().fsub(ac, bd), fb.ins().fadd(ad, bc))
+                        }
+                        // (a + bi) / (c + di) = ((ac + bd) + (bc - ad)i) / (c^2 + d^2)
+                        BinOp::DIV => {
+                            let cc = fb.ins().fmul(c, c);
+                            let dd = fb.ins().fmul(d, d);
+                            let n = fb.ins().fadd(cc, dd);
+                            let ac = fb.ins().fmul(a, c);
+                            let bd = fb.ins().fmul(b, d);
+                            let ad = fb.ins().fmul(a, d);
+                            let bc = fb.ins().fmul(b, c);
+                            let re = fb.ins().fadd(ac, bd);
+                            let im = fb.ins().fsub(bc, ad);
+                            (fb.ins().fdiv(re, n), fb.ins().fdiv(im, n))
+                        }
+                        BinOp::POW => call_fn("pow_complex", &[a, b, c, d], fb),
+                        // ordered by the real part
+                        BinOp::MIN | BinOp::MAX => {
+                            let cc = match op {
+                                BinOp::MIN => FloatCC::LessThanOrEqual,
+                                _ => FloatCC::GreaterThanOrEqual,
+                            };
+                            // like f64::min a nan rhs keeps lhs
+                            let cmp = fb.ins().fcmp(cc, a, c);
+                            let nan = fb.ins().fcmp(FloatCC::Unordered, c, c);
+                            let lhs = fb.ins().bor(cmp, nan);
+                            (fb.ins().select(lhs, a, c), fb.ins().select(lhs, b, d))
+                        }
+                        BinOp::LT => (
+                            Self::asmbl_cmp(FloatCC::LessThan, a, c, fb),
+                            fb.ins().f64const(0.0),
+                        ),
+                        BinOp::LE => (
+                            Self::asmbl_cmp(FloatCC::LessThanOrEqual, a, c, fb),
+                            fb.ins().f64const(0.0),
+                        ),
+                        BinOp::MOD => call_fn("mod_complex", &[a, b, c, d], fb),
+                    };
 
-        let fn_id = module_mut
-            .declare_function(fn_name, Linkage::Local, &ctx_mut.func.signature)
-            .unwrap();
+                    fb.def_var(vars[dst].0, re);
+                    fb.def_var(vars[dst].1, im);
+                }
+                // the condition is the real part
+                Instr::Select {
+                    cond,
+                    lhs,
+                    rhs,
+                    dst,
+                } => {
+                    let dst = dst as usize;
+                    let (c, _) = use_oprnd(cond, fb);
+                    let (a, b) = use_oprnd(lhs, fb);
+                    let (e, f) = use_oprnd(rhs, fb);
+                    let zero = fb.ins().f64const(0.0);
+                    let c = fb.ins().fcmp(FloatCC::NotEqual, c, zero);
 
-        module_mut.define_function(fn_id, &mut *ctx_mut).unwrap();
-        module_mut.finalize_definitions().unwrap();
+                    let (re, im) = (fb.ins().select(c, a, e), fb.ins().select(c, b, f));
 
-        if self.emit_asm {
-            *self.asm.borrow_mut() = Some(
-                ctx_mut
-                    .compiled_code()
-                    .unwrap()
-                    .vcode
-                    .clone()
-                    .unwrap()
-                    .to_string(),
-            );
+                    fb.def_var(vars[dst].0, re);
+                    fb.def_var(vars[dst].1, im);
+                }
+            }
         }
-
-        module_mut.clear_context(&mut *ctx_mut);
-        let fn_ptr = module_mut.get_finalized_function(fn_id);
-        unsafe { std::mem::transmute(fn_ptr) }
     }
 
     fn asmbl_f64x2_body(
@@ -1066,7 +2283,7 @@ impl<'a> JIT<'a> {
         fb: &mut FunctionBuilder,
         fn_refs: &FnRefTable,
         vars: &[Variable],
-        ptr_ty: Type,
+        math: MathImpl,
     ) {
         let use_oprnd = |oprnd: Oprnd, fb: &mut FunctionBuilder| match oprnd {
             Oprnd::Reg(indx) => fb.use_var(vars[indx as usize]),
@@ -1076,23 +2293,8 @@ impl<'a> JIT<'a> {
             }
         };
 
-        let call_fn = |name: &str, v: &[Value], fb: &mut FunctionBuilder| {
-            let ret_slot = fb.create_sized_stack_slot(ir::StackSlotData {
-                kind: ir::StackSlotKind::ExplicitSlot,
-                size: 2 * 8,
-                align_shift: 0,
-            });
-
-            let fn_ref = fn_refs[name];
-            let ret_addr = fb.ins().stack_addr(ptr_ty, ret_slot, 0);
-            let mut args = vec![ret_addr];
-            args.extend_from_slice(v);
-
-            let _ = fb.ins().call(fn_ref, &args);
-            let res = fb
-                .ins()
-                .load(types::F64X2, ir::MemFlags::new(), ret_addr, 0);
-            res
+        let call_fn = |name: &str, v: &[Value], fb: &mut FunctionBuilder| {
+            Self::asmbl_call_f64x2(name, v, fb, fn_refs)
         };
 
         for &instr in bytecode {
@@ -1101,17 +2303,34 @@ impl<'a> JIT<'a> {
                     let dst = dst as usize;
                     let val = use_oprnd(val, fb);
 
-                    let res = match op {
-                        UnOp::MOV => val,
-                        UnOp::SIN => call_fn("sin_f64x2", &[val], fb),
-                        UnOp::COS => call_fn("cos_f64x2", &[val], fb),
-                        UnOp::TAN => call_fn("tan_f64x2", &[val], fb),
+                    let res = match (op, math) {
+                        (UnOp::MOV, _) => val,
+                        (UnOp::SIN, MathImpl::Libm) => call_fn("sin_f64x2", &[val], fb),
+                        (UnOp::COS, MathImpl::Libm) => call_fn("cos_f64x2", &[val], fb),
+                        (UnOp::TAN, MathImpl::Libm) => call_fn("tan_f64x2", &[val], fb),
+                        (UnOp::FLOOR, _) => fb.ins().floor(val),
+                        (UnOp::CEIL, _) => fb.ins().ceil(val),
+                        (UnOp::ROUND, _) => fb.ins().nearest(val),
+                        (UnOp::FRACT, _) => Self::asmbl_fract(val, fb),
+                        (UnOp::SIN, MathImpl::Inline) => Self::asmbl_sin(val, fb),
+                        (UnOp::COS, MathImpl::Inline) => Self::asmbl_cos(val, fb),
+                        (UnOp::TAN, MathImpl::Inline) => Self::asmbl_tan(val, fb),
+                        (
+                            op @ (UnOp::GAMMA | UnOp::ERF | UnOp::J0 | UnOp::J1 | UnOp::LAMBERTW),
+                            _,
+                        ) => call_fn(&special_fn_name(op, "f64x2"), &[val], fb),
+                        (UnOp::CALL(f), _) => {
+                            let f = f.get().f64 as *const u8;
+                            let f = native_fn(f, "native_f64x2", fn_refs, fb);
+                            call_fn("native_f64x2", &[f, val], fb)
+                        }
                     };
 
                     fb.def_var(vars[dst], res);
                 }
                 Instr::BinOp { op, lhs, rhs, dst } => {
                     let dst = dst as usize;
+                    let exp = Self::small_int_exponent(rhs);
                     let lhs = use_oprnd(lhs, fb);
                     let rhs = use_oprnd(rhs, fb);
 
@@ -1120,103 +2339,214 @@ impl<'a> JIT<'a> {
                         BinOp::SUB => fb.ins().fsub(lhs, rhs),
                         BinOp::MUL => fb.ins().fmul(lhs, rhs),
                         BinOp::DIV => fb.ins().fdiv(lhs, rhs),
-                        BinOp::POW => call_fn("pow_f64x2", &[lhs, rhs], fb),
+                        BinOp::POW => match (math, exp) {
+                            (MathImpl::Libm, _) => call_fn("pow_f64x2", &[lhs, rhs], fb),
+                            (MathImpl::Inline, Some(n)) => Self::asmbl_powi(lhs, n, fb),
+                            (MathImpl::Inline, None) => Self::asmbl_pow(lhs, rhs, fb),
+                        },
+                        BinOp::MIN | BinOp::MAX => Self::asmbl_min_max(op, lhs, rhs, fb),
+                        BinOp::LT => Self::asmbl_cmp(FloatCC::LessThan, lhs, rhs, fb),
+                        BinOp::LE => Self::asmbl_cmp(FloatCC::LessThanOrEqual, lhs, rhs, fb),
+                        BinOp::MOD => Self::asmbl_mod(lhs, rhs, fb),
                     };
 
                     fb.def_var(vars[dst], res);
                 }
+                Instr::Select {
+                    cond,
+                    lhs,
+                    rhs,
+                    dst,
+                } => {
+                    let dst = dst as usize;
+                    let cond = use_oprnd(cond, fb);
+                    let lhs = use_oprnd(lhs, fb);
+                    let rhs = use_oprnd(rhs, fb);
+                    let res = Self::asmbl_select_nonzero(cond, lhs, rhs, fb);
+                    fb.def_var(vars[dst], res);
+                }
             }
         }
     }
 
-    pub unsafe fn compile_static_2intrvl_intrvl(
-        &self,
-        fn_name: &str,
-        bytecode: &[Instr],
-    ) -> extern "C" fn(F64X2, F64X2, *mut F64X2) {
-        let non_static = self.compile_2intrvl_intrvl(fn_name, bytecode);
-        unsafe { std::mem::transmute(non_static) }
-    }
-
-    pub fn compile_2intrvl_intrvl(
-        &self,
-        fn_name: &str,
+    /// forward mode derivatives, every register is a pair of (value, derivative)
+    fn asmbl_dual_body(
         bytecode: &[Instr],
-    ) -> extern "C" fn(F64X2, F64X2, *mut F64X2) -> Lt<'a> {
-        let mut ctx_mut = self.ctx.borrow_mut();
-        let mut module_mut = self.module.borrow_mut();
-
-        ctx_mut.set_disasm(self.emit_asm);
-        let ptr_ty = module_mut.target_config().pointer_type();
-
-        let mut sig = module_mut.make_signature();
-
-        for _ in 0..2 {
-            sig.params.push(AbiParam::new(types::F64X2));
-        }
-
-        sig.params
-            .push(AbiParam::special(ptr_ty, ir::ArgumentPurpose::StructReturn));
-
-        ctx_mut.func.signature = sig;
-
-        let mut fn_ctx = FunctionBuilderContext::new();
-        let mut fb = FunctionBuilder::new(&mut ctx_mut.func, &mut fn_ctx);
-
-        let entry = fb.create_block();
-        fb.append_block_params_for_function_params(entry);
-        fb.switch_to_block(entry);
-        fb.seal_block(entry);
-
-        let fn_refs = Self::decl_functions_in_function(&mut *module_mut, fb.func, &self.glob_fns);
-
-        let nan = fb.ins().f64const(f64::NAN);
-        let nan = fb.ins().splat(types::F64X2, nan);
-
-        let mut vars = vec![];
-        for i in 0..16 {
-            let v = Variable::from_u32(i);
-            fb.declare_var(v, types::F64X2);
-            fb.def_var(v, nan);
-            vars.push(v);
-        }
-
-        for i in 0..2 {
-            let x = fb.block_params(entry)[i as usize];
-            fb.def_var(vars[i as usize], x);
-        }
+        fb: &mut FunctionBuilder,
+        fn_refs: &FnRefTable,
+        vars: &[(Variable, Variable)],
+        math: MathImpl,
+    ) {
+        let use_oprnd = |oprnd: Oprnd, fb: &mut FunctionBuilder| match oprnd {
+            Oprnd::Reg(indx) => {
+                let (v, d) = vars[indx as usize];
+                (fb.use_var(v), fb.use_var(d))
+            }
+            Oprnd::Imm(imm) => (fb.ins().f64const(imm), fb.ins().f64const(0.0)),
+        };
 
-        let out_ptr = fb.block_params(entry)[2];
+        let call_fn = |name: &str, v: &[Value], fb: &mut FunctionBuilder| match (name, math) {
+            (_, MathImpl::Libm) => {
+                let fn_ref = fn_refs[name];
+                let call = fb.ins().call(fn_ref, v);
+                fb.inst_results(call)[0]
+            }
+            ("sin_f64", _) => Self::asmbl_sin(v[0], fb),
+            ("cos_f64", _) => Self::asmbl_cos(v[0], fb),
+            ("tan_f64", _) => Self::asmbl_tan(v[0], fb),
+            ("ln_f64", _) => Self::asmbl_ln(v[0], fb),
+            ("pow_f64", _) => Self::asmbl_pow(v[0], v[1], fb),
+            _ => unreachable!("no inline kernel for {name}"),
+        };
 
-        Self::asmbl_intrvl_body2(bytecode, &mut fb, &fn_refs, &vars, ptr_ty);
+        for &instr in bytecode {
+            match instr {
+                Instr::UnOp { op, val, dst } => {
+                    let dst = dst as usize;
+                    let (a, da) = use_oprnd(val, fb);
+
+                    let (v, d) = match op {
+                        UnOp::MOV => (a, da),
+                        UnOp::SIN => {
+                            let sin = call_fn("sin_f64", &[a], fb);
+                            let cos = call_fn("cos_f64", &[a], fb);
+                            (sin, fb.ins().fmul(cos, da))
+                        }
+                        UnOp::COS => {
+                            let sin = call_fn("sin_f64", &[a], fb);
+                            let cos = call_fn("cos_f64", &[a], fb);
+                            let d = fb.ins().fmul(sin, da);
+                            (cos, fb.ins().fneg(d))
+                        }
+                        // tan' = 1 + tan^2
+                        UnOp::TAN => {
+                            let tan = call_fn("tan_f64", &[a], fb);
+                            let one = fb.ins().f64const(1.0);
+                            let d = fb.ins().fma(tan, tan, one);
+                            (tan, fb.ins().fmul(d, da))
+                        }
+                        // steps are flat everywhere but at the jumps
+                        UnOp::FLOOR => (fb.ins().floor(a), fb.ins().f64const(0.0)),
+                        UnOp::CEIL => (fb.ins().ceil(a), fb.ins().f64const(0.0)),
+                        UnOp::ROUND => (fb.ins().nearest(a), fb.ins().f64const(0.0)),
+                        UnOp::FRACT => (Self::asmbl_fract(a, fb), da),
+                        // no inline kernels, always called
+                        op @ (UnOp::GAMMA | UnOp::ERF | UnOp::J0 | UnOp::J1 | UnOp::LAMBERTW) => {
+                            let call_rt = |name: String, fb: &mut FunctionBuilder| {
+                                let call = fb.ins().call(fn_refs[name.as_str()], &[a]);
+                                fb.inst_results(call)[0]
+                            };
+                            let v = call_rt(special_fn_name(op, "f64"), fb);
+                            let d = call_rt(special_fn_name(op, "deriv_f64"), fb);
+                            (v, fb.ins().fmul(d, da))
+                        }
+                        UnOp::CALL(f) => {
+                            let f = f.get();
+                            let call_rt = |f: fn(f64) -> f64, fb: &mut FunctionBuilder| {
+                                let f = native_fn(f as *const u8, "native_f64", fn_refs, fb);
+                                let call = fb.ins().call(fn_refs["native_f64"], &[f, a]);
+                                fb.inst_results(call)[0]
+                            };
+                            let v = call_rt(f.f64, fb);
+                            let d = call_rt(f.deriv, fb);
+                            (v, fb.ins().fmul(d, da))
+                        }
+                    };
 
-        let ret = fb.use_var(vars[0]);
-        fb.ins().store(ir::MemFlags::new(), ret, out_ptr, 0);
-        fb.ins().return_(&[]);
-        fb.finalize();
+                    fb.def_var(vars[dst].0, v);
+                    fb.def_var(vars[dst].1, d);
+                }
+                Instr::BinOp { op, lhs, rhs, dst } => {
+                    let dst = dst as usize;
+                    let (a, da) = use_oprnd(lhs, fb);
+                    let (b, db) = use_oprnd(rhs, fb);
+
+                    let (v, d) = match op {
+                        BinOp::ADD => (fb.ins().fadd(a, b), fb.ins().fadd(da, db)),
+                        BinOp::SUB => (fb.ins().fsub(a, b), fb.ins().fsub(da, db)),
+                        BinOp::MUL => {
+                            let l = fb.ins().fmul(da, b);
+                            let r = fb.ins().fmul(a, db);
+                            (fb.ins().fmul(a, b), fb.ins().fadd(l, r))
+                        }
+                        // (a / b)' = (a' - a / b * b') / b
+                        BinOp::DIV => {
+                            let v = fb.ins().fdiv(a, b);
+                            let r = fb.ins().fmul(v, db);
+                            let n = fb.ins().fsub(da, r);
+                            (v, fb.ins().fdiv(n, b))
+                        }
+                        // (a^b)' = b * a^(b - 1) * a' + a^b * ln(a) * b'
+                        // the second term is skipped for constant exponents so negative bases work
+                        BinOp::POW => {
+                            let v = call_fn("pow_f64", &[a, b], fb);
+                            let one = fb.ins().f64const(1.0);
+                            let b_1 = fb.ins().fsub(b, one);
+                            let p = call_fn("pow_f64", &[a, b_1], fb);
+                            let p = fb.ins().fmul(b, p);
+                            let l = fb.ins().fmul(p, da);
+
+                            let ln = call_fn("ln_f64", &[a], fb);
+                            let r = fb.ins().fmul(v, ln);
+                            let r = fb.ins().fmul(r, db);
+                            let d = fb.ins().fadd(l, r);
+
+                            let zero = fb.ins().f64const(0.0);
+                            let is_const = fb.ins().fcmp(FloatCC::Equal, db, zero);
+                            (v, fb.ins().select(is_const, l, d))
+                        }
+                        BinOp::MIN | BinOp::MAX => {
+                            let cc = match op {
+                                BinOp::MIN => FloatCC::LessThanOrEqual,
+                                _ => FloatCC::GreaterThanOrEqual,
+                            };
+                            // like f64::min a nan rhs keeps lhs
+                            let cmp = fb.ins().fcmp(cc, a, b);
+                            let nan = fb.ins().fcmp(FloatCC::Unordered, b, b);
+                            let lhs = fb.ins().bor(cmp, nan);
+                            (fb.ins().select(lhs, a, b), fb.ins().select(lhs, da, db))
+                        }
+                        BinOp::LT => (
+                            Self::asmbl_cmp(FloatCC::LessThan, a, b, fb),
+                            fb.ins().f64const(0.0),
+                        ),
+                        BinOp::LE => (
+                            Self::asmbl_cmp(FloatCC::LessThanOrEqual, a, b, fb),
+                            fb.ins().f64const(0.0),
+                        ),
+                        // (a - b floor(a / b))' = a' - b' floor(a / b)
+                        BinOp::MOD => {
+                            let q = fb.ins().fdiv(a, b);
+                            let k = fb.ins().floor(q);
+                            let bk = fb.ins().fmul(b, k);
+                            let dbk = fb.ins().fmul(db, k);
+                            (fb.ins().fsub(a, bk), fb.ins().fsub(da, dbk))
+                        }
+                    };
 
-        let fn_id = module_mut
-            .declare_function(fn_name, Linkage::Local, &ctx_mut.func.signature)
-            .unwrap();
+                    fb.def_var(vars[dst].0, v);
+                    fb.def_var(vars[dst].1, d);
+                }
+                Instr::Select {
+                    cond,
+                    lhs,
+                    rhs,
+                    dst,
+                } => {
+                    let dst = dst as usize;
+                    let (c, _) = use_oprnd(cond, fb);
+                    let (a, da) = use_oprnd(lhs, fb);
+                    let (b, db) = use_oprnd(rhs, fb);
+                    let zero = fb.ins().f64const(0.0);
+                    let c = fb.ins().fcmp(FloatCC::NotEqual, c, zero);
 
-        module_mut.define_function(fn_id, &mut *ctx_mut).unwrap();
-        module_mut.finalize_definitions().unwrap();
+                    let (v, d) = (fb.ins().select(c, a, b), fb.ins().select(c, da, db));
 
-        if self.emit_asm {
-            *self.asm.borrow_mut() = Some(
-                ctx_mut
-                    .compiled_code()
-                    .unwrap()
-                    .vcode
-                    .clone()
-                    .unwrap()
-                    .to_string(),
-            );
+                    fb.def_var(vars[dst].0, v);
+                    fb.def_var(vars[dst].1, d);
+                }
+            }
         }
-
-        module_mut.clear_context(&mut *ctx_mut);
-        let fn_ptr = module_mut.get_finalized_function(fn_id);
-        unsafe { std::mem::transmute(fn_ptr) }
     }
 
     fn asmbl_intrvl_body2(
@@ -1224,7 +2554,7 @@ impl<'a> JIT<'a> {
         fb: &mut FunctionBuilder,
         fn_refs: &FnRefTable,
         vars: &[Variable],
-        ptr_ty: Type,
+        math: MathImpl,
     ) {
         let use_oprnd = |oprnd: Oprnd, fb: &mut FunctionBuilder| match oprnd {
             Oprnd::Reg(indx) => fb.use_var(vars[indx as usize]),
@@ -1235,22 +2565,7 @@ impl<'a> JIT<'a> {
         };
 
         let call_fn = |name: &str, v: &[Value], fb: &mut FunctionBuilder| {
-            let ret_slot = fb.create_sized_stack_slot(ir::StackSlotData {
-                kind: ir::StackSlotKind::ExplicitSlot,
-                size: 2 * 8,
-                align_shift: 0,
-            });
-
-            let fn_ref = fn_refs[name];
-            let ret_addr = fb.ins().stack_addr(ptr_ty, ret_slot, 0);
-            let mut args = vec![ret_addr];
-            args.extend_from_slice(v);
-
-            let _ = fb.ins().call(fn_ref, &args);
-            let res = fb
-                .ins()
-                .load(types::F64X2, ir::MemFlags::new(), ret_addr, 0);
-            res
+            Self::asmbl_call_f64x2(name, v, fb, fn_refs)
         };
 
         for &instr in bytecode {
@@ -1259,11 +2574,32 @@ impl<'a> JIT<'a> {
                     let dst = dst as usize;
                     let val = use_oprnd(val, fb);
 
-                    let res = match op {
-                        UnOp::MOV => val,
-                        UnOp::SIN => call_fn("sin_intrvl", &[val], fb),
-                        UnOp::COS => call_fn("cos_intrvl", &[val], fb),
-                        UnOp::TAN => call_fn("tan_intrvl", &[val], fb),
+                    let res = match (op, math) {
+                        (UnOp::MOV, _) => val,
+                        (UnOp::SIN, MathImpl::Libm) => call_fn("sin_intrvl", &[val], fb),
+                        (UnOp::COS, MathImpl::Libm) => call_fn("cos_intrvl", &[val], fb),
+                        (UnOp::TAN, MathImpl::Libm) => call_fn("tan_intrvl", &[val], fb),
+                        (UnOp::SIN, MathImpl::Inline) => {
+                            Self::asmbl_sin_cos_intrvl_inline(val, false, fb)
+                        }
+                        (UnOp::COS, MathImpl::Inline) => {
+                            Self::asmbl_sin_cos_intrvl_inline(val, true, fb)
+                        }
+                        (UnOp::TAN, MathImpl::Inline) => Self::asmbl_tan_intrvl_inline(val, fb),
+                        // monotonic, computed lane wise
+                        (UnOp::FLOOR, _) => fb.ins().floor(val),
+                        (UnOp::CEIL, _) => fb.ins().ceil(val),
+                        (UnOp::ROUND, _) => fb.ins().nearest(val),
+                        (UnOp::FRACT, _) => call_fn("fract_intrvl", &[val], fb),
+                        (
+                            op @ (UnOp::GAMMA | UnOp::ERF | UnOp::J0 | UnOp::J1 | UnOp::LAMBERTW),
+                            _,
+                        ) => call_fn(&special_fn_name(op, "intrvl"), &[val], fb),
+                        (UnOp::CALL(f), _) => {
+                            let f = f.get().intrvl as *const u8;
+                            let f = native_fn(f, "native_intrvl", fn_refs, fb);
+                            call_fn("native_intrvl", &[f, val], fb)
+                        }
                     };
 
                     fb.def_var(vars[dst], res);
@@ -1283,10 +2619,27 @@ impl<'a> JIT<'a> {
                         BinOp::MUL => call_fn("mul_intrvl", &[lhs, rhs], fb),
                         BinOp::DIV => call_fn("div_intrvl", &[lhs, rhs], fb),
                         BinOp::POW => call_fn("pow_intrvl", &[lhs, rhs], fb),
+                        BinOp::MIN | BinOp::MAX => Self::asmbl_min_max(op, lhs, rhs, fb),
+                        BinOp::LT => Self::asmbl_cmp_intrvl(FloatCC::LessThan, lhs, rhs, fb),
+                        BinOp::LE => Self::asmbl_cmp_intrvl(FloatCC::LessThanOrEqual, lhs, rhs, fb),
+                        BinOp::MOD => call_fn("mod_intrvl", &[lhs, rhs], fb),
                     };
 
                     fb.def_var(vars[dst], res);
                 }
+                Instr::Select {
+                    cond,
+                    lhs,
+                    rhs,
+                    dst,
+                } => {
+                    let dst = dst as usize;
+                    let cond = use_oprnd(cond, fb);
+                    let lhs = use_oprnd(lhs, fb);
+                    let rhs = use_oprnd(rhs, fb);
+                    let res = Self::asmbl_select_intrvl(cond, lhs, rhs, fb);
+                    fb.def_var(vars[dst], res);
+                }
             }
         }
     }
@@ -1296,7 +2649,6 @@ impl<'a> JIT<'a> {
         fb: &mut FunctionBuilder,
         fn_refs: &FnRefTable,
         vars: &[Variable],
-        ptr_ty: Type,
     ) {
         let use_oprnd = |oprnd: Oprnd, fb: &mut FunctionBuilder| match oprnd {
             Oprnd::Reg(indx) => fb.use_var(vars[indx as usize]),
@@ -1307,22 +2659,7 @@ impl<'a> JIT<'a> {
         };
 
         let call_fn = |name: &str, v: &[Value], fb: &mut FunctionBuilder| {
-            let ret_slot = fb.create_sized_stack_slot(ir::StackSlotData {
-                kind: ir::StackSlotKind::ExplicitSlot,
-                size: 2 * 8,
-                align_shift: 0,
-            });
-
-            let fn_ref = fn_refs[name];
-            let ret_addr = fb.ins().stack_addr(ptr_ty, ret_slot, 0);
-            let mut args = vec![ret_addr];
-            args.extend_from_slice(v);
-
-            let _ = fb.ins().call(fn_ref, &args);
-            let res = fb
-                .ins()
-                .load(types::F64X2, ir::MemFlags::new(), ret_addr, 0);
-            res
+            Self::asmbl_call_f64x2(name, v, fb, fn_refs)
         };
 
         for &instr in bytecode {
@@ -1336,6 +2673,18 @@ impl<'a> JIT<'a> {
                         UnOp::SIN => Self::asmbl_sin_intrvl(val, fb, fn_refs),
                         UnOp::COS => Self::asmbl_cos_intrvl(val, fb, fn_refs),
                         UnOp::TAN => call_fn("tan_intrvl", &[val], fb),
+                        UnOp::FLOOR => fb.ins().floor(val),
+                        UnOp::CEIL => fb.ins().ceil(val),
+                        UnOp::ROUND => fb.ins().nearest(val),
+                        UnOp::FRACT => call_fn("fract_intrvl", &[val], fb),
+                        op @ (UnOp::GAMMA | UnOp::ERF | UnOp::J0 | UnOp::J1 | UnOp::LAMBERTW) => {
+                            call_fn(&special_fn_name(op, "intrvl"), &[val], fb)
+                        }
+                        UnOp::CALL(f) => {
+                            let f = f.get().intrvl as *const u8;
+                            let f = native_fn(f, "native_intrvl", fn_refs, fb);
+                            call_fn("native_intrvl", &[f, val], fb)
+                        }
                     };
 
                     fb.def_var(vars[dst], res);
@@ -1351,10 +2700,27 @@ impl<'a> JIT<'a> {
                         BinOp::MUL => Self::asmbl_mul_intrvl(lhs, rhs, fb),
                         BinOp::DIV => Self::asmbl_div_intrvl(lhs, rhs, fb),
                         BinOp::POW => Self::asmbl_pow_intrvl(lhs, rhs, fb, fn_refs),
+                        BinOp::MIN | BinOp::MAX => Self::asmbl_min_max(op, lhs, rhs, fb),
+                        BinOp::LT => Self::asmbl_cmp_intrvl(FloatCC::LessThan, lhs, rhs, fb),
+                        BinOp::LE => Self::asmbl_cmp_intrvl(FloatCC::LessThanOrEqual, lhs, rhs, fb),
+                        BinOp::MOD => call_fn("mod_intrvl", &[lhs, rhs], fb),
                     };
 
                     fb.def_var(vars[dst], res);
                 }
+                Instr::Select {
+                    cond,
+                    lhs,
+                    rhs,
+                    dst,
+                } => {
+                    let dst = dst as usize;
+                    let cond = use_oprnd(cond, fb);
+                    let lhs = use_oprnd(lhs, fb);
+                    let rhs = use_oprnd(rhs, fb);
+                    let res = Self::asmbl_select_intrvl(cond, lhs, rhs, fb);
+                    fb.def_var(vars[dst], res);
+                }
             }
         }
     }
@@ -1372,6 +2738,43 @@ impl<'a> JIT<'a> {
         fb.inst_results(call)[0]
     }
 
+    /// calls a function taking and returning F64X2, see [`FnParam::F64X2`]
+    fn asmbl_call_f64x2(
+        name: &str,
+        v: &[Value],
+        fb: &mut FunctionBuilder,
+        fn_refs: &FnRefTable,
+    ) -> Value {
+        let fn_ref = fn_refs[name];
+        let sig = fb.func.dfg.ext_funcs[fn_ref].signature;
+        // the out pointer comes first
+        let ptr_ty = fb.func.dfg.signatures[sig].params[0].value_type;
+
+        let slot = |fb: &mut FunctionBuilder| {
+            fb.create_sized_stack_slot(ir::StackSlotData {
+                kind: ir::StackSlotKind::ExplicitSlot,
+                size: 2 * 8,
+                align_shift: 4,
+            })
+        };
+        let out = slot(fb);
+
+        let mut args = vec![fb.ins().stack_addr(ptr_ty, out, 0)];
+        for &v in v {
+            // scalars like the callee of a native are passed as they are
+            if !fb.func.dfg.value_type(v).is_vector() {
+                args.push(v);
+                continue;
+            }
+            let arg = slot(fb);
+            fb.ins().stack_store(v, arg, 0);
+            args.push(fb.ins().stack_addr(ptr_ty, arg, 0));
+        }
+
+        fb.ins().call(fn_ref, &args);
+        fb.ins().stack_load(types::F64X2, out, 0)
+    }
+
     fn asmbl_add_intrvl(lhs: Value, rhs: Value, fb: &mut FunctionBuilder) -> Value {
         fb.ins().fadd(lhs, rhs)
     }
@@ -1448,6 +2851,38 @@ impl<'a> JIT<'a> {
         Self::asmbl_mul_intrvl(lhs, inv_rhs, fb)
     }
 
+    /// [lhs.hi < rhs.lo, lhs.lo < rhs.hi], so [1, 1] is true, [0, 0] false and [0, 1] undecided
+    fn asmbl_cmp_intrvl(cc: FloatCC, lhs: Value, rhs: Value, fb: &mut FunctionBuilder) -> Value {
+        let (a, b) = (fb.ins().extractlane(lhs, 0), fb.ins().extractlane(lhs, 1));
+        let (c, d) = (fb.ins().extractlane(rhs, 0), fb.ins().extractlane(rhs, 1));
+        let lo = Self::asmbl_cmp(cc, b, c, fb);
+        let hi = Self::asmbl_cmp(cc, a, d, fb);
+        Self::asmbl_f64x2(lo, hi, fb)
+    }
+
+    /// lhs if cond excludes zero, rhs if cond is [0, 0], else the hull of both
+    fn asmbl_select_intrvl(cond: Value, lhs: Value, rhs: Value, fb: &mut FunctionBuilder) -> Value {
+        let zero = fb.ins().f64const(0.0);
+        let cl = fb.ins().extractlane(cond, 0);
+        let ch = fb.ins().extractlane(cond, 1);
+        let pos = fb.ins().fcmp(FloatCC::GreaterThan, cl, zero);
+        let neg = fb.ins().fcmp(FloatCC::LessThan, ch, zero);
+        let decided = fb.ins().bor(pos, neg);
+        let lo_zero = fb.ins().fcmp(FloatCC::Equal, cl, zero);
+        let hi_zero = fb.ins().fcmp(FloatCC::Equal, ch, zero);
+        let is_zero = fb.ins().band(lo_zero, hi_zero);
+
+        let (al, ah) = (fb.ins().extractlane(lhs, 0), fb.ins().extractlane(lhs, 1));
+        let (bl, bh) = (fb.ins().extractlane(rhs, 0), fb.ins().extractlane(rhs, 1));
+        let min = fb.ins().fmin(al, bl);
+        let max = fb.ins().fmax(ah, bh);
+        let lo = fb.ins().select(is_zero, bl, min);
+        let lo = fb.ins().select(decided, al, lo);
+        let hi = fb.ins().select(is_zero, bh, max);
+        let hi = fb.ins().select(decided, ah, hi);
+        Self::asmbl_f64x2(lo, hi, fb)
+    }
+
     fn asmbl_f64x2(v0: Value, v1: Value, fb: &mut FunctionBuilder) -> Value {
         let vec = fb.ins().splat(types::F64X2, v0);
         fb.ins().insertlane(vec, v1, 1)
@@ -1723,117 +3158,656 @@ impl<'a> JIT<'a> {
             .brif(b_contains_zero, blk_undef, &[], blk_a_good, &[]);
         fb.seal_block(blk_a_good);
 
-        fb.switch_to_block(blk_a_good);
-        let hi_pow = Self::asmbl_call_f64("pow_f64", &[a_hi, b_hi], fb, fn_refs);
-        let vec_a2 = Self::asmbl_f64x2(zero, hi_pow, fb);
-        fb.ins().jump(blk_exit, &[vec_a2]);
+        fb.switch_to_block(blk_a_good);
+        let hi_pow = Self::asmbl_call_f64("pow_f64", &[a_hi, b_hi], fb, fn_refs);
+        let vec_a2 = Self::asmbl_f64x2(zero, hi_pow, fb);
+        fb.ins().jump(blk_exit, &[vec_a2]);
+
+        fb.switch_to_block(blk_a_neg);
+        fb.seal_block(blk_a_neg);
+        let b_eq = fb.ins().fcmp(FloatCC::Equal, b_lo, b_hi);
+        let floor_call = fb.ins().floor(b_lo);
+        let fract = fb.ins().fsub(b_lo, floor_call);
+        let fract_le_eps = fb.ins().fcmp(FloatCC::LessThanOrEqual, fract, eps);
+        let is_const_int = fb.ins().band(b_eq, fract_le_eps);
+        let blk_b_good = fb.create_block();
+        // println!("blk_b_good: {:?}", blk_b_good);
+        fb.ins().brif(is_const_int, blk_b_good, &[], blk_undef, &[]);
+        fb.seal_block(blk_b_good);
+
+        fb.switch_to_block(blk_b_good);
+        let p_hi = Self::asmbl_call_f64("pow_f64", &[a_hi, b_lo], fb, fn_refs);
+        let p_lo = Self::asmbl_call_f64("pow_f64", &[a_lo, b_lo], fb, fn_refs);
+        let lo_b = fb.ins().fmin(p_hi, p_lo);
+        let hi_b = fb.ins().fmax(p_hi, p_lo);
+        let vec_b = Self::asmbl_f64x2(lo_b, hi_b, fb);
+        fb.ins().jump(blk_exit, &[vec_b]);
+
+        let blk_c_body = fb.create_block();
+        // println!("blk_c_body: {:?}", blk_c_body);
+
+        fb.switch_to_block(blk_a_mixed_int);
+        fb.seal_block(blk_a_mixed_int);
+        fb.ins().brif(is_const_int, blk_c_body, &[], blk_undef, &[]);
+
+        fb.switch_to_block(blk_c_body);
+        fb.seal_block(blk_c_body);
+
+        let is_even = Self::asmbl_call_f64("is_even_f64", &[b_lo], fb, fn_refs);
+        let blk_c_even = fb.create_block();
+        let blk_c_odd = fb.create_block();
+        // println!("blk_c_even: {:?}", blk_c_even);
+        // println!("blk_c_odd: {:?}", blk_c_odd);
+        fb.ins().brif(is_even, blk_c_even, &[], blk_c_odd, &[]);
+
+        fb.switch_to_block(blk_c_even);
+        fb.seal_block(blk_c_even);
+        let abs_lo = fb.ins().fabs(a_lo); // assemble intrinsic for fabs
+        let abs_hi = fb.ins().fabs(a_hi);
+        let max_abs = fb.ins().fmax(abs_lo, abs_hi);
+        let pow_even = Self::asmbl_call_f64("pow_f64", &[max_abs, b_lo], fb, fn_refs);
+        let vec_ce = Self::asmbl_f64x2(zero, pow_even, fb);
+        fb.ins().jump(blk_exit, &[vec_ce]);
+
+        fb.switch_to_block(blk_c_odd);
+        fb.seal_block(blk_c_odd);
+        let lo_co = Self::asmbl_call_f64("pow_f64", &[a_lo, b_lo], fb, fn_refs);
+        let pow_co = Self::asmbl_call_f64("pow_f64", &[max_abs, b_lo], fb, fn_refs);
+        let vec_co = Self::asmbl_f64x2(lo_co, pow_co, fb);
+        fb.ins().jump(blk_exit, &[vec_co]);
+
+        fb.switch_to_block(blk_undef);
+        fb.seal_block(blk_undef);
+        let nan = fb.ins().f64const(f64::NAN);
+        let vec_u = Self::asmbl_f64x2(nan, nan, fb);
+        fb.ins().jump(blk_exit, &[vec_u]);
+
+        fb.switch_to_block(blk_exit);
+        fb.append_block_param(blk_exit, types::F64X2);
+        fb.seal_block(blk_exit);
+        fb.block_params(blk_exit)[0]
+    }
+}
+
+/// inline math kernels for [`MathImpl::Inline`], generic over `F64` and float vector types
+// the coefficients are kept as printed by fdlibm
+#[allow(clippy::excessive_precision)]
+impl JIT {
+    /// 1.5 * 2^52, adding it to an integral float < 2^51 leaves the integer in the low mantissa bits
+    const ROUND_MAGIC: f64 = 6755399441055744.0;
+
+    // pi/2 split into three parts, the first two have 33 significant bits so k * PIO2_* is exact
+    const PIO2_1: f64 = 1.57079632673412561417e+00;
+    const PIO2_2: f64 = 6.07710050630396597660e-11;
+    const PIO2_3: f64 = 2.02226624871116645580e-21;
+    /// bound on |x| below which k < 2^20 in [`JIT::asmbl_reduce_pio2`], so the reduction is
+    /// exact and the kernel error bounds of [`MathImpl::Inline`] hold
+    const REDUCE_MAX: f64 = 1e6;
+
+    const LN2_HI: f64 = 6.93147180369123816490e-01;
+    const LN2_LO: f64 = 1.90821492927058770002e-10;
+
+    fn asmbl_fconst(ty: Type, v: f64, fb: &mut FunctionBuilder) -> Value {
+        let c = fb.ins().f64const(v);
+        if ty.is_vector() {
+            fb.ins().splat(ty, c)
+        } else {
+            c
+        }
+    }
+
+    fn asmbl_iconst(ty: Type, v: i64, fb: &mut FunctionBuilder) -> Value {
+        let c = fb.ins().iconst(types::I64, v);
+        if ty.is_vector() {
+            fb.ins().splat(ty.as_int(), c)
+        } else {
+            c
+        }
+    }
+
+    /// `cond ? a : b` for the result of a (vector) fcmp
+    fn asmbl_select(cond: Value, a: Value, b: Value, fb: &mut FunctionBuilder) -> Value {
+        let ty = fb.func.dfg.value_type(a);
+        if ty.is_vector() {
+            let mask = fb.ins().bitcast(ty, ir::MemFlags::new(), cond);
+            fb.ins().bitselect(mask, a, b)
+        } else {
+            fb.ins().select(cond, a, b)
+        }
+    }
+
+    /// lane wise min / max like f64::min, a nan operand yields the other one.
+    /// fmin / fmax of cranelift return nan instead
+    fn asmbl_min_max(op: BinOp, a: Value, b: Value, fb: &mut FunctionBuilder) -> Value {
+        let m = match op {
+            BinOp::MAX => fb.ins().fmax(a, b),
+            _ => fb.ins().fmin(a, b),
+        };
+        let a_nan = fb.ins().fcmp(FloatCC::Unordered, a, a);
+        let b_nan = fb.ins().fcmp(FloatCC::Unordered, b, b);
+        let m = Self::asmbl_select(a_nan, b, m, fb);
+        Self::asmbl_select(b_nan, a, m, fb)
+    }
+
+    /// val - floor(val)
+    fn asmbl_fract(val: Value, fb: &mut FunctionBuilder) -> Value {
+        let floor = fb.ins().floor(val);
+        fb.ins().fsub(val, floor)
+    }
+
+    /// lhs - rhs * floor(lhs / rhs)
+    fn asmbl_mod(lhs: Value, rhs: Value, fb: &mut FunctionBuilder) -> Value {
+        let q = fb.ins().fdiv(lhs, rhs);
+        let k = fb.ins().floor(q);
+        let m = fb.ins().fmul(rhs, k);
+        fb.ins().fsub(lhs, m)
+    }
+
+    /// 1 if the comparison holds, else 0
+    fn asmbl_cmp(cc: FloatCC, a: Value, b: Value, fb: &mut FunctionBuilder) -> Value {
+        let ty = fb.func.dfg.value_type(a);
+        let cond = fb.ins().fcmp(cc, a, b);
+        let one = Self::asmbl_fconst(ty, 1.0, fb);
+        let zero = Self::asmbl_fconst(ty, 0.0, fb);
+        Self::asmbl_select(cond, one, zero, fb)
+    }
+
+    /// `cond != 0 ? a : b`, a nan condition picks a
+    fn asmbl_select_nonzero(cond: Value, a: Value, b: Value, fb: &mut FunctionBuilder) -> Value {
+        let ty = fb.func.dfg.value_type(cond);
+        let zero = Self::asmbl_fconst(ty, 0.0, fb);
+        let cond = fb.ins().fcmp(FloatCC::NotEqual, cond, zero);
+        Self::asmbl_select(cond, a, b, fb)
+    }
+
+    fn asmbl_bits(v: Value, fb: &mut FunctionBuilder) -> Value {
+        let ty = fb.func.dfg.value_type(v);
+        fb.ins().bitcast(ty.as_int(), ir::MemFlags::new(), v)
+    }
+
+    fn asmbl_from_bits(ty: Type, v: Value, fb: &mut FunctionBuilder) -> Value {
+        fb.ins().bitcast(ty, ir::MemFlags::new(), v)
+    }
+
+    /// c[0] + x * (c[1] + x * (c[2] + ...))
+    fn asmbl_horner(x: Value, c: &[f64], fb: &mut FunctionBuilder) -> Value {
+        let ty = fb.func.dfg.value_type(x);
+        let (last, rest) = c.split_last().unwrap();
+        let mut acc = Self::asmbl_fconst(ty, *last, fb);
+        for &c in rest.iter().rev() {
+            let c = Self::asmbl_fconst(ty, c, fb);
+            acc = fb.ins().fmul(acc, x);
+            acc = fb.ins().fadd(acc, c);
+        }
+        acc
+    }
+
+    /// integral `k` with |k| < 2^51 as integer
+    fn asmbl_to_int(k: Value, fb: &mut FunctionBuilder) -> Value {
+        let ty = fb.func.dfg.value_type(k);
+        let magic = Self::asmbl_fconst(ty, Self::ROUND_MAGIC, fb);
+        let k = fb.ins().fadd(k, magic);
+        let k = Self::asmbl_bits(k, fb);
+        let magic = Self::asmbl_iconst(ty, Self::ROUND_MAGIC.to_bits() as i64, fb);
+        fb.ins().isub(k, magic)
+    }
+
+    /// integer `i` with |i| < 2^51 as float
+    fn asmbl_to_float(ty: Type, i: Value, fb: &mut FunctionBuilder) -> Value {
+        let magic = Self::asmbl_iconst(ty, Self::ROUND_MAGIC.to_bits() as i64, fb);
+        let i = fb.ins().iadd(i, magic);
+        let f = Self::asmbl_from_bits(ty, i, fb);
+        let magic = Self::asmbl_fconst(ty, Self::ROUND_MAGIC, fb);
+        fb.ins().fsub(f, magic)
+    }
+
+    /// 2^k for integer k in [-1022, 1023]
+    fn asmbl_exp2i(ty: Type, k: Value, fb: &mut FunctionBuilder) -> Value {
+        let bias = Self::asmbl_iconst(ty, 1023, fb);
+        let e = fb.ins().iadd(k, bias);
+        let e = fb.ins().ishl_imm(e, 52);
+        Self::asmbl_from_bits(ty, e, fb)
+    }
+
+    /// x = k * pi/2 + r with |r| <= pi/4, returns (k, r)
+    fn asmbl_reduce_pio2(x: Value, fb: &mut FunctionBuilder) -> (Value, Value) {
+        let ty = fb.func.dfg.value_type(x);
+        let inv = Self::asmbl_fconst(ty, std::f64::consts::FRAC_2_PI, fb);
+        let k = fb.ins().fmul(x, inv);
+        let k = fb.ins().nearest(k);
+
+        let mut r = x;
+        for c in [Self::PIO2_1, Self::PIO2_2, Self::PIO2_3] {
+            let c = Self::asmbl_fconst(ty, c, fb);
+            let kc = fb.ins().fmul(k, c);
+            r = fb.ins().fsub(r, kc);
+        }
+        (k, r)
+    }
+
+    /// sin(r) for |r| <= pi/4
+    fn asmbl_sin_kernel(r: Value, fb: &mut FunctionBuilder) -> Value {
+        const S: [f64; 6] = [
+            -1.66666666666666324348e-01,
+            8.33333333332248946124e-03,
+            -1.98412698298579493134e-04,
+            2.75573137070700676789e-06,
+            -2.50507602534068634195e-08,
+            1.58969099521155010221e-10,
+        ];
+        let z = fb.ins().fmul(r, r);
+        let v = fb.ins().fmul(z, r);
+        let p = Self::asmbl_horner(z, &S, fb);
+        let p = fb.ins().fmul(v, p);
+        fb.ins().fadd(r, p)
+    }
+
+    /// cos(r) for |r| <= pi/4
+    fn asmbl_cos_kernel(r: Value, fb: &mut FunctionBuilder) -> Value {
+        const C: [f64; 6] = [
+            4.16666666666666019037e-02,
+            -1.38888888888741095749e-03,
+            2.48015872894767294178e-05,
+            -2.75573143513906633035e-07,
+            2.08757232129817482790e-09,
+            -1.13596475577881948265e-11,
+        ];
+        let ty = fb.func.dfg.value_type(r);
+        let one = Self::asmbl_fconst(ty, 1.0, fb);
+        let half = Self::asmbl_fconst(ty, 0.5, fb);
+
+        // 1 - z/2 + z^2 * p(z), with the rounding error of 1 - z/2 added back
+        let z = fb.ins().fmul(r, r);
+        let hz = fb.ins().fmul(z, half);
+        let w = fb.ins().fsub(one, hz);
+        let p = Self::asmbl_horner(z, &C, fb);
+        let zz = fb.ins().fmul(z, z);
+        let p = fb.ins().fmul(zz, p);
+        let e = fb.ins().fsub(one, w);
+        let e = fb.ins().fsub(e, hz);
+        let e = fb.ins().fadd(e, p);
+        fb.ins().fadd(w, e)
+    }
+
+    /// quadrant of `k` as float in 0..4
+    fn asmbl_quadrant(k: Value, fb: &mut FunctionBuilder) -> Value {
+        let ty = fb.func.dfg.value_type(k);
+        let quarter = Self::asmbl_fconst(ty, 0.25, fb);
+        let four = Self::asmbl_fconst(ty, 4.0, fb);
+        let h = fb.ins().fmul(k, quarter);
+        let fl = fb.ins().floor(h);
+        let fr = fb.ins().fsub(h, fl);
+        fb.ins().fmul(fr, four)
+    }
+
+    fn asmbl_is_odd(q: Value, fb: &mut FunctionBuilder) -> Value {
+        let ty = fb.func.dfg.value_type(q);
+        let half = Self::asmbl_fconst(ty, 0.5, fb);
+        let h = fb.ins().fmul(q, half);
+        let fl = fb.ins().nearest(h);
+        fb.ins().fcmp(FloatCC::NotEqual, h, fl)
+    }
+
+    fn asmbl_sin_cos(x: Value, cos: bool, fb: &mut FunctionBuilder) -> Value {
+        let ty = fb.func.dfg.value_type(x);
+        let (k, r) = Self::asmbl_reduce_pio2(x, fb);
+        let q = Self::asmbl_quadrant(k, fb);
+
+        let s = Self::asmbl_sin_kernel(r, fb);
+        let c = Self::asmbl_cos_kernel(r, fb);
+
+        // cos(x) = sin(x + pi/2)
+        let q = if cos {
+            let one = Self::asmbl_fconst(ty, 1.0, fb);
+            fb.ins().fadd(q, one)
+        } else {
+            q
+        };
+
+        let odd = Self::asmbl_is_odd(q, fb);
+        let res = Self::asmbl_select(odd, c, s, fb);
+
+        let (two, four) = (
+            Self::asmbl_fconst(ty, 2.0, fb),
+            Self::asmbl_fconst(ty, 4.0, fb),
+        );
+        let ge_2 = fb.ins().fcmp(FloatCC::GreaterThanOrEqual, q, two);
+        let lt_4 = fb.ins().fcmp(FloatCC::LessThan, q, four);
+        let neg = fb.ins().band(ge_2, lt_4);
+        let neg_res = fb.ins().fneg(res);
+        Self::asmbl_select(neg, neg_res, res, fb)
+    }
+
+    fn asmbl_sin(x: Value, fb: &mut FunctionBuilder) -> Value {
+        Self::asmbl_sin_cos(x, false, fb)
+    }
+
+    fn asmbl_cos(x: Value, fb: &mut FunctionBuilder) -> Value {
+        Self::asmbl_sin_cos(x, true, fb)
+    }
+
+    fn asmbl_tan(x: Value, fb: &mut FunctionBuilder) -> Value {
+        let (k, r) = Self::asmbl_reduce_pio2(x, fb);
+        let s = Self::asmbl_sin_kernel(r, fb);
+        let c = Self::asmbl_cos_kernel(r, fb);
+
+        // tan(r + pi/2) = -cos(r) / sin(r)
+        let odd = Self::asmbl_is_odd(k, fb);
+        let t = fb.ins().fdiv(s, c);
+        let cot = fb.ins().fdiv(c, s);
+        let cot = fb.ins().fneg(cot);
+        Self::asmbl_select(odd, cot, t, fb)
+    }
 
-        fb.switch_to_block(blk_a_neg);
-        fb.seal_block(blk_a_neg);
-        let b_eq = fb.ins().fcmp(FloatCC::Equal, b_lo, b_hi);
-        let floor_call = fb.ins().floor(b_lo);
-        let fract = fb.ins().fsub(b_lo, floor_call);
-        let fract_le_eps = fb.ins().fcmp(FloatCC::LessThanOrEqual, fract, eps);
-        let is_const_int = fb.ins().band(b_eq, fract_le_eps);
-        let blk_b_good = fb.create_block();
-        // println!("blk_b_good: {:?}", blk_b_good);
-        fb.ins().brif(is_const_int, blk_b_good, &[], blk_undef, &[]);
-        fb.seal_block(blk_b_good);
+    fn asmbl_exp(x: Value, fb: &mut FunctionBuilder) -> Value {
+        // 1 / n! for n in 0..=13, |r| <= ln(2) / 2 so the remainder is < 2^-57
+        const C: [f64; 14] = [
+            1.0,
+            1.0,
+            1.0 / 2.0,
+            1.0 / 6.0,
+            1.0 / 24.0,
+            1.0 / 120.0,
+            1.0 / 720.0,
+            1.0 / 5040.0,
+            1.0 / 40320.0,
+            1.0 / 362880.0,
+            1.0 / 3628800.0,
+            1.0 / 39916800.0,
+            1.0 / 479001600.0,
+            1.0 / 6227020800.0,
+        ];
+        let ty = fb.func.dfg.value_type(x);
+
+        // keeps k in range of the exponent, exp over- or underflows long before
+        let max = Self::asmbl_fconst(ty, 1000.0, fb);
+        let min = Self::asmbl_fconst(ty, -1000.0, fb);
+        let x = fb.ins().fmin(x, max);
+        let x = fb.ins().fmax(x, min);
+
+        let log2_e = Self::asmbl_fconst(ty, std::f64::consts::LOG2_E, fb);
+        let k = fb.ins().fmul(x, log2_e);
+        let k = fb.ins().nearest(k);
+
+        let ln2_hi = Self::asmbl_fconst(ty, Self::LN2_HI, fb);
+        let ln2_lo = Self::asmbl_fconst(ty, Self::LN2_LO, fb);
+        let hi = fb.ins().fmul(k, ln2_hi);
+        let lo = fb.ins().fmul(k, ln2_lo);
+        let r = fb.ins().fsub(x, hi);
+        let r = fb.ins().fsub(r, lo);
+        let p = Self::asmbl_horner(r, &C, fb);
+
+        // 2^k = 2^k1 * 2^k2 so subnormal and infinite results are rounded correctly
+        let k = Self::asmbl_to_int(k, fb);
+        let k1 = fb.ins().sshr_imm(k, 1);
+        let k2 = fb.ins().isub(k, k1);
+        let s1 = Self::asmbl_exp2i(ty, k1, fb);
+        let s2 = Self::asmbl_exp2i(ty, k2, fb);
+        let p = fb.ins().fmul(p, s1);
+        fb.ins().fmul(p, s2)
+    }
 
-        fb.switch_to_block(blk_b_good);
-        let p_hi = Self::asmbl_call_f64("pow_f64", &[a_hi, b_lo], fb, fn_refs);
-        let p_lo = Self::asmbl_call_f64("pow_f64", &[a_lo, b_lo], fb, fn_refs);
-        let lo_b = fb.ins().fmin(p_hi, p_lo);
-        let hi_b = fb.ins().fmax(p_hi, p_lo);
-        let vec_b = Self::asmbl_f64x2(lo_b, hi_b, fb);
-        fb.ins().jump(blk_exit, &[vec_b]);
+    fn asmbl_ln(x: Value, fb: &mut FunctionBuilder) -> Value {
+        const LG: [f64; 7] = [
+            6.666666666666735130e-01,
+            3.999999999940941908e-01,
+            2.857142874366239149e-01,
+            2.222219843214978396e-01,
+            1.818357216161805012e-01,
+            1.531383769920937332e-01,
+            1.479819860511658591e-01,
+        ];
+        let ty = fb.func.dfg.value_type(x);
+
+        // scale subnormals into the normal range
+        let min_pos = Self::asmbl_fconst(ty, f64::MIN_POSITIVE, fb);
+        let is_sub = fb.ins().fcmp(FloatCC::LessThan, x, min_pos);
+        let scale = Self::asmbl_fconst(ty, 2f64.powi(54), fb);
+        let scaled = fb.ins().fmul(x, scale);
+        let m = Self::asmbl_select(is_sub, scaled, x, fb);
+        let e_sub = Self::asmbl_fconst(ty, -54.0, fb);
+        let e_norm = Self::asmbl_fconst(ty, 0.0, fb);
+        let e_adj = Self::asmbl_select(is_sub, e_sub, e_norm, fb);
+
+        // x = 2^e * m with m in [sqrt(1/2), sqrt(2))
+        let bits = Self::asmbl_bits(m, fb);
+        let sqrt_half =
+            Self::asmbl_iconst(ty, std::f64::consts::FRAC_1_SQRT_2.to_bits() as i64, fb);
+        let e = fb.ins().isub(bits, sqrt_half);
+        let e = fb.ins().sshr_imm(e, 52);
+        let e_bits = fb.ins().ishl_imm(e, 52);
+        let m = fb.ins().isub(bits, e_bits);
+        let m = Self::asmbl_from_bits(ty, m, fb);
+        let e = Self::asmbl_to_float(ty, e, fb);
+        let e = fb.ins().fadd(e, e_adj);
+
+        // ln(m) = f - f^2/2 + s * (f^2/2 + R(s)) with f = m - 1, s = f / (2 + f)
+        let one = Self::asmbl_fconst(ty, 1.0, fb);
+        let two = Self::asmbl_fconst(ty, 2.0, fb);
+        let half = Self::asmbl_fconst(ty, 0.5, fb);
+        let f = fb.ins().fsub(m, one);
+        let d = fb.ins().fadd(f, two);
+        let s = fb.ins().fdiv(f, d);
+        let z = fb.ins().fmul(s, s);
+        let r = Self::asmbl_horner(z, &LG, fb);
+        let r = fb.ins().fmul(z, r);
+        let ff = fb.ins().fmul(f, f);
+        let hfsq = fb.ins().fmul(ff, half);
+
+        let ln2_hi = Self::asmbl_fconst(ty, Self::LN2_HI, fb);
+        let ln2_lo = Self::asmbl_fconst(ty, Self::LN2_LO, fb);
+        let t = fb.ins().fadd(hfsq, r);
+        let t = fb.ins().fmul(s, t);
+        let e_lo = fb.ins().fmul(e, ln2_lo);
+        let t = fb.ins().fadd(t, e_lo);
+        let t = fb.ins().fsub(hfsq, t);
+        let t = fb.ins().fsub(t, f);
+        let e_hi = fb.ins().fmul(e, ln2_hi);
+        let res = fb.ins().fsub(e_hi, t);
+
+        // ln(0) = -inf, ln(inf) = inf, ln(x < 0) = nan
+        let zero = Self::asmbl_fconst(ty, 0.0, fb);
+        let inf = Self::asmbl_fconst(ty, f64::INFINITY, fb);
+        let neg_inf = Self::asmbl_fconst(ty, f64::NEG_INFINITY, fb);
+        let nan = Self::asmbl_fconst(ty, f64::NAN, fb);
+        let is_zero = fb.ins().fcmp(FloatCC::Equal, x, zero);
+        let res = Self::asmbl_select(is_zero, neg_inf, res, fb);
+        let is_inf = fb.ins().fcmp(FloatCC::Equal, x, inf);
+        let res = Self::asmbl_select(is_inf, inf, res, fb);
+        let is_nan = fb.ins().fcmp(FloatCC::UnorderedOrLessThan, x, zero);
+        Self::asmbl_select(is_nan, nan, res, fb)
+    }
 
-        let blk_c_body = fb.create_block();
-        // println!("blk_c_body: {:?}", blk_c_body);
+    /// x^y like `f64::powf`, except (-1)^inf and (-0)^odd
+    fn asmbl_pow(x: Value, y: Value, fb: &mut FunctionBuilder) -> Value {
+        let ty = fb.func.dfg.value_type(x);
+        let zero = Self::asmbl_fconst(ty, 0.0, fb);
+        let one = Self::asmbl_fconst(ty, 1.0, fb);
+        let half = Self::asmbl_fconst(ty, 0.5, fb);
+        let nan = Self::asmbl_fconst(ty, f64::NAN, fb);
+
+        let ax = fb.ins().fabs(x);
+        let ln = Self::asmbl_ln(ax, fb);
+        let t = fb.ins().fmul(y, ln);
+        let res = Self::asmbl_exp(t, fb);
+
+        // negative bases are only defined for integer exponents
+        let y_round = fb.ins().nearest(y);
+        let y_int = fb.ins().fcmp(FloatCC::Equal, y, y_round);
+        let y_half = fb.ins().fmul(y, half);
+        let y_half_round = fb.ins().nearest(y_half);
+        let y_even = fb.ins().fcmp(FloatCC::Equal, y_half, y_half_round);
+        let y_odd = fb.ins().bnot(y_even);
+        let y_odd = fb.ins().band(y_int, y_odd);
+        let x_neg = fb.ins().fcmp(FloatCC::LessThan, x, zero);
+
+        let neg = fb.ins().band(x_neg, y_odd);
+        let neg_res = fb.ins().fneg(res);
+        let res = Self::asmbl_select(neg, neg_res, res, fb);
+        let y_frac = fb.ins().bnot(y_int);
+        let undef = fb.ins().band(x_neg, y_frac);
+        let res = Self::asmbl_select(undef, nan, res, fb);
+
+        let y_zero = fb.ins().fcmp(FloatCC::Equal, y, zero);
+        let x_one = fb.ins().fcmp(FloatCC::Equal, x, one);
+        let is_one = fb.ins().bor(y_zero, x_one);
+        Self::asmbl_select(is_one, one, res, fb)
+    }
 
-        fb.switch_to_block(blk_a_mixed_int);
-        fb.seal_block(blk_a_mixed_int);
-        fb.ins().brif(is_const_int, blk_c_body, &[], blk_undef, &[]);
+    /// x^n by repeated squaring
+    fn asmbl_powi(x: Value, n: u32, fb: &mut FunctionBuilder) -> Value {
+        let ty = fb.func.dfg.value_type(x);
+        let mut res = Self::asmbl_fconst(ty, 1.0, fb);
+        let (mut base, mut n) = (x, n);
+        while n > 0 {
+            if n & 1 == 1 {
+                res = fb.ins().fmul(res, base);
+            }
+            n >>= 1;
+            if n > 0 {
+                base = fb.ins().fmul(base, base);
+            }
+        }
+        res
+    }
 
-        fb.switch_to_block(blk_c_body);
-        fb.seal_block(blk_c_body);
+    /// small positive integer exponents that [`JIT::asmbl_powi`] expands
+    fn small_int_exponent(rhs: Oprnd) -> Option<u32> {
+        match rhs {
+            Oprnd::Imm(e) if e.fract() == 0.0 && (1.0..=16.0).contains(&e) => Some(e as u32),
+            _ => None,
+        }
+    }
 
-        let is_even = Self::asmbl_call_f64("is_even_f64", &[b_lo], fb, fn_refs);
-        let blk_c_even = fb.create_block();
-        let blk_c_odd = fb.create_block();
-        // println!("blk_c_even: {:?}", blk_c_even);
-        // println!("blk_c_odd: {:?}", blk_c_odd);
-        fb.ins().brif(is_even, blk_c_even, &[], blk_c_odd, &[]);
+    /// whether the reduction of one bound is inexact, see [`JIT::REDUCE_MAX`]
+    fn asmbl_out_of_reduce_range(lo: Value, hi: Value, fb: &mut FunctionBuilder) -> Value {
+        let lo = fb.ins().fabs(lo);
+        let hi = fb.ins().fabs(hi);
+        let a = fb.ins().fmax(lo, hi);
+        let bound = fb.ins().f64const(Self::REDUCE_MAX);
+        fb.ins().fcmp(FloatCC::GreaterThan, a, bound)
+    }
 
-        fb.switch_to_block(blk_c_even);
-        fb.seal_block(blk_c_even);
-        let abs_lo = fb.ins().fabs(a_lo); // assemble intrinsic for fabs
-        let abs_hi = fb.ins().fabs(a_hi);
-        let max_abs = fb.ins().fmax(abs_lo, abs_hi);
-        let pow_even = Self::asmbl_call_f64("pow_f64", &[max_abs, b_lo], fb, fn_refs);
-        let vec_ce = Self::asmbl_f64x2(zero, pow_even, fb);
-        fb.ins().jump(blk_exit, &[vec_ce]);
+    /// sin or cos of an interval, the bounds are widened outward by the kernel error.
+    /// [-1, 1] beyond [`JIT::REDUCE_MAX`] where the error is not bounded
+    fn asmbl_sin_cos_intrvl_inline(val: Value, cos: bool, fb: &mut FunctionBuilder) -> Value {
+        use std::f64::consts::{FRAC_PI_2, PI};
+        // (position of the maximum, position of the minimum) in [0, 2pi)
+        let (max_at, min_at) = if cos {
+            (0.0, PI)
+        } else {
+            (FRAC_PI_2, 3.0 * FRAC_PI_2)
+        };
 
-        fb.switch_to_block(blk_c_odd);
-        fb.seal_block(blk_c_odd);
-        let lo_co = Self::asmbl_call_f64("pow_f64", &[a_lo, b_lo], fb, fn_refs);
-        let pow_co = Self::asmbl_call_f64("pow_f64", &[max_abs, b_lo], fb, fn_refs);
-        let vec_co = Self::asmbl_f64x2(lo_co, pow_co, fb);
-        fb.ins().jump(blk_exit, &[vec_co]);
+        let s = Self::asmbl_sin_cos(val, cos, fb);
+        let lo = fb.ins().extractlane(val, 0);
+        let hi = fb.ins().extractlane(val, 1);
+        let s_lo = fb.ins().extractlane(s, 0);
+        let s_hi = fb.ins().extractlane(s, 1);
+
+        let one = fb.ins().f64const(1.0);
+        let neg_one = fb.ins().f64const(-1.0);
+        let min = fb.ins().fmin(s_lo, s_hi);
+        let max = fb.ins().fmax(s_lo, s_hi);
+        let min = Self::asmbl_widen_down(min, 4, fb);
+        let max = Self::asmbl_widen_up(max, 4, fb);
+        let min = fb.ins().fmax(min, neg_one);
+        let max = fb.ins().fmin(max, one);
+
+        let has_max = Self::asmbl_contains_periodic(lo, hi, max_at, 2.0 * PI, fb);
+        let has_min = Self::asmbl_contains_periodic(lo, hi, min_at, 2.0 * PI, fb);
+        let big = Self::asmbl_out_of_reduce_range(lo, hi, fb);
+        let has_max = fb.ins().bor(has_max, big);
+        let has_min = fb.ins().bor(has_min, big);
+        let max = fb.ins().select(has_max, one, max);
+        let min = fb.ins().select(has_min, neg_one, min);
+        Self::asmbl_f64x2(min, max, fb)
+    }
+
+    /// tan of an interval, undefined if it contains a pole and the whole line beyond
+    /// [`JIT::REDUCE_MAX`]
+    fn asmbl_tan_intrvl_inline(val: Value, fb: &mut FunctionBuilder) -> Value {
+        use std::f64::consts::{FRAC_PI_2, PI};
+
+        let t = Self::asmbl_tan(val, fb);
+        let lo = fb.ins().extractlane(val, 0);
+        let hi = fb.ins().extractlane(val, 1);
+        let t_lo = fb.ins().extractlane(t, 0);
+        let t_hi = fb.ins().extractlane(t, 1);
+        let t_lo = Self::asmbl_widen_down(t_lo, 8, fb);
+        let t_hi = Self::asmbl_widen_up(t_hi, 8, fb);
+
+        let big = Self::asmbl_out_of_reduce_range(lo, hi, fb);
+        let inf = fb.ins().f64const(f64::INFINITY);
+        let neg_inf = fb.ins().f64const(f64::NEG_INFINITY);
+        let t_lo = fb.ins().select(big, neg_inf, t_lo);
+        let t_hi = fb.ins().select(big, inf, t_hi);
 
-        fb.switch_to_block(blk_undef);
-        fb.seal_block(blk_undef);
         let nan = fb.ins().f64const(f64::NAN);
-        let vec_u = Self::asmbl_f64x2(nan, nan, fb);
-        fb.ins().jump(blk_exit, &[vec_u]);
+        let pole = Self::asmbl_contains_periodic(lo, hi, FRAC_PI_2, PI, fb);
+        let t_lo = fb.ins().select(pole, nan, t_lo);
+        let t_hi = fb.ins().select(pole, nan, t_hi);
+        Self::asmbl_f64x2(t_lo, t_hi, fb)
+    }
 
-        fb.switch_to_block(blk_exit);
-        fb.append_block_param(blk_exit, types::F64X2);
-        fb.seal_block(blk_exit);
-        fb.block_params(blk_exit)[0]
+    /// whether [lo, hi] contains `at + k * period` for some integer k, errs towards true
+    fn asmbl_contains_periodic(
+        lo: Value,
+        hi: Value,
+        at: f64,
+        period: f64,
+        fb: &mut FunctionBuilder,
+    ) -> Value {
+        let at = fb.ins().f64const(at);
+        let inv = fb.ins().f64const(1.0 / period);
+        let slack = fb.ins().f64const(1e-9);
+
+        let l = fb.ins().fsub(lo, at);
+        let l = fb.ins().fmul(l, inv);
+        let l = fb.ins().fsub(l, slack);
+        let l = fb.ins().ceil(l);
+        let h = fb.ins().fsub(hi, at);
+        let h = fb.ins().fmul(h, inv);
+        let h = fb.ins().fadd(h, slack);
+        let h = fb.ins().floor(h);
+        fb.ins().fcmp(FloatCC::LessThanOrEqual, l, h)
+    }
+
+    /// v + n ulp, plus the smallest normal so results near zero are covered
+    fn asmbl_widen_up(v: Value, n: u32, fb: &mut FunctionBuilder) -> Value {
+        let eps = fb.ins().f64const(n as f64 * f64::EPSILON);
+        let tiny = fb.ins().f64const(f64::MIN_POSITIVE);
+        let a = fb.ins().fabs(v);
+        let w = fb.ins().fmul(a, eps);
+        let w = fb.ins().fadd(w, tiny);
+        fb.ins().fadd(v, w)
+    }
+
+    fn asmbl_widen_down(v: Value, n: u32, fb: &mut FunctionBuilder) -> Value {
+        let eps = fb.ins().f64const(n as f64 * f64::EPSILON);
+        let tiny = fb.ins().f64const(f64::MIN_POSITIVE);
+        let a = fb.ins().fabs(v);
+        let w = fb.ins().fmul(a, eps);
+        let w = fb.ins().fadd(w, tiny);
+        fb.ins().fsub(v, w)
     }
 }
 
 #[cfg(test)]
 mod test {
-    use rand::seq::IndexedRandom;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
 
-    use crate::jit::{Program, Reg};
+    use crate::jit::{Program, random};
 
     use super::*;
-
-    fn random_instr(dst: Reg) -> Instr {
-        let mut rng = rand::rng();
-        if rand::random_bool(0.4) {
-            let op = *[UnOp::MOV, UnOp::SIN, UnOp::COS, UnOp::TAN]
-                .choose(&mut rng)
-                .unwrap();
-            let val = Oprnd::Reg(rand::random_range(0..2));
-            Instr::UnOp { op, val, dst }
-        } else {
-            let op = *[BinOp::ADD, BinOp::SUB, BinOp::MUL, BinOp::DIV, BinOp::POW]
-                .choose(&mut rng)
-                .unwrap();
-            let lhs = Oprnd::Reg(rand::random_range(0..2));
-            let rhs = if rand::random_bool(0.5) {
-                Oprnd::Reg(rand::random_range(0..2))
-            } else {
-                let imm = match op {
-                    BinOp::DIV => rand::random_range(0.1..5.0),
-                    _ => rand::random_range(-5.0..5.0),
-                };
-                Oprnd::Imm(imm)
-            };
-            Instr::BinOp { op, lhs, rhs, dst }
-        }
-    }
-
-    fn gen_random_program(max_len: usize) -> Vec<Instr> {
-        let len = rand::random_range(1..=max_len);
-        (0..len)
-            .map(|i| random_instr((i % 2) as Reg))
-            .chain(std::iter::once(bytecode!(ADD[0, 1] -> 0)))
-            .collect()
+    use utils::native::{self, NativeFn};
+
+    fn gen_random_program(rng: &mut impl Rng, max_len: usize) -> Vec<Instr> {
+        let len = rng.random_range(1..=max_len);
+        let mut prog = random::program(rng, len, 2);
+        // both registers reach the output
+        prog.bytecode.push(bytecode!(ADD[0, 1] -> 0));
+        prog.bytecode
     }
 
     fn cmp_float(l: f64, r: f64, tol: f64) -> bool {
@@ -1842,6 +3816,7 @@ mod test {
 
     #[test]
     fn fuzz_cmp_f64_vs_f64x2() {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
         const N: usize = 500;
         const MAX_LEN: usize = 20;
         const TOL: f64 = f64::EPSILON * 10.0;
@@ -1849,23 +3824,20 @@ mod test {
         for _ in 0..N {
             let jit = JIT::init();
 
-            let prog = gen_random_program(MAX_LEN);
-            println!("{:?}", prog);
-            let f_scalar = jit.compile_2f64_f64("scalar", &prog);
-            let f_simd = jit.compile_2f64x2_f64("simd", &prog);
-
-            let prog = Program::from(prog);
+            let prog = Program::from(gen_random_program(&mut rng, MAX_LEN));
+            println!("{prog}");
+            let f_scalar = jit.compile::<f64>("scalar", &prog, 2);
+            let f_simd = jit.compile::<F64X2>("simd", &prog, 2);
 
-            let x1 = rand::random_range(-10.0..10.0);
-            let y1 = rand::random_range(-10.0..10.0);
-            let x2 = rand::random_range(-10.0..10.0);
-            let y2 = rand::random_range(-10.0..10.0);
+            let x1 = rng.random_range(-10.0..10.0);
+            let y1 = rng.random_range(-10.0..10.0);
+            let x2 = rng.random_range(-10.0..10.0);
+            let y2 = rng.random_range(-10.0..10.0);
 
-            let res_s0 = *f_scalar(x1, y1);
-            let res_s1 = *f_scalar(x2, y2);
+            let res_s0 = f_scalar.eval(&[x1, y1]);
+            let res_s1 = f_scalar.eval(&[x2, y2]);
 
-            let mut res_v = F64X2(0.0, 0.0);
-            f_simd(F64X2(x1, x2), F64X2(y1, y2), &mut res_v);
+            let res_v = f_simd.eval(&[F64X2(x1, x2), F64X2(y1, y2)]);
 
             assert!(
                 cmp_float(res_v.0, res_s0, TOL),
@@ -1882,6 +3854,79 @@ mod test {
         }
     }
 
+    #[test]
+    fn fuzz_cmp_f64_vs_wide() {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        assert!([1, 2, 4, 8].contains(&simd_lanes()));
+
+        for _ in 0..200 {
+            let jit = JIT::init();
+            let prog = Program::with_outputs(gen_random_program(&mut rng, 20), vec![0, 1]);
+            let f_scalar = jit.compile::<f64>("scalar", &prog, 2);
+            let f_x4 = jit.compile::<F64X4>("x4", &prog, 2);
+            let f_x8 = jit.compile::<F64X8>("x8", &prog, 2);
+
+            let xs: [f64; 8] = std::array::from_fn(|_| rng.random_range(-10.0..10.0));
+            let ys: [f64; 8] = std::array::from_fn(|_| rng.random_range(-10.0..10.0));
+
+            let mut out_x4 = [F64X4::UNDEF; 2];
+            let x4 = |v: &[f64; 8]| F64X4([v[0], v[1], v[2], v[3]]);
+            f_x4.call(&[x4(&xs), x4(&ys)], &mut out_x4);
+            let mut out_x8 = [F64X8::UNDEF; 2];
+            f_x8.call(&[F64X8(xs), F64X8(ys)], &mut out_x8);
+
+            for i in 0..8 {
+                let mut out = [0.0; 2];
+                f_scalar.call(&[xs[i], ys[i]], &mut out);
+                for o in 0..2 {
+                    assert!(cmp_float(out_x8[o].0[i], out[o], 0.0), "{prog}");
+                    if i < 4 {
+                        assert!(cmp_float(out_x4[o].0[i], out[o], 0.0), "{prog}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn row_kernels() {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        let jit = JIT::init();
+        for _ in 0..50 {
+            let prog = Program::from(gen_random_program(&mut rng, 20));
+            let f = jit.compile::<f64>("f", &prog, 2);
+            let rows = [
+                jit.compile_row::<f64>("row", &prog),
+                jit.compile_row::<F64X2>("row", &prog),
+                jit.compile_row::<F64X4>("row", &prog),
+                jit.compile_row::<F64X8>("row", &prog),
+            ];
+
+            let params = RowParams {
+                origin: [rng.random_range(-5.0..5.0), rng.random_range(-5.0..5.0)],
+                step: [rng.random_range(-0.5..0.5), rng.random_range(-0.5..0.5)],
+            };
+            let n = rng.random_range(0..20);
+            let expected: Vec<_> = (0..n)
+                .map(|k| {
+                    let x = params.origin[0] + k as f64 * params.step[0];
+                    let y = params.origin[1] + k as f64 * params.step[1];
+                    f.eval(&[x, y])
+                })
+                .collect();
+
+            for row in rows {
+                // one more slot to check the kernel stops at n
+                let mut out = vec![1.0; n + 1];
+                row.eval(&params, &mut out[..n]);
+                for k in 0..n {
+                    assert!(cmp_float(out[k], expected[k], 0.0), "{prog}\nk={k}");
+                }
+                assert_eq!(out[n], 1.0);
+            }
+        }
+    }
+
     #[test]
     fn f64_binary() {
         let x = 1.5f64;
@@ -1909,12 +3954,53 @@ mod test {
 
         let mut jit = JIT::init();
         jit.emit_asm = true;
-        let func = jit.compile_2f64_f64("binary_fn", &code);
+        let func = jit.compile::<f64>("binary_fn", &Program::from(code.to_vec()), 2);
 
-        let res = *func(x, y);
+        let res = func.eval(&[x, y]);
         assert_eq!(res, a, "{res} != {a}");
     }
 
+    #[test]
+    fn fn_info() {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        let mut jit = JIT::init();
+        jit.emit_asm = true;
+
+        let f = jit.compile::<f64>("f", &Program::from(gen_random_program(&mut rng, 10)), 2);
+        let g = jit.compile_row::<F64X2>("g", &Program::from(gen_random_program(&mut rng, 10)));
+        let asm = jit.asm.borrow().clone();
+
+        let f_info = jit.fn_info(f.addr()).unwrap();
+        let g_info = jit.fn_info(g.addr()).unwrap();
+        assert_eq!(jit.functions().len(), 2);
+        assert_eq!(f_info.name, "f.0");
+        assert_eq!(g_info.name, "g.1");
+        assert!(f_info.size > 0 && g_info.size > 0);
+        assert!(f_info.asm.is_some());
+        assert_eq!(g_info.asm, asm);
+        assert_ne!(f_info.asm, g_info.asm);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn perf_map() {
+        let path = std::env::temp_dir().join(format!("jit_perf_{}.map", std::process::id()));
+        let mut jit = JIT::init();
+        jit.perf_map = Some(path.clone());
+
+        let prog = Program::from(vec![bytecode!(ADD[0, 1] -> 0)]);
+        let f = jit.compile::<f64>("f", &prog, 2);
+        let g = jit.compile_row::<F64X2>("g", &prog);
+
+        let map = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        for addr in [f.addr(), g.addr()] {
+            let info = jit.fn_info(addr).unwrap();
+            let line = format!("{:x} {:x} {}", info.addr, info.size, info.name);
+            assert!(map.lines().any(|l| l == line), "{line} not in perf map");
+        }
+    }
+
     #[test]
     fn f64x2_binary() {
         let x = F64X2(1.5, 2.3);
@@ -1938,11 +4024,10 @@ mod test {
         a = a.pow(&F64X2(2.3, 2.3));
         a = F64X2(1.0, 1.0) / a;
 
-        let mut jit = JIT::init();
+        let jit = JIT::init();
 
-        let mut res = F64X2(0., 0.);
-        let func = jit.compile_2f64x2_f64("binary_fn", &code);
-        func(x, y, &mut res);
+        let func = jit.compile::<F64X2>("binary_fn", &Program::from(code.to_vec()), 2);
+        let res = func.eval(&[x, y]);
 
         let diff = (res - a).abs();
         assert!(diff.0 < f64::EPSILON * 10.0, "{}", diff.0);
@@ -1965,12 +4050,490 @@ mod test {
 
         let jit = JIT::init();
 
-        let mut res = F64X2(0., 0.);
-        let func = jit.compile_2intrvl_intrvl("binary_fn", &code);
-        func(F64X2(i1.lo, i1.hi), F64X2(i2.lo, i2.hi), &mut res);
+        let func = jit.compile::<Intrvl>("binary_fn", &Program::from(code.to_vec()), 2);
+        let res = func.eval(&[i1, i2]);
+        let res = F64X2(res.lo, res.hi);
 
         let diff = (res - a).abs();
         assert!(diff.0 < f64::EPSILON * 10.0, "{}", diff.0);
         assert!(diff.1 < f64::EPSILON * 10.0, "{}", diff.1);
     }
+
+    #[test]
+    fn complex_binary() {
+        let z = Complex::new(0.7, -1.3);
+
+        let code = bytecode! [
+            SIN[0] -> 1,
+            MUL[0, 0] -> 0,
+            ADD[0, imm(1.)] -> 0,
+            DIV[1, 0] -> 0,
+            POW[0, imm(3.)] -> 1,
+            COS[0] -> 0,
+            SUB[1, 0] -> 0,
+            POW[0, imm(0.5)] -> 0,
+        ];
+
+        let a = z.sin().div(z.mul(z).add(Complex::ONE));
+        let a = a.pow(Complex::real(3.0)).sub(a.cos());
+        let a = a.pow(Complex::real(0.5));
+
+        let jit = JIT::init();
+        let func = jit.compile::<Complex>("complex_fn", &Program::from(code.to_vec()), 2);
+
+        let res = func.eval(&[z, Complex::ZERO]);
+        assert_eq!(res, a, "{res} != {a}");
+    }
+
+    #[test]
+    fn multiple_outputs() {
+        // (x + y, x * y, sin(x))
+        let code = bytecode! [
+            MUL[0, 1] -> 2,
+            SIN[0] -> 3,
+            ADD[0, 1] -> 0,
+        ];
+        let prog = Program::with_outputs(code.to_vec(), vec![0, 2, 3]);
+        assert_eq!(prog.n_outputs(), 3);
+
+        let jit = JIT::init();
+
+        let f = jit.compile::<f64>("f64_n", &prog, 2);
+        let mut res = [f64::NAN; 3];
+        f.call(&[1.5, 2.5], &mut res);
+        assert_eq!(res, [4.0, 3.75, 1.5f64.sin()]);
+
+        let f = jit.compile::<F64X2>("f64x2_n", &prog, 2);
+        let (x, y) = (F64X2(1.5, -1.0), F64X2(2.5, 3.0));
+        let mut res = [F64X2::UNDEF; 3];
+        f.call(&[x, y], &mut res);
+        assert_eq!(res, [x + y, x * y, x.sin()]);
+
+        let f = jit.compile::<Intrvl>("intrvl_n", &prog, 2);
+        let (x, y) = (Intrvl::new(1.0, 2.0), Intrvl::new(-1.0, 3.0));
+        let mut res = [Intrvl::UNDEF; 3];
+        f.call(&[x, y], &mut res);
+        assert_eq!(res[0], Intrvl::new(0.0, 5.0));
+        assert_eq!(res[1], Intrvl::new(-2.0, 6.0));
+        let sin = x.sin();
+        assert!(res[2].lo <= sin.lo && res[2].hi >= sin.hi, "{:?}", res[2]);
+    }
+
+    #[test]
+    fn dual() {
+        // x * sin(y) + x^3 / y, min(x, 2)
+        let code = bytecode! [
+            SIN[1] -> 2,
+            MUL[0, 2] -> 2,
+            POW[0, imm(3.0)] -> 3,
+            DIV[3, 1] -> 3,
+            ADD[2, 3] -> 2,
+            MIN[0, imm(2.0)] -> 3,
+        ];
+        let prog = Program::with_outputs(code.to_vec(), vec![2, 3]);
+
+        let jit = JIT::init();
+        let f = jit.compile::<Dual>("dual", &prog, 2);
+
+        let (x, y) = (-1.5, 0.7);
+        let mut res = [Dual::UNDEF; 2];
+        f.call(&[Dual::var(x), Dual::cnst(y)], &mut res);
+        assert_eq!(res[0].v, x * y.sin() + x.powi(3) / y);
+        let dx = y.sin() + 3.0 * x * x / y;
+        assert!((res[0].d - dx).abs() < 1e-12, "{} != {dx}", res[0].d);
+        assert_eq!(res[1], Dual::var(x));
+
+        f.call(&[Dual::cnst(x), Dual::var(y)], &mut res);
+        let dy = x * y.cos() - x.powi(3) / (y * y);
+        assert!((res[0].d - dy).abs() < 1e-12, "{} != {dy}", res[0].d);
+        assert_eq!(res[1].d, 0.0);
+    }
+
+    #[test]
+    fn min_max_nan() {
+        let code = bytecode! [
+            MIN[0, 1] -> 2,
+            MAX[0, 1] -> 3,
+        ];
+        let prog = Program::with_outputs(code.to_vec(), vec![2, 3]);
+        let jit = JIT::init();
+        let nan = f64::NAN;
+
+        // a nan operand returns the other one, like f64::min
+        for (x, y) in [(nan, 2.0), (2.0, nan), (1.0, 2.0), (nan, nan)] {
+            let expected = [x.min(y), x.max(y)];
+            let bits = |r: &[f64]| r.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+
+            let mut res = [0.0; 2];
+            jit.compile::<f64>("f64", &prog, 2).call(&[x, y], &mut res);
+            assert_eq!(bits(&res), bits(&expected), "f64 {x} {y}");
+
+            let mut res = [F64X2::UNDEF; 2];
+            let f = jit.compile::<F64X2>("f64x2", &prog, 2);
+            f.call(&[F64X2(x, 0.0), F64X2(y, 0.0)], &mut res);
+            assert_eq!(
+                bits(&[res[0].0, res[1].0]),
+                bits(&expected),
+                "f64x2 {x} {y}"
+            );
+
+            let mut res = [Intrvl::UNDEF; 2];
+            let f = jit.compile::<Intrvl>("intrvl", &prog, 2);
+            f.call(&[Intrvl::new(x, 3.0), Intrvl::new(y, 3.0)], &mut res);
+            assert_eq!(
+                bits(&[res[0].lo, res[1].lo]),
+                bits(&expected),
+                "intrvl {x} {y}"
+            );
+
+            let mut res = [Dual::UNDEF; 2];
+            let f = jit.compile::<Dual>("dual", &prog, 2);
+            f.call(&[Dual::var(x), Dual::cnst(y)], &mut res);
+            assert_eq!(bits(&[res[0].v, res[1].v]), bits(&expected), "dual {x} {y}");
+
+            let mut res = [Complex::ZERO; 2];
+            let f = jit.compile::<Complex>("complex", &prog, 2);
+            f.call(&[Complex::real(x), Complex::real(y)], &mut res);
+            assert_eq!(
+                bits(&[res[0].re, res[1].re]),
+                bits(&expected),
+                "complex {x} {y}"
+            );
+        }
+    }
+
+    #[test]
+    fn special() {
+        // (f(x), y)
+        for op in [UnOp::GAMMA, UnOp::ERF, UnOp::J0, UnOp::J1, UnOp::LAMBERTW] {
+            let f = op.special().unwrap();
+            let code = [Instr::UnOp {
+                op,
+                val: Oprnd::Reg(0),
+                dst: 0,
+            }];
+            let prog = Program::from(code.to_vec());
+            let jit = JIT::init();
+            let x = 0.7;
+
+            let res = jit.compile::<f64>("f64", &prog, 2).eval(&[x, 0.0]);
+            assert_eq!(res, f.eval(x));
+
+            let res = jit
+                .compile::<F64X2>("f64x2", &prog, 2)
+                .eval(&[F64X2(x, 2.5), F64X2::UNDEF]);
+            assert_eq!(res, F64X2(f.eval(x), f.eval(2.5)));
+
+            let res = jit
+                .compile::<Intrvl>("intrvl", &prog, 2)
+                .eval(&[Intrvl::new(x, 2.5), Intrvl::UNDEF]);
+            assert_eq!((res.lo, res.hi), f.intrvl(x, 2.5));
+
+            let res = jit
+                .compile::<Complex>("complex", &prog, 2)
+                .eval(&[Complex::real(x), Complex::ZERO]);
+            assert_eq!(res, Complex::real(f.eval(x)));
+
+            let res = jit
+                .compile::<Dual>("dual", &prog, 2)
+                .eval(&[Dual::var(x), Dual::UNDEF]);
+            assert_eq!((res.v, res.d), (f.eval(x), f.deriv(x)));
+        }
+    }
+
+    fn softplus(x: f64) -> f64 {
+        x.exp().ln_1p()
+    }
+
+    #[test]
+    fn native() {
+        let f = native::register(NativeFn::new(
+            "jit_test_softplus",
+            softplus,
+            |x| 1.0 / (1.0 + (-x).exp()),
+            |lo, hi| (softplus(lo), softplus(hi)),
+        ));
+        // natives are written by name
+        let prog = Program::from_asm("CALL_jit_test_softplus[0] -> 0").unwrap();
+        assert_eq!(
+            prog.bytecode[0],
+            Instr::UnOp {
+                op: UnOp::CALL(f),
+                val: Oprnd::Reg(0),
+                dst: 0,
+            }
+        );
+        assert_eq!(prog.to_string(), "CALL_jit_test_softplus[0] -> 0");
+
+        let jit = JIT::init();
+        let x = 0.7;
+
+        let res = jit.compile::<f64>("f64", &prog, 2).eval(&[x, 0.0]);
+        assert_eq!(res, softplus(x));
+
+        let res = jit
+            .compile::<F64X2>("f64x2", &prog, 2)
+            .eval(&[F64X2(x, 2.5), F64X2::UNDEF]);
+        assert_eq!(res, F64X2(softplus(x), softplus(2.5)));
+
+        let res = jit
+            .compile::<Intrvl>("intrvl", &prog, 2)
+            .eval(&[Intrvl::new(x, 2.5), Intrvl::UNDEF]);
+        assert_eq!((res.lo, res.hi), (softplus(x), softplus(2.5)));
+
+        let complex = jit.compile::<Complex>("complex", &prog, 2);
+        let res = complex.eval(&[Complex::real(x), Complex::ZERO]);
+        assert_eq!(res, Complex::real(softplus(x)));
+        // natives are only defined on the real axis
+        let res = complex.eval(&[Complex::new(x, 1.0), Complex::ZERO]);
+        assert!(res.re.is_nan() && res.im.is_nan());
+
+        let res = jit
+            .compile::<Dual>("dual", &prog, 2)
+            .eval(&[Dual::var(x), Dual::UNDEF]);
+        assert_eq!((res.v, res.d), (softplus(x), f.deriv(x)));
+    }
+
+    #[test]
+    fn entry() {
+        let prog = Program::from(vec![bytecode!(ADD[0, 1] -> 0)]);
+        let jit = JIT::init();
+        let entry = jit.compile::<f64>("add", &prog, 2).entry();
+        assert_eq!(jit.get(entry).eval(&[1.0, 2.0]), 3.0);
+        assert_eq!(
+            entry.sig,
+            Signature {
+                word: WordKind::F64,
+                arity: 2,
+                outputs: 1
+            }
+        );
+
+        let other = JIT::init();
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| other.get(entry)));
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn cache() {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        let prog = Program::from(gen_random_program(&mut rng, 10));
+        let jit = JIT::init();
+        let f = jit.compile::<f64>("f", &prog, 2).entry();
+        let g = jit.compile::<f64>("f", &prog, 2).entry();
+        assert_eq!(f.ptr as usize, g.ptr as usize);
+        assert_eq!(jit.n_functions(), 1);
+
+        // same name, different signature
+        jit.compile::<F64X2>("f", &prog, 2);
+        jit.compile::<f64>("f", &prog, 1);
+        assert_eq!(jit.n_functions(), 3);
+    }
+
+    #[test]
+    fn free_memory() {
+        let prog = Program::from(vec![bytecode!(ADD[0, 1] -> 0)]);
+        let mut jit = JIT::init();
+        let entry = jit.compile::<f64>("add", &prog, 2).entry();
+        jit.free_memory();
+        assert_eq!(jit.n_functions(), 0);
+        assert_eq!(jit.compile::<f64>("add", &prog, 2).eval(&[1.0, 2.0]), 3.0);
+
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| jit.get(entry)));
+        assert!(res.is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn free_memory_no_growth() {
+        // in bytes, the page size differs between hosts so statm is not used
+        fn rss() -> usize {
+            let status = std::fs::read_to_string("/proc/self/status").unwrap();
+            let line = status.lines().find(|l| l.starts_with("VmRSS:")).unwrap();
+            let kb: usize = line.split_whitespace().nth(1).unwrap().parse().unwrap();
+            kb * 1024
+        }
+
+        let mut jit = JIT::init();
+        let mut start = 0;
+
+        for i in 0..4000 {
+            if i == 1000 {
+                start = rss();
+            }
+
+            let prog = Program::from(vec![Instr::BinOp {
+                op: BinOp::ADD,
+                lhs: Oprnd::Reg(0),
+                rhs: Oprnd::Imm(i as f64),
+                dst: 0,
+            }]);
+            assert_eq!(
+                jit.compile::<f64>("f", &prog, 1).eval(&[1.0]),
+                1.0 + i as f64
+            );
+
+            if i % 100 == 99 {
+                jit.free_memory();
+            }
+        }
+
+        // without freeing the 3000 functions take ~24MB
+        let growth = rss().saturating_sub(start);
+        assert!(growth < 8 << 20, "memory grew by {growth} bytes");
+    }
+
+    fn ulp_diff(a: f64, b: f64) -> u64 {
+        if a.is_nan() && b.is_nan() || a == b {
+            return 0;
+        }
+        // maps floats onto a monotonic integer line
+        let ord = |f: f64| {
+            let bits = f.to_bits() as i64;
+            if bits < 0 { i64::MIN - bits } else { bits }
+        };
+        ord(a).abs_diff(ord(b))
+    }
+
+    #[test]
+    fn inline_math_ulp() {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        let mut jit = JIT::init();
+        jit.math = MathImpl::Inline;
+
+        let unop = |op| {
+            Program::from(vec![Instr::UnOp {
+                op,
+                val: Oprnd::Reg(0),
+                dst: 0,
+            }])
+        };
+        /// op, reference and the max ulp for |x| < 10 and |x| < JIT::REDUCE_MAX
+        type UlpCase = (UnOp, fn(f64) -> f64, [u64; 2]);
+        // the documented max ulp, the worst case seen over 3 million samples per range
+        let cases: [UlpCase; 3] = [
+            (UnOp::SIN, f64::sin, [1, 2]),
+            (UnOp::COS, f64::cos, [1, 2]),
+            (UnOp::TAN, f64::tan, [3, 3]),
+        ];
+
+        for (op, f, max_ulp) in cases {
+            let f_inline = jit.compile::<f64>("f", &unop(op), 1);
+            let f_simd = jit.compile::<F64X2>("f", &unop(op), 1);
+
+            let mut worst = [0; 2];
+            for i in 0..100_000 {
+                let x: f64 = match i % 3 {
+                    0 => rng.random_range(-10.0..10.0),
+                    1 => rng.random_range(-JIT::REDUCE_MAX..JIT::REDUCE_MAX),
+                    _ => rng.random_range(-1e-3..1e-3),
+                };
+                let range = (x.abs() >= 10.0) as usize;
+                let ulp = ulp_diff(f_inline.eval(&[x]), f(x));
+                worst[range] = worst[range].max(ulp);
+
+                let F64X2(s0, s1) = f_simd.eval(&[F64X2(x, -x)]);
+                assert_eq!(s0.to_bits(), f_inline.eval(&[x]).to_bits());
+                assert_eq!(s1.to_bits(), f_inline.eval(&[-x]).to_bits());
+            }
+            assert!(
+                worst[0] <= max_ulp[0] && worst[1] <= max_ulp[1],
+                "{op}: {worst:?} ulp, documented {max_ulp:?}"
+            );
+
+            for x in [0.0, -0.0, f64::INFINITY, f64::NAN] {
+                assert!(cmp_float(f_inline.eval(&[x]), f(x), 0.0), "{op}({x})");
+            }
+        }
+
+        let pow = Program::from(vec![bytecode!(POW[0, 1] -> 0)]);
+        let f_pow = jit.compile::<f64>("pow", &pow, 2);
+        for _ in 0..100_000 {
+            let x: f64 = rng.random_range(0.0..100.0);
+            let y: f64 = rng.random_range(-10.0..10.0);
+            let max_ulp = 2 + (2.0 * y * x.ln()).abs() as u64;
+            let ulp = ulp_diff(f_pow.eval(&[x, y]), x.powf(y));
+            assert!(ulp <= max_ulp, "pow({x}, {y}): {ulp} ulp");
+        }
+
+        let specials = [
+            0.0,
+            1.0,
+            -1.0,
+            2.0,
+            -2.0,
+            0.5,
+            -3.0,
+            f64::INFINITY,
+            f64::NAN,
+        ];
+        for x in specials {
+            for y in specials {
+                if x == -1.0 && y.is_infinite() {
+                    continue;
+                }
+                assert!(
+                    cmp_float(f_pow.eval(&[x, y]), x.powf(y), 1e-14 * x.powf(y).abs()),
+                    "pow({x}, {y})"
+                );
+            }
+        }
+
+        let cube = Program::from(vec![bytecode!(POW[0, imm(3.0)] -> 0)]);
+        let f_cube = jit.compile::<f64>("cube", &cube, 1);
+        assert_eq!(f_cube.eval(&[-2.0]), -8.0);
+
+        let f_dual = jit.compile::<Dual>("dual", &pow, 2);
+        let d = f_dual.eval(&[Dual::var(2.0), Dual::cnst(3.0)]);
+        assert!(cmp_float(d.v, 8.0, 1e-14) && cmp_float(d.d, 12.0, 1e-14));
+    }
+
+    #[test]
+    fn inline_math_intrvl() {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        let mut jit = JIT::init();
+        jit.math = MathImpl::Inline;
+
+        for op in [UnOp::SIN, UnOp::COS, UnOp::TAN] {
+            let prog = Program::from(vec![Instr::UnOp {
+                op,
+                val: Oprnd::Reg(0),
+                dst: 0,
+            }]);
+            let f = jit.compile::<Intrvl>("f", &prog, 1);
+
+            for i in 0..10_000 {
+                // beyond REDUCE_MAX the reduction is inexact
+                let a = match i % 2 {
+                    0 => rng.random_range(-20.0..20.0),
+                    _ => rng.random_range(-1e12..1e12),
+                };
+                let b = a + rng.random_range(0.0..4.0);
+                let res = f.eval(&[Intrvl::new(a, b)]);
+                if res.lo.is_nan() {
+                    assert_eq!(op, UnOp::TAN);
+                    continue;
+                }
+
+                for t in [0.0, 0.1, 0.25, 0.5, 0.75, 0.9, 1.0] {
+                    let x: f64 = a + (b - a) * t;
+                    let y = match op {
+                        UnOp::SIN => x.sin(),
+                        UnOp::COS => x.cos(),
+                        _ => x.tan(),
+                    };
+                    assert!(
+                        res.lo <= y && y <= res.hi,
+                        "{op}([{a}, {b}]) = {res:?} at {x}"
+                    );
+                }
+            }
+        }
+
+        let prog = Program::from(vec![bytecode!(TAN[0] -> 0)]);
+        let f = jit.compile::<Intrvl>("f", &prog, 1);
+        assert!(f.eval(&[Intrvl::new(1.0, 2.0)]).lo.is_nan());
+        let res = f.eval(&[Intrvl::new(1e7, 1e7 + 0.1)]);
+        assert!(res.lo == f64::NEG_INFINITY || res.lo.is_nan());
+    }
 }
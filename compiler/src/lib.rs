@@ -1,4 +1,7 @@
 #[macro_use]
 pub mod jit;
+pub mod aot;
 pub mod jit2;
-pub use jit::JITCompiler;
+pub mod opt;
+pub mod wasm;
+pub mod wgsl;
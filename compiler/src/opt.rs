@@ -0,0 +1,656 @@
+//! optimization passes over the jit bytecode
+//!
+//! registers that are read before they are written are the inputs of a program, the
+//! registers listed in [`Program::outputs`] its results. every pass keeps both in place.
+
+use crate::jit::{BinOp, Instr, Oprnd, Program, Reg, UnOp};
+
+const NUM_REGS: usize = Reg::MAX as usize + 1;
+
+/// runs all passes, the result computes the same outputs from the same inputs
+pub fn optimize(program: &Program) -> Program {
+    // in ssa form every value stays available for cse, the registers are reassigned after
+    let ssa = to_ssa(program)
+        .map(|p| eliminate_dead_code(&simplify_all(&p)))
+        .and_then(|p| allocate(&p, &program.outputs))
+        .filter(|p| num_regs(p) <= num_regs(program));
+
+    ssa.unwrap_or_else(|| compact_registers(&eliminate_dead_code(&simplify_all(program))))
+}
+
+fn simplify_all(program: &Program) -> Program {
+    let mut p = program.clone();
+
+    // folding and cse expose new opportunities for each other
+    for _ in 0..4 {
+        let next = eliminate_common_subexprs(&simplify(&fold_constants(&p)));
+        if next == p {
+            break;
+        }
+        p = next;
+    }
+    p
+}
+
+pub fn eval_binop(op: BinOp, lhs: f64, rhs: f64) -> f64 {
+    match op {
+        BinOp::ADD => lhs + rhs,
+        BinOp::SUB => lhs - rhs,
+        BinOp::MUL => lhs * rhs,
+        BinOp::DIV => lhs / rhs,
+        BinOp::POW => lhs.powf(rhs),
+        BinOp::MIN => lhs.min(rhs),
+        BinOp::MAX => lhs.max(rhs),
+        BinOp::LT => (lhs < rhs) as u8 as f64,
+        BinOp::LE => (lhs <= rhs) as u8 as f64,
+        BinOp::MOD => lhs - rhs * (lhs / rhs).floor(),
+    }
+}
+
+pub fn eval_unop(op: UnOp, val: f64) -> f64 {
+    match op {
+        UnOp::MOV => val,
+        UnOp::SIN => val.sin(),
+        UnOp::COS => val.cos(),
+        UnOp::TAN => val.tan(),
+        UnOp::FLOOR => val.floor(),
+        UnOp::CEIL => val.ceil(),
+        UnOp::ROUND => val.round_ties_even(),
+        UnOp::FRACT => val - val.floor(),
+        UnOp::GAMMA | UnOp::ERF | UnOp::J0 | UnOp::J1 | UnOp::LAMBERTW => {
+            op.special().unwrap().eval(val)
+        }
+        // natives are pure, so calls with constant arguments are folded as well
+        UnOp::CALL(f) => f.eval(val),
+    }
+}
+
+/// evaluates the program with f64 words, uninitialized registers are NaN
+pub fn eval(program: &Program, inputs: &[f64]) -> Vec<f64> {
+    let mut reg = [f64::NAN; NUM_REGS];
+    reg[..inputs.len()].copy_from_slice(inputs);
+
+    let val = |reg: &[f64; NUM_REGS], oprnd: Oprnd| match oprnd {
+        Oprnd::Reg(r) => reg[r as usize],
+        Oprnd::Imm(imm) => imm,
+    };
+
+    for &instr in &program.bytecode {
+        match instr {
+            Instr::UnOp { op, val: v, dst } => reg[dst as usize] = eval_unop(op, val(&reg, v)),
+            Instr::BinOp { op, lhs, rhs, dst } => {
+                reg[dst as usize] = eval_binop(op, val(&reg, lhs), val(&reg, rhs))
+            }
+            Instr::Select {
+                cond,
+                lhs,
+                rhs,
+                dst,
+            } => {
+                let taken = if val(&reg, cond) != 0.0 { lhs } else { rhs };
+                reg[dst as usize] = val(&reg, taken)
+            }
+        }
+    }
+
+    program.outputs.iter().map(|&r| reg[r as usize]).collect()
+}
+
+fn dst(instr: &Instr) -> Reg {
+    match *instr {
+        Instr::UnOp { dst, .. } | Instr::BinOp { dst, .. } | Instr::Select { dst, .. } => dst,
+    }
+}
+
+fn oprnds(instr: &Instr) -> impl Iterator<Item = Oprnd> {
+    let (a, b, c) = match *instr {
+        Instr::UnOp { val, .. } => (val, None, None),
+        Instr::BinOp { lhs, rhs, .. } => (lhs, Some(rhs), None),
+        Instr::Select { cond, lhs, rhs, .. } => (cond, Some(lhs), Some(rhs)),
+    };
+    std::iter::once(a).chain(b).chain(c)
+}
+
+fn map_oprnds(instr: Instr, mut f: impl FnMut(Oprnd) -> Oprnd) -> Instr {
+    match instr {
+        Instr::UnOp { op, val, dst } => Instr::UnOp {
+            op,
+            val: f(val),
+            dst,
+        },
+        Instr::BinOp { op, lhs, rhs, dst } => Instr::BinOp {
+            op,
+            lhs: f(lhs),
+            rhs: f(rhs),
+            dst,
+        },
+        Instr::Select {
+            cond,
+            lhs,
+            rhs,
+            dst,
+        } => Instr::Select {
+            cond: f(cond),
+            lhs: f(lhs),
+            rhs: f(rhs),
+            dst,
+        },
+    }
+}
+
+fn with_dst(instr: Instr, dst: Reg) -> Instr {
+    match instr {
+        Instr::UnOp { op, val, .. } => Instr::UnOp { op, val, dst },
+        Instr::BinOp { op, lhs, rhs, .. } => Instr::BinOp { op, lhs, rhs, dst },
+        Instr::Select { cond, lhs, rhs, .. } => Instr::Select {
+            cond,
+            lhs,
+            rhs,
+            dst,
+        },
+    }
+}
+
+fn mov(val: Oprnd, dst: Reg) -> Instr {
+    Instr::UnOp {
+        op: UnOp::MOV,
+        val,
+        dst,
+    }
+}
+
+fn reads(oprnd: Oprnd, reg: Reg) -> bool {
+    oprnd == Oprnd::Reg(reg)
+}
+
+/// the value of a register as seen by the following instructions
+#[derive(Clone)]
+struct Copies([Option<Oprnd>; NUM_REGS]);
+
+impl Copies {
+    fn new() -> Self {
+        Self([None; NUM_REGS])
+    }
+
+    fn resolve(&self, oprnd: Oprnd) -> Oprnd {
+        match oprnd {
+            Oprnd::Reg(r) => self.0[r as usize].unwrap_or(oprnd),
+            imm => imm,
+        }
+    }
+
+    /// forgets everything derived from the old value of reg
+    fn clobber(&mut self, reg: Reg) {
+        self.0[reg as usize] = None;
+        for c in &mut self.0 {
+            if c.is_some_and(|c| reads(c, reg)) {
+                *c = None;
+            }
+        }
+    }
+
+    fn set(&mut self, reg: Reg, val: Oprnd) {
+        self.clobber(reg);
+        if !reads(val, reg) {
+            self.0[reg as usize] = Some(val);
+        }
+    }
+}
+
+/// pow is only folded where f64, interval and complex evaluation agree
+fn fold_pow(b: f64, e: f64) -> Option<f64> {
+    (b >= 0.0 || e.fract() == 0.0).then(|| b.powf(e))
+}
+
+/// propagates copies and constants and evaluates instructions with only immediate operands
+pub fn fold_constants(program: &Program) -> Program {
+    let mut copies = Copies::new();
+    let mut bytecode = Vec::with_capacity(program.bytecode.len());
+
+    for &instr in &program.bytecode {
+        let instr = map_oprnds(instr, |o| copies.resolve(o));
+        let dst = dst(&instr);
+
+        let folded = match instr {
+            Instr::UnOp {
+                op,
+                val: Oprnd::Imm(v),
+                ..
+            } => Some(eval_unop(op, v)),
+            Instr::BinOp {
+                op,
+                lhs: Oprnd::Imm(l),
+                rhs: Oprnd::Imm(r),
+                ..
+            } => match op {
+                BinOp::POW => fold_pow(l, r),
+                _ => Some(eval_binop(op, l, r)),
+            },
+            _ => None,
+        };
+
+        let instr = match (folded, instr) {
+            (Some(v), _) => mov(Oprnd::Imm(v), dst),
+            (None, instr) => instr,
+        };
+
+        match instr {
+            Instr::UnOp {
+                op: UnOp::MOV, val, ..
+            } => copies.set(dst, val),
+            _ => copies.clobber(dst),
+        }
+        bytecode.push(instr);
+    }
+
+    Program::with_outputs(bytecode, program.outputs.clone())
+}
+
+/// rewrites x + -0, x - 0, x * 1, x / 1, x^1 and selects with a constant condition or equal
+/// sides to moves and x^2 to x * x. x + 0 is kept, it is 0 and not -0 for x = -0
+pub fn simplify(program: &Program) -> Program {
+    let is = |o: Oprnd, v: f64| oprnd_key(o) == oprnd_key(Oprnd::Imm(v));
+
+    let bytecode = program
+        .bytecode
+        .iter()
+        .map(|&instr| match instr {
+            Instr::BinOp { op, lhs, rhs, dst } => match op {
+                BinOp::ADD if is(lhs, -0.0) => mov(rhs, dst),
+                BinOp::ADD if is(rhs, -0.0) => mov(lhs, dst),
+                BinOp::SUB if is(rhs, 0.0) => mov(lhs, dst),
+                BinOp::MUL if is(lhs, 1.0) => mov(rhs, dst),
+                BinOp::MUL | BinOp::DIV | BinOp::POW if is(rhs, 1.0) => mov(lhs, dst),
+                BinOp::POW if is(rhs, 2.0) => Instr::BinOp {
+                    op: BinOp::MUL,
+                    lhs,
+                    rhs: lhs,
+                    dst,
+                },
+                _ => instr,
+            },
+            Instr::Select {
+                cond: Oprnd::Imm(c),
+                lhs,
+                rhs,
+                dst,
+            } => mov(if c != 0.0 { lhs } else { rhs }, dst),
+            Instr::Select { lhs, rhs, dst, .. } if oprnd_key(lhs) == oprnd_key(rhs) => {
+                mov(lhs, dst)
+            }
+            _ => instr,
+        })
+        .collect();
+
+    Program::with_outputs(bytecode, program.outputs.clone())
+}
+
+/// bit exact key of an operand, so 0 and -0 are different values
+fn oprnd_key(o: Oprnd) -> (bool, u64) {
+    match o {
+        Oprnd::Reg(r) => (false, r as u64),
+        Oprnd::Imm(imm) => (true, imm.to_bits()),
+    }
+}
+
+/// the op, None for SEL, and the operand keys
+type ExprKey = (Option<Result<BinOp, UnOp>>, [(bool, u64); 3]);
+
+fn expr_key(instr: &Instr) -> ExprKey {
+    match *instr {
+        Instr::UnOp { op, val, .. } => (Some(Err(op)), [oprnd_key(val), (false, 0), (false, 0)]),
+        Instr::BinOp { op, lhs, rhs, .. } => {
+            let (mut l, mut r) = (oprnd_key(lhs), oprnd_key(rhs));
+            if matches!(op, BinOp::ADD | BinOp::MUL | BinOp::MIN | BinOp::MAX) && l > r {
+                std::mem::swap(&mut l, &mut r);
+            }
+            (Some(Ok(op)), [l, r, (false, 0)])
+        }
+        Instr::Select { cond, lhs, rhs, .. } => {
+            (None, [oprnd_key(cond), oprnd_key(lhs), oprnd_key(rhs)])
+        }
+    }
+}
+
+/// replaces recomputations of a value that is still held by a register with a move
+pub fn eliminate_common_subexprs(program: &Program) -> Program {
+    let mut exprs: Vec<(ExprKey, Reg)> = vec![];
+    let mut bytecode = Vec::with_capacity(program.bytecode.len());
+
+    for &instr in &program.bytecode {
+        let dst = dst(&instr);
+        let is_mov = matches!(instr, Instr::UnOp { op: UnOp::MOV, .. });
+        let reads_dst = oprnds(&instr).any(|o| reads(o, dst));
+        let key = expr_key(&instr);
+
+        let cse = match exprs.iter().find(|(k, _)| !is_mov && *k == key) {
+            Some(&(_, reg)) => mov(Oprnd::Reg(reg), dst),
+            None => instr,
+        };
+
+        exprs.retain(|&(k, reg)| reg != dst && !k.1.contains(&(false, dst as u64)));
+        if !is_mov && !reads_dst {
+            exprs.push((key, dst));
+        }
+        bytecode.push(cse);
+    }
+
+    Program::with_outputs(bytecode, program.outputs.clone())
+}
+
+/// removes instructions whose result is overwritten or never read
+pub fn eliminate_dead_code(program: &Program) -> Program {
+    let mut live = [false; NUM_REGS];
+    for &r in &program.outputs {
+        live[r as usize] = true;
+    }
+
+    let mut bytecode = vec![];
+    for &instr in program.bytecode.iter().rev() {
+        let dst = dst(&instr);
+        let self_mov = instr == mov(Oprnd::Reg(dst), dst);
+        if !live[dst as usize] || self_mov {
+            continue;
+        }
+
+        live[dst as usize] = false;
+        for o in oprnds(&instr) {
+            if let Oprnd::Reg(r) = o {
+                live[r as usize] = true;
+            }
+        }
+        bytecode.push(instr);
+    }
+    bytecode.reverse();
+
+    Program::with_outputs(bytecode, program.outputs.clone())
+}
+
+/// a value held in a register from its definition to its last use
+#[derive(Debug, Clone, Copy)]
+struct Interval {
+    start: i64,
+    end: i64,
+    /// inputs and outputs must stay in their register
+    fixed: Option<Reg>,
+    reg: Reg,
+}
+
+impl Interval {
+    fn overlaps(&self, other: &Interval) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+}
+
+/// value held by r, registers read before they are written are fixed inputs
+fn use_reg(
+    r: Reg,
+    i: i64,
+    value: &mut [Option<usize>; NUM_REGS],
+    intervals: &mut Vec<Interval>,
+) -> usize {
+    let v = *value[r as usize].get_or_insert_with(|| {
+        intervals.push(Interval {
+            start: -1,
+            end: -1,
+            fixed: Some(r),
+            reg: r,
+        });
+        intervals.len() - 1
+    });
+    intervals[v].end = intervals[v].end.max(i);
+    v
+}
+
+fn num_regs(program: &Program) -> usize {
+    let mut regs = [false; NUM_REGS];
+    for instr in &program.bytecode {
+        regs[dst(instr) as usize] = true;
+        for o in oprnds(instr) {
+            if let Oprnd::Reg(r) = o {
+                regs[r as usize] = true;
+            }
+        }
+    }
+    for &r in &program.outputs {
+        regs[r as usize] = true;
+    }
+    regs.iter().filter(|&&r| r).count()
+}
+
+/// assigns registers to values such that the i-th output ends up in targets[i]
+///
+/// None if a fixed input or output register is needed by two values at once
+fn allocate(program: &Program, targets: &[Reg]) -> Option<Program> {
+    let n = program.bytecode.len() as i64;
+    let mut intervals: Vec<Interval> = vec![];
+    // current value of every register
+    let mut value: [Option<usize>; NUM_REGS] = [None; NUM_REGS];
+    // (operand values, dst value) of every instruction
+    let mut uses = vec![];
+
+    for (i, instr) in program.bytecode.iter().enumerate() {
+        let i = i as i64;
+        let ops: Vec<_> = oprnds(instr)
+            .map(|o| match o {
+                Oprnd::Reg(r) => Some(use_reg(r, i, &mut value, &mut intervals)),
+                Oprnd::Imm(_) => None,
+            })
+            .collect();
+
+        let dst = dst(instr);
+        intervals.push(Interval {
+            start: i,
+            end: i + 1,
+            fixed: None,
+            reg: dst,
+        });
+        value[dst as usize] = Some(intervals.len() - 1);
+        uses.push((ops, intervals.len() - 1));
+    }
+
+    for (&r, &target) in program.outputs.iter().zip(targets) {
+        let v = use_reg(r, n + 1, &mut value, &mut intervals);
+        if intervals[v].fixed.is_some_and(|f| f != target) {
+            return None;
+        }
+        intervals[v].fixed = Some(target);
+    }
+
+    // fixed values first, then greedy by start
+    let mut order: Vec<_> = (0..intervals.len()).collect();
+    order.sort_by_key(|&v| (intervals[v].fixed.is_none(), intervals[v].start));
+
+    let mut assigned: Vec<Vec<usize>> = vec![vec![]; NUM_REGS];
+    for v in order {
+        let iv = intervals[v];
+        let is_free = |r: usize| {
+            assigned[r]
+                .iter()
+                .all(|&other| !intervals[other].overlaps(&iv))
+        };
+        let reg = match iv.fixed {
+            Some(r) => is_free(r as usize).then_some(r as usize)?,
+            None => (0..NUM_REGS).find(|&r| is_free(r))?,
+        };
+        assigned[reg].push(v);
+        intervals[v].reg = reg as Reg;
+    }
+
+    let bytecode = program
+        .bytecode
+        .iter()
+        .zip(&uses)
+        .map(|(&instr, (ops, dst_val))| {
+            let mut ops = ops.iter();
+            let instr = map_oprnds(instr, |o| match (o, ops.next()) {
+                (Oprnd::Reg(_), Some(&Some(v))) => Oprnd::Reg(intervals[v].reg),
+                (o, _) => o,
+            });
+            with_dst(instr, intervals[*dst_val].reg)
+        })
+        .collect();
+
+    Some(eliminate_dead_code(&Program::with_outputs(
+        bytecode,
+        targets.to_vec(),
+    )))
+}
+
+/// renames registers so that values with disjoint lifetimes share a register
+///
+/// returns the program unchanged if it would need more registers than before
+pub fn compact_registers(program: &Program) -> Program {
+    match allocate(program, &program.outputs) {
+        Some(p) if num_regs(&p) <= num_regs(program) => p,
+        _ => program.clone(),
+    }
+}
+
+/// writes every value to a new register, so that cse can reuse all of them
+///
+/// the outputs are renamed as well, None if there are not enough registers
+pub fn to_ssa(program: &Program) -> Option<Program> {
+    let first = program
+        .bytecode
+        .iter()
+        .flat_map(|i| oprnds(i).chain([Oprnd::Reg(dst(i))]))
+        .chain(program.outputs.iter().map(|&r| Oprnd::Reg(r)))
+        .filter_map(|o| match o {
+            Oprnd::Reg(r) => Some(r as usize + 1),
+            Oprnd::Imm(_) => None,
+        })
+        .max()
+        .unwrap_or(0);
+
+    if first + program.bytecode.len() > NUM_REGS {
+        return None;
+    }
+
+    let mut name: [Reg; NUM_REGS] = std::array::from_fn(|r| r as Reg);
+    let mut bytecode = Vec::with_capacity(program.bytecode.len());
+
+    for (i, &instr) in program.bytecode.iter().enumerate() {
+        let instr = map_oprnds(instr, |o| match o {
+            Oprnd::Reg(r) => Oprnd::Reg(name[r as usize]),
+            imm => imm,
+        });
+        let dst = (first + i) as Reg;
+        name[self::dst(&instr) as usize] = dst;
+
+        bytecode.push(with_dst(instr, dst));
+    }
+
+    let outputs = program.outputs.iter().map(|&r| name[r as usize]).collect();
+    Some(Program::with_outputs(bytecode, outputs))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::jit::random;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    fn random_program(rng: &mut impl Rng, len: usize) -> Program {
+        let mut prog = random::program(rng, len, 6);
+        prog.outputs = (0..rng.random_range(1..3))
+            .map(|_| rng.random_range(0..6))
+            .collect();
+        prog
+    }
+
+    /// the sign of zero matters, it flips the sign of 1 / x
+    fn same(a: f64, b: f64) -> bool {
+        if a == 0.0 && b == 0.0 {
+            return a.to_bits() == b.to_bits();
+        }
+        a.is_nan() && b.is_nan() || a == b || (a - b).abs() <= 1e-9 * a.abs().max(b.abs())
+    }
+
+    fn check(pass: fn(&Program) -> Program, name: &str) {
+        // seeded, so a failure can be reproduced
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        for _ in 0..2000 {
+            let len = rng.random_range(1..16);
+            let prog = random_program(&mut rng, len);
+            let opt = pass(&prog);
+
+            for _ in 0..4 {
+                let inputs = [rng.random_range(-4.0..4.0), rng.random_range(-4.0..4.0)];
+                let expected = eval(&prog, &inputs);
+                let res = eval(&opt, &inputs);
+                assert!(
+                    expected.iter().zip(&res).all(|(&a, &b)| same(a, b)),
+                    "{name}: {expected:?} != {res:?} for {inputs:?}\n{prog}\n--\n{opt}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn differential() {
+        check(fold_constants, "fold_constants");
+        check(simplify, "simplify");
+        check(eliminate_common_subexprs, "eliminate_common_subexprs");
+        check(eliminate_dead_code, "eliminate_dead_code");
+        check(compact_registers, "compact_registers");
+        check(|p| to_ssa(p).unwrap(), "to_ssa");
+        check(optimize, "optimize");
+    }
+
+    #[test]
+    fn shared_subexprs() {
+        // sin(1/x) + sin(1/x) * 2^3
+        let code = crate::bytecode! [
+            DIV[imm(1.0), 0] -> 2,
+            SIN[2] -> 3,
+            DIV[imm(1.0), 0] -> 4,
+            SIN[4] -> 5,
+            POW[imm(2.0), imm(3.0)] -> 6,
+            MUL[5, 6] -> 5,
+            MUL[1, imm(1.0)] -> 7,
+            ADD[3, 5] -> 0,
+        ];
+        let prog = Program::from(code.to_vec());
+        let opt = optimize(&prog);
+
+        assert_eq!(
+            opt.bytecode,
+            crate::bytecode! [
+                DIV[imm(1.0), 0] -> 0,
+                SIN[0] -> 0,
+                MUL[0, imm(8.0)] -> 1,
+                ADD[0, 1] -> 0,
+            ]
+        );
+        assert_eq!(eval(&opt, &[0.5, 0.0]), eval(&prog, &[0.5, 0.0]));
+    }
+
+    #[test]
+    fn signed_zero() {
+        let simplified = |code: &[Instr]| simplify(&Program::from(code.to_vec())).bytecode;
+
+        // -0 + 0 is 0, only adding -0 or subtracting 0 keeps x
+        let kept = [
+            [crate::bytecode![ADD[0, imm(0.0)] -> 0]],
+            [crate::bytecode![ADD[imm(0.0), 0] -> 0]],
+            [crate::bytecode![SUB[0, imm(-0.0)] -> 0]],
+        ];
+        for code in kept {
+            assert_eq!(simplified(&code), code);
+            let res = eval(&Program::from(code.to_vec()), &[-0.0, 0.0])[0];
+            assert_eq!(res.to_bits(), 0.0f64.to_bits(), "{code:?}");
+        }
+        for code in [
+            [crate::bytecode![ADD[0, imm(-0.0)] -> 0]],
+            [crate::bytecode![ADD[imm(-0.0), 0] -> 0]],
+            [crate::bytecode![SUB[0, imm(0.0)] -> 0]],
+        ] {
+            assert_eq!(simplified(&code), [mov(Oprnd::Reg(0), 0)]);
+        }
+
+        // the sides of the select differ in the sign of zero
+        let code = [crate::bytecode![SEL[1, imm(0.0), imm(-0.0)] -> 0]];
+        assert_eq!(simplified(&code), code);
+    }
+}
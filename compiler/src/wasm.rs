@@ -0,0 +1,774 @@
+//! webassembly backend
+//!
+//! compiles a [`Program`] to a module exporting the same entry points as [`crate::jit2::JIT`]:
+//! - `f64(x, y) -> f64`
+//! - `f64x2(x0, x1, y0, y1) -> (f64, f64)`, evaluated with simd
+//! - `intrvl(x_lo, x_hi, y_lo, y_hi) -> (lo, hi)`
+//!
+//! transcendental and non trivial interval functions are imported from `env`, see [`IMPORTS`].
+//! in the browser they are provided by the host page. natively the modules are validated with
+//! walrus, running them with wasmer ([`WasmModule`]) needs the `wasmer` feature, because
+//! wasmer-vm 6.0 links against `__rust_probestack`, which current toolchains no longer export.
+
+use wasm_encoder::{
+    CodeSection, EntityType, ExportKind, ExportSection, Function, FunctionSection, ImportSection,
+    InstructionSink, Module, TypeSection, ValType,
+};
+
+use crate::jit::{BinOp, Instr, Oprnd, Program, Reg, UnOp};
+use utils::native::NativeId;
+
+const N_REGS: u32 = crate::jit2::N_REGS as u32;
+
+/// functions imported from `env` as (name, number of f64 params, number of f64 results)
+///
+/// intervals are passed as (lo, hi). the natives of [`utils::native`] take the id of the callee
+/// as last argument
+pub const IMPORTS: &[(&str, usize, usize)] = &[
+    ("sin_f64", 1, 1),
+    ("cos_f64", 1, 1),
+    ("tan_f64", 1, 1),
+    ("pow_f64", 2, 1),
+    ("mul_intrvl", 4, 2),
+    ("div_intrvl", 4, 2),
+    ("pow_intrvl", 4, 2),
+    ("sin_intrvl", 2, 2),
+    ("cos_intrvl", 2, 2),
+    ("tan_intrvl", 2, 2),
+    ("fract_intrvl", 2, 2),
+    ("mod_intrvl", 4, 2),
+    ("gamma_f64", 1, 1),
+    ("erf_f64", 1, 1),
+    ("j0_f64", 1, 1),
+    ("j1_f64", 1, 1),
+    ("lambert_w_f64", 1, 1),
+    ("gamma_intrvl", 2, 2),
+    ("erf_intrvl", 2, 2),
+    ("j0_intrvl", 2, 2),
+    ("j1_intrvl", 2, 2),
+    ("lambert_w_intrvl", 2, 2),
+    ("native_f64", 2, 1),
+    ("native_intrvl", 3, 2),
+];
+
+fn import(name: &str) -> u32 {
+    IMPORTS.iter().position(|(n, ..)| *n == name).unwrap() as u32
+}
+
+/// the id of a native as the f64 argument of the imports
+fn native_arg(f: NativeId) -> f64 {
+    f.to_raw() as f64
+}
+
+fn unop_name(op: UnOp) -> &'static str {
+    match op {
+        UnOp::SIN => "sin",
+        UnOp::COS => "cos",
+        UnOp::TAN => "tan",
+        _ => op.special().expect("no imported function").name(),
+    }
+}
+
+/// compiles the program to a wasm module, the entry points return the first output
+pub fn compile(program: &Program) -> Vec<u8> {
+    let mut types = TypeSection::new();
+    let mut imports = ImportSection::new();
+    for (i, &(name, params, results)) in IMPORTS.iter().enumerate() {
+        types
+            .ty()
+            .function(vec![ValType::F64; params], vec![ValType::F64; results]);
+        imports.import("env", name, EntityType::Function(i as u32));
+    }
+
+    let n = IMPORTS.len() as u32;
+    types.ty().function([ValType::F64; 2], [ValType::F64]);
+    types.ty().function([ValType::F64; 4], [ValType::F64; 2]);
+
+    let mut functions = FunctionSection::new();
+    let mut exports = ExportSection::new();
+    let mut code = CodeSection::new();
+    let bodies = [
+        ("f64", n, f64_body(program)),
+        ("f64x2", n + 1, f64x2_body(program)),
+        ("intrvl", n + 1, intrvl_body(program)),
+    ];
+    for (i, (name, ty, body)) in bodies.iter().enumerate() {
+        functions.function(*ty);
+        exports.export(name, ExportKind::Func, n + i as u32);
+        code.function(body);
+    }
+
+    let mut module = Module::new();
+    module
+        .section(&types)
+        .section(&imports)
+        .section(&functions)
+        .section(&exports)
+        .section(&code);
+    module.finish()
+}
+
+fn f64_body(program: &Program) -> Function {
+    // params: x, y
+    let reg = |r: Reg| 2 + r as u32;
+    let (tmp0, tmp1) = (2 + N_REGS, 3 + N_REGS);
+    let mut f = Function::new([(N_REGS + 2, ValType::F64)]);
+    let mut ins = f.instructions();
+
+    for r in 0..N_REGS {
+        match r {
+            0 | 1 => ins.local_get(r),
+            _ => ins.f64_const(f64::NAN.into()),
+        };
+        ins.local_set(2 + r);
+    }
+
+    let push = |o: Oprnd, ins: &mut InstructionSink| {
+        match o {
+            Oprnd::Reg(r) => ins.local_get(reg(r)),
+            Oprnd::Imm(v) => ins.f64_const(v.into()),
+        };
+    };
+
+    for &instr in &program.bytecode {
+        match instr {
+            Instr::UnOp { op, val, dst } => {
+                push(val, &mut ins);
+                match op {
+                    UnOp::MOV => (),
+                    UnOp::FLOOR => _ = ins.f64_floor(),
+                    UnOp::CEIL => _ = ins.f64_ceil(),
+                    UnOp::ROUND => _ = ins.f64_nearest(),
+                    UnOp::FRACT => _ = ins.local_tee(tmp0).local_get(tmp0).f64_floor().f64_sub(),
+                    UnOp::CALL(f) => {
+                        _ = ins
+                            .f64_const(native_arg(f).into())
+                            .call(import("native_f64"))
+                    }
+                    _ => _ = ins.call(import(&format!("{}_f64", unop_name(op)))),
+                }
+                ins.local_set(reg(dst));
+            }
+            Instr::BinOp { op, lhs, rhs, dst } => {
+                push(lhs, &mut ins);
+                push(rhs, &mut ins);
+                match op {
+                    BinOp::ADD => ins.f64_add(),
+                    BinOp::SUB => ins.f64_sub(),
+                    BinOp::MUL => ins.f64_mul(),
+                    BinOp::DIV => ins.f64_div(),
+                    BinOp::POW => ins.call(import("pow_f64")),
+                    BinOp::MIN | BinOp::MAX => {
+                        min_max_f64(ins.local_set(tmp1).local_set(tmp0), op, tmp0, tmp1)
+                    }
+                    BinOp::LT => ins.f64_lt().f64_convert_i32_u(),
+                    BinOp::LE => ins.f64_le().f64_convert_i32_u(),
+                    // lhs - rhs * floor(lhs / rhs)
+                    BinOp::MOD => ins
+                        .local_set(tmp1)
+                        .local_tee(tmp0)
+                        .local_get(tmp1)
+                        .local_get(tmp0)
+                        .local_get(tmp1)
+                        .f64_div()
+                        .f64_floor()
+                        .f64_mul()
+                        .f64_sub(),
+                };
+                ins.local_set(reg(dst));
+            }
+            Instr::Select {
+                cond,
+                lhs,
+                rhs,
+                dst,
+            } => {
+                push(lhs, &mut ins);
+                push(rhs, &mut ins);
+                push(cond, &mut ins);
+                ins.f64_const(0.0.into()).f64_ne().select();
+                ins.local_set(reg(dst));
+            }
+        }
+    }
+
+    ins.local_get(reg(program.outputs[0])).end();
+    f
+}
+
+fn f64x2_body(program: &Program) -> Function {
+    // params: x0, x1, y0, y1
+    let reg = |r: Reg| 4 + r as u32;
+    let (tmp0, tmp1) = (4 + N_REGS, 5 + N_REGS);
+    let mut f = Function::new([(N_REGS + 2, ValType::V128)]);
+    let mut ins = f.instructions();
+
+    for r in 0..N_REGS {
+        match r {
+            0 | 1 => ins
+                .local_get(2 * r)
+                .f64x2_splat()
+                .local_get(2 * r + 1)
+                .f64x2_replace_lane(1),
+            _ => ins.f64_const(f64::NAN.into()).f64x2_splat(),
+        };
+        ins.local_set(4 + r);
+    }
+
+    let push = |o: Oprnd, ins: &mut InstructionSink| {
+        match o {
+            Oprnd::Reg(r) => ins.local_get(reg(r)),
+            Oprnd::Imm(v) => ins.f64_const(v.into()).f64x2_splat(),
+        };
+    };
+
+    for &instr in &program.bytecode {
+        match instr {
+            Instr::UnOp { op, val, dst } => {
+                push(val, &mut ins);
+                match op {
+                    UnOp::MOV => (),
+                    UnOp::FLOOR => _ = ins.f64x2_floor(),
+                    UnOp::CEIL => _ = ins.f64x2_ceil(),
+                    UnOp::ROUND => _ = ins.f64x2_nearest(),
+                    UnOp::FRACT => {
+                        _ = ins
+                            .local_tee(tmp0)
+                            .local_get(tmp0)
+                            .f64x2_floor()
+                            .f64x2_sub()
+                    }
+                    UnOp::CALL(f) => {
+                        ins.local_set(tmp0);
+                        for lane in 0..2 {
+                            ins.local_get(tmp0)
+                                .f64x2_extract_lane(lane)
+                                .f64_const(native_arg(f).into())
+                                .call(import("native_f64"));
+                            match lane {
+                                0 => ins.f64x2_splat(),
+                                _ => ins.f64x2_replace_lane(1),
+                            };
+                        }
+                    }
+                    _ => {
+                        let name = format!("{}_f64", unop_name(op));
+                        per_lane(ins.local_set(tmp0), &name, &[tmp0]);
+                    }
+                }
+                ins.local_set(reg(dst));
+            }
+            Instr::BinOp { op, lhs, rhs, dst } => {
+                push(lhs, &mut ins);
+                push(rhs, &mut ins);
+                match op {
+                    BinOp::ADD => ins.f64x2_add(),
+                    BinOp::SUB => ins.f64x2_sub(),
+                    BinOp::MUL => ins.f64x2_mul(),
+                    BinOp::DIV => ins.f64x2_div(),
+                    BinOp::MIN | BinOp::MAX => {
+                        min_max_f64x2(ins.local_set(tmp1).local_set(tmp0), op, tmp0, tmp1)
+                    }
+                    BinOp::LT => ins
+                        .f64x2_lt()
+                        .f64_const(1.0.into())
+                        .f64x2_splat()
+                        .v128_and(),
+                    BinOp::LE => ins
+                        .f64x2_le()
+                        .f64_const(1.0.into())
+                        .f64x2_splat()
+                        .v128_and(),
+                    BinOp::MOD => ins
+                        .local_set(tmp1)
+                        .local_tee(tmp0)
+                        .local_get(tmp1)
+                        .local_get(tmp0)
+                        .local_get(tmp1)
+                        .f64x2_div()
+                        .f64x2_floor()
+                        .f64x2_mul()
+                        .f64x2_sub(),
+                    BinOp::POW => per_lane(
+                        ins.local_set(tmp1).local_set(tmp0),
+                        "pow_f64",
+                        &[tmp0, tmp1],
+                    ),
+                };
+                ins.local_set(reg(dst));
+            }
+            Instr::Select {
+                cond,
+                lhs,
+                rhs,
+                dst,
+            } => {
+                push(lhs, &mut ins);
+                push(rhs, &mut ins);
+                push(cond, &mut ins);
+                ins.f64_const(0.0.into())
+                    .f64x2_splat()
+                    .f64x2_ne()
+                    .v128_bitselect();
+                ins.local_set(reg(dst));
+            }
+        }
+    }
+
+    ins.local_get(reg(program.outputs[0]))
+        .local_tee(tmp0)
+        .f64x2_extract_lane(0)
+        .local_get(tmp0)
+        .f64x2_extract_lane(1)
+        .end();
+    f
+}
+
+/// applies the imported scalar function to each lane of the v128 args
+fn per_lane<'a, 'b>(
+    ins: &'a mut InstructionSink<'b>,
+    name: &str,
+    args: &[u32],
+) -> &'a mut InstructionSink<'b> {
+    for lane in 0..2 {
+        for &arg in args {
+            ins.local_get(arg).f64x2_extract_lane(lane);
+        }
+        ins.call(import(name));
+        match lane {
+            0 => ins.f64x2_splat(),
+            _ => ins.f64x2_replace_lane(1),
+        };
+    }
+    ins
+}
+
+/// pushes 1 if the select with condition (e, f) always takes the lhs
+/// min / max of the locals a and b like f64::min, a nan operand yields the other one.
+/// f64.min of wasm returns nan instead
+fn min_max_f64<'a, 'b>(
+    ins: &'a mut InstructionSink<'b>,
+    op: BinOp,
+    a: u32,
+    b: u32,
+) -> &'a mut InstructionSink<'b> {
+    ins.local_get(a).local_get(b).local_get(a).local_get(b);
+    match op {
+        BinOp::MAX => ins.f64_max(),
+        _ => ins.f64_min(),
+    };
+    ins.local_get(a).local_get(a).f64_ne().select();
+    ins.local_get(b).local_get(b).f64_ne().select()
+}
+
+/// lane wise [`min_max_f64`]
+fn min_max_f64x2<'a, 'b>(
+    ins: &'a mut InstructionSink<'b>,
+    op: BinOp,
+    a: u32,
+    b: u32,
+) -> &'a mut InstructionSink<'b> {
+    ins.local_get(a).local_get(b).local_get(a).local_get(b);
+    match op {
+        BinOp::MAX => ins.f64x2_max(),
+        _ => ins.f64x2_min(),
+    };
+    ins.local_get(a).local_get(a).f64x2_ne().v128_bitselect();
+    ins.local_get(b).local_get(b).f64x2_ne().v128_bitselect()
+}
+
+fn select_decided(ins: &mut InstructionSink, e: u32, f: u32) {
+    ins.local_get(e)
+        .f64_const(0.0.into())
+        .f64_gt()
+        .local_get(f)
+        .f64_const(0.0.into())
+        .f64_lt()
+        .i32_or();
+}
+
+/// pushes 1 if the select with condition (e, f) always takes the rhs
+fn select_zero(ins: &mut InstructionSink, e: u32, f: u32) {
+    ins.local_get(e)
+        .f64_const(0.0.into())
+        .f64_eq()
+        .local_get(f)
+        .f64_const(0.0.into())
+        .f64_eq()
+        .i32_and();
+}
+
+fn intrvl_body(program: &Program) -> Function {
+    // params: x_lo, x_hi, y_lo, y_hi
+    let lo = |r: Reg| 4 + 2 * r as u32;
+    let hi = |r: Reg| 5 + 2 * r as u32;
+    // operands (a, b) op (c, d), (e, f) is the condition of a select
+    let [a, b, c, d, e, f] = std::array::from_fn(|i| 4 + 2 * N_REGS + i as u32);
+    let mut func = Function::new([(2 * N_REGS + 6, ValType::F64)]);
+    let mut ins = func.instructions();
+
+    for r in 0..2 * N_REGS {
+        match r {
+            0..4 => ins.local_get(r),
+            _ => ins.f64_const(f64::NAN.into()),
+        };
+        ins.local_set(4 + r);
+    }
+
+    let push = |o: Oprnd, ins: &mut InstructionSink| {
+        match o {
+            Oprnd::Reg(r) => ins.local_get(lo(r)).local_get(hi(r)),
+            Oprnd::Imm(v) => ins.f64_const(v.into()).f64_const(v.into()),
+        };
+    };
+
+    for &instr in &program.bytecode {
+        match instr {
+            Instr::UnOp { op, val, dst } => {
+                push(val, &mut ins);
+                match op {
+                    UnOp::MOV => (),
+                    // monotonic, computed lane wise
+                    UnOp::FLOOR | UnOp::CEIL | UnOp::ROUND => {
+                        ins.local_set(b).local_set(a);
+                        for l in [a, b] {
+                            ins.local_get(l);
+                            match op {
+                                UnOp::FLOOR => ins.f64_floor(),
+                                UnOp::CEIL => ins.f64_ceil(),
+                                _ => ins.f64_nearest(),
+                            };
+                        }
+                    }
+                    UnOp::FRACT => _ = ins.call(import("fract_intrvl")),
+                    UnOp::CALL(f) => {
+                        _ = ins
+                            .f64_const(native_arg(f).into())
+                            .call(import("native_intrvl"))
+                    }
+                    _ => _ = ins.call(import(&format!("{}_intrvl", unop_name(op)))),
+                }
+                ins.local_set(hi(dst)).local_set(lo(dst));
+            }
+            Instr::BinOp { op, lhs, rhs, dst } => {
+                push(lhs, &mut ins);
+                push(rhs, &mut ins);
+                match op {
+                    BinOp::MUL => _ = ins.call(import("mul_intrvl")),
+                    BinOp::DIV => _ = ins.call(import("div_intrvl")),
+                    BinOp::POW => _ = ins.call(import("pow_intrvl")),
+                    BinOp::MOD => _ = ins.call(import("mod_intrvl")),
+                    // [b < c, a < d]: 1 if it holds everywhere, 0 if nowhere
+                    BinOp::LT | BinOp::LE => {
+                        ins.local_set(d).local_set(c).local_set(b).local_set(a);
+                        for (l, r) in [(b, c), (a, d)] {
+                            ins.local_get(l).local_get(r);
+                            match op {
+                                BinOp::LT => ins.f64_lt(),
+                                _ => ins.f64_le(),
+                            };
+                            ins.f64_convert_i32_u();
+                        }
+                    }
+                    _ => {
+                        ins.local_set(d).local_set(c).local_set(b).local_set(a);
+                        // [a - d, b - c] for sub, lane wise otherwise
+                        let lanes = match op {
+                            BinOp::SUB => [(a, d), (b, c)],
+                            _ => [(a, c), (b, d)],
+                        };
+                        for (l, r) in lanes {
+                            match op {
+                                BinOp::ADD => ins.local_get(l).local_get(r).f64_add(),
+                                BinOp::SUB => ins.local_get(l).local_get(r).f64_sub(),
+                                _ => min_max_f64(&mut ins, op, l, r),
+                            };
+                        }
+                    }
+                }
+                ins.local_set(hi(dst)).local_set(lo(dst));
+            }
+            Instr::Select {
+                cond,
+                lhs,
+                rhs,
+                dst,
+            } => {
+                push(lhs, &mut ins);
+                push(rhs, &mut ins);
+                push(cond, &mut ins);
+                ins.local_set(f).local_set(e);
+                ins.local_set(d).local_set(c).local_set(b).local_set(a);
+                // the lhs if decided, the rhs if the condition is zero, the hull otherwise
+                for (l, r, is_lo) in [(a, c, true), (b, d, false)] {
+                    ins.local_get(l).local_get(r).local_get(l).local_get(r);
+                    match is_lo {
+                        true => ins.f64_min(),
+                        false => ins.f64_max(),
+                    };
+                    select_zero(&mut ins, e, f);
+                    ins.select();
+                    select_decided(&mut ins, e, f);
+                    ins.select();
+                }
+                ins.local_set(hi(dst)).local_set(lo(dst));
+            }
+        }
+    }
+
+    let out = program.outputs[0];
+    ins.local_get(lo(out)).local_get(hi(out)).end();
+    func
+}
+
+#[cfg(all(feature = "wasmer", not(target_arch = "wasm32")))]
+pub use runtime::WasmModule;
+
+#[cfg(all(feature = "wasmer", not(target_arch = "wasm32")))]
+mod runtime {
+    use utils::{native::NativeId, special::Special};
+    use wasmer::{Function, Imports, Instance, Module, Store, TypedFunction};
+
+    use crate::jit::Program;
+    use crate::jit2::{F64X2, Intrvl};
+
+    type Quad = (f64, f64, f64, f64);
+
+    /// a compiled program instantiated with wasmer
+    pub struct WasmModule {
+        store: Store,
+        f64_fn: TypedFunction<(f64, f64), f64>,
+        f64x2_fn: TypedFunction<Quad, (f64, f64)>,
+        intrvl_fn: TypedFunction<Quad, (f64, f64)>,
+    }
+
+    fn intrvl_fn(f: fn(Intrvl) -> Intrvl) -> impl Fn(f64, f64) -> (f64, f64) + Send + Sync {
+        move |lo, hi| {
+            let i = f(Intrvl::new(lo, hi));
+            (i.lo, i.hi)
+        }
+    }
+
+    fn intrvl_fn2(
+        f: fn(Intrvl, Intrvl) -> Intrvl,
+    ) -> impl Fn(f64, f64, f64, f64) -> (f64, f64) + Send + Sync {
+        move |a, b, c, d| {
+            let i = f(Intrvl::new(a, b), Intrvl::new(c, d));
+            (i.lo, i.hi)
+        }
+    }
+
+    impl WasmModule {
+        pub fn new(program: &Program) -> anyhow::Result<Self> {
+            let mut store = Store::default();
+            let module = Module::new(&store, super::compile(program))?;
+
+            let mut imports = Imports::new();
+            let mut define = |name: &str, f: Function| imports.define("env", name, f);
+            define("sin_f64", Function::new_typed(&mut store, f64::sin));
+            define("cos_f64", Function::new_typed(&mut store, f64::cos));
+            define("tan_f64", Function::new_typed(&mut store, f64::tan));
+            define("pow_f64", Function::new_typed(&mut store, f64::powf));
+            define(
+                "mul_intrvl",
+                Function::new_typed(&mut store, intrvl_fn2(Intrvl::mul)),
+            );
+            define(
+                "div_intrvl",
+                Function::new_typed(&mut store, intrvl_fn2(Intrvl::div)),
+            );
+            define(
+                "pow_intrvl",
+                Function::new_typed(&mut store, intrvl_fn2(Intrvl::pow)),
+            );
+            define(
+                "sin_intrvl",
+                Function::new_typed(&mut store, intrvl_fn(Intrvl::sin)),
+            );
+            define(
+                "cos_intrvl",
+                Function::new_typed(&mut store, intrvl_fn(Intrvl::cos)),
+            );
+            define(
+                "tan_intrvl",
+                Function::new_typed(&mut store, intrvl_fn(Intrvl::tan)),
+            );
+            define(
+                "fract_intrvl",
+                Function::new_typed(&mut store, intrvl_fn(Intrvl::fract)),
+            );
+            define(
+                "mod_intrvl",
+                Function::new_typed(&mut store, intrvl_fn2(Intrvl::modulo)),
+            );
+            for f in Special::ALL {
+                define(
+                    &format!("{f}_f64"),
+                    Function::new_typed(&mut store, move |x: f64| f.eval(x)),
+                );
+                define(
+                    &format!("{f}_intrvl"),
+                    Function::new_typed(&mut store, move |lo: f64, hi: f64| f.intrvl(lo, hi)),
+                );
+            }
+            let native = |id: f64| NativeId::from_raw(id as u32).expect("unregistered native");
+            define(
+                "native_f64",
+                Function::new_typed(&mut store, move |x: f64, id: f64| native(id).eval(x)),
+            );
+            define(
+                "native_intrvl",
+                Function::new_typed(&mut store, move |lo: f64, hi: f64, id: f64| {
+                    native(id).intrvl(lo, hi)
+                }),
+            );
+
+            let instance = Instance::new(&mut store, &module, &imports)?;
+            let exports = &instance.exports;
+            Ok(Self {
+                f64_fn: exports.get_typed_function(&store, "f64")?,
+                f64x2_fn: exports.get_typed_function(&store, "f64x2")?,
+                intrvl_fn: exports.get_typed_function(&store, "intrvl")?,
+                store,
+            })
+        }
+
+        pub fn f64(&mut self, x: f64, y: f64) -> f64 {
+            self.f64_fn.call(&mut self.store, x, y).unwrap()
+        }
+
+        pub fn f64x2(&mut self, x: F64X2, y: F64X2) -> F64X2 {
+            let (a, b) = (self.f64x2_fn)
+                .call(&mut self.store, x.0, x.1, y.0, y.1)
+                .unwrap();
+            F64X2(a, b)
+        }
+
+        pub fn intrvl(&mut self, x: F64X2, y: F64X2) -> F64X2 {
+            let (lo, hi) = (self.intrvl_fn)
+                .call(&mut self.store, x.0, x.1, y.0, y.1)
+                .unwrap();
+            F64X2(lo, hi)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::OnceLock;
+
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    use super::*;
+    use crate::jit::random;
+    use utils::native::{self, NativeFn};
+
+    fn cube() -> NativeId {
+        static CUBE: OnceLock<NativeId> = OnceLock::new();
+        *CUBE.get_or_init(|| {
+            native::register(NativeFn::new(
+                "wasm_test_cube",
+                |x| x * x * x,
+                |x| 3.0 * x * x,
+                |lo, hi| (lo * lo * lo, hi * hi * hi),
+            ))
+        })
+    }
+
+    /// a random program where some unary ops call [`cube`]
+    fn random_program(rng: &mut impl Rng, len: usize) -> Program {
+        let mut program = random::program(rng, len, 4);
+        for instr in &mut program.bytecode {
+            if let Instr::UnOp { op, .. } = instr
+                && rng.random_bool(0.1)
+            {
+                *op = UnOp::CALL(cube());
+            }
+        }
+        program
+    }
+
+    fn exports(bytes: &[u8]) -> Vec<(String, usize, usize)> {
+        let module = walrus::Module::from_buffer(bytes).unwrap();
+        module
+            .exports
+            .iter()
+            .map(|e| {
+                let walrus::ExportItem::Function(f) = e.item else {
+                    panic!("{} is not a function", e.name)
+                };
+                let ty = module.types.get(module.funcs.get(f).ty());
+                (e.name.clone(), ty.params().len(), ty.results().len())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn validate() {
+        let program = Program::from(
+            bytecode![
+                SIN[0] -> 2,
+                POW[2, imm(2.0)] -> 2,
+                MIN[2, 1] -> 0,
+            ]
+            .to_vec(),
+        );
+        let expected = [("f64", 2, 1), ("f64x2", 4, 2), ("intrvl", 4, 2)]
+            .map(|(name, params, results)| (name.to_string(), params, results));
+        assert_eq!(exports(&compile(&program)), expected);
+    }
+
+    #[test]
+    fn validate_random() {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        for _ in 0..200 {
+            let len = rng.random_range(1..16);
+            let program = random_program(&mut rng, len);
+            assert_eq!(exports(&compile(&program)).len(), 3, "{program}");
+        }
+    }
+
+    #[cfg(feature = "wasmer")]
+    fn same(a: f64, b: f64) -> bool {
+        a.to_bits() == b.to_bits() || a.is_nan() && b.is_nan()
+    }
+
+    #[cfg(feature = "wasmer")]
+    #[test]
+    fn cmp_jit2() {
+        use crate::jit2::{F64X2, Intrvl, JIT};
+
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        let jit = JIT::init();
+        for i in 0..100 {
+            let program = random_program(&mut rng, 12);
+            let f64_fn = jit.compile::<f64>(&format!("f64_{i}"), &program, 2);
+            let f64x2_fn = jit.compile::<F64X2>(&format!("f64x2_{i}"), &program, 2);
+            let intrvl_fn = jit.compile::<Intrvl>(&format!("intrvl_{i}"), &program, 2);
+            let mut wasm = WasmModule::new(&program).unwrap();
+
+            let mut pair = || F64X2(rng.random_range(-4.0..4.0), rng.random_range(-4.0..4.0));
+            let (x, y) = (pair(), pair());
+
+            let expected = f64_fn.eval(&[x.0, y.0]);
+            assert!(same(wasm.f64(x.0, y.0), expected), "{program}");
+
+            let expected = f64x2_fn.eval(&[x, y]);
+            let res = wasm.f64x2(x, y);
+            assert!(
+                same(res.0, expected.0) && same(res.1, expected.1),
+                "{program}"
+            );
+
+            let x = F64X2(x.0.min(x.1), x.0.max(x.1));
+            let y = F64X2(y.0.min(y.1), y.0.max(y.1));
+            let expected = intrvl_fn.eval(&[Intrvl::new(x.0, x.1), Intrvl::new(y.0, y.1)]);
+            let expected = F64X2(expected.lo, expected.hi);
+            let res = wasm.intrvl(x, y);
+            assert!(
+                same(res.0, expected.0) && same(res.1, expected.1),
+                "{program}\n{res} != {expected}"
+            );
+        }
+    }
+}
@@ -0,0 +1,674 @@
+//! wgsl backend
+//!
+//! compiles a [`Program`] to wgsl source defining:
+//! - `fn f(x: f32, y: f32) -> f32`
+//! - `fn f_intrvl(x: vec2<f32>, y: vec2<f32>) -> vec2<f32>`, intervals stored as (lo, hi)
+//!
+//! the interval functions follow [`crate::jit2::Intrvl`], empty intervals are (nan, nan).
+//! they compute in f32 with the rounding of the gpu and do not round outward, so `f_intrvl` is
+//! an estimate of the range and not a guaranteed enclosure like the jit intervals. the tests
+//! only check that naga accepts the generated source, not that it matches the jit.
+//! [`fragment_shader`] wraps the source in a full screen pass rendering the zero set per pixel.
+//! the natives of [`utils::native`] run on the host and evaluate to nan and undef on the gpu.
+
+use std::fmt::Write;
+
+use crate::jit::{BinOp, Instr, Oprnd, Program, Reg, UnOp};
+
+const PRELUDE: &str = r#"
+const PI: f32 = 3.14159265358979;
+const HALF_PI: f32 = 1.57079632679490;
+const TWO_PI: f32 = 6.28318530717959;
+// the branch point of the lambert w function is -1 / e
+const INV_E: f32 = 0.367879441171442;
+// (x, gamma(x)) at the minimum of gamma for x > 0
+const GAMMA_MIN: vec2<f32> = vec2(1.46163214496836, 0.885603194410889);
+// the minimum of J0 and the maximum of J1, rounded outward
+const J0_MIN: f32 = -0.40276;
+const J1_MAX: f32 = 0.58187;
+
+// not representable as constant expressions
+fn nan() -> f32 {
+    return bitcast<f32>(0x7fc00000u);
+}
+
+fn inf() -> f32 {
+    return bitcast<f32>(0x7f800000u);
+}
+
+fn undef() -> vec2<f32> {
+    return vec2(nan());
+}
+
+fn is_int(v: f32) -> bool {
+    return v == round(v);
+}
+
+// wgsl pow is undefined for negative bases
+fn pow_f32(a: f32, b: f32) -> f32 {
+    if a >= 0.0 {
+        return pow(a, b);
+    } else if !is_int(b) {
+        return nan();
+    }
+    let p = pow(-a, b);
+    return select(p, -p, abs(b % 2.0) == 1.0);
+}
+
+fn is_empty(a: vec2<f32>) -> bool {
+    return a.x != a.x || a.y != a.y;
+}
+
+fn contains_zero(a: vec2<f32>) -> bool {
+    return a.x <= 0.0 && 0.0 <= a.y;
+}
+
+fn is_const_int(a: vec2<f32>) -> bool {
+    return a.x == a.y && is_int(a.x);
+}
+
+fn rem_euclid(a: f32, b: f32) -> f32 {
+    return a - b * floor(a / b);
+}
+
+fn contains_angle(l: f32, u: f32, v: f32) -> bool {
+    return (l <= v && v <= u) || (l > u && (v >= l || v <= u));
+}
+
+fn min_max(a: f32, b: f32) -> vec2<f32> {
+    return vec2(min(a, b), max(a, b));
+}
+
+fn intrvl(lo: f32, hi: f32) -> vec2<f32> {
+    if lo > hi {
+        return vec2((lo + hi) / 2.0);
+    }
+    return vec2(lo, hi);
+}
+
+fn add_intrvl(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    if is_empty(a) || is_empty(b) {
+        return undef();
+    }
+    return intrvl(a.x + b.x, a.y + b.y);
+}
+
+fn sub_intrvl(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    if is_empty(a) || is_empty(b) {
+        return undef();
+    }
+    return intrvl(a.x - b.y, a.y - b.x);
+}
+
+fn mul_intrvl(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    if is_empty(a) || is_empty(b) {
+        return undef();
+    }
+    let l = min_max(a.x * b.x, a.x * b.y);
+    let u = min_max(a.y * b.x, a.y * b.y);
+    let res = vec2(min(l.x, u.x), max(l.y, u.y));
+    if is_empty(res) {
+        return undef();
+    }
+    return intrvl(res.x, res.y);
+}
+
+fn div_intrvl(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    var denom: vec2<f32>;
+    if !contains_zero(b) {
+        denom = vec2(1.0 / b.y, 1.0 / b.x);
+    } else if b.y == 0.0 {
+        denom = vec2(-inf(), 1.0 / b.x);
+    } else if b.x == 0.0 {
+        denom = vec2(1.0 / b.y, inf());
+    } else {
+        return undef();
+    }
+    return mul_intrvl(a, intrvl(denom.x, denom.y));
+}
+
+fn pow_intrvl(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    if a.x > 0.0 {
+        let l = min_max(pow(a.x, b.x), pow(a.y, b.y));
+        let u = min_max(pow(a.y, b.x), pow(a.x, b.y));
+        return vec2(min(l.x, u.x), max(l.y, u.y));
+    } else if a.x >= 0.0 {
+        if contains_zero(b) {
+            return undef();
+        }
+        return intrvl(0.0, pow(a.y, b.y));
+    } else if !is_const_int(b) {
+        return undef();
+    } else if a.y < 0.0 {
+        return min_max(pow_f32(a.y, b.x), pow_f32(a.x, b.x));
+    }
+    let m = pow_f32(max(abs(a.x), a.y), b.x);
+    if abs(b.x % 2.0) == 0.0 {
+        return intrvl(0.0, m);
+    }
+    return intrvl(pow_f32(a.x, b.x), m);
+}
+
+fn sin_intrvl(a: vec2<f32>) -> vec2<f32> {
+    if is_empty(a) {
+        return undef();
+    } else if a.y - a.x >= TWO_PI {
+        return vec2(-1.0, 1.0);
+    }
+    let l = rem_euclid(a.x, TWO_PI);
+    let u = rem_euclid(a.y, TWO_PI);
+    let lo = select(min(sin(l), sin(u)), -1.0, contains_angle(l, u, 3.0 * HALF_PI));
+    let hi = select(max(sin(l), sin(u)), 1.0, contains_angle(l, u, HALF_PI));
+    return intrvl(lo, hi);
+}
+
+fn cos_intrvl(a: vec2<f32>) -> vec2<f32> {
+    if is_empty(a) {
+        return undef();
+    } else if a.y - a.x >= TWO_PI {
+        return vec2(-1.0, 1.0);
+    }
+    let l = rem_euclid(a.x, TWO_PI);
+    let u = rem_euclid(a.y, TWO_PI);
+    let lo = select(min(cos(l), cos(u)), -1.0, contains_angle(l, u, PI));
+    let hi = select(max(cos(l), cos(u)), 1.0, contains_angle(l, u, 0.0));
+    return intrvl(lo, hi);
+}
+
+fn tan_intrvl(a: vec2<f32>) -> vec2<f32> {
+    if is_empty(a) || a.y - a.x >= PI {
+        return undef();
+    }
+    let l = rem_euclid(a.x, PI);
+    let u = rem_euclid(a.y, PI);
+    if contains_angle(l, u, HALF_PI) {
+        return undef();
+    }
+    return intrvl(min(tan(a.x), tan(a.y)), max(tan(a.x), tan(a.y)));
+}
+
+// [0, 1] if an integer lies inside
+fn fract_intrvl(a: vec2<f32>) -> vec2<f32> {
+    if is_empty(a) {
+        return undef();
+    } else if floor(a.x) != floor(a.y) {
+        return vec2(0.0, 1.0);
+    }
+    return a - floor(a.x);
+}
+
+// a - b floor(a / b), only exact for a constant b without a jump inside a
+fn mod_intrvl(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    if is_empty(a) || is_empty(b) || contains_zero(b) {
+        return undef();
+    }
+    let k = floor(a.x / b.x);
+    if b.x == b.y && k == floor(a.y / b.x) {
+        return a - b.x * k;
+    }
+    return vec2(min(b.x, 0.0), max(b.y, 0.0));
+}
+
+// special functions in f32, see utils::special for the f64 versions
+
+// lanczos approximation with g = 5 for x >= 0.5
+fn gamma_pos(x: f32) -> f32 {
+    let z = x - 1.0;
+    var s = 1.000000000190015;
+    s += 76.18009172947146 / (z + 1.0);
+    s -= 86.50532032941677 / (z + 2.0);
+    s += 24.01409824083091 / (z + 3.0);
+    s -= 1.231739572450155 / (z + 4.0);
+    s += 0.1208650973866179e-2 / (z + 5.0);
+    s -= 0.5395239384953e-5 / (z + 6.0);
+    let t = z + 5.5;
+    return sqrt(TWO_PI) * pow(t, z + 0.5) * exp(-t) * s;
+}
+
+fn gamma_f32(x: f32) -> f32 {
+    if x <= 0.0 && is_int(x) {
+        return nan();
+    } else if x < 0.5 {
+        // reflection, gamma(x) gamma(1 - x) = pi / sin(pi x)
+        return PI / (sin(PI * x) * gamma_pos(1.0 - x));
+    }
+    return gamma_pos(x);
+}
+
+// abramowitz and stegun 7.1.26
+fn erf_f32(x: f32) -> f32 {
+    let t = 1.0 / (1.0 + 0.3275911 * abs(x));
+    let p = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    return sign(x) * (1.0 - p * exp(-x * x));
+}
+
+// (J0(x), J1(x)), power series below 8, hankel's expansion with two terms above
+fn bessel_f32(x: f32) -> vec2<f32> {
+    let a = abs(x);
+    var j: vec2<f32>;
+    if a < 8.0 {
+        let q = -0.25 * a * a;
+        var t = vec2(1.0, 0.5 * a);
+        j = t;
+        for (var k = 1.0; k < 30.0; k += 1.0) {
+            t *= q / vec2(k * k, k * (k + 1.0));
+            j += t;
+        }
+    } else {
+        let z = 1.0 / (8.0 * a);
+        let p = vec2(1.0 - 4.5 * z * z, 1.0 + 7.5 * z * z);
+        let q = vec2(-z, 3.0 * z);
+        let chi = a - vec2(0.25, 0.75) * PI;
+        j = sqrt(2.0 / (PI * a)) * (p * cos(chi) - q * sin(chi));
+    }
+    return vec2(j.x, select(j.y, -j.y, x < 0.0));
+}
+
+fn j0_f32(x: f32) -> f32 {
+    return bessel_f32(x).x;
+}
+
+fn j1_f32(x: f32) -> f32 {
+    return bessel_f32(x).y;
+}
+
+// principal branch, halley's method
+fn lambert_w_f32(x: f32) -> f32 {
+    if x < -INV_E {
+        return nan();
+    }
+    var w: f32;
+    if x < -0.25 {
+        // series around the branch point
+        let p = sqrt(max(2.0 * (x / INV_E + 1.0), 0.0));
+        w = -1.0 + p - p * p / 3.0;
+        if p < 1e-3 {
+            return w;
+        }
+    } else if x < 3.0 {
+        w = log(1.0 + x);
+    } else {
+        w = log(x) - log(log(x));
+    }
+    for (var i = 0; i < 6; i++) {
+        let ew = exp(w);
+        let f = w * ew - x;
+        w -= f / (ew * (w + 1.0) - (w + 2.0) * f / (2.0 * w + 2.0));
+    }
+    return w;
+}
+
+// |gamma| is log convex between the poles, so its maximum lies on the boundary
+fn gamma_intrvl(a: vec2<f32>) -> vec2<f32> {
+    if is_empty(a) || floor(min(a.y, 0.0)) >= a.x {
+        return undef();
+    }
+    let v = min_max(gamma_f32(a.x), gamma_f32(a.y));
+    if a.x > 0.0 {
+        if a.x < GAMMA_MIN.x && GAMMA_MIN.x < a.y {
+            return vec2(GAMMA_MIN.y, v.y);
+        }
+        return v;
+    } else if v.x > 0.0 {
+        return vec2(0.0, v.y);
+    }
+    return vec2(v.x, 0.0);
+}
+
+fn erf_intrvl(a: vec2<f32>) -> vec2<f32> {
+    if is_empty(a) {
+        return undef();
+    }
+    return intrvl(erf_f32(a.x), erf_f32(a.y));
+}
+
+// |f'| <= l keeps f within (f(a.x) + f(a.y) -+ l (a.y - a.x)) / 2
+fn lipschitz_intrvl(fa: f32, fb: f32, l: f32, a: vec2<f32>) -> vec2<f32> {
+    let w = l * (a.y - a.x);
+    return 0.5 * vec2(fa + fb - w, fa + fb + w);
+}
+
+fn j0_intrvl(a: vec2<f32>) -> vec2<f32> {
+    if is_empty(a) {
+        return undef();
+    }
+    let j = lipschitz_intrvl(j0_f32(a.x), j0_f32(a.y), J1_MAX, a);
+    return clamp(j, vec2(J0_MIN), vec2(1.0));
+}
+
+fn j1_intrvl(a: vec2<f32>) -> vec2<f32> {
+    if is_empty(a) {
+        return undef();
+    }
+    let j = lipschitz_intrvl(j1_f32(a.x), j1_f32(a.y), 0.5, a);
+    return clamp(j, vec2(-J1_MAX), vec2(J1_MAX));
+}
+
+fn lambert_w_intrvl(a: vec2<f32>) -> vec2<f32> {
+    if is_empty(a) || a.y < -INV_E {
+        return undef();
+    }
+    return intrvl(lambert_w_f32(max(a.x, -INV_E)), lambert_w_f32(a.y));
+}
+
+// 1 if a < b for every pair, 0 if for none
+fn lt_intrvl(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    return vec2(select(0.0, 1.0, a.y < b.x), select(0.0, 1.0, a.x < b.y));
+}
+
+fn le_intrvl(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    return vec2(select(0.0, 1.0, a.y <= b.x), select(0.0, 1.0, a.x <= b.y));
+}
+
+// the hull of both sides if the condition is undecided
+fn select_intrvl(c: vec2<f32>, a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    if c.x > 0.0 || c.y < 0.0 {
+        return a;
+    } else if c.x == 0.0 && c.y == 0.0 {
+        return b;
+    }
+    return vec2(min(a.x, b.x), max(a.y, b.y));
+}
+"#;
+
+const RENDER: &str = r#"
+struct View {
+    min: vec2<f32>,
+    max: vec2<f32>,
+    color: vec4<f32>,
+    // line thickness in pixels
+    thickness: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> view: View;
+
+struct FsIn {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) indx: u32) -> FsIn {
+    // full screen triangle
+    let uv = vec2(f32((indx << 1u) & 2u), f32(indx & 2u));
+
+    var out: FsIn;
+    out.pos = vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@fragment
+fn fs_main(in: FsIn) -> @location(0) vec4<f32> {
+    let p = mix(view.min, view.max, in.uv);
+    let d = f(p.x, p.y);
+
+    // distance to the zero set in pixels, d / |grad d|
+    let grad = length(vec2(dpdx(d), dpdy(d)));
+    // a sign change inside the pixel quad always counts as a hit
+    let sign_change = fwidth(sign(d)) > 0.0;
+
+    if d != d {
+        discard;
+    }
+
+    var dist = abs(d) / max(grad, 1e-20);
+    if sign_change {
+        dist = min(dist, 0.5);
+    }
+    let alpha = clamp(0.5 * view.thickness + 0.5 - dist, 0.0, 1.0);
+    return vec4(view.color.rgb, view.color.a * alpha);
+}
+"#;
+
+fn literal(v: f64) -> String {
+    let v = v as f32;
+    if v.is_nan() {
+        "nan()".into()
+    } else if v.is_infinite() {
+        format!("({}inf())", if v < 0.0 { "-" } else { "" })
+    } else {
+        format!("({v:?}f)")
+    }
+}
+
+/// highest register used by the program + 1
+fn n_regs(program: &Program) -> Reg {
+    let reg = |o: Oprnd| match o {
+        Oprnd::Reg(r) => r,
+        Oprnd::Imm(_) => 0,
+    };
+    program
+        .bytecode
+        .iter()
+        .map(|&i| match i {
+            Instr::UnOp { val, dst, .. } => reg(val).max(dst),
+            Instr::BinOp { lhs, rhs, dst, .. } => reg(lhs).max(reg(rhs)).max(dst),
+            Instr::Select {
+                cond,
+                lhs,
+                rhs,
+                dst,
+            } => reg(cond).max(reg(lhs)).max(reg(rhs)).max(dst),
+        })
+        .chain(program.outputs.iter().copied())
+        .max()
+        .unwrap_or(0)
+        .max(1)
+        + 1
+}
+
+fn f32_fn(program: &Program, src: &mut String) -> std::fmt::Result {
+    let oprnd = |o: Oprnd| match o {
+        Oprnd::Reg(r) => format!("r{r}"),
+        Oprnd::Imm(v) => literal(v),
+    };
+
+    writeln!(src, "fn f(x: f32, y: f32) -> f32 {{")?;
+    writeln!(src, "    var r0 = x;")?;
+    writeln!(src, "    var r1 = y;")?;
+    for r in 2..n_regs(program) {
+        writeln!(src, "    var r{r} = nan();")?;
+    }
+
+    for &instr in &program.bytecode {
+        match instr {
+            Instr::UnOp { op, val, dst } => {
+                let val = oprnd(val);
+                match op {
+                    UnOp::MOV => writeln!(src, "    r{dst} = {val};")?,
+                    UnOp::SIN => writeln!(src, "    r{dst} = sin({val});")?,
+                    UnOp::COS => writeln!(src, "    r{dst} = cos({val});")?,
+                    UnOp::TAN => writeln!(src, "    r{dst} = tan({val});")?,
+                    UnOp::FLOOR => writeln!(src, "    r{dst} = floor({val});")?,
+                    UnOp::CEIL => writeln!(src, "    r{dst} = ceil({val});")?,
+                    UnOp::ROUND => writeln!(src, "    r{dst} = round({val});")?,
+                    UnOp::FRACT => writeln!(src, "    r{dst} = {val} - floor({val});")?,
+                    op @ (UnOp::GAMMA | UnOp::ERF | UnOp::J0 | UnOp::J1 | UnOp::LAMBERTW) => {
+                        let f = op.special().unwrap();
+                        writeln!(src, "    r{dst} = {f}_f32({val});")?
+                    }
+                    UnOp::CALL(_) => writeln!(src, "    r{dst} = nan();")?,
+                }
+            }
+            Instr::BinOp { op, lhs, rhs, dst } => {
+                let (lhs, rhs) = (oprnd(lhs), oprnd(rhs));
+                match op {
+                    BinOp::ADD => writeln!(src, "    r{dst} = {lhs} + {rhs};")?,
+                    BinOp::SUB => writeln!(src, "    r{dst} = {lhs} - {rhs};")?,
+                    BinOp::MUL => writeln!(src, "    r{dst} = {lhs} * {rhs};")?,
+                    BinOp::DIV => writeln!(src, "    r{dst} = {lhs} / {rhs};")?,
+                    BinOp::POW => writeln!(src, "    r{dst} = pow_f32({lhs}, {rhs});")?,
+                    BinOp::MIN => writeln!(src, "    r{dst} = min({lhs}, {rhs});")?,
+                    BinOp::MAX => writeln!(src, "    r{dst} = max({lhs}, {rhs});")?,
+                    BinOp::LT => writeln!(src, "    r{dst} = select(0.0, 1.0, {lhs} < {rhs});")?,
+                    BinOp::LE => writeln!(src, "    r{dst} = select(0.0, 1.0, {lhs} <= {rhs});")?,
+                    BinOp::MOD => writeln!(src, "    r{dst} = rem_euclid({lhs}, {rhs});")?,
+                }
+            }
+            Instr::Select {
+                cond,
+                lhs,
+                rhs,
+                dst,
+            } => {
+                let (cond, lhs, rhs) = (oprnd(cond), oprnd(lhs), oprnd(rhs));
+                writeln!(src, "    r{dst} = select({rhs}, {lhs}, {cond} != 0.0);")?;
+            }
+        }
+    }
+
+    writeln!(src, "    return r{};", program.outputs[0])?;
+    writeln!(src, "}}")
+}
+
+fn intrvl_fn(program: &Program, src: &mut String) -> std::fmt::Result {
+    let oprnd = |o: Oprnd| match o {
+        Oprnd::Reg(r) => format!("r{r}"),
+        Oprnd::Imm(v) => format!("vec2({})", literal(v)),
+    };
+
+    writeln!(
+        src,
+        "fn f_intrvl(x: vec2<f32>, y: vec2<f32>) -> vec2<f32> {{"
+    )?;
+    writeln!(src, "    var r0 = x;")?;
+    writeln!(src, "    var r1 = y;")?;
+    for r in 2..n_regs(program) {
+        writeln!(src, "    var r{r} = undef();")?;
+    }
+
+    for &instr in &program.bytecode {
+        match instr {
+            Instr::UnOp { op, val, dst } => {
+                let val = oprnd(val);
+                match op {
+                    UnOp::MOV => writeln!(src, "    r{dst} = {val};")?,
+                    UnOp::SIN => writeln!(src, "    r{dst} = sin_intrvl({val});")?,
+                    UnOp::COS => writeln!(src, "    r{dst} = cos_intrvl({val});")?,
+                    UnOp::TAN => writeln!(src, "    r{dst} = tan_intrvl({val});")?,
+                    UnOp::FLOOR => writeln!(src, "    r{dst} = floor({val});")?,
+                    UnOp::CEIL => writeln!(src, "    r{dst} = ceil({val});")?,
+                    UnOp::ROUND => writeln!(src, "    r{dst} = round({val});")?,
+                    UnOp::FRACT => writeln!(src, "    r{dst} = fract_intrvl({val});")?,
+                    op @ (UnOp::GAMMA | UnOp::ERF | UnOp::J0 | UnOp::J1 | UnOp::LAMBERTW) => {
+                        let f = op.special().unwrap();
+                        writeln!(src, "    r{dst} = {f}_intrvl({val});")?
+                    }
+                    UnOp::CALL(_) => writeln!(src, "    r{dst} = undef();")?,
+                }
+            }
+            Instr::BinOp { op, lhs, rhs, dst } => {
+                let (lhs, rhs) = (oprnd(lhs), oprnd(rhs));
+                let f = match op {
+                    BinOp::ADD => "add_intrvl",
+                    BinOp::SUB => "sub_intrvl",
+                    BinOp::MUL => "mul_intrvl",
+                    BinOp::DIV => "div_intrvl",
+                    BinOp::POW => "pow_intrvl",
+                    BinOp::MIN => "min",
+                    BinOp::MAX => "max",
+                    BinOp::LT => "lt_intrvl",
+                    BinOp::LE => "le_intrvl",
+                    BinOp::MOD => "mod_intrvl",
+                };
+                writeln!(src, "    r{dst} = {f}({lhs}, {rhs});")?;
+            }
+            Instr::Select {
+                cond,
+                lhs,
+                rhs,
+                dst,
+            } => {
+                let (cond, lhs, rhs) = (oprnd(cond), oprnd(lhs), oprnd(rhs));
+                writeln!(src, "    r{dst} = select_intrvl({cond}, {lhs}, {rhs});")?;
+            }
+        }
+    }
+
+    writeln!(src, "    return r{};", program.outputs[0])?;
+    writeln!(src, "}}")
+}
+
+/// wgsl source defining `f` and `f_intrvl` together with their helper functions
+pub fn compile(program: &Program) -> String {
+    let mut src = PRELUDE.to_string();
+    src.push('\n');
+    f32_fn(program, &mut src).unwrap();
+    src.push('\n');
+    intrvl_fn(program, &mut src).unwrap();
+    src
+}
+
+/// shader drawing the zero set of the program with entry points `vs_main` and `fs_main`
+///
+/// the vertex stage emits a full screen triangle from 3 vertices without buffers,
+/// the `View` uniform at group 0 binding 0 maps the screen to (min, max) in world space.
+/// lines are antialiased using the screen space gradient of `f`.
+pub fn fragment_shader(program: &Program) -> String {
+    let mut src = compile(program);
+    src.push_str(RENDER);
+    src
+}
+
+#[cfg(test)]
+mod test {
+    use naga::valid::{Capabilities, ValidationFlags, Validator};
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+    use crate::jit::random;
+
+    fn validate(src: &str) {
+        let module = match naga::front::wgsl::parse_str(src) {
+            Ok(module) => module,
+            Err(err) => panic!("{}\n{src}", err.emit_to_string(src)),
+        };
+        if let Err(err) =
+            Validator::new(ValidationFlags::all(), Capabilities::all()).validate(&module)
+        {
+            panic!("{}\n{src}", err.emit_to_string(src));
+        }
+    }
+
+    #[test]
+    fn literals() {
+        let ops = [BinOp::ADD, BinOp::SUB, BinOp::MUL, BinOp::DIV, BinOp::POW];
+        let imms = [f64::INFINITY, f64::NEG_INFINITY, f64::NAN, -1e-30, 1e30];
+        let bytecode: Vec<_> = ops
+            .into_iter()
+            .zip(imms)
+            .map(|(op, v)| Instr::BinOp {
+                op,
+                lhs: Oprnd::Reg(0),
+                rhs: Oprnd::Imm(v),
+                dst: 0,
+            })
+            .collect();
+        let program = Program::from(bytecode);
+        validate(&compile(&program));
+    }
+
+    #[test]
+    fn validate_random() {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        for _ in 0..50 {
+            validate(&compile(&random::program(&mut rng, 12, 4)));
+        }
+    }
+
+    #[test]
+    fn validate_fragment() {
+        let program = Program::from(
+            bytecode![
+                MUL[0, 0] -> 2,
+                MUL[1, 1] -> 3,
+                ADD[2, 3] -> 0,
+                SUB[0, imm(1.0)] -> 0,
+            ]
+            .to_vec(),
+        );
+        validate(&fragment_shader(&program));
+    }
+}
@@ -0,0 +1,256 @@
+//! line based text format shared by the assemblers of `compiler::jit::Program` and
+//! `atlas::vm::Opcode`
+//!
+//! ```text
+//! # comment
+//! reg x = 1
+//! reg y = 2
+//! MUL[x, y] -> 3
+//! ADD[3, 0.5_f] -> 1
+//! EXT
+//! ```
+//!
+//! an instruction is `OP[operands] -> dst`, the brackets can be omitted without operands and
+//! the destination without output. operands are register numbers, register labels declared
+//! with `reg` or immediates with the suffix `_f`. every other line starting with a lower case
+//! word is a directive, e.g. `ret 0, 1`
+
+use std::fmt;
+
+/// error of the line `line`, counted from 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.msg)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+impl AsmError {
+    pub fn new(line: usize, msg: impl Into<String>) -> Self {
+        Self {
+            line,
+            msg: msg.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operand {
+    Reg(u32),
+    Imm(f64),
+}
+
+impl Operand {
+    /// the register, errors on immediates
+    pub fn reg(self, line: usize) -> Result<u32, AsmError> {
+        match self {
+            Operand::Reg(r) => Ok(r),
+            Operand::Imm(i) => Err(AsmError::new(line, format!("expected register, got {i}_f"))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Line<'a> {
+    Instr {
+        op: &'a str,
+        args: Vec<Operand>,
+        dst: Option<u32>,
+    },
+    /// a directive other than `reg`, with its comma separated operands
+    Directive { name: &'a str, args: Vec<Operand> },
+}
+
+/// names of registers, printed instead of their number
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Labels {
+    names: Vec<(String, u32)>,
+}
+
+impl Labels {
+    pub fn new<S: Into<String>>(names: impl IntoIterator<Item = (S, u32)>) -> Self {
+        Self {
+            names: names.into_iter().map(|(n, r)| (n.into(), r)).collect(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<u32> {
+        self.names.iter().find(|(n, _)| n == name).map(|&(_, r)| r)
+    }
+
+    /// label of `reg` or its number
+    pub fn reg(&self, reg: u32) -> String {
+        match self.names.iter().find(|&&(_, r)| r == reg) {
+            Some((n, _)) => n.clone(),
+            None => reg.to_string(),
+        }
+    }
+}
+
+/// the `reg` directives declaring the labels
+impl fmt::Display for Labels {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, reg) in &self.names {
+            writeln!(f, "reg {name} = {reg}")?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn is_label(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn operand(s: &str, labels: &Labels, line: usize) -> Result<Operand, AsmError> {
+    let s = s.trim();
+    if let Some(Ok(imm)) = s.strip_suffix("_f").map(str::parse) {
+        return Ok(Operand::Imm(imm));
+    }
+    if let Ok(reg) = s.parse() {
+        return Ok(Operand::Reg(reg));
+    }
+    labels
+        .get(s)
+        .map(Operand::Reg)
+        .ok_or_else(|| AsmError::new(line, format!("unknown operand `{s}`")))
+}
+
+fn operands(s: &str, labels: &Labels, line: usize) -> Result<Vec<Operand>, AsmError> {
+    if s.trim().is_empty() {
+        return Ok(vec![]);
+    }
+    s.split(',').map(|o| operand(o, labels, line)).collect()
+}
+
+/// parses `src` into lines with line numbers and the labels declared with `reg`
+pub fn parse(src: &str) -> Result<(Vec<(usize, Line<'_>)>, Labels), AsmError> {
+    let mut labels = Labels::default();
+    let mut lines = vec![];
+
+    for (i, text) in src.lines().enumerate() {
+        let line = i + 1;
+        let text = text.split('#').next().unwrap().trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        let word_end = text
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(text.len());
+        let (word, rest) = text.split_at(word_end);
+
+        if word.starts_with(|c: char| c.is_ascii_lowercase()) {
+            if word == "reg" {
+                let (name, reg) = rest
+                    .split_once('=')
+                    .ok_or_else(|| AsmError::new(line, "expected `reg <name> = <register>`"))?;
+                let name = name.trim();
+                if !is_label(name) {
+                    return Err(AsmError::new(line, format!("invalid label `{name}`")));
+                }
+                let reg = reg
+                    .trim()
+                    .parse()
+                    .map_err(|_| AsmError::new(line, format!("invalid register `{reg}`")))?;
+                labels.names.push((name.to_string(), reg));
+            } else {
+                let args = operands(rest, &labels, line)?;
+                lines.push((line, Line::Directive { name: word, args }));
+            }
+            continue;
+        }
+
+        let (ops, dst) = match rest.split_once("->") {
+            Some((ops, dst)) => (ops, Some(operand(dst, &labels, line)?.reg(line)?)),
+            None => (rest, None),
+        };
+        let ops = ops.trim();
+        let args = match ops.strip_prefix('[') {
+            Some(ops) => {
+                let ops = ops
+                    .strip_suffix(']')
+                    .ok_or_else(|| AsmError::new(line, "missing `]`"))?;
+                operands(ops, &labels, line)?
+            }
+            None if ops.is_empty() => vec![],
+            None => return Err(AsmError::new(line, format!("unexpected `{ops}`"))),
+        };
+
+        lines.push((
+            line,
+            Line::Instr {
+                op: word,
+                args,
+                dst,
+            },
+        ));
+    }
+
+    Ok((lines, labels))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_lines() {
+        let src = "
+            # labels
+            reg x = 1
+            reg y_f = 2
+            MUL[x, y_f] -> 3  # product
+            ADD[3, -0.5_f] -> x
+            POP -> 4
+            EXT
+            ret 3, x
+        ";
+        let (lines, labels) = parse(src).unwrap();
+        assert_eq!(labels, Labels::new([("x", 1), ("y_f", 2)]));
+        assert_eq!(labels.reg(2), "y_f");
+        assert_eq!(labels.reg(5), "5");
+
+        let instr = |op, args: &[Operand], dst| Line::Instr {
+            op,
+            args: args.to_vec(),
+            dst,
+        };
+        let expected = [
+            (
+                5,
+                instr("MUL", &[Operand::Reg(1), Operand::Reg(2)], Some(3)),
+            ),
+            (
+                6,
+                instr("ADD", &[Operand::Reg(3), Operand::Imm(-0.5)], Some(1)),
+            ),
+            (7, instr("POP", &[], Some(4))),
+            (8, instr("EXT", &[], None)),
+            (
+                9,
+                Line::Directive {
+                    name: "ret",
+                    args: vec![Operand::Reg(3), Operand::Reg(1)],
+                },
+            ),
+        ];
+        assert_eq!(lines, expected);
+
+        let err = |src| parse(src).unwrap_err();
+        assert_eq!(err("SIN[z] -> 1"), AsmError::new(1, "unknown operand `z`"));
+        assert_eq!(err("\nSIN[1 -> 1").line, 2);
+        assert_eq!(err("SIN[1] -> 1_f").msg, "expected register, got 1_f");
+        assert_eq!(err("reg 1x = 2").msg, "invalid label `1x`");
+    }
+}
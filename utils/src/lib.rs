@@ -1,5 +1,9 @@
 use std::fmt;
 
+pub mod asm;
+pub mod native;
+pub mod special;
+
 pub trait ExplicitCopy: Copy {
     #[inline(always)]
     fn copy(&self) -> Self {
@@ -447,6 +451,109 @@ impl Intrvl {
         self.ln().mul(e).exp()
     }
 
+    /// min(x, y) = [min(x_min, y_min), min(x_max, y_max)]
+    #[inline]
+    pub const fn min(self, o: Self) -> Self {
+        Self::new(self.lo.min(o.lo), self.hi.min(o.hi))
+    }
+
+    /// max(x, y) = [max(x_min, y_min), max(x_max, y_max)]
+    #[inline]
+    pub const fn max(self, o: Self) -> Self {
+        Self::new(self.lo.max(o.lo), self.hi.max(o.hi))
+    }
+
+    /// smallest interval containing x and y
+    #[inline]
+    pub const fn hull(self, o: Self) -> Self {
+        Self::new(self.lo.min(o.lo), self.hi.max(o.hi))
+    }
+
+    /// x < y is 1 if it holds for every pair, 0 if for none and [0, 1] otherwise
+    #[inline]
+    pub const fn lt(self, o: Self) -> Self {
+        if self.is_valid() || o.is_valid() {
+            Self::UNDEF
+        } else if self.hi < o.lo {
+            Self::scalar(1.0)
+        } else if self.lo >= o.hi {
+            Self::scalar(0.0)
+        } else {
+            Self { lo: 0.0, hi: 1.0 }
+        }
+    }
+
+    /// x <= y, see [`Intrvl::lt`]
+    #[inline]
+    pub const fn le(self, o: Self) -> Self {
+        if self.is_valid() || o.is_valid() {
+            Self::UNDEF
+        } else if self.hi <= o.lo {
+            Self::scalar(1.0)
+        } else if self.lo > o.hi {
+            Self::scalar(0.0)
+        } else {
+            Self { lo: 0.0, hi: 1.0 }
+        }
+    }
+
+    /// x if self is not zero, y if it is. the hull of both if self is undecided
+    #[inline]
+    pub const fn select(self, x: Self, y: Self) -> Self {
+        if self.is_valid() {
+            Self::UNDEF
+        } else if self.lo > 0.0 || self.hi < 0.0 {
+            x
+        } else if self.lo == 0.0 && self.hi == 0.0 {
+            y
+        } else {
+            x.hull(y)
+        }
+    }
+
+    /// floor is monotonic, the jumps at the integers in between are not part of the interval
+    #[inline]
+    pub fn floor(self) -> Self {
+        Self::new(self.lo.floor(), self.hi.floor())
+    }
+
+    #[inline]
+    pub fn ceil(self) -> Self {
+        Self::new(self.lo.ceil(), self.hi.ceil())
+    }
+
+    /// rounds half to even
+    #[inline]
+    pub fn round(self) -> Self {
+        Self::new(self.lo.round_ties_even(), self.hi.round_ties_even())
+    }
+
+    /// x - floor(x), [0, 1] if an integer lies inside
+    #[inline]
+    pub fn fract(self) -> Self {
+        let k = self.lo.floor();
+        if self.is_valid() || k == self.hi.floor() {
+            Self::new(self.lo - k, self.hi - k)
+        } else {
+            Self { lo: 0.0, hi: 1.0 }
+        }
+    }
+
+    /// x - y floor(x / y), the sign follows y. between 0 and y if x / y crosses an integer
+    #[inline]
+    pub fn modulo(self, o: Self) -> Self {
+        if self.is_valid() || o.is_valid() || o.contains_zero() {
+            return Self::UNDEF;
+        }
+        if o.lo == o.hi {
+            let k = (self.lo / o.lo).floor();
+            if k == (self.hi / o.lo).floor() {
+                return Self::new(self.lo - o.lo * k, self.hi - o.lo * k);
+            }
+        }
+        Self::new(o.lo.min(0.0), o.hi.max(0.0))
+    }
+
     #[inline]
     pub fn sin(self) -> Self {
         use std::f64::consts;
@@ -570,6 +677,225 @@ impl Intrvl {
         let hi = self.hi.tan();
         Self { lo, hi }
     }
+
+    /// undefined if the interval leaves the domain of `f`, see [`special`] for the enclosures
+    #[inline]
+    pub fn special(self, f: special::Special) -> Self {
+        f.intrvl(self.lo, self.hi).into()
+    }
+
+    /// the enclosure registered with the native
+    #[inline]
+    pub fn native(self, f: native::NativeId) -> Self {
+        f.intrvl(self.lo, self.hi).into()
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[repr(C)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl From<(f64, f64)> for Complex {
+    fn from((re, im): (f64, f64)) -> Self {
+        Self::new(re, im)
+    }
+}
+
+impl From<f64> for Complex {
+    fn from(re: f64) -> Self {
+        Self::real(re)
+    }
+}
+
+impl fmt::Display for Complex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.im.is_sign_negative() {
+            write!(f, "{} - {}i", self.re, -self.im)
+        } else {
+            write!(f, "{} + {}i", self.re, self.im)
+        }
+    }
+}
+
+impl Complex {
+    pub const UNDEF: Self = Self {
+        re: f64::NAN,
+        im: f64::NAN,
+    };
+    pub const ZERO: Self = Self::real(0.0);
+    pub const ONE: Self = Self::real(1.0);
+    pub const I: Self = Self::new(0.0, 1.0);
+
+    #[inline]
+    pub const fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    #[inline]
+    pub const fn real(re: f64) -> Self {
+        Self { re, im: 0.0 }
+    }
+
+    #[inline]
+    pub const fn is_real(&self) -> bool {
+        self.im == 0.0
+    }
+
+    #[inline]
+    pub const fn is_finite(&self) -> bool {
+        self.re.is_finite() && self.im.is_finite()
+    }
+
+    /// modulus |z|
+    #[inline]
+    pub fn abs(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    /// argument in (-pi, pi]
+    #[inline]
+    pub fn arg(self) -> f64 {
+        self.im.atan2(self.re)
+    }
+
+    #[inline]
+    pub const fn conj(self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+
+    #[inline]
+    pub const fn add(self, o: Self) -> Self {
+        Self::new(self.re + o.re, self.im + o.im)
+    }
+
+    #[inline]
+    pub const fn sub(self, o: Self) -> Self {
+        Self::new(self.re - o.re, self.im - o.im)
+    }
+
+    /// (a + bi)(c + di) = (ac - bd) + (ad + bc)i
+    #[inline]
+    pub const fn mul(self, o: Self) -> Self {
+        Self::new(
+            self.re * o.re - self.im * o.im,
+            self.re * o.im + self.im * o.re,
+        )
+    }
+
+    /// (a + bi) / (c + di) = (a + bi)(c - di) / (c^2 + d^2)
+    #[inline]
+    pub const fn div(self, o: Self) -> Self {
+        let n = o.re * o.re + o.im * o.im;
+        let z = self.mul(o.conj());
+        Self::new(z.re / n, z.im / n)
+    }
+
+    /// exp(a + bi) = e^a (cos b + i sin b)
+    #[inline]
+    pub fn exp(self) -> Self {
+        let r = self.re.exp();
+        let (s, c) = self.im.sin_cos();
+        Self::new(r * c, r * s)
+    }
+
+    /// principal branch: ln|z| + i arg(z)
+    #[inline]
+    pub fn ln(self) -> Self {
+        Self::new(self.abs().ln(), self.arg())
+    }
+
+    /// z^n by repeated squaring
+    #[inline]
+    pub fn powi(self, n: i32) -> Self {
+        let mut base = if n < 0 { Self::ONE.div(self) } else { self };
+        let mut n = n.unsigned_abs();
+        let mut res = Self::ONE;
+        while n > 0 {
+            if n & 1 == 1 {
+                res = res.mul(base);
+            }
+            base = base.mul(base);
+            n >>= 1;
+        }
+        res
+    }
+
+    /// z^w = exp(w * ln(z)), small integer exponents are computed exactly
+    #[inline]
+    pub fn pow(self, e: Self) -> Self {
+        if e.is_real() && e.re.fract() == 0.0 && e.re.abs() <= 64.0 {
+            return self.powi(e.re as i32);
+        }
+        if self == Self::ZERO {
+            return if e.re > 0.0 { Self::ZERO } else { Self::UNDEF };
+        }
+        e.mul(self.ln()).exp()
+    }
+
+    /// sin(a + bi) = sin a cosh b + i cos a sinh b
+    #[inline]
+    pub fn sin(self) -> Self {
+        let (s, c) = self.re.sin_cos();
+        Self::new(s * self.im.cosh(), c * self.im.sinh())
+    }
+
+    /// cos(a + bi) = cos a cosh b - i sin a sinh b
+    #[inline]
+    pub fn cos(self) -> Self {
+        let (s, c) = self.re.sin_cos();
+        Self::new(c * self.im.cosh(), -s * self.im.sinh())
+    }
+
+    #[inline]
+    pub fn tan(self) -> Self {
+        self.sin().div(self.cos())
+    }
+
+    /// component wise
+    #[inline]
+    pub fn floor(self) -> Self {
+        Self::new(self.re.floor(), self.im.floor())
+    }
+
+    #[inline]
+    pub fn ceil(self) -> Self {
+        Self::new(self.re.ceil(), self.im.ceil())
+    }
+
+    #[inline]
+    pub fn round(self) -> Self {
+        Self::new(self.re.round_ties_even(), self.im.round_ties_even())
+    }
+
+    #[inline]
+    pub fn fract(self) -> Self {
+        self.sub(self.floor())
+    }
+
+    /// z - w floor(z / w), the remainder of the gaussian integer division
+    #[inline]
+    pub fn modulo(self, o: Self) -> Self {
+        self.sub(o.mul(self.div(o).floor()))
+    }
+
+    /// the special functions are only evaluated on the real axis
+    pub fn special(self, f: special::Special) -> Self {
+        match self.is_real() {
+            true => Self::real(f.eval(self.re)),
+            false => Self::UNDEF,
+        }
+    }
+
+    /// natives are only evaluated on the real axis
+    pub fn native(self, f: native::NativeId) -> Self {
+        match self.is_real() {
+            true => Self::real(f.eval(self.re)),
+            false => Self::UNDEF,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -605,4 +931,28 @@ mod test {
         let bits: Vec<_> = g.iter().collect();
         assert_eq!(&bits, &[(1, 1), (3, 1), (3, 3)], "{bits:?}");
     }
+
+    #[test]
+    fn complex_identities() {
+        let close = |a: Complex, b: Complex| (a.sub(b)).abs() < 1e-12;
+        let z = Complex::new(0.5, -1.25);
+        let w = Complex::new(-2.0, 0.75);
+
+        assert!(close(z.mul(w).div(w), z));
+        assert!(close(Complex::I.mul(Complex::I), Complex::real(-1.0)));
+        assert!(close(z.ln().exp(), z));
+        assert!(close(z.pow(Complex::real(3.0)), z.mul(z).mul(z)));
+        assert!(close(z.pow(Complex::real(-1.0)), Complex::ONE.div(z)));
+        assert!(close(z.pow(w), w.mul(z.ln()).exp()));
+
+        // sin^2 + cos^2 = 1
+        let (s, c) = (z.sin(), z.cos());
+        assert!(close(s.mul(s).add(c.mul(c)), Complex::ONE));
+        assert!(close(z.tan(), s.div(c)));
+
+        // e^(i pi) = -1
+        let e = Complex::new(0.0, std::f64::consts::PI).exp();
+        assert!(close(e, Complex::real(-1.0)));
+        assert_eq!(Complex::ZERO.pow(Complex::real(2.0)), Complex::ZERO);
+    }
 }
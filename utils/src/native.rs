@@ -0,0 +1,171 @@
+//! functions registered by the host and called by every evaluator
+//!
+//! a native function is a pure rust function with a point evaluation, its derivative and an
+//! interval enclosure, like the functions of [`crate::special`]. the registry only grows, so a
+//! [`NativeId`] stays valid for the lifetime of the process and compiled code can refer to it.
+//! ids are process local, programs refer to natives by name in their text format (`CALL_<name>`).
+
+use std::sync::RwLock;
+
+/// enclosure of the whole real line, used for missing derivative enclosures
+const UNBOUNDED: (f64, f64) = (f64::NEG_INFINITY, f64::INFINITY);
+
+static NATIVES: RwLock<Vec<NativeFn>> = RwLock::new(Vec::new());
+
+/// an interval enclosure over [lo, hi]
+pub type IntrvlFn = fn(f64, f64) -> (f64, f64);
+
+#[derive(Debug, Clone, Copy)]
+pub struct NativeFn {
+    /// a label, see [`crate::asm`]
+    pub name: &'static str,
+    pub f64: fn(f64) -> f64,
+    pub deriv: fn(f64) -> f64,
+    /// encloses `f64` over [lo, hi], (NaN, NaN) if the interval leaves the domain
+    pub intrvl: IntrvlFn,
+    /// encloses `deriv` over [lo, hi], unbounded if missing
+    pub deriv_intrvl: Option<IntrvlFn>,
+}
+
+impl NativeFn {
+    pub const fn new(
+        name: &'static str,
+        f64: fn(f64) -> f64,
+        deriv: fn(f64) -> f64,
+        intrvl: IntrvlFn,
+    ) -> Self {
+        Self {
+            name,
+            f64,
+            deriv,
+            intrvl,
+            deriv_intrvl: None,
+        }
+    }
+
+    pub const fn with_deriv_intrvl(mut self, deriv_intrvl: IntrvlFn) -> Self {
+        self.deriv_intrvl = Some(deriv_intrvl);
+        self
+    }
+}
+
+/// index into the registry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NativeId(u32);
+
+/// adds `f` to the registry
+///
+/// # Panics
+///
+/// if the name is not a label or already registered
+pub fn register(f: NativeFn) -> NativeId {
+    assert!(
+        crate::asm::is_label(f.name),
+        "native name `{}` is not a label",
+        f.name
+    );
+    let mut natives = NATIVES.write().unwrap();
+    if natives.iter().any(|n| n.name == f.name) {
+        // release the lock first, the panic would poison it
+        drop(natives);
+        panic!("native `{}` is already registered", f.name);
+    }
+    natives.push(f);
+    NativeId(natives.len() as u32 - 1)
+}
+
+/// the id of the native registered under `name`
+pub fn lookup(name: &str) -> Option<NativeId> {
+    let natives = NATIVES.read().unwrap();
+    natives
+        .iter()
+        .position(|n| n.name == name)
+        .map(|i| NativeId(i as u32))
+}
+
+impl NativeId {
+    /// None if no native was registered with the index
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        ((raw as usize) < NATIVES.read().unwrap().len()).then_some(Self(raw))
+    }
+
+    pub const fn to_raw(self) -> u32 {
+        self.0
+    }
+
+    /// the lock is released before the function is called, so natives may use the registry
+    pub fn get(self) -> NativeFn {
+        NATIVES.read().unwrap()[self.0 as usize]
+    }
+
+    pub fn name(self) -> &'static str {
+        self.get().name
+    }
+
+    pub fn eval(self, x: f64) -> f64 {
+        (self.get().f64)(x)
+    }
+
+    pub fn deriv(self, x: f64) -> f64 {
+        (self.get().deriv)(x)
+    }
+
+    pub fn intrvl(self, lo: f64, hi: f64) -> (f64, f64) {
+        (self.get().intrvl)(lo, hi)
+    }
+
+    pub fn deriv_intrvl(self, lo: f64, hi: f64) -> (f64, f64) {
+        match self.get().deriv_intrvl {
+            Some(f) => f(lo, hi),
+            None => UNBOUNDED,
+        }
+    }
+}
+
+impl std::fmt::Display for NativeId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn cube_intrvl(lo: f64, hi: f64) -> (f64, f64) {
+        (lo.powi(3), hi.powi(3))
+    }
+
+    #[test]
+    fn registry() {
+        let cube = NativeFn::new("test_cube", |x| x * x * x, |x| 3.0 * x * x, cube_intrvl);
+        let id = register(cube);
+        assert_eq!(lookup("test_cube"), Some(id));
+        assert_eq!(NativeId::from_raw(id.to_raw()), Some(id));
+        assert_eq!(lookup("test_missing"), None);
+        assert_eq!(id.to_string(), "test_cube");
+
+        assert_eq!(id.eval(2.0), 8.0);
+        assert_eq!(id.deriv(2.0), 12.0);
+        assert_eq!(id.intrvl(-1.0, 2.0), (-1.0, 8.0));
+        assert_eq!(id.deriv_intrvl(-1.0, 2.0), UNBOUNDED);
+
+        let double = NativeFn::new(
+            "test_double",
+            |x| 2.0 * x,
+            |_| 2.0,
+            |lo, hi| (2.0 * lo, 2.0 * hi),
+        )
+        .with_deriv_intrvl(|_, _| (2.0, 2.0));
+        let id = register(double);
+        assert_eq!(id.deriv_intrvl(1.0, 2.0), (2.0, 2.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "already registered")]
+    fn duplicate_name() {
+        let id = |x| x;
+        register(NativeFn::new("test_dup", id, |_| 1.0, |lo, hi| (lo, hi)));
+        register(NativeFn::new("test_dup", id, |_| 1.0, |lo, hi| (lo, hi)));
+    }
+}
@@ -0,0 +1,671 @@
+//! special functions shared by every evaluator
+//!
+//! each function has a point evaluation, its derivative and an interval enclosure `*_intrvl`
+//! returning (NaN, NaN) if the interval lies outside of the domain or contains a pole. the
+//! enclosures are widened by the error bound of the approximations, so they contain the exact
+//! range and not only the computed one.
+
+use std::f64::consts::{E, FRAC_2_SQRT_PI, PI};
+
+/// relative and absolute error bound of the approximations
+const ERR_REL: f64 = 1e-13;
+const ERR_ABS: f64 = 1e-14;
+
+/// (J0(x_min), 1) and (-J1(x_max), J1(x_max)) rounded outward
+const J0_RANGE: (f64, f64) = (-0.402_76, 1.0);
+const J1_RANGE: (f64, f64) = (-0.581_87, 0.581_87);
+
+/// the floats above are too coarse to locate the extrema of J0 and J1, their enclosures are
+/// the whole range
+const BESSEL_COARSE: f64 = 1e12;
+
+/// the branch point of the lambert w function
+const W_BRANCH: f64 = -1.0 / E;
+
+const UNDEF: (f64, f64) = (f64::NAN, f64::NAN);
+
+// lanczos approximation with g = 7, n = 9
+const LANCZOS_G: f64 = 7.0;
+const LANCZOS: [f64; 9] = [
+    0.999_999_999_999_809_9,
+    676.520_368_121_885_1,
+    -1_259.139_216_722_402_8,
+    771.323_428_777_653_1,
+    -176.615_029_162_140_6,
+    12.507_343_278_686_905,
+    -0.138_571_095_265_720_12,
+    9.984_369_578_019_572e-6,
+    1.505_632_735_149_311_6e-7,
+];
+
+/// sin(pi x) without the rounding error of pi x for large x
+fn sin_pi(x: f64) -> f64 {
+    let n = x.round();
+    let s = (PI * (x - n)).sin();
+    if n % 2.0 == 0.0 { s } else { -s }
+}
+
+/// the non positive integers
+fn is_pole(x: f64) -> bool {
+    x <= 0.0 && x == x.floor()
+}
+
+/// an integer <= 0 lies in [lo, hi]
+fn contains_pole(lo: f64, hi: f64) -> bool {
+    hi.floor().min(0.0) >= lo
+}
+
+pub fn gamma(x: f64) -> f64 {
+    if is_pole(x) {
+        return f64::NAN;
+    } else if x < 0.5 {
+        // reflection, gamma(x) gamma(1 - x) = pi / sin(pi x)
+        return PI / (sin_pi(x) * gamma(1.0 - x));
+    } else if x > 171.7 {
+        return f64::INFINITY;
+    }
+
+    let x = x - 1.0;
+    let t = x + LANCZOS_G + 0.5;
+    let sum = (1..LANCZOS.len()).fold(LANCZOS[0], |s, i| s + LANCZOS[i] / (x + i as f64));
+    // t^(x + 1/2) is split so it does not overflow before the exp(-t)
+    let p = t.powf(0.5 * (x + 0.5));
+    (2.0 * PI).sqrt() * p * ((-t).exp() * p) * sum
+}
+
+/// the logarithmic derivative gamma' / gamma
+pub fn digamma(x: f64) -> f64 {
+    if is_pole(x) {
+        return f64::NAN;
+    } else if x < 0.5 {
+        // reflection, psi(1 - x) - psi(x) = pi cot(pi x)
+        let r = x - x.round();
+        return digamma(1.0 - x) - PI / (PI * r).tan();
+    }
+
+    let mut x = x;
+    let mut acc = 0.0;
+    while x < 12.0 {
+        acc -= 1.0 / x;
+        x += 1.0;
+    }
+    let inv = 1.0 / (x * x);
+    let series = inv
+        * (1.0 / 12.0
+            - inv * (1.0 / 120.0 - inv * (1.0 / 252.0 - inv * (1.0 / 240.0 - inv / 132.0))));
+    acc + x.ln() - 0.5 / x - series
+}
+
+pub fn gamma_deriv(x: f64) -> f64 {
+    gamma(x) * digamma(x)
+}
+
+pub fn erf(x: f64) -> f64 {
+    let a = x.abs();
+    let r = if a < 2.5 {
+        // 2/sqrt(pi) e^(-x^2) sum 2^n x^(2n+1) / (1 3 5 ... (2n+1)), the terms are positive
+        let x2 = 2.0 * a * a;
+        let mut term = a;
+        let mut sum = a;
+        let mut n = 0.0;
+        while term > sum * 1e-17 {
+            n += 1.0;
+            term *= x2 / (2.0 * n + 1.0);
+            sum += term;
+        }
+        FRAC_2_SQRT_PI * (-a * a).exp() * sum
+    } else {
+        1.0 - erfc_cf(a)
+    };
+    r.copysign(x)
+}
+
+/// continued fraction of erfc for x >= 2.5, evaluated from the tail
+fn erfc_cf(x: f64) -> f64 {
+    let mut f = x;
+    for k in (1..64).rev() {
+        f = x + 0.5 * k as f64 / f;
+    }
+    (-x * x).exp() / (f * PI.sqrt())
+}
+
+pub fn erf_deriv(x: f64) -> f64 {
+    FRAC_2_SQRT_PI * (-x * x).exp()
+}
+
+/// bessel function of the first kind of order 0
+pub fn j0(x: f64) -> f64 {
+    bessel(x).0
+}
+
+/// bessel function of the first kind of order 1
+pub fn j1(x: f64) -> f64 {
+    bessel(x).1
+}
+
+pub fn j0_deriv(x: f64) -> f64 {
+    -j1(x)
+}
+
+pub fn j1_deriv(x: f64) -> f64 {
+    if x == 0.0 {
+        return 0.5;
+    }
+    let (j0, j1) = bessel(x);
+    j0 - j1 / x
+}
+
+/// x^2 J1'' + x J1' + (x^2 - 1) J1 = 0, only its sign is used
+fn j1_deriv2(x: f64) -> f64 {
+    if x.abs() < 1.0 {
+        let x2 = x * x;
+        return x * (-3.0 / 8.0 + 5.0 / 96.0 * x2);
+    }
+    -j1_deriv(x) / x - (1.0 - 1.0 / (x * x)) * j1(x)
+}
+
+/// (J0(x), J1(x))
+fn bessel(x: f64) -> (f64, f64) {
+    let a = x.abs();
+    let (j0, j1) = if a.is_nan() {
+        UNDEF
+    } else if a < 4.0 {
+        bessel_series(a)
+    } else if a < 25.0 {
+        bessel_miller(a)
+    } else {
+        bessel_hankel(a)
+    };
+    // J0 is even and J1 odd
+    (j0, if x < 0.0 { -j1 } else { j1 })
+}
+
+/// power series, the terms do not cancel for x < 4
+fn bessel_series(x: f64) -> (f64, f64) {
+    let q = -0.25 * x * x;
+    let (mut t0, mut t1) = (1.0, 0.5 * x);
+    let (mut j0, mut j1) = (t0, t1);
+    for k in 1..40 {
+        let k = k as f64;
+        t0 *= q / (k * k);
+        t1 *= q / (k * (k + 1.0));
+        j0 += t0;
+        j1 += t1;
+    }
+    (j0, j1)
+}
+
+/// backward recurrence J(n-1) = 2n/x J(n) - J(n+1) started far above x, normalized by
+/// J0 + 2 (J2 + J4 + ...) = 1
+fn bessel_miller(x: f64) -> (f64, f64) {
+    let start = 2 * ((x as usize + 15 + (40.0 * x).sqrt() as usize) / 2);
+    let (mut next, mut cur) = (0.0, 1e-30);
+    let mut sum = 0.0;
+    let mut j1 = 0.0;
+
+    for n in (1..=start).rev() {
+        let prev = 2.0 * n as f64 / x * cur - next;
+        (next, cur) = (cur, prev);
+        match n - 1 {
+            1 => j1 = cur,
+            m if m > 0 && m % 2 == 0 => sum += 2.0 * cur,
+            _ => (),
+        }
+    }
+    sum += cur;
+    (cur / sum, j1 / sum)
+}
+
+/// hankel's asymptotic expansion for large x
+///
+/// the phases x - pi/4 and x - 3pi/4 are expanded into sin x and cos x, which are reduced
+/// exactly. rounding x - pi/4 would shift the phase by up to ulp(x) / 2
+fn bessel_hankel(x: f64) -> (f64, f64) {
+    let amp = (1.0 / (PI * x)).sqrt();
+    let (sin, cos) = x.sin_cos();
+    // sqrt(2) cos(x - pi/4) and sqrt(2) sin(x - pi/4), x - 3pi/4 is a quarter turn less
+    let (c, s) = (cos + sin, sin - cos);
+    let (p0, q0) = hankel_pq(0.0, x);
+    let (p1, q1) = hankel_pq(4.0, x);
+    (amp * (p0 * c - q0 * s), amp * (p1 * s + q1 * c))
+}
+
+/// the series P and Q with mu = 4 nu^2, summed up to their smallest term
+fn hankel_pq(mu: f64, x: f64) -> (f64, f64) {
+    let (mut p, mut q) = (1.0, 0.0);
+    let mut t: f64 = 1.0;
+    for k in 1..40 {
+        let kf = k as f64;
+        let next = t * (mu - (2.0 * kf - 1.0).powi(2)) / (8.0 * kf * x);
+        if next.abs() >= t.abs() || next == 0.0 {
+            break;
+        }
+        t = next;
+        match k % 4 {
+            1 => q += t,
+            2 => p -= t,
+            3 => q -= t,
+            _ => p += t,
+        }
+    }
+    (p, q)
+}
+
+/// principal branch W0 of the inverse of w e^w, NaN below -1/e
+pub fn lambert_w(x: f64) -> f64 {
+    if x.is_nan() || x < W_BRANCH {
+        return f64::NAN;
+    } else if x == f64::INFINITY {
+        return x;
+    }
+
+    let mut w = if x < -0.25 {
+        // series around the branch point
+        let p = (2.0 * (E * x + 1.0)).max(0.0).sqrt();
+        let w = -1.0 + p - p * p / 3.0 + 11.0 / 72.0 * p * p * p;
+        if p < 1e-4 {
+            return w;
+        }
+        w
+    } else if x < 3.0 {
+        x.ln_1p()
+    } else {
+        let (l1, l2) = (x.ln(), x.ln().ln());
+        l1 - l2 + l2 / l1
+    };
+
+    // halley's method
+    for _ in 0..32 {
+        let ew = w.exp();
+        let f = w * ew - x;
+        let w1 = w + 1.0;
+        let dw = f / (ew * w1 - (w + 2.0) * f / (2.0 * w1));
+        w -= dw;
+        if dw.abs() <= 1e-15 * (1.0 + w.abs()) {
+            break;
+        }
+    }
+    w
+}
+
+pub fn lambert_w_deriv(x: f64) -> f64 {
+    let w = lambert_w(x);
+    1.0 / ((1.0 + w) * w.exp())
+}
+
+/// NaN bounds or lo > hi
+fn is_undef(lo: f64, hi: f64) -> bool {
+    lo.is_nan() || hi.is_nan() || lo > hi
+}
+
+fn widen((lo, hi): (f64, f64)) -> (f64, f64) {
+    (
+        lo - (ERR_REL * lo.abs() + ERR_ABS),
+        hi + (ERR_REL * hi.abs() + ERR_ABS),
+    )
+}
+
+fn clamp((lo, hi): (f64, f64), (min, max): (f64, f64)) -> (f64, f64) {
+    (lo.max(min), hi.min(max))
+}
+
+fn min_max(a: f64, b: f64) -> (f64, f64) {
+    (a.min(b), a.max(b))
+}
+
+/// hull of a smooth f over [lo, hi]. the sign changes of df on a grid of width `step` locate
+/// the extrema, so df may have at most one root in every interval of that length
+fn enclose(
+    f: impl Fn(f64) -> f64,
+    df: impl Fn(f64) -> f64,
+    lo: f64,
+    hi: f64,
+    step: f64,
+) -> (f64, f64) {
+    let (mut min, mut max) = min_max(f(lo), f(hi));
+    let (mut a, mut da) = (lo, df(lo));
+
+    while a < hi {
+        let b = (a + step).min(hi);
+        if b <= a {
+            break;
+        }
+        let db = df(b);
+
+        if (da < 0.0) != (db < 0.0) {
+            let (mut l, mut u) = (a, b);
+            for _ in 0..64 {
+                let m = 0.5 * (l + u);
+                if (df(m) < 0.0) == (da < 0.0) {
+                    l = m;
+                } else {
+                    u = m;
+                }
+            }
+            let v = f(0.5 * (l + u));
+            (min, max) = (min.min(v), max.max(v));
+        }
+        let v = f(b);
+        (min, max) = (min.min(v), max.max(v));
+        (a, da) = (b, db);
+    }
+    widen((min, max))
+}
+
+pub fn gamma_intrvl(lo: f64, hi: f64) -> (f64, f64) {
+    if is_undef(lo, hi) || contains_pole(lo, hi) {
+        return UNDEF;
+    }
+    // gamma' = gamma psi and psi has one root between two poles
+    enclose(gamma, digamma, lo, hi, f64::INFINITY)
+}
+
+/// gamma'' = gamma (psi^2 + psi'), so gamma' is monotonic between two poles
+pub fn gamma_deriv_intrvl(lo: f64, hi: f64) -> (f64, f64) {
+    if is_undef(lo, hi) || contains_pole(lo, hi) {
+        return UNDEF;
+    }
+    widen(min_max(gamma_deriv(lo), gamma_deriv(hi)))
+}
+
+pub fn erf_intrvl(lo: f64, hi: f64) -> (f64, f64) {
+    if is_undef(lo, hi) {
+        return UNDEF;
+    }
+    clamp(widen((erf(lo), erf(hi))), (-1.0, 1.0))
+}
+
+pub fn erf_deriv_intrvl(lo: f64, hi: f64) -> (f64, f64) {
+    if is_undef(lo, hi) {
+        return UNDEF;
+    }
+    clamp(
+        enclose(erf_deriv, |x| -x, lo, hi, f64::INFINITY),
+        (0.0, FRAC_2_SQRT_PI),
+    )
+}
+
+/// the roots of J0' = -J1 are more than pi apart
+pub fn j0_intrvl(lo: f64, hi: f64) -> (f64, f64) {
+    if is_undef(lo, hi) {
+        return UNDEF;
+    } else if hi - lo > 64.0 || lo.abs().max(hi.abs()) > BESSEL_COARSE {
+        return J0_RANGE;
+    }
+    clamp(enclose(j0, j0_deriv, lo, hi, 1.0), J0_RANGE)
+}
+
+pub fn j1_intrvl(lo: f64, hi: f64) -> (f64, f64) {
+    if is_undef(lo, hi) {
+        return UNDEF;
+    } else if hi - lo > 64.0 || lo.abs().max(hi.abs()) > BESSEL_COARSE {
+        return J1_RANGE;
+    }
+    clamp(enclose(j1, j1_deriv, lo, hi, 1.0), J1_RANGE)
+}
+
+pub fn j0_deriv_intrvl(lo: f64, hi: f64) -> (f64, f64) {
+    let (l, u) = j1_intrvl(lo, hi);
+    (-u, -l)
+}
+
+/// |J1'| = |J0 - J2| / 2 <= 1
+pub fn j1_deriv_intrvl(lo: f64, hi: f64) -> (f64, f64) {
+    if is_undef(lo, hi) {
+        return UNDEF;
+    } else if hi - lo > 64.0 || lo.abs().max(hi.abs()) > BESSEL_COARSE {
+        return (-1.0, 1.0);
+    }
+    clamp(enclose(j1_deriv, j1_deriv2, lo, hi, 1.0), (-1.0, 1.0))
+}
+
+/// increasing, only the part above -1/e is kept
+pub fn lambert_w_intrvl(lo: f64, hi: f64) -> (f64, f64) {
+    if is_undef(lo, hi) || hi < W_BRANCH {
+        return UNDEF;
+    }
+    let lo = lo.max(W_BRANCH);
+    clamp(widen((lambert_w(lo), lambert_w(hi))), (-1.0, f64::INFINITY))
+}
+
+/// decreasing, infinite at the branch point
+pub fn lambert_w_deriv_intrvl(lo: f64, hi: f64) -> (f64, f64) {
+    if is_undef(lo, hi) || hi < W_BRANCH {
+        return UNDEF;
+    }
+    let lo = lo.max(W_BRANCH);
+    clamp(
+        widen((lambert_w_deriv(hi), lambert_w_deriv(lo))),
+        (0.0, f64::INFINITY),
+    )
+}
+
+/// the special functions as an operand of the evaluators
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Special {
+    Gamma,
+    Erf,
+    J0,
+    J1,
+    LambertW,
+}
+
+impl Special {
+    pub const ALL: [Self; 5] = [Self::Gamma, Self::Erf, Self::J0, Self::J1, Self::LambertW];
+
+    /// the runtime functions of the compilers are named `{name}_{type}`
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Gamma => "gamma",
+            Self::Erf => "erf",
+            Self::J0 => "j0",
+            Self::J1 => "j1",
+            Self::LambertW => "lambert_w",
+        }
+    }
+
+    pub fn eval(self, x: f64) -> f64 {
+        match self {
+            Self::Gamma => gamma(x),
+            Self::Erf => erf(x),
+            Self::J0 => j0(x),
+            Self::J1 => j1(x),
+            Self::LambertW => lambert_w(x),
+        }
+    }
+
+    pub fn deriv(self, x: f64) -> f64 {
+        match self {
+            Self::Gamma => gamma_deriv(x),
+            Self::Erf => erf_deriv(x),
+            Self::J0 => j0_deriv(x),
+            Self::J1 => j1_deriv(x),
+            Self::LambertW => lambert_w_deriv(x),
+        }
+    }
+
+    pub fn intrvl(self, lo: f64, hi: f64) -> (f64, f64) {
+        match self {
+            Self::Gamma => gamma_intrvl(lo, hi),
+            Self::Erf => erf_intrvl(lo, hi),
+            Self::J0 => j0_intrvl(lo, hi),
+            Self::J1 => j1_intrvl(lo, hi),
+            Self::LambertW => lambert_w_intrvl(lo, hi),
+        }
+    }
+
+    pub fn deriv_intrvl(self, lo: f64, hi: f64) -> (f64, f64) {
+        match self {
+            Self::Gamma => gamma_deriv_intrvl(lo, hi),
+            Self::Erf => erf_deriv_intrvl(lo, hi),
+            Self::J0 => j0_deriv_intrvl(lo, hi),
+            Self::J1 => j1_deriv_intrvl(lo, hi),
+            Self::LambertW => lambert_w_deriv_intrvl(lo, hi),
+        }
+    }
+}
+
+impl std::fmt::Display for Special {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() <= 1e-14 * (1.0 + b.abs())
+    }
+
+    #[test]
+    fn values() {
+        // reference values from mpmath
+        for (x, g) in [
+            (0.5, 1.772_453_850_905_516),
+            (4.5, 11.631_728_396_567_45),
+            (-2.5, -0.945_308_720_482_941_9),
+            (-0.5, -3.544_907_701_811_032),
+        ] {
+            assert!(close(gamma(x), g), "gamma({x}) = {}", gamma(x));
+        }
+        assert!((gamma(170.5) / 5.562_092_414_56e305 - 1.0).abs() < 1e-12);
+        assert!(gamma(-3.0).is_nan() && gamma(0.0).is_nan());
+
+        for (x, d) in [
+            (0.5, -1.963_510_026_021_423_5),
+            (-3.25, 4.466_275_872_350_28),
+        ] {
+            assert!(close(digamma(x), d), "digamma({x}) = {}", digamma(x));
+        }
+
+        for (x, e) in [
+            (0.1, 0.112_462_916_018_284_9),
+            (1.0, 0.842_700_792_949_714_9),
+            (2.4, 0.999_311_486_103_354_9),
+            (2.6, 0.999_763_965_583_470_7),
+            (-1.7, -0.983_790_458_590_774_6),
+        ] {
+            assert!(close(erf(x), e), "erf({x}) = {}", erf(x));
+        }
+
+        for (x, a, b) in [
+            (0.5, 0.938_469_807_240_812_9, 0.242_268_457_674_873_9),
+            (3.0, -0.260_051_954_901_933_4, 0.339_058_958_525_936_5),
+            (4.5, -0.320_542_508_985_121_4, -0.231_060_431_923_370_6),
+            (10.0, -0.245_935_764_451_348_3, 0.043_472_746_168_861_44),
+            (24.0, -0.056_230_274_166_859_27, -0.154_038_065_183_121_2),
+            (26.0, 0.155_999_315_522_421_1, 0.015_045_730_586_915_81),
+            (100.0, 0.019_985_850_304_223_12, -0.077_145_352_014_112_16),
+            (-7.0, 0.300_079_270_519_555_6, 0.004_682_823_482_345_833),
+        ] {
+            assert!(close(j0(x), a), "j0({x}) = {}", j0(x));
+            assert!(close(j1(x), b), "j1({x}) = {}", j1(x));
+        }
+
+        // the phase of the hankel expansion is not rounded, mpmath with 50 digits
+        for (x, a, b) in [
+            (1e5, -1.719_201_116_235_972_3e-3, 1.846_757_562_882_567_7e-3),
+            (
+                1e6 + 0.5,
+                6.385_652_662_827_222e-4,
+                -4.783_864_016_401_584e-4,
+            ),
+            (
+                1e8 + 0.25,
+                1.298_731_736_067_963_3e-5,
+                7.872_437_555_012_737e-5,
+            ),
+            (1e11, 2.318_543_245_634_479_7e-6, 9.952_662_667_957_864e-7),
+            (
+                -3e7 - 0.5,
+                2.439_749_413_129_972_2e-6,
+                -1.456_526_908_776_081_3e-4,
+            ),
+        ] {
+            let rel = |v: f64, r: f64| (v - r).abs() <= 1e-13 * r.abs();
+            assert!(rel(j0(x), a), "j0({x}) = {}", j0(x));
+            assert!(rel(j1(x), b), "j1({x}) = {}", j1(x));
+        }
+
+        for (x, w) in [
+            (-0.3, -0.489_402_227_180_214_9),
+            (-0.36, -0.806_084_315_970_817_6),
+            (0.5, 0.351_733_711_249_195_8),
+            (10.0, 1.745_528_002_740_699_4),
+            (1e10, 20.028_685_413_304_95),
+        ] {
+            assert!(close(lambert_w(x), w), "W({x}) = {}", lambert_w(x));
+        }
+        assert_eq!(lambert_w(-1.0 / E), -1.0);
+        assert!(lambert_w(-0.5).is_nan());
+    }
+
+    #[test]
+    fn derivatives() {
+        let h = 1e-6;
+        for x in [-2.5, -0.3, 0.7, 1.5, 3.0, 7.5] {
+            let num = |f: fn(f64) -> f64| (f(x + h) - f(x - h)) / (2.0 * h);
+            // lambert w is undefined below -1/e
+            let approx = |a: f64, b: f64| {
+                (a - b).abs() < 1e-6 * (1.0 + b.abs()) || (a.is_nan() && b.is_nan())
+            };
+            assert!(approx(gamma_deriv(x), num(gamma)), "{x}");
+            assert!(approx(erf_deriv(x), num(erf)), "{x}");
+            assert!(approx(j0_deriv(x), num(j0)), "{x}");
+            assert!(approx(j1_deriv(x), num(j1)), "{x}");
+            assert!(approx(lambert_w_deriv(x), num(lambert_w)), "{x}");
+        }
+    }
+
+    #[test]
+    fn enclosures() {
+        type Fns = (fn(f64) -> f64, fn(f64, f64) -> (f64, f64));
+        let fns: [Fns; 10] = [
+            (gamma, gamma_intrvl),
+            (erf, erf_intrvl),
+            (j0, j0_intrvl),
+            (j1, j1_intrvl),
+            (lambert_w, lambert_w_intrvl),
+            (gamma_deriv, gamma_deriv_intrvl),
+            (erf_deriv, erf_deriv_intrvl),
+            (j0_deriv, j0_deriv_intrvl),
+            (j1_deriv, j1_deriv_intrvl),
+            (lambert_w_deriv, lambert_w_deriv_intrvl),
+        ];
+        let intervals = [
+            (0.2, 3.0),
+            (-2.9, -2.1),
+            (-0.3, 0.4),
+            (1.0, 1.7),
+            (-12.0, 9.0),
+            (20.0, 31.0),
+            (2.5, 2.5),
+        ];
+
+        for (f, intrvl) in fns {
+            for (lo, hi) in intervals {
+                let (l, u) = intrvl(lo, hi);
+                if l.is_nan() {
+                    continue;
+                }
+                for i in 0..=200 {
+                    let x = lo + (hi - lo) * i as f64 / 200.0;
+                    let v = f(x);
+                    if v.is_finite() {
+                        assert!(l <= v && v <= u, "{v} not in [{l}, {u}] at {x}");
+                    }
+                }
+            }
+        }
+
+        // the minimum of gamma and the poles
+        let (l, _) = gamma_intrvl(1.0, 2.0);
+        assert!(l < 0.885_603_194_410_888_7 && l > 0.8856);
+        assert!(gamma_intrvl(-1.5, -0.5).0.is_nan());
+        assert!(lambert_w_intrvl(-2.0, -1.0).0.is_nan());
+        assert_eq!(j0_intrvl(0.0, 1e3), J0_RANGE);
+        assert_eq!(j1_intrvl(2e12, 2e12 + 1.0), J1_RANGE);
+    }
+}